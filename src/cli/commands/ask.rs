@@ -0,0 +1,154 @@
+use std::collections::HashSet;
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+
+use crate::config::client;
+use crate::fs::walker::DirectoryWalker;
+use crate::index::SemanticIndex;
+use crate::index::retrieve::{
+    self, Snippet, SnippetSource, build_prompt, context_budget, extract_keywords, file_url, find_citations,
+};
+use crate::terminal::output::{
+    StyledText, Color, error_text, info_text, warning_text, dim_text, TerminalControl,
+};
+
+/// Semantic chunks requested when a `.forge/index` exists
+const SEMANTIC_RESULTS: usize = 8;
+
+pub fn run(question: &str, path: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+    let root = Path::new(path.unwrap_or("."));
+    if !root.is_dir() {
+        return Err(format!("Not a directory: {}", root.display()).into());
+    }
+
+    let config = client::get_config_or_default();
+    let (client, model) = client::create_ollama_client()?;
+
+    println!("{} {}",
+        StyledText::new("❓ Question:").fg(Color::BrightYellow).bold(),
+        StyledText::new(question).fg(Color::BrightGreen));
+    println!("{}", info_text("🔎 Gathering context..."));
+
+    let files: Vec<PathBuf> = DirectoryWalker::new(root).into_iter().files().collect();
+    let keywords = extract_keywords(question);
+    let keyword_hits = retrieve::keyword_snippets(root, &files, &keywords);
+    let symbol_hits = retrieve::symbol_snippets(root, &files, &keywords);
+
+    // The semantic index is optional; fall back to keywords if it can't be used
+    let semantic_hits = match SemanticIndex::open_existing(root) {
+        Ok(index) => match retrieve::semantic_snippets(&index, &client, question, SEMANTIC_RESULTS) {
+            Ok(hits) => hits,
+            Err(e) => {
+                println!("{}", dim_text(&format!("   Semantic index unavailable: {}", e)));
+                Vec::new()
+            }
+        },
+        Err(_) => Vec::new(),
+    };
+
+    let budget = context_budget(config.llm.context_window, config.llm.max_tokens, question);
+    let snippets = retrieve::pack_snippets(retrieve::merge_snippets(vec![symbol_hits, keyword_hits, semantic_hits]), budget);
+
+    let linkify = io::stdout().is_terminal();
+    let absolute_root = root.canonicalize().unwrap_or_else(|_| root.to_path_buf());
+
+    if snippets.is_empty() {
+        println!("{}", warning_text("⚠️  No relevant code found; answering without context"));
+    } else {
+        print_sources(&snippets, &absolute_root, linkify);
+    }
+    println!();
+
+    print!("{} ", StyledText::new("🤖 Answer:").fg(Color::BrightBlue).bold());
+    io::stdout().flush()?;
+
+    let known_paths: HashSet<String> = snippets.iter().map(|s| s.path.display().to_string()).collect();
+    let mut printer = CitationPrinter::new(known_paths, absolute_root, linkify);
+    let prompt = build_prompt(question, &snippets);
+
+    let result = client.generate_stream(&model, &prompt, |chunk| {
+        printer.push(chunk);
+        io::stdout().flush()?;
+        Ok(())
+    });
+    printer.finish();
+    println!();
+
+    if let Err(e) = result {
+        println!("{}", error_text(&format!("❌ Connection error: {}", e)));
+        println!("{}", dim_text("   • Make sure Ollama is running: ollama serve"));
+        println!("{}", dim_text(&format!("   • Verify model is available: ollama list | grep {}", model)));
+        return Err(e);
+    }
+
+    Ok(())
+}
+
+fn print_sources(snippets: &[Snippet], root: &Path, linkify: bool) {
+    println!("{}", StyledText::new("📚 Context:").fg(Color::BrightYellow).bold());
+    for (i, snippet) in snippets.iter().enumerate() {
+        let reference = format!("{}:{}-{}", snippet.path.display(), snippet.start_line, snippet.end_line);
+        let source = match snippet.source {
+            SnippetSource::Keyword => "keyword",
+            SnippetSource::Semantic => "semantic",
+            SnippetSource::Symbol => "symbol",
+        };
+        println!("  {} {} {}",
+            StyledText::new(&format!("[{}]", i + 1)).fg(Color::BrightBlue),
+            link(&reference, &root.join(&snippet.path), snippet.start_line, linkify),
+            dim_text(&format!("({})", source)));
+    }
+}
+
+fn link(text: &str, target: &Path, line: usize, linkify: bool) -> String {
+    let styled = StyledText::new(text).fg(Color::BrightCyan).to_string();
+    if linkify {
+        TerminalControl::hyperlink(&file_url(target, line), &styled)
+    } else {
+        styled
+    }
+}
+
+/// Prints streamed answer text a line at a time, turning `path:line`
+/// citations of context files into terminal links
+struct CitationPrinter {
+    buffer: String,
+    known_paths: HashSet<String>,
+    root: PathBuf,
+    linkify: bool,
+}
+
+impl CitationPrinter {
+    fn new(known_paths: HashSet<String>, root: PathBuf, linkify: bool) -> Self {
+        Self { buffer: String::new(), known_paths, root, linkify }
+    }
+
+    fn push(&mut self, chunk: &str) {
+        self.buffer.push_str(chunk);
+        while let Some(newline) = self.buffer.find('\n') {
+            let line: String = self.buffer.drain(..=newline).collect();
+            print!("{}", self.render(&line));
+        }
+    }
+
+    fn finish(&mut self) {
+        let rest = std::mem::take(&mut self.buffer);
+        print!("{}", self.render(&rest));
+    }
+
+    fn render(&self, line: &str) -> String {
+        if !self.linkify {
+            return line.to_string();
+        }
+
+        let mut rendered = String::new();
+        let mut last = 0;
+        for citation in find_citations(line, &self.known_paths) {
+            rendered.push_str(&line[last..citation.start]);
+            rendered.push_str(&link(&line[citation.start..citation.end], &self.root.join(&citation.path), citation.line, true));
+            last = citation.end;
+        }
+        rendered.push_str(&line[last..]);
+        rendered
+    }
+}
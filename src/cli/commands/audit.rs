@@ -0,0 +1,97 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::config::client;
+use crate::forge_process::audit::{self, AuditEntry, AuditLog, AuditQuery, Initiator, Status};
+use crate::terminal::output::{dim_text, warning_text, BorderStyle, StatusIndicator, StatusType, Table};
+
+/// Longest command shown in the table; `--json` has them in full
+const COMMAND_WIDTH: usize = 60;
+
+/// `forge audit [--since <age|date>] [--risk <level>] [--status <status>]
+/// [--by <initiator>] [-n N] [--json]`: the commands forge ran, newest first
+pub fn run(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    let mut query = AuditQuery::default();
+    let mut limit = 20;
+    let mut json = false;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let mut value = |name: &str| iter.next().ok_or_else(|| format!("{} needs a value", name));
+        match arg.as_str() {
+            "--since" => {
+                let since = value("--since")?;
+                query.since = Some(audit::parse_since(since, now)
+                    .ok_or_else(|| format!("--since takes an age like 2h or 7d, or a date like 2026-10-18, not '{}'", since))?);
+            }
+            "--risk" => query.risk = Some(value("--risk")?.parse()?),
+            "--status" => {
+                let status = value("--status")?;
+                query.status = Some(Status::parse(status).ok_or_else(|| format!("--status is ok, failed or refused, not '{}'", status))?);
+            }
+            "--by" => {
+                let initiator = value("--by")?;
+                query.initiator = Some(Initiator::parse(initiator).ok_or_else(|| format!("--by is user, workflow or agent, not '{}'", initiator))?);
+            }
+            "--limit" | "-n" => {
+                limit = value("--limit")?
+                    .parse()
+                    .ok()
+                    .filter(|&n| n > 0)
+                    .ok_or("--limit requires a positive number")?;
+            }
+            "--json" => json = true,
+            other => return Err(format!(
+                "Unknown option '{}'\nUsage: forge audit [--since <age|date>] [--risk <level>] [--status ok|failed|refused] [--by user|workflow|agent] [-n N] [--json]",
+                other
+            ).into()),
+        }
+    }
+
+    let config = client::get_config_or_default().audit;
+    let path = audit::default_path().ok_or("Could not determine home directory")?;
+    let log = AuditLog::new(path).with_rotation(config.max_size_mb as u64 * 1024 * 1024, config.max_files);
+    let (entries, errors) = log.read();
+    for (path, line, error) in &errors {
+        eprintln!("{}", warning_text(&format!("⚠️  Skipping {}:{}: {}", path.display(), line, error)));
+    }
+
+    let mut entries: Vec<&AuditEntry> = entries.iter().filter(|entry| query.matches(entry)).collect();
+    entries.reverse();
+    if json {
+        for entry in entries.iter().take(limit) {
+            println!("{}", entry.to_json());
+        }
+        return Ok(());
+    }
+    if entries.is_empty() {
+        let message = if config.enabled { "No matching commands in the audit log" } else { "No matching commands; auditing is off (audit.enabled)" };
+        println!("{}", StatusIndicator::new(StatusType::Info, message).render());
+        return Ok(());
+    }
+
+    let mut table = Table::new(vec!["Time", "By", "Risk", "Decision", "Result", "Duration", "Command"]).border_style(BorderStyle::None);
+    for entry in entries.iter().take(limit) {
+        let risk = format!("{:?}", entry.risk).to_lowercase();
+        let result = match (entry.status(), entry.exit_code) {
+            (Status::Ok, _) => "✅ ok".to_string(),
+            (Status::Refused, _) => "🚫 refused".to_string(),
+            (Status::Failed, Some(code)) => format!("❌ exit {}", code),
+            (Status::Failed, None) => "❌ failed".to_string(),
+        };
+        let duration = format!("{:.1}s", entry.duration.as_secs_f64());
+        table.add_row(vec![&entry.time(), entry.initiator.as_str(), &risk, entry.approval.as_str(), &result, &duration, &shorten(&entry.command)]);
+    }
+    println!("{}", table.render());
+    if entries.len() > limit {
+        println!("{}", dim_text(&format!("{} older entries not shown; use --limit", entries.len() - limit)));
+    }
+    Ok(())
+}
+
+fn shorten(command: &str) -> String {
+    let command = command.split_whitespace().collect::<Vec<_>>().join(" ");
+    if command.chars().count() <= COMMAND_WIDTH {
+        return command;
+    }
+    command.chars().take(COMMAND_WIDTH - 1).chain(['…']).collect()
+}
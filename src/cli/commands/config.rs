@@ -54,12 +54,18 @@ fn show_config() -> Result<(), ConfigError> {
     println!("  {} {}", 
         StyledText::new("Model:").fg(Color::White),
         StyledText::new(&config.llm.default_model).fg(Color::BrightGreen));
+    println!("  {} {}", 
+        StyledText::new("Embedding Model:").fg(Color::White),
+        StyledText::new(&config.llm.embedding_model).fg(Color::BrightGreen));
     println!("  {} {}", 
         StyledText::new("Temperature:").fg(Color::White),
         StyledText::new(&config.llm.temperature.to_string()).fg(Color::BrightGreen));
     println!("  {} {}", 
         StyledText::new("Max Tokens:").fg(Color::White),
         StyledText::new(&config.llm.max_tokens.to_string()).fg(Color::BrightGreen));
+    println!("  {} {}", 
+        StyledText::new("Context Window:").fg(Color::White),
+        StyledText::new(&config.llm.context_window.to_string()).fg(Color::BrightGreen));
     println!("  {} {}", 
         StyledText::new("Timeout (s):").fg(Color::White),
         StyledText::new(&config.llm.timeout_seconds.to_string()).fg(Color::BrightGreen));
@@ -99,6 +105,28 @@ fn show_config() -> Result<(), ConfigError> {
         StyledText::new(&format!("{:?}", config.safety.restricted_paths)).fg(Color::BrightGreen));
     println!();
     
+    println!("{}", StyledText::new("🐚 Shell Settings:")
+        .fg(Color::BrightYellow)
+        .bold());
+    println!("  {} {}", 
+        StyledText::new("Explain Failures:").fg(Color::White),
+        StyledText::new(&config.shell.explain_failures.to_string()).fg(Color::BrightGreen));
+    println!();
+    
+    println!("{}", StyledText::new("📜 Audit Settings:")
+        .fg(Color::BrightYellow)
+        .bold());
+    println!("  {} {}", 
+        StyledText::new("Enabled:").fg(Color::White),
+        StyledText::new(&config.audit.enabled.to_string()).fg(Color::BrightGreen));
+    println!("  {} {}", 
+        StyledText::new("Max Size (MB):").fg(Color::White),
+        StyledText::new(&config.audit.max_size_mb.to_string()).fg(Color::BrightGreen));
+    println!("  {} {}", 
+        StyledText::new("Max Files:").fg(Color::White),
+        StyledText::new(&config.audit.max_files.to_string()).fg(Color::BrightGreen));
+    println!();
+    
     println!("{}", StyledText::new("🔑 API Keys:")
         .fg(Color::BrightYellow)
         .bold());
@@ -138,8 +166,10 @@ fn set_config(key: &str, value: &str) -> Result<(), Box<dyn std::error::Error>>
     match (parts[0], parts[1]) {
         ("llm", "default_provider") => config.llm.default_provider = value.to_string(),
         ("llm", "default_model") => config.llm.default_model = value.to_string(),
+        ("llm", "embedding_model") => config.llm.embedding_model = value.to_string(),
         ("llm", "temperature") => config.llm.temperature = value.parse()?,
         ("llm", "max_tokens") => config.llm.max_tokens = value.parse()?,
+        ("llm", "context_window") => config.llm.context_window = value.parse()?,
         ("llm", "timeout_seconds") => config.llm.timeout_seconds = value.parse()?,
         ("ui", "theme") => config.ui.theme = value.to_string(),
         ("ui", "show_line_numbers") => config.ui.show_line_numbers = value.parse()?,
@@ -148,6 +178,10 @@ fn set_config(key: &str, value: &str) -> Result<(), Box<dyn std::error::Error>>
         ("safety", "enable_safety_checks") => config.safety.enable_safety_checks = value.parse()?,
         ("safety", "allow_system_commands") => config.safety.allow_system_commands = value.parse()?,
         ("safety", "max_file_size_mb") => config.safety.max_file_size_mb = value.parse()?,
+        ("shell", "explain_failures") => config.shell.explain_failures = value.parse()?,
+        ("audit", "enabled") => config.audit.enabled = value.parse()?,
+        ("audit", "max_size_mb") => config.audit.max_size_mb = value.parse()?,
+        ("audit", "max_files") => config.audit.max_files = value.parse()?,
         ("api_keys", provider) => {
             config.api_keys.insert(provider.to_string(), value.to_string());
         }
@@ -174,8 +208,10 @@ fn get_config(key: &str) -> Result<(), Box<dyn std::error::Error>> {
     let value = match (parts[0], parts[1]) {
         ("llm", "default_provider") => config.llm.default_provider,
         ("llm", "default_model") => config.llm.default_model,
+        ("llm", "embedding_model") => config.llm.embedding_model,
         ("llm", "temperature") => config.llm.temperature.to_string(),
         ("llm", "max_tokens") => config.llm.max_tokens.to_string(),
+        ("llm", "context_window") => config.llm.context_window.to_string(),
         ("llm", "timeout_seconds") => config.llm.timeout_seconds.to_string(),
         ("ui", "theme") => config.ui.theme,
         ("ui", "show_line_numbers") => config.ui.show_line_numbers.to_string(),
@@ -184,6 +220,10 @@ fn get_config(key: &str) -> Result<(), Box<dyn std::error::Error>> {
         ("safety", "enable_safety_checks") => config.safety.enable_safety_checks.to_string(),
         ("safety", "allow_system_commands") => config.safety.allow_system_commands.to_string(),
         ("safety", "max_file_size_mb") => config.safety.max_file_size_mb.to_string(),
+        ("shell", "explain_failures") => config.shell.explain_failures.to_string(),
+        ("audit", "enabled") => config.audit.enabled.to_string(),
+        ("audit", "max_size_mb") => config.audit.max_size_mb.to_string(),
+        ("audit", "max_files") => config.audit.max_files.to_string(),
         ("api_keys", provider) => {
             match config.api_keys.get(provider) {
                 Some(_) => "***configured***".to_string(),
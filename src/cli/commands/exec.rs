@@ -1,8 +1,26 @@
-use crate::config::client;
-use crate::forge_process::executor::{ProcessExecutor, ExecutionOptions};
-use crate::terminal::output::{success_text, error_text, info_text};
+use std::io::{self, IsTerminal};
+
+use crate::config::{client, ForgeConfig};
+use crate::forge_process::audit::AuditLog;
+use crate::forge_process::executor::{Confirmation, ProcessExecutor, ExecutionOptions};
+use crate::forge_process::shell::{self, Shell};
+use crate::terminal::output::{success_text, error_text, info_text, dim_text};
 
 pub fn run(command: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let mut explain_failure = false;
+    let mut confirmation = Confirmation::Interactive;
+    let mut command = command;
+    while let Some((flag, rest)) = command.split_first() {
+        if flag == "--explain-failure" {
+            explain_failure = true;
+        } else if let Some(flag) = Confirmation::parse_flag(flag)? {
+            confirmation = flag;
+        } else {
+            break;
+        }
+        command = rest;
+    }
+    let explain_failure = explain_failure || ForgeConfig::load().is_ok_and(|config| config.shell.explain_failures);
     if command.is_empty() {
         println!("No command provided.");
         return Ok(());
@@ -14,7 +32,8 @@ pub fn run(command: &[String]) -> Result<(), Box<dyn std::error::Error>> {
     println!();
 
     // Use the new enhanced executor
-    let mut executor = ProcessExecutor::new();
+    let mut executor = ProcessExecutor::new().with_confirmation(confirmation);
+    println!("{}", dim_text(&format!("Confirmation: {}", executor.confirmation())));
     
     let options = ExecutionOptions {
         timeout: Some(std::time::Duration::from_secs(300)), // 5 minutes default
@@ -67,16 +86,33 @@ pub fn run(command: &[String]) -> Result<(), Box<dyn std::error::Error>> {
                 println!("{}", success_text("✅ Command completed successfully"));
             } else {
                 println!("{}", error_text("❌ Command failed"));
+                if explain_failure {
+                    explain(&executor, &full_command, result.exit_code, &result.stderr);
+                }
             }
         }
-        Err(e) => {
-            println!("{}", error_text(&format!("Failed to execute command: {}", e)));
-        }
+        // Refusals fail the command, so scripts can tell
+        Err(e) => return Err(format!("Failed to execute command: {}", e).into()),
     }
 
     Ok(())
 }
 
+/// Show the model's diagnosis of a failed command, and in a terminal
+/// offer to run its fix under the executor's confirmation and safety rules
+fn explain(executor: &ProcessExecutor, command: &str, exit_code: i32, stderr: &str) {
+    let mut shell = Shell::new()
+        .with_audit_log(AuditLog::configured())
+        .with_confirmation(executor.confirmation().clone())
+        .with_safety_checker(executor.safety_checker().clone());
+    let mut editor = io::stdin().is_terminal().then(shell::line_editor);
+    match shell.explain_failure(command, exit_code, stderr, editor.as_mut()) {
+        Ok(Some(result)) => shell::print_result(&result),
+        Ok(None) => {}
+        Err(e) => println!("{}", error_text(&format!("Couldn't explain the failure: {}", e))),
+    }
+}
+
 #[allow(dead_code)]
 fn is_dangerous_command(command: &str) -> bool {
     let dangerous_commands = [
@@ -0,0 +1,79 @@
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::Instant;
+
+use crate::config::client;
+use crate::fs::ignore::IgnoreOptions;
+use crate::index::{FileStatus, SemanticIndex};
+use crate::terminal::output::{
+    StyledText, Color, success_text, error_text, info_text, dim_text, EnhancedProgressBar, TerminalControl,
+};
+
+pub fn run(path: Option<&str>, ignore_options: IgnoreOptions) -> Result<(), Box<dyn std::error::Error>> {
+    let root = Path::new(path.unwrap_or("."));
+    if !root.is_dir() {
+        return Err(format!("Not a directory: {}", root.display()).into());
+    }
+
+    let (client, model) = client::create_embedding_client()?;
+
+    println!("{} {}",
+        info_text("🧠 Indexing"),
+        StyledText::new(&root.display().to_string()).fg(Color::BrightCyan));
+    println!("  {} {}",
+        StyledText::new("Model:").fg(Color::BrightYellow).bold(),
+        StyledText::new(&model).fg(Color::BrightGreen));
+    println!();
+
+    let mut index = SemanticIndex::open(root, &model)?;
+    let started = Instant::now();
+    let mut progress: Option<EnhancedProgressBar> = None;
+
+    print!("{}", TerminalControl::hide_cursor());
+    let result = index.update(&client, ignore_options, |done, total, file, status| {
+        let progress = progress.get_or_insert_with(|| EnhancedProgressBar::new(total, 40).with_title("Embedding"));
+        progress.set_progress(done);
+        let label = match status {
+            FileStatus::Embedded => "embedded",
+            FileStatus::Unchanged => "unchanged",
+            FileStatus::Skipped => "skipped",
+        };
+        progress.set_status(&format!("{} ({})", file.display(), label));
+
+        let elapsed = started.elapsed().as_secs_f64();
+        if elapsed > 0.0 {
+            progress.set_rate(done as f64 / elapsed);
+        }
+
+        print!("{}{}", TerminalControl::clear_line(), progress.render());
+        let _ = io::stdout().flush();
+    });
+    print!("{}", TerminalControl::show_cursor());
+    println!();
+    println!();
+
+    // Keep whatever was embedded before a failure so the next run resumes
+    index.save()?;
+
+    let stats = match result {
+        Ok(stats) => stats,
+        Err(e) => {
+            println!("{}", error_text(&format!("❌ Indexing stopped: {}", e)));
+            println!("{}", dim_text("   Progress so far was saved; run `forge index` again to resume"));
+            return Err(e);
+        }
+    };
+
+    println!("{} {} {} {} {}",
+        success_text("✅ Indexed"),
+        StyledText::new(&index.store().file_count().to_string()).fg(Color::BrightCyan).bold(),
+        StyledText::new("files,").fg(Color::White),
+        StyledText::new(&index.store().chunk_count().to_string()).fg(Color::BrightGreen).bold(),
+        StyledText::new("chunks").fg(Color::White));
+    println!("{}", dim_text(&format!(
+        "   {} embedded ({} chunks), {} unchanged, {} skipped, {} removed in {:.1}s",
+        stats.embedded, stats.chunks_embedded, stats.unchanged, stats.skipped, stats.removed,
+        started.elapsed().as_secs_f64())));
+
+    Ok(())
+}
@@ -1,8 +1,14 @@
+pub mod ask;
 pub mod chat;
 pub mod edit;
 pub mod search;
+pub mod index;
+pub mod outline;
 pub mod exec;
 pub mod shell;
+pub mod suggest;
+pub mod policy;
+pub mod audit;
 pub mod status;
 pub mod workflow;
 pub mod config;
\ No newline at end of file
@@ -0,0 +1,72 @@
+use std::path::{Path, PathBuf};
+
+use crate::fs::walker::{DirectoryWalker, SortOrder};
+use crate::index::outline::{self, Language, SymbolKind};
+use crate::terminal::output::{StyledText, Color, warning_text, dim_text};
+
+/// Characters of repo map printed by `forge outline --map`
+const MAP_BUDGET: usize = 64 * 1024;
+
+pub fn run(file: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let path = Path::new(file);
+    if !path.is_file() {
+        return Err(format!("Not a file: {}", path.display()).into());
+    }
+
+    let symbols = outline::outline_file(path)?
+        .ok_or_else(|| format!("Unsupported language: {} (supported: .rs, .py, .js, .jsx, .ts, .tsx, .go)", path.display()))?;
+
+    println!("{} {}",
+        StyledText::new("🗂️  Outline:").fg(Color::BrightYellow).bold(),
+        StyledText::new(&path.display().to_string()).fg(Color::BrightCyan));
+    println!();
+
+    if symbols.is_empty() {
+        println!("{}", warning_text("⚠️  No symbols found"));
+        return Ok(());
+    }
+
+    for symbol in &symbols {
+        println!("{}{} {} {}",
+            "  ".repeat(symbol.depth + 1),
+            StyledText::new(&symbol.kind.to_string()).fg(kind_color(symbol.kind)),
+            StyledText::new(&symbol.name).fg(Color::BrightWhite).bold(),
+            dim_text(&format!("lines {}-{}", symbol.start_line, symbol.end_line)));
+    }
+
+    Ok(())
+}
+
+/// Print each source file with its top-level declarations, compact enough
+/// to hand to the model
+pub fn run_map(path: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+    let root = Path::new(path.unwrap_or("."));
+    if !root.is_dir() {
+        return Err(format!("Not a directory: {}", root.display()).into());
+    }
+
+    let files: Vec<PathBuf> = DirectoryWalker::new(root)
+        .with_sort(SortOrder::Name)
+        .into_iter()
+        .files()
+        .filter(|file| Language::from_path(file).is_some())
+        .collect();
+
+    let map = outline::repo_map(root, &files, MAP_BUDGET);
+    if map.is_empty() {
+        println!("{}", warning_text("⚠️  No supported source files found"));
+    } else {
+        print!("{}", map);
+    }
+
+    Ok(())
+}
+
+fn kind_color(kind: SymbolKind) -> Color {
+    match kind {
+        SymbolKind::Function | SymbolKind::Method | SymbolKind::Macro => Color::BrightBlue,
+        SymbolKind::Struct | SymbolKind::Enum | SymbolKind::Class | SymbolKind::Type => Color::BrightGreen,
+        SymbolKind::Trait | SymbolKind::Interface | SymbolKind::Impl => Color::BrightMagenta,
+        SymbolKind::Module => Color::BrightYellow,
+    }
+}
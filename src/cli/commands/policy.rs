@@ -0,0 +1,45 @@
+use std::env;
+
+use crate::forge_process::safety::SafetyChecker;
+use crate::forge_process::safety_policy;
+use crate::forge_process::shell;
+use crate::terminal::output::{dim_text, info_text, StyledText};
+
+/// `forge policy check "<command>"`: rate a command the way `forge exec`
+/// and `forge shell` would, with the policies in effect here
+pub fn run(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    match args.split_first() {
+        Some((subcommand, command)) if subcommand == "check" && !command.is_empty() => check(&command.join(" ")),
+        _ => Err("Usage: forge policy check \"<command>\"".into()),
+    }
+}
+
+fn check(command: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let dir = env::current_dir()?;
+    // Unlike running a command, a broken policy is an error here
+    let checker = SafetyChecker::for_directory(&dir)?;
+
+    if checker.policy_sources().is_empty() {
+        println!("{}", dim_text("No policy files, so only the built-in rules apply"));
+        let user_file = safety_policy::user_policy_file().map(|path| path.display().to_string()).unwrap_or_default();
+        println!("{}", dim_text(&format!("Add rules to .forge/policy.toml or {}", user_file)));
+    } else {
+        println!("{}", info_text("Policies, the strictest deny rule wins:"));
+        for source in checker.policy_sources() {
+            println!("   {}", source.display());
+        }
+        let ignored = checker.policy().ignored().count();
+        if ignored > 0 {
+            println!("{}", dim_text(&format!("{} allow rule(s) in the project policy ignored; a project can only deny", ignored)));
+        }
+    }
+    println!();
+
+    let risk = checker.assess_command(command);
+    println!("{}", StyledText::new(command).bold());
+    shell::print_risk(&risk);
+    for suggestion in &risk.suggestions {
+        println!("   💡 {}", suggestion);
+    }
+    Ok(())
+}
@@ -1,7 +1,54 @@
-use crate::fs::{walker::walk_directory, search::search_multiple_files, glob::glob};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use crate::fs::{walker::DirectoryWalker, search::{ParallelSearch, SearchEvent, FileMatches}, glob::glob_with_options, ignore::IgnoreOptions};
+use crate::config::client;
+use crate::index::SemanticIndex;
+use crate::index::outline::{Language, find_symbols};
 use crate::terminal::output::{StyledText, Color, success_text, warning_text, dim_text, MultiStageProgress, GLOBAL_TASK_MONITOR, NotificationType};
 
-pub fn run(query: &str, path: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+/// How `forge search` interprets its query
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchMode {
+    Text,
+    Semantic { top_k: usize },
+    Symbol,
+}
+
+impl SearchMode {
+    /// Split `--semantic`, `--top <n>` and `--symbol` out of the search
+    /// arguments, returning the mode and the remaining arguments
+    pub fn from_args(args: &[String]) -> Result<(Self, Vec<String>), String> {
+        let mut semantic = false;
+        let mut symbol = false;
+        let mut top_k = 5;
+        let mut rest = Vec::new();
+
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            match arg.as_str() {
+                "--semantic" => semantic = true,
+                "--symbol" => symbol = true,
+                "--top" => {
+                    top_k = iter.next()
+                        .and_then(|n| n.parse().ok())
+                        .ok_or("--top requires a number")?;
+                }
+                _ => rest.push(arg.clone()),
+            }
+        }
+
+        let mode = match (semantic, symbol) {
+            (true, true) => return Err("--semantic and --symbol can't be combined".to_string()),
+            (true, false) => SearchMode::Semantic { top_k },
+            (false, true) => SearchMode::Symbol,
+            (false, false) => SearchMode::Text,
+        };
+        Ok((mode, rest))
+    }
+}
+
+pub fn run(query: &str, path: Option<&str>, ignore_options: IgnoreOptions) -> Result<(), Box<dyn std::error::Error>> {
     let search_path = path.unwrap_or(".");
     
     // Start background task tracking
@@ -41,24 +88,18 @@ pub fn run(query: &str, path: Option<&str>) -> Result<(), Box<dyn std::error::Er
     })?;
     
     // Check if search_path is a glob pattern
-    let files = if search_path.contains('*') || search_path.contains('?') || search_path.contains('[') {
+    let files: Box<dyn Iterator<Item = PathBuf> + Send> = if search_path.contains('*') || search_path.contains('?') || search_path.contains('[') {
         progress.update_stage_progress(1, 0.5, Some("Expanding glob pattern"))?;
-        let glob_files = glob(search_path)?;
-        // Convert PathBuf to String for compatibility with existing code
-        glob_files.iter().map(|p| p.to_string_lossy().to_string()).collect()
+        let files = glob_with_options(search_path, ignore_options)?;
+        progress.update_stage_progress(1, 1.0, Some(&format!("Found {} files", files.len())))?;
+        Box::new(files.into_iter())
     } else {
-        walk_directory(search_path)?
+        // Walk lazily so the search starts before the whole tree has been read
+        progress.update_stage_progress(1, 1.0, Some(&format!("Walking {}", search_path)))?;
+        Box::new(DirectoryWalker::new(search_path).with_ignore_options(ignore_options).into_iter().files())
     };
     
-    progress.update_stage_progress(1, 1.0, Some(&format!("Found {} files", files.len())))?;
     progress.complete_stage(1)?;
-    
-    if files.is_empty() {
-        progress.fail_stage(1, "No files found")?;
-        GLOBAL_TASK_MONITOR.fail_task(&task_id, &format!("No files found in: {}", search_path));
-        println!("{}", warning_text(&format!("⚠️  No files found in: {}", search_path)));
-        return Ok(());
-    }
 
     // Stage 3: Search Content
     progress.start_stage(2)?;
@@ -68,7 +109,17 @@ pub fn run(query: &str, path: Option<&str>) -> Result<(), Box<dyn std::error::Er
         task.progress = progress.clone();
     })?;
     
-    let results = search_multiple_files(&files, query)?;
+    println!("{}", progress.render(60));
+    println!();
+
+    let (files_searched, total_matches, files_matched) = stream_results(files, query);
+
+    if files_searched == 0 {
+        progress.fail_stage(2, "No files found")?;
+        GLOBAL_TASK_MONITOR.fail_task(&task_id, &format!("No files found in: {}", search_path));
+        println!("{}", warning_text(&format!("⚠️  No files found in: {}", search_path)));
+        return Ok(());
+    }
     
     progress.update_stage_progress(2, 1.0, Some("Search complete"))?;
     progress.complete_stage(2)?;
@@ -77,7 +128,7 @@ pub fn run(query: &str, path: Option<&str>) -> Result<(), Box<dyn std::error::Er
     progress.start_stage(3)?;
     progress.update_stage_progress(3, 0.5, Some("Processing results"))?;
     
-    if results.is_empty() {
+    if files_matched == 0 {
         progress.fail_stage(3, "No matches found")?;
         GLOBAL_TASK_MONITOR.fail_task(&task_id, &format!("No matches found for: '{}'", query));
         println!("{}", warning_text(&format!("⚠️  No matches found for: \"{}\"", query)));
@@ -85,12 +136,11 @@ pub fn run(query: &str, path: Option<&str>) -> Result<(), Box<dyn std::error::Er
         return Ok(());
     }
 
-    let total_matches = results.iter().map(|(_, matches)| matches.len()).sum::<usize>();
-    progress.update_stage_progress(3, 1.0, Some(&format!("Found {} matches in {} files", total_matches, results.len())))?;
+    progress.update_stage_progress(3, 1.0, Some(&format!("Found {} matches in {} files", total_matches, files_matched)))?;
     progress.complete_stage(3)?;
     
     // Complete the background task
-    GLOBAL_TASK_MONITOR.complete_task(&task_id, &format!("Found {} matches in {} files", total_matches, results.len()));
+    GLOBAL_TASK_MONITOR.complete_task(&task_id, &format!("Found {} matches in {} files", total_matches, files_matched));
     
     // Add a success notification
     GLOBAL_TASK_MONITOR.add_notification(
@@ -106,40 +156,8 @@ pub fn run(query: &str, path: Option<&str>) -> Result<(), Box<dyn std::error::Er
         success_text("✅ Found"),
         StyledText::new(&total_matches.to_string()).fg(Color::BrightGreen).bold(),
         StyledText::new("matches in").fg(Color::White),
-        StyledText::new(&results.len().to_string()).fg(Color::BrightCyan).bold(),
-        StyledText::new("files:").fg(Color::White));
-    println!();
-
-    for (file_index, (file_path, matches)) in results.iter().enumerate() {
-        println!("{} {} {} {}", 
-            StyledText::new(&format!("📄 [{}]", file_index + 1)).fg(Color::BrightBlue).bold(),
-            StyledText::new(file_path).fg(Color::BrightCyan),
-            StyledText::new(&format!("({} matches)", matches.len())).fg(Color::BrightBlack),
-            if matches.len() > 5 { dim_text("[showing first 5]") } else { StyledText::new("") });
-        
-        let display_matches = if matches.len() > 5 { &matches[..5] } else { matches };
-        
-        for (line_num, col, _len) in display_matches {
-            // Read the line to show context
-            if let Ok(content) = std::fs::read_to_string(&file_path) {
-                let lines: Vec<&str> = content.lines().collect();
-                if *line_num > 0 && *line_num <= lines.len() {
-                    let line = lines[*line_num - 1];
-                    let line_text = line.trim();
-                    
-                    // Show line number and content
-                    println!("    {}{} {}", 
-                        StyledText::new(&format!("{:>4}:", line_num)).fg(Color::BrightBlack),
-                        StyledText::new(&format!("{:>3}", col)).fg(Color::BrightBlack),
-                        highlight_match(line_text, query));
-                }
-            }
-        }
-        if matches.len() > 5 {
-            println!("{}", dim_text(&format!("    ... and {} more matches", matches.len() - 5)));
-        }
-        println!();
-    }
+        StyledText::new(&files_matched.to_string()).fg(Color::BrightCyan).bold(),
+        StyledText::new("files").fg(Color::White));
     
     // Show ETA information if available
     if let Some(_eta) = progress.get_eta_seconds() {
@@ -149,164 +167,161 @@ pub fn run(query: &str, path: Option<&str>) -> Result<(), Box<dyn std::error::Er
     Ok(())
 }
 
-fn highlight_match(line: &str, query: &str) -> String {
-    if let Some(pos) = line.to_lowercase().find(&query.to_lowercase()) {
-        let before = &line[..pos];
-        let matched = &line[pos..pos + query.len()];
-        let after = &line[pos + query.len()..];
-        
-        format!("{}{}{}", 
-            before,
-            format!("{}", StyledText::new(matched).fg(Color::BrightYellow).bold()),
-            after)
-    } else {
-        line.to_string()
+/// Run the parallel search and print each file's matches as soon as they arrive.
+///
+/// Returns the number of files searched, the total number of matches and the
+/// number of files that matched.
+fn stream_results<I>(files: I, query: &str) -> (usize, usize, usize)
+where
+    I: IntoIterator<Item = PathBuf> + Send + 'static,
+    I::IntoIter: Send,
+{
+    let files_searched = Arc::new(AtomicUsize::new(0));
+    let counter = Arc::clone(&files_searched);
+    let files = files.into_iter().inspect(move |_| {
+        counter.fetch_add(1, Ordering::Relaxed);
+    });
+
+    let mut total_matches = 0;
+    let mut files_matched = 0;
+    let mut binary_skipped = 0;
+
+    for event in ParallelSearch::new(query).search(files) {
+        match event {
+            SearchEvent::Matched(file) => {
+                files_matched += 1;
+                total_matches += file.matches.len();
+                print_file_matches(files_matched, &file, query);
+            }
+            SearchEvent::SkippedBinary(_) => binary_skipped += 1,
+            SearchEvent::Error(_, _) => {
+                // Skip files that can't be read
+            }
+        }
     }
+
+    if binary_skipped > 0 {
+        println!("{}", dim_text(&format!("   Skipped {} binary files", binary_skipped)));
+        println!();
+    }
+
+    (files_searched.load(Ordering::Relaxed), total_matches, files_matched)
 }
 
-/// Enhanced search function that supports both glob patterns and regular directory search
-pub fn run_with_glob(query: &str, pattern: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
-    let search_pattern = pattern.unwrap_or("**/*");
-    
-    // Start background task tracking
-    let task_id = GLOBAL_TASK_MONITOR.start_task("search", &format!("Search for '{}' in pattern '{}'", query, search_pattern));
-    
-    // Create multi-stage progress
-    let mut progress = MultiStageProgress::new(&format!("🔍 Forge Glob Search: \"{}\"", query));
-    progress.add_stage("Initialize", 0.1);
-    progress.add_stage("Expand Pattern", 0.3);
-    progress.add_stage("Search Content", 0.5);
-    progress.add_stage("Format Results", 0.1);
-    
-    println!("{}", progress.render(60));
-    println!();
-    
-    // Stage 1: Initialize
-    progress.start_stage(0)?;
-    progress.update_stage_progress(0, 0.5, Some("Setting up search parameters"))?;
-    
-    println!("  {} {}", 
-        StyledText::new("Query:").fg(Color::BrightYellow).bold(),
-        StyledText::new(&format!("\"{}\"", query)).fg(Color::BrightGreen));
-    println!("  {} {}", 
-        StyledText::new("Pattern:").fg(Color::BrightYellow).bold(),
-        StyledText::new(search_pattern).fg(Color::BrightMagenta));
-    
-    progress.update_stage_progress(0, 1.0, Some("Ready to search"))?;
-    progress.complete_stage(0)?;
-    
-    // Stage 2: Expand Pattern
-    progress.start_stage(1)?;
-    progress.update_stage_progress(1, 0.2, Some("Expanding glob pattern"))?;
-    
-    // Update task progress
-    GLOBAL_TASK_MONITOR.update_task(&task_id, |task| {
-        task.progress = progress.clone();
-    })?;
-    
-    let file_paths = glob(search_pattern)?;
-    
-    // Filter to only files (not directories) and convert to String
-    let files: Vec<String> = file_paths.iter()
-        .filter(|p| p.is_file())
-        .map(|p| p.to_string_lossy().to_string())
-        .collect();
-    
-    progress.update_stage_progress(1, 1.0, Some(&format!("Found {} files matching pattern", files.len())))?;
-    progress.complete_stage(1)?;
-    
-    if files.is_empty() {
-        progress.fail_stage(1, "No files found matching pattern")?;
-        GLOBAL_TASK_MONITOR.fail_task(&task_id, &format!("No files found matching pattern: {}", search_pattern));
-        println!("{}", warning_text(&format!("⚠️  No files found matching pattern: {}", search_pattern)));
-        return Ok(());
+fn print_file_matches(file_index: usize, file: &FileMatches, query: &str) {
+    let matches = &file.matches;
+
+    println!("{} {} {} {}", 
+        StyledText::new(&format!("📄 [{}]", file_index)).fg(Color::BrightBlue).bold(),
+        StyledText::new(&file.path.to_string_lossy()).fg(Color::BrightCyan),
+        StyledText::new(&format!("({} matches)", matches.len())).fg(Color::BrightBlack),
+        if matches.len() > 5 { dim_text("[showing first 5]") } else { StyledText::new("") });
+    
+    for m in matches.iter().take(5) {
+        // Show line number and content
+        println!("    {}{} {}", 
+            StyledText::new(&format!("{:>4}:", m.line_number)).fg(Color::BrightBlack),
+            StyledText::new(&format!("{:>3}", m.column)).fg(Color::BrightBlack),
+            highlight_match(m.line.trim(), query));
+    }
+    if matches.len() > 5 {
+        println!("{}", dim_text(&format!("    ... and {} more matches", matches.len() - 5)));
     }
+    println!();
+}
 
-    // Stage 3: Search Content
-    progress.start_stage(2)?;
-    progress.update_stage_progress(2, 0.1, Some("Starting content search"))?;
-    
-    GLOBAL_TASK_MONITOR.update_task(&task_id, |task| {
-        task.progress = progress.clone();
-    })?;
-    
-    let results = search_multiple_files(&files, query)?;
-    
-    progress.update_stage_progress(2, 1.0, Some("Search complete"))?;
-    progress.complete_stage(2)?;
-    
-    // Stage 4: Format Results  
-    progress.start_stage(3)?;
-    progress.update_stage_progress(3, 0.5, Some("Processing results"))?;
-    
-    if results.is_empty() {
-        progress.fail_stage(3, "No matches found")?;
-        GLOBAL_TASK_MONITOR.fail_task(&task_id, &format!("No matches found for: '{}'", query));
-        println!("{}", warning_text(&format!("⚠️  No matches found for: \"{}\"", query)));
-        println!("{}", dim_text("   Try a different query or search pattern"));
+/// Rank indexed code chunks by embedding similarity to `question`
+pub fn run_semantic(question: &str, path: Option<&str>, top_k: usize) -> Result<(), Box<dyn std::error::Error>> {
+    let root = Path::new(path.unwrap_or("."));
+    let index = SemanticIndex::open_existing(root)?;
+    let (client, _) = client::create_embedding_client()?;
+
+    println!("{} {}", 
+        StyledText::new("🧠 Semantic search:").fg(Color::BrightYellow).bold(),
+        StyledText::new(&format!("\"{}\"", question)).fg(Color::BrightGreen));
+    println!("{}", dim_text(&format!("   {} chunks from {} files, model {}",
+        index.store().chunk_count(), index.store().file_count(), index.model())));
+    println!();
+
+    let hits = index.query(&client, question, top_k)?;
+    if hits.is_empty() {
+        println!("{}", warning_text("⚠️  The index is empty"));
         return Ok(());
     }
 
-    let total_matches = results.iter().map(|(_, matches)| matches.len()).sum::<usize>();
-    progress.update_stage_progress(3, 1.0, Some(&format!("Found {} matches in {} files", total_matches, results.len())))?;
-    progress.complete_stage(3)?;
-    
-    // Complete the background task
-    GLOBAL_TASK_MONITOR.complete_task(&task_id, &format!("Found {} matches in {} files", total_matches, results.len()));
-    
-    // Add a success notification
-    GLOBAL_TASK_MONITOR.add_notification(
-        &format!("search-complete-{}", std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_millis()),
-        &format!("🔍 Glob search completed: {} matches found for '{}'", total_matches, query),
-        NotificationType::Success
-    );
-    
-    println!("{}", progress.render(60));
-    println!();
-    
-    println!("{} {} {} {} {}", 
-        success_text("✅ Found"),
-        StyledText::new(&total_matches.to_string()).fg(Color::BrightGreen).bold(),
-        StyledText::new("matches in").fg(Color::White),
-        StyledText::new(&results.len().to_string()).fg(Color::BrightCyan).bold(),
-        StyledText::new("files:").fg(Color::White));
-    println!();
+    for (i, hit) in hits.iter().enumerate() {
+        println!("{} {} {}", 
+            StyledText::new(&format!("📄 [{}]", i + 1)).fg(Color::BrightBlue).bold(),
+            StyledText::new(&format!("{}:{}-{}", hit.path.display(), hit.chunk.start_line, hit.chunk.end_line)).fg(Color::BrightCyan),
+            StyledText::new(&format!("(score {:.3})", hit.score)).fg(Color::BrightBlack));
 
-    for (file_index, (file_path, matches)) in results.iter().enumerate() {
-        println!("{} {} {} {}", 
-            StyledText::new(&format!("📄 [{}]", file_index + 1)).fg(Color::BrightBlue).bold(),
-            StyledText::new(file_path).fg(Color::BrightCyan),
-            StyledText::new(&format!("({} matches)", matches.len())).fg(Color::BrightBlack),
-            if matches.len() > 5 { dim_text("[showing first 5]") } else { StyledText::new("") });
-        
-        let display_matches = if matches.len() > 5 { &matches[..5] } else { matches };
-        
-        for (line_num, col, _len) in display_matches {
-            // Read the line to show context
-            if let Ok(content) = std::fs::read_to_string(&file_path) {
-                let lines: Vec<&str> = content.lines().collect();
-                if *line_num > 0 && *line_num <= lines.len() {
-                    let line = lines[*line_num - 1];
-                    let line_text = line.trim();
-                    
-                    // Show line number and content
-                    println!("    {}{} {}", 
-                        StyledText::new(&format!("{:>4}:", line_num)).fg(Color::BrightBlack),
-                        StyledText::new(&format!("{:>3}", col)).fg(Color::BrightBlack),
-                        highlight_match(line_text, query));
-                }
-            }
+        let lines: Vec<&str> = hit.chunk.text.lines().collect();
+        for (offset, line) in lines.iter().take(8).enumerate() {
+            println!("    {} {}", 
+                StyledText::new(&format!("{:>4}:", hit.chunk.start_line + offset)).fg(Color::BrightBlack),
+                line);
         }
-        if matches.len() > 5 {
-            println!("{}", dim_text(&format!("    ... and {} more matches", matches.len() - 5)));
+        if lines.len() > 8 {
+            println!("{}", dim_text(&format!("    ... {} more lines", lines.len() - 8)));
         }
         println!();
     }
-    
-    // Show ETA information if available
-    if let Some(_eta) = progress.get_eta_seconds() {
-        println!("⏰ Process completed in {:.1}s", progress.start_time.elapsed().as_secs_f64());
+
+    Ok(())
+}
+
+/// Find declarations named like `name` in supported source files
+pub fn run_symbol(name: &str, path: Option<&str>, ignore_options: IgnoreOptions) -> Result<(), Box<dyn std::error::Error>> {
+    let root = Path::new(path.unwrap_or("."));
+    let files: Vec<PathBuf> = DirectoryWalker::new(root)
+        .with_ignore_options(ignore_options)
+        .into_iter()
+        .files()
+        .filter(|file| Language::from_path(file).is_some())
+        .collect();
+
+    println!("{} {}", 
+        StyledText::new("🔣 Symbol search:").fg(Color::BrightYellow).bold(),
+        StyledText::new(name).fg(Color::BrightGreen));
+    println!("{}", dim_text(&format!("   Scanning {} source files", files.len())));
+    println!();
+
+    let matches = find_symbols(&files, name);
+    if matches.is_empty() {
+        println!("{}", warning_text(&format!("⚠️  No symbols found matching: \"{}\"", name)));
+        return Ok(());
+    }
+
+    for (file, symbol) in &matches {
+        let location = format!("{}:{}-{}", file.strip_prefix(root).unwrap_or(file).display(), symbol.start_line, symbol.end_line);
+        let parent = symbol.parent.as_ref().map(|p| format!("in {}", p)).unwrap_or_default();
+        println!("  {:<8} {} {} {}", 
+            StyledText::new(&symbol.kind.to_string()).fg(Color::BrightMagenta),
+            StyledText::new(&symbol.name).fg(Color::BrightWhite).bold(),
+            StyledText::new(&location).fg(Color::BrightCyan),
+            dim_text(&parent));
     }
 
+    println!();
+    println!("{} {} {}", 
+        success_text("✅ Found"),
+        StyledText::new(&matches.len().to_string()).fg(Color::BrightGreen).bold(),
+        StyledText::new("symbols").fg(Color::White));
+
     Ok(())
+}
+
+fn highlight_match(line: &str, query: &str) -> String {
+    if let Some(pos) = line.to_lowercase().find(&query.to_lowercase()) {
+        let before = &line[..pos];
+        let matched = &line[pos..pos + query.len()];
+        let after = &line[pos + query.len()..];
+        
+        format!("{}{}{}", 
+            before,
+            format!("{}", StyledText::new(matched).fg(Color::BrightYellow).bold()),
+            after)
+    } else {
+        line.to_string()
+    }
 }
\ No newline at end of file
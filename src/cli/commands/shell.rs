@@ -1,13 +1,21 @@
+use crate::config::ForgeConfig;
 use crate::forge_process::executor::ProcessExecutor;
 use crate::terminal::output::{success_text, info_text};
 
-pub fn run() -> Result<(), Box<dyn std::error::Error>> {
+pub fn run(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let explain_failures = match args {
+        [] => false,
+        [flag] if flag == "--explain-failure" => true,
+        _ => return Err("Usage: forge shell [--explain-failure]".into()),
+    };
+    let explain_failures = explain_failures || ForgeConfig::load().is_ok_and(|config| config.shell.explain_failures);
+
     println!("{}", info_text("🐚 Starting Forge Interactive Shell"));
     println!("Enhanced shell with safety checks, history, and AI integration");
     println!("Type 'help' for available commands, 'exit' to return to forge");
     println!();
 
-    let mut executor = ProcessExecutor::new();
+    let mut executor = ProcessExecutor::new().with_explain_failures(explain_failures);
     executor.run_interactive_shell()?;
 
     println!("{}", success_text("Returned to Forge CLI"));
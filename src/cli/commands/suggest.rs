@@ -0,0 +1,26 @@
+use std::io::{self, IsTerminal};
+
+use crate::forge_process::audit::AuditLog;
+use crate::forge_process::shell::{self, Shell};
+use crate::terminal::output::dim_text;
+
+/// `forge suggest "<request>"`: ask the model for a command, then run,
+/// edit or cancel it. Without a terminal the command is only shown.
+pub fn run(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let request = args.join(" ");
+    if request.trim().is_empty() {
+        return Err("Say what the command should do\nUsage: forge suggest \"<request>\"".into());
+    }
+
+    let mut shell = Shell::new().with_audit_log(AuditLog::configured());
+    let mut editor = io::stdin().is_terminal().then(shell::line_editor);
+    let Some(result) = shell.suggest(request.trim(), editor.as_mut())? else {
+        if editor.is_none() {
+            println!("{}", dim_text("Run forge suggest in a terminal to run or edit the command"));
+        }
+        return Ok(());
+    };
+
+    shell::print_result(&result);
+    Ok(())
+}
@@ -1,10 +1,22 @@
-use crate::forge_process::workflow::{WorkflowRunner, CommonWorkflows};
+use crate::forge_process::workflow::{WorkflowRunner, WorkflowStep, ForEach, OutputSource, CommonWorkflows};
+use crate::forge_process::workflow_file::{
+    self, WorkflowEntry, WorkflowFileError, WorkflowSource,
+};
+use crate::forge_process::workflow_history::{self, Phase, RunRecord, StepStatus};
+use crate::forge_process::workflow_generate::{self, Draft};
+use crate::forge_process::safety::RiskLevel;
+use crate::forge_process::executor::Confirmation;
+use crate::config::client;
 use crate::terminal::output::{
-    success_text, error_text,
-    EnhancedProgressBar, StatusIndicator, StatusType, Table, BorderStyle,
+    success_text, error_text, warning_text, dim_text, info_text, prompt_yes_no, StyledText, Color,
+    StatusIndicator, StatusType, Table, BorderStyle,
     TerminalControl, BoxDrawing, Spinner
 };
-use std::io::{self, Write};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
 use std::thread;
 use std::time::Duration;
 
@@ -14,41 +26,132 @@ pub fn run(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
         return Ok(());
     }
 
+    let (entries, errors) = load_workflows()?;
     let mut runner = WorkflowRunner::new();
-    
-    // Add common workflows
-    runner.add_workflow(CommonWorkflows::rust_build_and_test());
-    runner.add_workflow(CommonWorkflows::git_workflow());
+    for entry in &entries {
+        runner.add_workflow(entry.workflow.clone());
+    }
 
     match args[0].as_str() {
         "list" => {
-            show_workflow_list(&runner);
+            report_invalid(&errors);
+            show_workflow_list(&entries);
         }
         "run" => {
-            if args.len() < 2 {
+            let options = parse_run_args(&args[1..])?;
+            let run_args = options.rest;
+            if run_args.is_empty() {
                 let status = StatusIndicator::new(StatusType::Error, "Workflow name required");
                 println!("{}", status.render());
-                println!("Usage: forge workflow run <workflow-name>");
+                println!("Usage: forge workflow run <workflow-name | file.toml> [--jobs <n>] [--set NAME=value]... [--yes[=<level>] | --deny-risky | --force-dangerous]");
                 return Ok(());
             }
+            if let Some(jobs) = options.jobs {
+                runner = runner.with_jobs(jobs);
+            }
+            if let Some(confirmation) = options.confirmation {
+                runner = runner.with_confirmation(confirmation);
+            }
+            runner = runner.with_variables(options.variables);
             
-            let workflow_name = &args[1];
-            run_workflow_with_progress(&mut runner, workflow_name)?;
+            let mut workflow_name = run_args[0].clone();
+            if workflow_name.ends_with(".toml") && Path::new(&workflow_name).is_file() {
+                let workflow = workflow_file::load_workflow(Path::new(&workflow_name))?;
+                workflow_name = workflow.name.clone();
+                runner.add_workflow(workflow);
+            } else if runner.get_workflow(&workflow_name).is_none() {
+                report_invalid(&errors);
+                return Err(format!("Workflow '{}' not found (see `forge workflow list`)", workflow_name).into());
+            }
+            run_workflow_with_progress(&mut runner, &workflow_name)?;
+        }
+        "validate" => {
+            validate_workflows(args.get(1).map(Path::new), &entries, &errors)?;
         }
         "create" => {
-            if args.len() < 3 {
+            let flags = ["--user", "--force"];
+            let positional: Vec<&str> = args[1..].iter().map(|s| s.as_str()).filter(|a| !flags.contains(a)).collect();
+            if positional.len() < 2 {
                 println!("{}", error_text("Error: Workflow name and commands required"));
-                println!("Usage: forge workflow create <name> <command1> [command2] ...");
+                println!("Usage: forge workflow create <name> <command1> [command2] ... [--user] [--force]");
                 return Ok(());
             }
             
-            let workflow_name = &args[1];
-            let commands: Vec<&str> = args[2..].iter().map(|s| s.as_str()).collect();
-            
-            let workflow = runner.create_simple_workflow(workflow_name, commands);
-            runner.add_workflow(workflow);
+            let workflow_name = positional[0];
+            workflow_file::validate_name(workflow_name)?;
+            let workflow = runner.create_simple_workflow(workflow_name, positional[1..].to_vec());
+
+            let dir = if args.iter().any(|a| a == "--user") {
+                workflow_file::user_workflow_dir().ok_or("Could not determine home directory")?
+            } else {
+                project_workflow_dir()?
+            };
+            let path = dir.join(format!("{}.toml", workflow_name));
+            if path.exists() && !args.iter().any(|a| a == "--force") {
+                return Err(format!("{} already exists (use --force to overwrite)", path.display()).into());
+            }
+            workflow_file::save_workflow(&dir, &workflow)?;
             
             println!("{}", success_text(&format!("✅ Created workflow: {}", workflow_name)));
+            println!("{}", dim_text(&format!("   Saved to {}", path.display())));
+            if let Some(existing) = entries.iter().find(|e| e.workflow.name == workflow_name && e.source.path() != Some(path.as_path())) {
+                println!("{}", dim_text(&format!("   Overrides the {} workflow of the same name", existing.source.label())));
+            }
+        }
+        "show" => {
+            let explain = args.iter().any(|a| a == "--explain");
+            let name = args[1..].iter().find(|a| *a != "--explain");
+            let entry = find_entry(&entries, name, "show")?;
+            show_workflow(entry);
+            if explain {
+                explain_workflow(entry)?;
+            }
+        }
+        "generate" => {
+            generate_workflow(&args[1..], &entries)?;
+        }
+        "edit" => {
+            let entry = find_entry(&entries, args.get(1), "edit")?;
+            edit_workflow(entry)?;
+        }
+        "rm" => {
+            let entry = find_entry(&entries, args.get(1), "rm")?;
+            let path = entry.source.path()
+                .ok_or_else(|| format!("'{}' is a built-in workflow and can't be removed", entry.workflow.name))?;
+
+            let confirmed = args.iter().any(|a| a == "--yes" || a == "-y") || (io::stdin().is_terminal()
+                && prompt_yes_no(&format!("Remove workflow '{}' ({})?", entry.workflow.name, path.display()), false)?);
+            if !confirmed {
+                if !io::stdin().is_terminal() {
+                    return Err("Refusing to remove without confirmation; pass --yes".into());
+                }
+                println!("{}", dim_text("Cancelled"));
+                return Ok(());
+            }
+
+            fs::remove_file(path)?;
+            println!("{}", success_text(&format!("✅ Removed workflow: {}", entry.workflow.name)));
+        }
+        "export" => {
+            let entry = find_entry(&entries, args.get(1), "export")?;
+            let content = workflow_file::to_toml(&entry.workflow)?;
+            match args.get(2) {
+                Some(file) => {
+                    fs::write(file, content)?;
+                    println!("{}", success_text(&format!("✅ Exported '{}' to {}", entry.workflow.name, file)));
+                }
+                None => print!("{}", content),
+            }
+        }
+        "history" => {
+            show_history(&args[1..])?;
+        }
+        "show-run" => {
+            let id = args.get(1).ok_or("Run id required\nUsage: forge workflow show-run <id | latest>")?;
+            show_run(&workflow_history::find_run(&runs_dir()?, id)?);
+        }
+        "report" => {
+            write_report(&args[1..])?;
         }
         "demo" => {
             run_demo_workflow(&mut runner)?;
@@ -77,39 +180,309 @@ fn show_help() {
     // Create a table for commands
     let mut table = Table::new(vec!["Command", "Description", "Example"]).border_style(BorderStyle::Single);
     table.add_row(vec!["list", "List available workflows", "forge workflow list"]);
-    table.add_row(vec!["run <name>", "Execute a workflow", "forge workflow run rust-build-test"]);
-    table.add_row(vec!["create <name> <cmd>...", "Create new workflow", "forge workflow create my-build 'cargo build'"]);
+    table.add_row(vec!["run <name|file> [-j N]", "Execute a workflow, N steps at a time", "forge workflow run rust-build-test -j 4"]);
+    table.add_row(vec!["run <name> --set K=V", "Set a workflow variable for this run", "forge workflow run release --set VERSION=1.2"]);
+    table.add_row(vec!["run <name> --yes[=level]", "Approve risky steps up to a level without asking", "forge workflow run deploy --yes=high"]);
+    table.add_row(vec!["validate [file]", "Check workflow files for errors", "forge workflow validate"]);
+    table.add_row(vec!["create <name> <cmd>...", "Create and save a workflow", "forge workflow create my-build 'cargo build'"]);
+    table.add_row(vec!["show <name> [--explain]", "Show a workflow's steps, explained by the model", "forge workflow show release --explain"]);
+    table.add_row(vec!["generate \"<goal>\"", "Draft a workflow with the model for review", "forge workflow generate \"test and tag a release\""]);
+    table.add_row(vec!["edit <name>", "Edit a workflow in $EDITOR", "forge workflow edit my-build"]);
+    table.add_row(vec!["rm <name>", "Delete a workflow file", "forge workflow rm my-build"]);
+    table.add_row(vec!["export <name> [file]", "Print or save a workflow as TOML", "forge workflow export rust-build-test"]);
+    table.add_row(vec!["history [name] [-n N]", "List recorded runs, newest first", "forge workflow history release"]);
+    table.add_row(vec!["show-run <id|latest>", "Show a recorded run's steps and output", "forge workflow show-run latest"]);
+    table.add_row(vec!["report [id] --format F", "Write a junit or markdown report of a run", "forge workflow report --format junit"]);
     table.add_row(vec!["demo", "Run demonstration", "forge workflow demo"]);
     
     println!("{}", table.render());
+    println!();
+    println!("{}", dim_text("`create` saves to .forge/workflows; add --user for ~/.config/forge/workflows."));
+    println!("{}", dim_text("Runs are recorded in .forge/runs as JSON."));
 }
 
-fn show_workflow_list(runner: &WorkflowRunner) {
+/// Built-in workflows overlaid with those from `.forge/workflows` and
+/// `~/.config/forge/workflows`, which replace built-ins of the same name
+fn load_workflows() -> Result<(Vec<WorkflowEntry>, Vec<WorkflowFileError>), Box<dyn std::error::Error>> {
+    let cwd = std::env::current_dir()?;
+    let project_dir = workflow_file::project_workflow_dir(&cwd);
+    let user_dir = workflow_file::user_workflow_dir();
+    let discovery = workflow_file::discover(project_dir.as_deref(), user_dir.as_deref());
+
+    let mut entries: Vec<WorkflowEntry> = [CommonWorkflows::rust_build_and_test(), CommonWorkflows::git_workflow()]
+        .into_iter()
+        .filter(|builtin| !discovery.workflows.iter().any(|entry| entry.workflow.name == builtin.name))
+        .map(|workflow| WorkflowEntry { workflow, source: WorkflowSource::Builtin })
+        .collect();
+    entries.extend(discovery.workflows);
+    entries.sort_by(|a, b| a.workflow.name.cmp(&b.workflow.name));
+
+    Ok((entries, discovery.errors))
+}
+
+/// `.forge/workflows` of the enclosing project, or of the current directory
+/// if there is none yet
+fn project_workflow_dir() -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let cwd = std::env::current_dir()?;
+    Ok(workflow_file::project_workflow_dir(&cwd).unwrap_or_else(|| cwd.join(".forge").join("workflows")))
+}
+
+fn find_entry<'a>(entries: &'a [WorkflowEntry], name: Option<&String>, command: &str) -> Result<&'a WorkflowEntry, Box<dyn std::error::Error>> {
+    let name = name.ok_or_else(|| format!("Workflow name required\nUsage: forge workflow {} <name>", command))?;
+    entries
+        .iter()
+        .find(|entry| &entry.workflow.name == name)
+        .ok_or_else(|| format!("Workflow '{}' not found (see `forge workflow list`)", name).into())
+}
+
+fn show_workflow(entry: &WorkflowEntry) {
+    let workflow = &entry.workflow;
+    println!("{} {}",
+        StyledText::new("📋 Workflow:").fg(Color::BrightYellow).bold(),
+        StyledText::new(&workflow.name).fg(Color::BrightGreen).bold());
+    if let Some(description) = &workflow.description {
+        println!("   {}", description);
+    }
+    let location = entry.source.path().map(|p| format!(" ({})", p.display())).unwrap_or_default();
+    println!("{}", dim_text(&format!("   Source: {}{}", entry.source.label(), location)));
+    println!("{}", dim_text(&format!("   On failure: {}", format!("{:?}", workflow.on_failure).to_lowercase())));
+
+    if !workflow.variables.is_empty() || !workflow.required_variables.is_empty() {
+        println!();
+        println!("{}", StyledText::new("Variables:").fg(Color::BrightCyan).bold());
+        let mut variables: Vec<_> = workflow.variables.iter().collect();
+        variables.sort();
+        for name in &workflow.required_variables {
+            println!("   {} {}", StyledText::new(name).fg(Color::BrightWhite), warning_text("(required, --set)"));
+        }
+        for (name, value) in variables {
+            println!("   {} = {}", StyledText::new(name).fg(Color::BrightWhite), value);
+        }
+    }
+
+    println!();
+    println!("{}", StyledText::new("Steps:").fg(Color::BrightCyan).bold());
+    for (i, step) in workflow.steps.iter().enumerate() {
+        print_step(i + 1, step);
+    }
+
+    if !workflow.finally.is_empty() {
+        println!();
+        println!("{}", StyledText::new("Finally:").fg(Color::BrightCyan).bold());
+        for (i, step) in workflow.finally.iter().enumerate() {
+            print_step(i + 1, step);
+        }
+    }
+}
+
+fn print_step(number: usize, step: &WorkflowStep) {
+    println!("  {} {}",
+        StyledText::new(&format!("{}.", number)).fg(Color::BrightBlue),
+        StyledText::new(&step.name).fg(Color::BrightWhite).bold());
+    println!("     {} {}", dim_text("$"), StyledText::new(&step.command).fg(Color::BrightGreen));
+    if let Some(description) = &step.description {
+        println!("     {}", dim_text(description));
+    }
+    if let Some(rollback) = &step.rollback {
+        println!("     {} {}", dim_text("↩ rollback:"), StyledText::new(rollback).fg(Color::BrightYellow));
+    }
+
+    let mut details = Vec::new();
+    match &step.depends_on {
+        Some(deps) if deps.is_empty() => details.push("no dependencies".to_string()),
+        Some(deps) => details.push(format!("after {}", deps.join(", "))),
+        None => {}
+    }
+    if let Some(timeout) = step.timeout {
+        details.push(format!("timeout {}s", timeout.as_secs()));
+    }
+    if step.retry_count > 0 {
+        details.push(format!("{} retries", step.retry_count));
+    }
+    if step.continue_on_failure {
+        details.push("continues on failure".to_string());
+    }
+    for condition in &step.conditions {
+        details.push(format!("if {:?}({})", condition.condition_type, condition.value));
+    }
+    if let Some(when) = &step.when {
+        details.push(format!("when {}", when));
+    }
+    for (key, values) in &step.matrix {
+        let values: Vec<String> = values.iter().map(|value| if value.is_empty() { "\"\"".to_string() } else { value.clone() }).collect();
+        details.push(format!("matrix {} = {}", key, values.join(" | ")));
+    }
+    match &step.for_each {
+        Some(ForEach::List(items)) => details.push(format!("for each of {}", items.join(", "))),
+        Some(ForEach::Glob(pattern)) => details.push(format!("for each file in {}", pattern)),
+        Some(ForEach::Lines(lines)) => details.push(format!("for each line of {}", lines)),
+        None => {}
+    }
+    for output in &step.outputs {
+        let source = match &output.source {
+            OutputSource::Stdout => "stdout".to_string(),
+            OutputSource::Regex(pattern) => format!("regex {}", pattern),
+            OutputSource::JsonPath(path) => format!("json {}", path),
+        };
+        details.push(format!("output {} from {}", output.name, source));
+    }
+    if !details.is_empty() {
+        println!("     {}", dim_text(&details.join(", ")));
+    }
+}
+
+/// Open the workflow's file in `$VISUAL`/`$EDITOR`, re-opening it until it
+/// validates or the user gives up, in which case the original is restored.
+/// Built-in workflows are first copied into the project.
+fn edit_workflow(entry: &WorkflowEntry) -> Result<(), Box<dyn std::error::Error>> {
+    let path = match entry.source.path() {
+        Some(path) => path.to_path_buf(),
+        None => {
+            let path = workflow_file::save_workflow(&project_workflow_dir()?, &entry.workflow)?;
+            println!("{}", info_text(&format!("📝 Copied built-in workflow to {}", path.display())));
+            path
+        }
+    };
+    let original = fs::read_to_string(&path)?;
+
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+    let mut parts = editor.split_whitespace();
+    let program = parts.next().ok_or("$EDITOR is empty")?;
+    let editor_args: Vec<&str> = parts.collect();
+
+    loop {
+        let status = Command::new(program).args(&editor_args).arg(&path).status()
+            .map_err(|e| format!("Failed to start editor '{}': {}", editor, e))?;
+        if !status.success() {
+            fs::write(&path, &original)?;
+            return Err(format!("Editor exited with {}; changes discarded", status).into());
+        }
+
+        match workflow_file::load_workflow(&path) {
+            Ok(workflow) => {
+                println!("{}", success_text(&format!("✅ Saved workflow '{}' ({} steps)", workflow.name, workflow.steps.len())));
+                return Ok(());
+            }
+            Err(e) => {
+                println!("{}", error_text(&format!("❌ {}", e)));
+                if !io::stdin().is_terminal() || !prompt_yes_no("Re-open the editor?", true)? {
+                    fs::write(&path, &original)?;
+                    println!("{}", warning_text("⚠️  Changes discarded"));
+                    return Err(e.into());
+                }
+            }
+        }
+    }
+}
+
+fn report_invalid(errors: &[WorkflowFileError]) {
+    for error in errors {
+        println!("{}", warning_text(&format!("⚠️  Skipping invalid workflow: {}", error)));
+    }
+    if !errors.is_empty() {
+        println!();
+    }
+}
+
+fn show_workflow_list(entries: &[WorkflowEntry]) {
     let status = StatusIndicator::new(StatusType::Info, "Available Workflows");
     println!("{}", status.render());
     println!();
     
-    let workflows = runner.list_workflows();
-    if workflows.is_empty() {
-        let empty_status = StatusIndicator::new(StatusType::Warning, "No custom workflows available");
+    if entries.is_empty() {
+        let empty_status = StatusIndicator::new(StatusType::Warning, "No workflows available");
         println!("{}", empty_status.render());
+        return;
+    }
+
+    let mut table = Table::new(vec!["Name", "Source", "Steps", "Description"]).border_style(BorderStyle::None);
+    for entry in entries {
+        let steps = entry.workflow.steps.len().to_string();
+        let description = entry.workflow.description.as_deref().unwrap_or("");
+        table.add_row(vec![&entry.workflow.name, entry.source.label(), &steps, description]);
+    }
+    println!("{}", table.render());
+
+    let dirs: Vec<String> = entries
+        .iter()
+        .filter_map(|entry| entry.source.path().and_then(Path::parent))
+        .map(|dir| dir.display().to_string())
+        .fold(Vec::new(), |mut dirs, dir| {
+            if !dirs.contains(&dir) {
+                dirs.push(dir);
+            }
+            dirs
+        });
+    if !dirs.is_empty() {
+        println!("{}", dim_text(&format!("Loaded from: {}", dirs.join(", "))));
+    }
+}
+
+fn validate_workflows(file: Option<&Path>, entries: &[WorkflowEntry], errors: &[WorkflowFileError]) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(file) = file {
+        let workflow = workflow_file::load_workflow(file)?;
+        println!("{}", success_text(&format!("✅ {}: workflow '{}' with {} steps is valid",
+            file.display(), workflow.name, workflow.steps.len())));
+        return Ok(());
+    }
+
+    for entry in entries {
+        if let Some(path) = entry.source.path() {
+            println!("{}", success_text(&format!("✅ {}", path.display())));
+        }
+    }
+    for error in errors {
+        println!("{}", error_text(&format!("❌ {}", error)));
+    }
+
+    if errors.is_empty() {
+        Ok(())
     } else {
-        let mut table = Table::new(vec!["#", "Workflow Name"]).border_style(BorderStyle::None);
-        for (i, workflow) in workflows.iter().enumerate() {
-            table.add_row(vec![&format!("{}", i + 1), workflow]);
+        Err(format!("{} invalid workflow file(s)", errors.len()).into())
+    }
+}
+
+struct RunOptions {
+    jobs: Option<usize>,
+    confirmation: Option<Confirmation>,
+    variables: HashMap<String, String>,
+    rest: Vec<String>,
+}
+
+/// Split `--jobs <n>` / `-j <n>`, `--set NAME=value` and the confirmation
+/// flags out of the `run` arguments
+fn parse_run_args(args: &[String]) -> Result<RunOptions, Box<dyn std::error::Error>> {
+    let mut jobs = None;
+    let mut confirmation = None;
+    let mut variables = HashMap::new();
+    let mut rest = Vec::new();
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--jobs" | "-j" => {
+                let n: usize = iter.next()
+                    .and_then(|n| n.parse().ok())
+                    .filter(|&n| n > 0)
+                    .ok_or("--jobs requires a positive number")?;
+                jobs = Some(n);
+            }
+            "--set" => {
+                let assignment = iter.next().ok_or("--set requires NAME=value")?;
+                let (name, value) = assignment.split_once('=')
+                    .filter(|(name, _)| !name.is_empty())
+                    .ok_or_else(|| format!("--set expects NAME=value, got '{}'", assignment))?;
+                variables.insert(name.to_string(), value.to_string());
+            }
+            _ => match Confirmation::parse_flag(arg)? {
+                Some(flag) => confirmation = Some(flag),
+                None => rest.push(arg.clone()),
+            },
         }
-        println!("{}", table.render());
     }
-    
-    println!();
-    let builtin_status = StatusIndicator::new(StatusType::Info, "Built-in Workflows");
-    println!("{}", builtin_status.render());
-    
-    let mut builtin_table = Table::new(vec!["Name", "Description"]).border_style(BorderStyle::None);
-    builtin_table.add_row(vec!["rust-build-test", "Build and test Rust project"]);
-    builtin_table.add_row(vec!["git-commit-push", "Git add, commit, and push workflow"]);
-    
-    println!("{}", builtin_table.render());
+    Ok(RunOptions { jobs, confirmation, variables, rest })
 }
 
 fn run_workflow_with_progress(runner: &mut WorkflowRunner, workflow_name: &str) -> Result<(), Box<dyn std::error::Error>> {
@@ -118,32 +491,15 @@ fn run_workflow_with_progress(runner: &mut WorkflowRunner, workflow_name: &str)
     println!("{}", start_status.render());
     println!();
     
-    // Show a progress bar simulation (in real implementation this would track actual steps)
-    let mut progress = EnhancedProgressBar::new(100, 40)
-        .with_title(&format!("Running {}", workflow_name));
-    
-    print!("{}", TerminalControl::hide_cursor());
-    
-    // Simulate progress
-    for i in 0..=100 {
-        progress.set_progress(i);
-        progress.set_status(&format!("Step {}/5", (i / 20) + 1));
-        progress.set_rate(2.5);
-        progress.set_eta(((100 - i) / 5) as u64);
-        
-        print!("{}", TerminalControl::clear_line());
-        print!("{}", progress.render());
-        io::stdout().flush()?;
-        
-        thread::sleep(Duration::from_millis(50));
-    }
-    
-    print!("{}", TerminalControl::show_cursor());
-    println!();
-    println!();
-    
     match runner.execute_workflow(workflow_name) {
         Ok(execution) => {
+            if let Some(workflow) = runner.get_workflow(workflow_name) {
+                let record = RunRecord::from_execution(workflow, &execution);
+                match runs_dir().and_then(|dir| record.save(&dir)) {
+                    Ok(_) => println!("{}", dim_text(&format!("Recorded run {} (forge workflow show-run {})", record.id, record.id))),
+                    Err(e) => println!("{}", warning_text(&format!("⚠️  Couldn't record this run: {}", e))),
+                }
+            }
             if execution.overall_success {
                 let success_status = StatusIndicator::new(StatusType::Success, 
                     "Workflow completed successfully!");
@@ -164,6 +520,251 @@ fn run_workflow_with_progress(runner: &mut WorkflowRunner, workflow_name: &str)
     Ok(())
 }
 
+/// `generate "<goal>" [--name NAME] [--user] [--yes] [--force]`
+fn generate_workflow(args: &[String], entries: &[WorkflowEntry]) -> Result<(), Box<dyn std::error::Error>> {
+    let mut name = None;
+    let mut goal = Vec::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--name" => {
+                let value = iter.next().ok_or("--name requires a workflow name")?;
+                workflow_file::validate_name(value)?;
+                name = Some(value.as_str());
+            }
+            "--user" | "--yes" | "-y" | "--force" => {}
+            _ => goal.push(arg.as_str()),
+        }
+    }
+    let goal = goal.join(" ");
+    if goal.trim().is_empty() {
+        return Err("Describe what the workflow should do\nUsage: forge workflow generate \"<goal>\" [--name NAME] [--user] [--yes] [--force]".into());
+    }
+
+    let (client, model) = client::create_ollama_client()?;
+    println!("{}", info_text(&format!("🤖 Drafting a workflow with {}...", model)));
+    let cwd = std::env::current_dir()?;
+    let draft = workflow_generate::draft_workflow(&client, &model, &goal, name, &cwd)?;
+    if draft.attempts > 1 {
+        println!("{}", dim_text(&format!("   Took {} attempts to get a valid workflow", draft.attempts)));
+    }
+    println!();
+    show_draft(&draft);
+
+    let dir = if args.iter().any(|a| a == "--user") {
+        workflow_file::user_workflow_dir().ok_or("Could not determine home directory")?
+    } else {
+        project_workflow_dir()?
+    };
+    let path = dir.join(format!("{}.toml", draft.workflow.name));
+    if path.exists() && !args.iter().any(|a| a == "--force") {
+        return Err(format!("{} already exists (use --name to pick another name, or --force to overwrite)", path.display()).into());
+    }
+
+    let yes = args.iter().any(|a| a == "--yes" || a == "-y");
+    if yes && draft.highest_risk() == RiskLevel::Critical {
+        return Err("The draft has critical-risk commands; review it and save it without --yes".into());
+    }
+    let confirmed = yes || (io::stdin().is_terminal()
+        && prompt_yes_no(&format!("Save workflow '{}' to {}?", draft.workflow.name, path.display()), false)?);
+    if !confirmed {
+        if !io::stdin().is_terminal() {
+            println!("{}", dim_text("Not saved; pass --yes to save without asking"));
+        } else {
+            println!("{}", dim_text("Not saved"));
+        }
+        return Ok(());
+    }
+
+    workflow_file::save_workflow(&dir, &draft.workflow)?;
+    println!("{}", success_text(&format!("✅ Saved workflow: {}", draft.workflow.name)));
+    println!("{}", dim_text(&format!("   {} (run it with `forge workflow run {}`)", path.display(), draft.workflow.name)));
+    if let Some(existing) = entries.iter().find(|e| e.workflow.name == draft.workflow.name && e.source.path() != Some(path.as_path())) {
+        println!("{}", dim_text(&format!("   Overrides the {} workflow of the same name", existing.source.label())));
+    }
+    Ok(())
+}
+
+/// The drafted file and the risk of each of its commands
+fn show_draft(draft: &Draft) {
+    println!("{}", StyledText::new("📄 Draft:").fg(Color::BrightYellow).bold());
+    for line in draft.content.lines() {
+        println!("   {}", line);
+    }
+    println!();
+
+    println!("{}", StyledText::new("🛡️  Risk review:").fg(Color::BrightYellow).bold());
+    for risk in &draft.risks {
+        let label = format!("{:?}", risk.risk.level);
+        let line = format!("{:<8} {} ({}): {}", label, risk.step, risk.kind, risk.command);
+        match risk.risk.level {
+            RiskLevel::Safe => println!("   {}", success_text(&line)),
+            RiskLevel::Low => println!("   {}", dim_text(&line)),
+            RiskLevel::Medium => println!("   {}", warning_text(&line)),
+            RiskLevel::High | RiskLevel::Critical => println!("   {}", error_text(&line)),
+        }
+        if risk.risk.level != RiskLevel::Safe {
+            println!("   {}", dim_text(&format!("         {}", risk.risk.reason)));
+        }
+    }
+    println!();
+}
+
+/// Stream the model's description of a workflow
+fn explain_workflow(entry: &WorkflowEntry) -> Result<(), Box<dyn std::error::Error>> {
+    let (client, model) = client::create_ollama_client()?;
+    let prompt = workflow_generate::explain_prompt(&entry.workflow)?;
+
+    println!();
+    print!("{} ", StyledText::new("🤖 Explanation:").fg(Color::BrightBlue).bold());
+    io::stdout().flush()?;
+    let result = client.generate_stream(&model, &prompt, |chunk| {
+        print!("{}", chunk);
+        io::stdout().flush()?;
+        Ok(())
+    });
+    println!();
+
+    if let Err(e) = result {
+        println!("{}", error_text(&format!("❌ Connection error: {}", e)));
+        println!("{}", dim_text("   • Make sure Ollama is running: ollama serve"));
+        return Err(e);
+    }
+    Ok(())
+}
+
+fn runs_dir() -> Result<PathBuf, Box<dyn std::error::Error>> {
+    Ok(workflow_history::runs_dir(&std::env::current_dir()?))
+}
+
+/// `history [workflow] [--limit N]`
+fn show_history(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let mut limit = 20;
+    let mut workflow = None;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--limit" | "-n" => {
+                limit = iter.next()
+                    .and_then(|n| n.parse().ok())
+                    .filter(|&n| n > 0)
+                    .ok_or("--limit requires a positive number")?;
+            }
+            name => workflow = Some(name),
+        }
+    }
+
+    let dir = runs_dir()?;
+    let (runs, errors) = workflow_history::list_runs(&dir);
+    for (path, error) in &errors {
+        println!("{}", warning_text(&format!("⚠️  Skipping {}: {}", path.display(), error)));
+    }
+    let runs: Vec<&RunRecord> = runs.iter().filter(|run| workflow.is_none_or(|name| run.workflow == name)).collect();
+    if runs.is_empty() {
+        let status = StatusIndicator::new(StatusType::Info, match workflow {
+            Some(name) => format!("No recorded runs of '{}'", name),
+            None => "No recorded workflow runs".to_string(),
+        }.as_str());
+        println!("{}", status.render());
+        return Ok(());
+    }
+
+    let mut table = Table::new(vec!["ID", "Workflow", "Started", "Duration", "Result", "Steps", "User"]).border_style(BorderStyle::None);
+    for run in runs.iter().take(limit) {
+        let duration = format!("{:.1}s", run.duration.as_secs_f64());
+        let result = if run.success { "✅ passed" } else { "❌ failed" };
+        let passed = run.steps.iter().filter(|step| step.phase == Phase::Step && step.status == StepStatus::Success).count();
+        let total = run.steps.iter().filter(|step| step.phase == Phase::Step).count();
+        let steps = format!("{}/{}", passed, total);
+        table.add_row(vec![&run.id, &run.workflow, &run.started(), &duration, result, &steps, &run.user]);
+    }
+    println!("{}", table.render());
+    if runs.len() > limit {
+        println!("{}", dim_text(&format!("{} older run(s) not shown; use --limit", runs.len() - limit)));
+    }
+    Ok(())
+}
+
+fn show_run(run: &RunRecord) {
+    let (status_type, outcome) = if run.success { (StatusType::Success, "passed") } else { (StatusType::Error, "failed") };
+    println!("{}", StatusIndicator::new(status_type, &format!("Workflow '{}' {}", run.workflow, outcome)).render());
+    println!("Run:       {}", run.id);
+    println!("Started:   {}", run.started());
+    println!("Duration:  {:.2}s", run.duration.as_secs_f64());
+    println!("By:        {}@{}", run.user, run.host);
+    println!("Directory: {}", run.directory);
+    println!();
+
+    for step in &run.steps {
+        let phase = match step.phase {
+            Phase::Step => String::new(),
+            phase => format!(" ({})", phase.as_str()),
+        };
+        let line = format!("{} {}{} ({:.2}s)", step.status.icon(), step.name, phase, step.duration.as_secs_f64());
+        match step.status {
+            StepStatus::Success => println!("{}", success_text(&line)),
+            StepStatus::Failure => println!("{}", error_text(&line)),
+            StepStatus::Skipped | StepStatus::Cancelled => println!("{}", dim_text(&line)),
+        }
+
+        let mut details = Vec::new();
+        if let Some(code) = step.exit_code {
+            details.push(format!("exit code {}", code));
+        }
+        if step.retries > 0 {
+            details.push(format!("{} retries", step.retries));
+        }
+        if !details.is_empty() {
+            println!("{}", dim_text(&format!("     {}", details.join(", "))));
+        }
+        if let Some(message) = &step.message {
+            println!("     {}", message);
+        }
+        for (name, value) in &step.outputs {
+            println!("     Output {} = {}", name, value);
+        }
+        for (label, text) in [("stdout", &step.stdout_tail), ("stderr", &step.stderr_tail)] {
+            if text.is_empty() {
+                continue;
+            }
+            println!("{}", dim_text(&format!("     {}:", label)));
+            for line in text.lines() {
+                println!("{}", dim_text(&format!("       {}", line)));
+            }
+        }
+    }
+}
+
+/// `report [id | latest] [--format junit|markdown] [--output file]`
+fn write_report(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let mut format = "markdown".to_string();
+    let mut output = None;
+    let mut id = "latest".to_string();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--format" | "-f" => format = iter.next().ok_or("--format requires junit or markdown")?.clone(),
+            "--output" | "-o" => output = Some(iter.next().ok_or("--output requires a file")?.clone()),
+            _ => id = arg.clone(),
+        }
+    }
+
+    let run = workflow_history::find_run(&runs_dir()?, &id)?;
+    let report = match format.as_str() {
+        "junit" => workflow_history::junit_report(&run),
+        "markdown" | "md" => workflow_history::markdown_report(&run),
+        other => return Err(format!("Unknown report format '{}' (expected junit or markdown)", other).into()),
+    };
+    match output {
+        Some(file) => {
+            fs::write(&file, report)?;
+            println!("{}", success_text(&format!("✅ Wrote {} report of run {} to {}", format, run.id, file)));
+        }
+        None => print!("{}", report),
+    }
+    Ok(())
+}
+
 fn run_demo_workflow(runner: &mut WorkflowRunner) -> Result<(), Box<dyn std::error::Error>> {
     let demo_status = StatusIndicator::new(StatusType::Info, "Running workflow demonstration");
     println!("{}", demo_status.render());
@@ -15,6 +15,14 @@ pub fn create_ollama_client() -> Result<(OllamaClient, String), Box<dyn std::err
     Ok((client, model))
 }
 
+/// Client and model for computing embeddings (`llm.embedding_model`)
+pub fn create_embedding_client() -> Result<(OllamaClient, String), Box<dyn std::error::Error>> {
+    let (client, _) = create_ollama_client()?;
+    let model = get_config_or_default().llm.embedding_model;
+    
+    Ok((client, model))
+}
+
 pub fn get_config_or_default() -> ForgeConfig {
     ForgeConfig::load().unwrap_or_else(|_| ForgeConfig::default())
 }
\ No newline at end of file
@@ -9,6 +9,8 @@ pub struct ForgeConfig {
     pub llm: LLMConfig,
     pub ui: UIConfig,
     pub safety: SafetyConfig,
+    pub shell: ShellConfig,
+    pub audit: AuditConfig,
     pub api_keys: HashMap<String, String>,
 }
 
@@ -16,8 +18,11 @@ pub struct ForgeConfig {
 pub struct LLMConfig {
     pub default_provider: String,
     pub default_model: String,
+    pub embedding_model: String,
     pub temperature: f32,
     pub max_tokens: u32,
+    /// Tokens the model accepts per request, prompt included
+    pub context_window: u32,
     pub timeout_seconds: u64,
 }
 
@@ -37,12 +42,30 @@ pub struct SafetyConfig {
     pub max_file_size_mb: u32,
 }
 
+#[derive(Debug, Clone, Default)]
+pub struct ShellConfig {
+    /// Ask the model why a command failed and how to fix it
+    pub explain_failures: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct AuditConfig {
+    /// Record every command forge runs in `~/.config/forge/audit.jsonl`
+    pub enabled: bool,
+    /// Rotate the log once it grows past this size
+    pub max_size_mb: u32,
+    /// Rotated logs to keep, as `audit.jsonl.1` and up
+    pub max_files: u32,
+}
+
 impl Default for ForgeConfig {
     fn default() -> Self {
         Self {
             llm: LLMConfig::default(),
             ui: UIConfig::default(),
             safety: SafetyConfig::default(),
+            shell: ShellConfig::default(),
+            audit: AuditConfig::default(),
             api_keys: HashMap::new(),
         }
     }
@@ -53,8 +76,10 @@ impl Default for LLMConfig {
         Self {
             default_provider: "ollama".to_string(),
             default_model: "llama3.2".to_string(),
+            embedding_model: "nomic-embed-text".to_string(),
             temperature: 0.7,
             max_tokens: 4096,
+            context_window: 8192,
             timeout_seconds: 30,
         }
     }
@@ -86,6 +111,16 @@ impl Default for SafetyConfig {
     }
 }
 
+impl Default for AuditConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_size_mb: 10,
+            max_files: 5,
+        }
+    }
+}
+
 impl ForgeConfig {
     pub fn load() -> Result<Self, ConfigError> {
         let config_path = Self::config_path()?;
@@ -122,13 +157,18 @@ impl ForgeConfig {
     }
 
     fn config_path() -> Result<PathBuf, ConfigError> {
-        let home = dirs::home_dir()
+        let dir = config_dir()
             .ok_or_else(|| ConfigError::PathError("Could not determine home directory".to_string()))?;
         
-        Ok(home.join(".config").join("forge").join("config.toml"))
+        Ok(dir.join("config.toml"))
     }
 }
 
+/// Where forge keeps its config and state, `~/.config/forge`
+pub fn config_dir() -> Option<PathBuf> {
+    dirs::home_dir().map(|home| home.join(".config").join("forge"))
+}
+
 #[derive(Debug)]
 pub enum ConfigError {
     IoError(String),
@@ -155,6 +195,8 @@ struct TomlConfig {
     llm: Option<TomlLLMConfig>,
     ui: Option<TomlUIConfig>,
     safety: Option<TomlSafetyConfig>,
+    shell: Option<TomlShellConfig>,
+    audit: Option<TomlAuditConfig>,
     api_keys: Option<HashMap<String, String>>,
 }
 
@@ -162,8 +204,10 @@ struct TomlConfig {
 struct TomlLLMConfig {
     default_provider: Option<String>,
     default_model: Option<String>,
+    embedding_model: Option<String>,
     temperature: Option<f32>,
     max_tokens: Option<u32>,
+    context_window: Option<u32>,
     timeout_seconds: Option<u64>,
 }
 
@@ -183,6 +227,18 @@ struct TomlSafetyConfig {
     max_file_size_mb: Option<u32>,
 }
 
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+struct TomlShellConfig {
+    explain_failures: Option<bool>,
+}
+
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+struct TomlAuditConfig {
+    enabled: Option<bool>,
+    max_size_mb: Option<u32>,
+    max_files: Option<u32>,
+}
+
 impl From<TomlConfig> for ForgeConfig {
     fn from(toml: TomlConfig) -> Self {
         let default = ForgeConfig::default();
@@ -195,12 +251,18 @@ impl From<TomlConfig> for ForgeConfig {
                 default_model: toml.llm.as_ref()
                     .and_then(|l| l.default_model.clone())
                     .unwrap_or(default.llm.default_model),
+                embedding_model: toml.llm.as_ref()
+                    .and_then(|l| l.embedding_model.clone())
+                    .unwrap_or(default.llm.embedding_model),
                 temperature: toml.llm.as_ref()
                     .and_then(|l| l.temperature)
                     .unwrap_or(default.llm.temperature),
                 max_tokens: toml.llm.as_ref()
                     .and_then(|l| l.max_tokens)
                     .unwrap_or(default.llm.max_tokens),
+                context_window: toml.llm.as_ref()
+                    .and_then(|l| l.context_window)
+                    .unwrap_or(default.llm.context_window),
                 timeout_seconds: toml.llm.as_ref()
                     .and_then(|l| l.timeout_seconds)
                     .unwrap_or(default.llm.timeout_seconds),
@@ -233,6 +295,22 @@ impl From<TomlConfig> for ForgeConfig {
                     .and_then(|s| s.max_file_size_mb)
                     .unwrap_or(default.safety.max_file_size_mb),
             },
+            shell: ShellConfig {
+                explain_failures: toml.shell.as_ref()
+                    .and_then(|s| s.explain_failures)
+                    .unwrap_or(default.shell.explain_failures),
+            },
+            audit: AuditConfig {
+                enabled: toml.audit.as_ref()
+                    .and_then(|a| a.enabled)
+                    .unwrap_or(default.audit.enabled),
+                max_size_mb: toml.audit.as_ref()
+                    .and_then(|a| a.max_size_mb)
+                    .unwrap_or(default.audit.max_size_mb),
+                max_files: toml.audit.as_ref()
+                    .and_then(|a| a.max_files)
+                    .unwrap_or(default.audit.max_files),
+            },
             api_keys: toml.api_keys.unwrap_or_default(),
         }
     }
@@ -244,8 +322,10 @@ impl From<ForgeConfig> for TomlConfig {
             llm: Some(TomlLLMConfig {
                 default_provider: Some(config.llm.default_provider),
                 default_model: Some(config.llm.default_model),
+                embedding_model: Some(config.llm.embedding_model),
                 temperature: Some(config.llm.temperature),
                 max_tokens: Some(config.llm.max_tokens),
+                context_window: Some(config.llm.context_window),
                 timeout_seconds: Some(config.llm.timeout_seconds),
             }),
             ui: Some(TomlUIConfig {
@@ -260,6 +340,14 @@ impl From<ForgeConfig> for TomlConfig {
                 restricted_paths: Some(config.safety.restricted_paths),
                 max_file_size_mb: Some(config.safety.max_file_size_mb),
             }),
+            shell: Some(TomlShellConfig {
+                explain_failures: Some(config.shell.explain_failures),
+            }),
+            audit: Some(TomlAuditConfig {
+                enabled: Some(config.audit.enabled),
+                max_size_mb: Some(config.audit.max_size_mb),
+                max_files: Some(config.audit.max_files),
+            }),
             api_keys: if config.api_keys.is_empty() { None } else { Some(config.api_keys) },
         }
     }
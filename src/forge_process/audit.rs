@@ -0,0 +1,473 @@
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::config::{self, AuditConfig, ForgeConfig};
+use crate::http::json::{parse_json, JsonValue};
+use crate::index::store::content_hash;
+use crate::terminal::output::warning_text;
+use super::safety::{CommandRisk, RiskLevel};
+use super::shell::CommandResult;
+use super::records::{format_utc, parse_utc, Fields};
+
+/// Who asked for a command to run
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Initiator {
+    /// Typed at `forge exec` or the shell
+    User,
+    /// A workflow step
+    Workflow,
+    /// Suggested by the model, like `? ...` commands and failure fixes
+    Agent,
+}
+
+/// How a command got past the safety check
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Approval {
+    /// Rated safe
+    NotNeeded,
+    /// Run by the confirmation strategy without asking, like `--yes`
+    Automatic,
+    /// The user said yes when asked
+    Confirmed,
+    /// Checked before running, like workflow steps before the workflow starts
+    Earlier,
+    /// Typed in the shell, which runs what it's given
+    Typed,
+    /// Not run
+    Refused,
+}
+
+/// How an audited command ended
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Status {
+    Ok,
+    Failed,
+    Refused,
+}
+
+impl Initiator {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Initiator::User => "user",
+            Initiator::Workflow => "workflow",
+            Initiator::Agent => "agent",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        [Initiator::User, Initiator::Workflow, Initiator::Agent].into_iter().find(|initiator| initiator.as_str() == s)
+    }
+}
+
+impl Approval {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Approval::NotNeeded => "not-needed",
+            Approval::Automatic => "auto-approved",
+            Approval::Confirmed => "confirmed",
+            Approval::Earlier => "approved-earlier",
+            Approval::Typed => "typed",
+            Approval::Refused => "refused",
+        }
+    }
+
+    fn parse(s: &str) -> Option<Self> {
+        [Approval::NotNeeded, Approval::Automatic, Approval::Confirmed, Approval::Earlier, Approval::Typed, Approval::Refused]
+            .into_iter()
+            .find(|approval| approval.as_str() == s)
+    }
+}
+
+impl Status {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Status::Ok => "ok",
+            Status::Failed => "failed",
+            Status::Refused => "refused",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        [Status::Ok, Status::Failed, Status::Refused].into_iter().find(|status| status.as_str() == s)
+    }
+}
+
+/// One line of the audit log
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuditEntry {
+    /// Seconds since the epoch, when the command was checked
+    pub timestamp: u64,
+    pub command: String,
+    pub cwd: String,
+    pub user: String,
+    pub initiator: Initiator,
+    pub risk: RiskLevel,
+    pub reason: String,
+    /// The confirmation strategy, like `interactive` or `auto-approve up to medium`
+    pub confirmation: String,
+    pub approval: Approval,
+    /// `None` when the command was refused or couldn't be started
+    pub exit_code: Option<i32>,
+    pub duration: Duration,
+    /// FNV-1a of stdout and stderr, when they were captured
+    pub output_hash: Option<String>,
+}
+
+impl AuditEntry {
+    /// An entry for a command about to run, or refused, in `cwd`
+    pub fn new(command: &str, cwd: &str, initiator: Initiator, risk: &CommandRisk, confirmation: &str, approval: Approval) -> Self {
+        AuditEntry {
+            timestamp: SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs(),
+            command: command.to_string(),
+            cwd: cwd.to_string(),
+            user: std::env::var("USER").or_else(|_| std::env::var("USERNAME")).unwrap_or_else(|_| "unknown".to_string()),
+            initiator,
+            risk: risk.level.clone(),
+            reason: risk.reason.clone(),
+            confirmation: confirmation.to_string(),
+            approval,
+            exit_code: None,
+            duration: Duration::ZERO,
+            output_hash: None,
+        }
+    }
+
+    /// Fill in how the command ended. Output that went to the terminal
+    /// instead of being captured isn't hashed.
+    pub fn finished(mut self, result: &CommandResult, duration: Duration) -> Self {
+        self.exit_code = Some(result.exit_code);
+        self.duration = duration;
+        if !result.stdout.is_empty() || !result.stderr.is_empty() {
+            let mut output = result.stdout.as_bytes().to_vec();
+            output.push(0);
+            output.extend_from_slice(result.stderr.as_bytes());
+            self.output_hash = Some(format!("{:016x}", content_hash(&output)));
+        }
+        self
+    }
+
+    /// Record a command that errored before it could run, without an exit code
+    pub fn not_started(mut self, duration: Duration) -> Self {
+        self.exit_code = None;
+        self.duration = duration;
+        self
+    }
+
+    pub fn status(&self) -> Status {
+        match (self.approval, self.exit_code) {
+            (Approval::Refused, _) => Status::Refused,
+            (_, Some(0)) => Status::Ok,
+            _ => Status::Failed,
+        }
+    }
+
+    /// When the command ran, like `2026-10-18 15:30:12 UTC`
+    pub fn time(&self) -> String {
+        format_utc(self.timestamp).replace('T', " ").replace('Z', " UTC")
+    }
+
+    pub fn to_json(&self) -> String {
+        let string = |s: &str| JsonValue::String(s.to_string());
+        let optional = |s: Option<&str>| s.map_or(JsonValue::Null, string);
+
+        let mut risk = HashMap::new();
+        risk.insert("level".to_string(), string(&level_name(&self.risk)));
+        risk.insert("reason".to_string(), string(&self.reason));
+
+        let mut confirmation = HashMap::new();
+        confirmation.insert("strategy".to_string(), string(&self.confirmation));
+        confirmation.insert("decision".to_string(), string(self.approval.as_str()));
+
+        let mut fields = HashMap::new();
+        fields.insert("time".to_string(), string(&format_utc(self.timestamp)));
+        fields.insert("command".to_string(), string(&self.command));
+        fields.insert("cwd".to_string(), string(&self.cwd));
+        fields.insert("user".to_string(), string(&self.user));
+        fields.insert("initiator".to_string(), string(self.initiator.as_str()));
+        fields.insert("risk".to_string(), JsonValue::Object(risk));
+        fields.insert("confirmation".to_string(), JsonValue::Object(confirmation));
+        fields.insert("exit_code".to_string(), self.exit_code.map_or(JsonValue::Null, |code| JsonValue::Number(code as f64)));
+        fields.insert("duration_ms".to_string(), JsonValue::Number(self.duration.as_millis() as f64));
+        fields.insert("output_hash".to_string(), optional(self.output_hash.as_deref()));
+        JsonValue::Object(fields).to_string()
+    }
+
+    pub fn from_json(text: &str) -> Result<Self, String> {
+        let document = parse_json(text).map_err(|e| format!("not valid JSON: {}", e.0))?;
+        let entry = Fields::of(&document, "entry")?;
+        let risk = Fields::of(entry.get("risk")?, "risk")?;
+        let confirmation = Fields::of(entry.get("confirmation")?, "confirmation")?;
+
+        let time = entry.string("time")?;
+        let initiator = entry.string("initiator")?;
+        let decision = confirmation.string("decision")?;
+        Ok(AuditEntry {
+            timestamp: parse_utc(&time).ok_or_else(|| format!("bad time '{}'", time))?,
+            command: entry.string("command")?,
+            cwd: entry.string("cwd")?,
+            user: entry.string("user")?,
+            initiator: Initiator::parse(&initiator).ok_or_else(|| format!("unknown initiator '{}'", initiator))?,
+            risk: risk.string("level")?.parse()?,
+            reason: risk.string("reason")?,
+            confirmation: confirmation.string("strategy")?,
+            approval: Approval::parse(&decision).ok_or_else(|| format!("unknown decision '{}'", decision))?,
+            exit_code: entry.optional_number("exit_code")?.map(|code| code as i32),
+            duration: Duration::from_millis(entry.number("duration_ms")? as u64),
+            output_hash: entry.optional_string("output_hash")?,
+        })
+    }
+}
+
+/// Which entries `forge audit` shows
+#[derive(Debug, Clone, Default)]
+pub struct AuditQuery {
+    /// Entries from this time on, in seconds since the epoch
+    pub since: Option<u64>,
+    /// Entries at least this risky
+    pub risk: Option<RiskLevel>,
+    pub status: Option<Status>,
+    pub initiator: Option<Initiator>,
+}
+
+impl AuditQuery {
+    pub fn matches(&self, entry: &AuditEntry) -> bool {
+        self.since.is_none_or(|since| entry.timestamp >= since)
+            && self.risk.as_ref().is_none_or(|risk| entry.risk >= *risk)
+            && self.status.is_none_or(|status| entry.status() == status)
+            && self.initiator.is_none_or(|initiator| entry.initiator == initiator)
+    }
+}
+
+/// The append-only log at `audit.jsonl`, one JSON entry per line. Once
+/// it passes `max_bytes` it becomes `audit.jsonl.1`, the older ones move
+/// up one and the oldest past `max_files` is deleted.
+#[derive(Debug, Clone)]
+pub struct AuditLog {
+    path: PathBuf,
+    max_bytes: u64,
+    max_files: u32,
+}
+
+impl AuditLog {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        let defaults = AuditConfig::default();
+        AuditLog {
+            path: path.into(),
+            max_bytes: defaults.max_size_mb as u64 * 1024 * 1024,
+            max_files: defaults.max_files,
+        }
+    }
+
+    pub fn with_rotation(mut self, max_bytes: u64, max_files: u32) -> Self {
+        self.max_bytes = max_bytes.max(1);
+        self.max_files = max_files;
+        self
+    }
+
+    /// The log as the config sets it up, or `None` when auditing is off.
+    /// Tests never write to it.
+    pub fn configured() -> Option<Self> {
+        if cfg!(test) {
+            return None;
+        }
+        let config = ForgeConfig::load().unwrap_or_default().audit;
+        if !config.enabled {
+            return None;
+        }
+        Some(AuditLog::new(default_path()?).with_rotation(config.max_size_mb as u64 * 1024 * 1024, config.max_files))
+    }
+
+    #[allow(dead_code)]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Add an entry, rotating first if it would take the log past its size
+    pub fn append(&self, entry: &AuditEntry) -> io::Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let line = entry.to_json() + "\n";
+        let size = fs::metadata(&self.path).map(|metadata| metadata.len()).unwrap_or(0);
+        if size > 0 && size + line.len() as u64 > self.max_bytes {
+            self.rotate()?;
+        }
+        // One write per entry, so entries from concurrent workflow steps
+        // don't interleave
+        OpenOptions::new().create(true).append(true).open(&self.path)?.write_all(line.as_bytes())
+    }
+
+    /// Add an entry, warning instead of failing when the log can't be written
+    pub fn record(&self, entry: &AuditEntry) {
+        if let Err(e) = self.append(entry) {
+            eprintln!("{}", warning_text(&format!("⚠️  Couldn't write the audit log {}: {}", self.path.display(), e)));
+        }
+    }
+
+    fn rotate(&self) -> io::Result<()> {
+        if self.max_files == 0 {
+            return fs::remove_file(&self.path);
+        }
+        let _ = fs::remove_file(self.rotated(self.max_files));
+        for n in (1..self.max_files).rev() {
+            let from = self.rotated(n);
+            if from.exists() {
+                fs::rename(from, self.rotated(n + 1))?;
+            }
+        }
+        fs::rename(&self.path, self.rotated(1))
+    }
+
+    fn rotated(&self, n: u32) -> PathBuf {
+        let mut name = self.path.clone().into_os_string();
+        name.push(format!(".{}", n));
+        PathBuf::from(name)
+    }
+
+    /// The log and its rotated files that exist, oldest first
+    pub fn files(&self) -> Vec<PathBuf> {
+        (1..=self.max_files).rev()
+            .map(|n| self.rotated(n))
+            .chain([self.path.clone()])
+            .filter(|path| path.is_file())
+            .collect()
+    }
+
+    /// Every entry, oldest first, and the lines that couldn't be read as
+    /// `(file, line number, error)`
+    pub fn read(&self) -> (Vec<AuditEntry>, Vec<(PathBuf, usize, String)>) {
+        let mut entries = Vec::new();
+        let mut errors = Vec::new();
+        for path in self.files() {
+            let text = match fs::read_to_string(&path) {
+                Ok(text) => text,
+                Err(e) => {
+                    errors.push((path, 0, e.to_string()));
+                    continue;
+                }
+            };
+            for (i, line) in text.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+                match AuditEntry::from_json(line) {
+                    Ok(entry) => entries.push(entry),
+                    Err(e) => errors.push((path.clone(), i + 1, e)),
+                }
+            }
+        }
+        (entries, errors)
+    }
+}
+
+/// `~/.config/forge/audit.jsonl`
+pub fn default_path() -> Option<PathBuf> {
+    config::config_dir().map(|dir| dir.join("audit.jsonl"))
+}
+
+/// A `--since` value: an age like `30m`, `12h` or `7d`, a date like
+/// `2026-10-18`, or a time like `2026-10-18T15:30:00Z`
+pub fn parse_since(value: &str, now: u64) -> Option<u64> {
+    if value.contains('T') {
+        return parse_utc(value);
+    }
+    if let Some((amount, unit)) = value.split_at_checked(value.len().saturating_sub(1)) {
+        if let Ok(amount) = amount.parse::<u64>() {
+            let seconds = match unit {
+                "s" => 1,
+                "m" => 60,
+                "h" => 3600,
+                "d" => 86_400,
+                "w" => 7 * 86_400,
+                _ => return None,
+            };
+            return Some(now.saturating_sub(amount.saturating_mul(seconds)));
+        }
+    }
+    parse_utc(&format!("{}T00:00:00Z", value))
+}
+
+fn level_name(level: &RiskLevel) -> String {
+    format!("{:?}", level).to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(command: &str, level: RiskLevel, approval: Approval, exit_code: Option<i32>) -> AuditEntry {
+        let risk = CommandRisk { level, reason: "Test".to_string(), suggestions: Vec::new() };
+        let mut entry = AuditEntry::new(command, "/work", Initiator::User, &risk, "interactive", approval);
+        entry.exit_code = exit_code;
+        entry
+    }
+
+    #[test]
+    fn test_json_round_trip_and_status() {
+        let risk = CommandRisk { level: RiskLevel::High, reason: "Removes files \"recursively\"".to_string(), suggestions: Vec::new() };
+        let result = CommandResult { success: false, exit_code: 2, stdout: "out\n".to_string(), stderr: "err\n".to_string(), is_exit: false };
+        let audited = AuditEntry::new("rm -rf build", "/work", Initiator::Agent, &risk, "auto-approve up to high", Approval::Automatic)
+            .finished(&result, Duration::from_millis(1500));
+        assert_eq!(audited.output_hash.as_ref().map(String::len), Some(16));
+        assert!(!audited.to_json().contains('\n'));
+        assert_eq!(AuditEntry::from_json(&audited.to_json()).unwrap(), audited);
+        assert_eq!(audited.status(), Status::Failed);
+
+        let other = CommandResult { stdout: "out\nerr\n".to_string(), stderr: String::new(), ..result };
+        assert_ne!(audited.clone().finished(&other, Duration::ZERO).output_hash, audited.output_hash);
+
+        assert_eq!(entry("ls", RiskLevel::Safe, Approval::NotNeeded, Some(0)).status(), Status::Ok);
+        assert_eq!(entry("rm -rf /", RiskLevel::Critical, Approval::Refused, None).status(), Status::Refused);
+        assert!(AuditEntry::from_json("{\"command\": \"ls\"}").unwrap_err().contains("missing"));
+    }
+
+    #[test]
+    fn test_queries() {
+        let mut old = entry("make", RiskLevel::Low, Approval::Confirmed, Some(0));
+        old.timestamp = parse_utc("2026-10-01T12:00:00Z").unwrap();
+        let mut risky = entry("rm -rf target", RiskLevel::High, Approval::Refused, None);
+        risky.initiator = Initiator::Workflow;
+        let failed = entry("cargo test", RiskLevel::Safe, Approval::NotNeeded, Some(101));
+        let entries = [old, risky, failed];
+        let commands = |query: AuditQuery| -> Vec<&str> {
+            entries.iter().filter(|entry| query.matches(entry)).map(|entry| entry.command.as_str()).collect()
+        };
+
+        assert_eq!(commands(AuditQuery::default()).len(), 3);
+        assert_eq!(commands(AuditQuery { risk: Some(RiskLevel::Low), ..Default::default() }), ["make", "rm -rf target"]);
+        assert_eq!(commands(AuditQuery { status: Some(Status::Failed), ..Default::default() }), ["cargo test"]);
+        assert_eq!(commands(AuditQuery { initiator: Some(Initiator::Workflow), ..Default::default() }), ["rm -rf target"]);
+        let since = parse_since("2026-10-02", 0);
+        assert_eq!(commands(AuditQuery { since, ..Default::default() }), ["rm -rf target", "cargo test"]);
+
+        assert_eq!(parse_since("2h", 10_000), Some(10_000 - 7200));
+        assert_eq!(parse_since("7d", 0), Some(0));
+        assert_eq!(parse_since("2026-10-18T15:30:00Z", 0), parse_utc("2026-10-18T15:30:00Z"));
+        assert_eq!(parse_since("soon", 0), None);
+        assert_eq!(parse_since("3y", 0), None);
+    }
+
+    #[test]
+    fn test_append_and_rotate() {
+        let dir = tempfile::tempdir().unwrap();
+        let line = entry("echo 0", RiskLevel::Safe, Approval::NotNeeded, Some(0)).to_json().len() as u64 + 1;
+        // Room for two entries per file, and two rotated files
+        let log = AuditLog::new(dir.path().join("logs").join("audit.jsonl")).with_rotation(line * 2, 2);
+        for i in 0..7 {
+            log.append(&entry(&format!("echo {}", i), RiskLevel::Safe, Approval::NotNeeded, Some(0))).unwrap();
+        }
+
+        assert_eq!(log.files().len(), 3);
+        assert!(!dir.path().join("logs").join("audit.jsonl.3").exists());
+        let (entries, errors) = log.read();
+        assert!(errors.is_empty());
+        let commands: Vec<&str> = entries.iter().map(|entry| entry.command.as_str()).collect();
+        assert_eq!(commands, ["echo 2", "echo 3", "echo 4", "echo 5", "echo 6"]);
+
+        fs::write(log.path(), "not json\n").unwrap();
+        let (entries, errors) = log.read();
+        assert_eq!(entries.len(), 4);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].1, 1);
+    }
+}
@@ -0,0 +1,291 @@
+use std::env;
+use std::path::Path;
+
+use crate::http::client::OllamaClient;
+use super::safety::{CommandRisk, SafetyChecker};
+use super::shell::ShellEnvironment;
+use super::shell_completion;
+use super::shell_parser;
+use super::workflow_generate::{self, generate_until_valid};
+
+/// How much of a failed command's stderr the model sees
+const STDERR_TAIL_LINES: usize = 40;
+const STDERR_TAIL_CHARS: usize = 4000;
+
+/// Tools the model is told about when they are on the path
+const KNOWN_TOOLS: [&str; 24] = [
+    "git", "cargo", "rustc", "find", "grep", "rg", "fd", "sed", "awk", "jq", "xargs", "sort",
+    "du", "df", "tar", "curl", "wget", "make", "python3", "node", "npm", "docker", "kubectl", "gh",
+];
+
+/// What the model knows about where the command will run
+#[derive(Debug, Clone)]
+pub struct CommandContext {
+    pub working_directory: String,
+    pub os: &'static str,
+    pub branch: Option<String>,
+    /// Which of `KNOWN_TOOLS` are installed
+    pub tools: Vec<String>,
+    /// The working directory's top-level entries
+    pub entries: String,
+}
+
+impl CommandContext {
+    pub fn from_environment(environment: &ShellEnvironment) -> Self {
+        let directory = Path::new(&environment.working_directory);
+        let path = environment.get_variable("PATH").cloned().unwrap_or_default();
+        let tools = KNOWN_TOOLS
+            .iter()
+            .filter(|tool| env::split_paths(&path).any(|dir| dir.join(tool).is_file()))
+            .map(|tool| tool.to_string())
+            .collect();
+        CommandContext {
+            working_directory: environment.working_directory.clone(),
+            os: env::consts::OS,
+            branch: shell_completion::current_branch(directory),
+            tools,
+            entries: workflow_generate::project_summary(directory),
+        }
+    }
+}
+
+/// A command proposed by the model, with its risk
+#[derive(Debug)]
+pub struct Suggestion {
+    pub command: String,
+    pub explanation: String,
+    pub risk: CommandRisk,
+    /// Model calls it took to get a command that parses
+    pub attempts: usize,
+}
+
+/// A command that exited non-zero
+#[derive(Debug)]
+pub struct Failure<'a> {
+    pub command: &'a str,
+    pub exit_code: i32,
+    pub stderr: &'a str,
+}
+
+/// The model's view of why a command failed
+#[derive(Debug)]
+pub struct Diagnosis {
+    pub diagnosis: String,
+    /// A corrected command, when the model has one
+    pub fix: Option<String>,
+    pub risk: Option<CommandRisk>,
+}
+
+/// Ask the model for a command that does `request`, rated by `checker`.
+/// An answer without a command that forge's shell can parse is sent back
+/// a few times.
+pub fn suggest_command(client: &OllamaClient, model: &str, request: &str, context: &CommandContext, checker: &SafetyChecker) -> Result<Suggestion, Box<dyn std::error::Error>> {
+    let prompt = suggest_prompt(request, context);
+    let ((command, explanation), attempts) = generate_until_valid(
+        client, model, &prompt, "suggest a usable command", "Reply in the format above.", parse_answer,
+    )?;
+    Ok(Suggestion { risk: checker.assess_command(&command), command, explanation, attempts })
+}
+
+/// Ask the model why `failure` happened and for a corrected command,
+/// retrying like `suggest_command` when the fix doesn't parse
+pub fn diagnose_failure(client: &OllamaClient, model: &str, failure: &Failure, context: &CommandContext, checker: &SafetyChecker) -> Result<Diagnosis, Box<dyn std::error::Error>> {
+    let prompt = diagnose_prompt(failure, context);
+    let ((diagnosis, fix), _) = generate_until_valid(
+        client, model, &prompt, "diagnose the failure", "Reply in the format above.", parse_diagnosis,
+    )?;
+    Ok(Diagnosis { risk: fix.as_deref().map(|fix| checker.assess_command(fix)), diagnosis, fix })
+}
+
+/// What the model is told about where commands run
+fn describe_context(context: &CommandContext) -> String {
+    let tools = if context.tools.is_empty() { "none of the usual ones".to_string() } else { context.tools.join(", ") };
+    let branch = match &context.branch {
+        Some(branch) => format!("It is a git repository on branch {}.\n", branch),
+        None => String::new(),
+    };
+    format!(
+        "Commands run on {} in {}.\n{}\
+        Installed tools: {}.\n\
+        The directory contains:\n{}\n\n\
+        Commands run in forge's own shell: quotes, $VARS, pipes, `&&`, `||`, `;`, `>`, `>>`, `<`, `2>&1` and ( subshells ) work; \
+        globs and $(command substitution) don't, so use find or the tool's own options instead. \
+        Prefer the least destructive command that does the job.",
+        context.os, context.working_directory, branch, tools, context.entries
+    )
+}
+
+fn suggest_prompt(request: &str, context: &CommandContext) -> String {
+    format!(
+        "You turn requests into a single shell command for the forge CLI.\n\n{}\n\n\
+        Request: {}\n\n\
+        Reply in exactly this format:\n\
+        COMMAND: <the command on one line>\n\
+        EXPLANATION: <one or two sentences on what it does>",
+        describe_context(context), request
+    )
+}
+
+fn diagnose_prompt(failure: &Failure, context: &CommandContext) -> String {
+    let stderr = tail(failure.stderr);
+    let stderr = if stderr.trim().is_empty() { "(nothing)" } else { stderr };
+    format!(
+        "You diagnose failed shell commands for the forge CLI.\n\n{}\n\n\
+        This command exited with status {}:\n{}\n\n\
+        The end of what it wrote to stderr:\n{}\n\n\
+        Explain the likely cause and give a corrected command if one would help.\n\
+        Reply in exactly this format:\n\
+        DIAGNOSIS: <what went wrong, in two or three sentences>\n\
+        FIX: <the corrected command on one line, or NONE>",
+        describe_context(context), failure.exit_code, failure.command, stderr
+    )
+}
+
+/// The last `STDERR_TAIL_LINES` lines of `text`, at most `STDERR_TAIL_CHARS` long
+fn tail(text: &str) -> &str {
+    let text = text.trim_end();
+    let start = text.rmatch_indices('\n').nth(STDERR_TAIL_LINES - 1).map_or(0, |(i, _)| i + 1);
+    let mut start = start.max(text.len().saturating_sub(STDERR_TAIL_CHARS));
+    while !text.is_char_boundary(start) {
+        start += 1;
+    }
+    &text[start..]
+}
+
+/// The text after each of `labels` in a model's answer, including the
+/// non-empty lines that follow up to the next label
+fn parse_fields<const N: usize>(response: &str, labels: [&str; N]) -> [String; N] {
+    let mut fields: [Vec<&str>; N] = std::array::from_fn(|_| Vec::new());
+    let mut current = None;
+    for line in response.lines() {
+        let trimmed = line.trim();
+        match labels.iter().enumerate().find_map(|(i, label)| strip_label(trimmed, label).map(|rest| (i, rest))) {
+            Some((i, rest)) => {
+                fields[i] = vec![rest];
+                current = Some(i);
+            }
+            None => {
+                if let (Some(i), false) = (current, trimmed.is_empty()) {
+                    fields[i].push(trimmed);
+                }
+            }
+        }
+    }
+    fields.map(|lines| lines.join("\n").trim().to_string())
+}
+
+/// The command on the first line of a field, without code quotes. It has
+/// to parse.
+fn parse_command(field: &str) -> Result<String, String> {
+    let command = field.lines().next().unwrap_or_default().trim_matches('`').trim().to_string();
+    match shell_parser::parse(&command) {
+        Ok(_) => Ok(command),
+        Err(e) => Err(format!("`{}` doesn't parse: {}", command, e)),
+    }
+}
+
+/// The command and explanation in a model's answer
+fn parse_answer(response: &str) -> Result<(String, String), String> {
+    let [command, explanation] = parse_fields(response, ["COMMAND:", "EXPLANATION:"]);
+    if command.is_empty() {
+        return Err("no COMMAND: line".to_string());
+    }
+    Ok((parse_command(&command)?, explanation.replace('\n', " ")))
+}
+
+/// The diagnosis and fix in a model's answer; `FIX: NONE` means no fix
+fn parse_diagnosis(response: &str) -> Result<(String, Option<String>), String> {
+    let [diagnosis, fix] = parse_fields(response, ["DIAGNOSIS:", "FIX:"]);
+    if diagnosis.is_empty() {
+        return Err("no DIAGNOSIS: line".to_string());
+    }
+    let fix = match parse_command(&fix)?.as_str() {
+        "" | "NONE" | "None" | "none" => None,
+        fix => Some(fix.to_string()),
+    };
+    Ok((diagnosis.replace('\n', " "), fix))
+}
+
+/// `line` without a case-insensitive `label`, or with leading `*` and
+/// `**` Markdown emphasis around the label
+fn strip_label<'a>(line: &'a str, label: &str) -> Option<&'a str> {
+    let line = line.trim_start_matches('*');
+    let head = line.get(..label.len())?;
+    head.eq_ignore_ascii_case(label).then(|| line[label.len()..].trim_start_matches('*').trim())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::forge_process::safety::RiskLevel;
+    use crate::http::json::escape_string;
+    use crate::http::stub::StubServer;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn context() -> CommandContext {
+        CommandContext {
+            working_directory: "/work".to_string(),
+            os: "linux",
+            branch: Some("main".to_string()),
+            tools: vec!["find".to_string(), "git".to_string()],
+            entries: "- Cargo.toml".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_suggest_retries_until_the_command_parses() {
+        let calls = AtomicUsize::new(0);
+        let server = StubServer::start(move |_| {
+            let answer = if calls.fetch_add(1, Ordering::SeqCst) == 0 {
+                "COMMAND: echo 'oops\nEXPLANATION: broken"
+            } else {
+                "**Command:** `find . -name '*.rs' -mtime -7 -size +10k`\nExplanation: Lists Rust files\nchanged this week."
+            };
+            format!("{{\"response\":{}}}", escape_string(answer))
+        });
+
+        let suggestion = suggest_command(&server.client(), "m", "big rust files from this week", &context(), &SafetyChecker::new()).unwrap();
+        assert_eq!(suggestion.command, "find . -name '*.rs' -mtime -7 -size +10k");
+        assert_eq!(suggestion.explanation, "Lists Rust files changed this week.");
+        assert_eq!(suggestion.attempts, 2);
+        assert_eq!(suggestion.risk.level, RiskLevel::Safe);
+
+        let requests = server.requests();
+        assert!(requests[0].body.contains("Installed tools: find, git."));
+        assert!(requests[0].body.contains("on branch main"));
+        assert!(requests[1].body.contains("It was rejected: `echo 'oops` doesn't parse: unterminated single quote"));
+    }
+
+    #[test]
+    fn test_diagnose_failure() {
+        let server = StubServer::start(|_| {
+            let answer = "DIAGNOSIS: There is no `tset` subcommand;\nyou meant `test`.\nFIX: `cargo test`";
+            format!("{{\"response\":{}}}", escape_string(answer))
+        });
+        let stderr = (1..=100).map(|i| format!("line {}\n", i)).collect::<String>();
+        let failure = Failure { command: "cargo tset", exit_code: 101, stderr: &stderr };
+
+        let diagnosis = diagnose_failure(&server.client(), "m", &failure, &context(), &SafetyChecker::new()).unwrap();
+        assert_eq!(diagnosis.diagnosis, "There is no `tset` subcommand; you meant `test`.");
+        assert_eq!(diagnosis.fix.as_deref(), Some("cargo test"));
+        assert!(diagnosis.risk.is_some());
+
+        let body = &server.requests()[0].body;
+        assert!(body.contains("exited with status 101:\\ncargo tset"));
+        assert!(body.contains("\\nline 61\\n") && !body.contains("line 60\\n"));
+        assert!(body.contains("- Cargo.toml"));
+
+        assert_eq!(parse_diagnosis("DIAGNOSIS: Offline.\nFIX: NONE"), Ok(("Offline.".to_string(), None)));
+        assert!(parse_diagnosis("FIX: ls").is_err());
+        assert_eq!(tail("a\nb\n"), "a\nb");
+    }
+
+    #[test]
+    fn test_parse_answer() {
+        assert_eq!(parse_answer("COMMAND: ls -la\nEXPLANATION: Lists files."), Ok(("ls -la".to_string(), "Lists files.".to_string())));
+        assert_eq!(parse_answer("command: du -sh .").map(|(command, _)| command), Ok("du -sh .".to_string()));
+        assert_eq!(parse_answer("Sure! Try ls."), Err("no COMMAND: line".to_string()));
+        assert_eq!(parse_answer("COMMAND:\nEXPLANATION: nothing"), Err("no COMMAND: line".to_string()));
+        assert_eq!(parse_answer("COMMAND: ls\nthen look around").map(|(command, _)| command), Ok("ls".to_string()));
+    }
+}
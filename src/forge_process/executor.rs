@@ -1,12 +1,15 @@
 #![allow(dead_code)]
 
+use std::fmt;
 use std::process::{Command, Stdio};
-use std::io::{self, Write, BufRead, BufReader};
+use std::io::{self, IsTerminal, Write, BufRead, BufReader};
 use std::time::{Duration, Instant};
 use std::thread;
+use std::path::PathBuf;
 
-use crate::terminal::output::{ProgressBar, Spinner, success_text, error_text, warning_text, info_text};
-use super::safety::{SafetyChecker, RiskLevel};
+use crate::terminal::output::{ProgressBar, Spinner, success_text, error_text, warning_text, info_text, dim_text};
+use super::audit::{Approval, AuditEntry, AuditLog, Initiator};
+use super::safety::{CommandRisk, SafetyChecker, RiskLevel};
 use super::shell::{Shell, CommandResult};
 
 #[derive(Debug, Clone)]
@@ -32,36 +35,175 @@ impl Default for ExecutionOptions {
     }
 }
 
+/// How commands the safety checker doesn't rate safe get approved
+#[derive(Debug, Clone, PartialEq)]
+pub enum Confirmation {
+    /// Ask on the terminal. Without one, anything that needs asking is
+    /// refused, and critical commands always are.
+    Interactive,
+    /// Run commands up to this level without asking and refuse the rest
+    AutoApprove(RiskLevel),
+    /// Refuse anything that isn't safe
+    Deny,
+    /// Run everything, critical commands included
+    ForceDangerous,
+}
+
+/// What a confirmation strategy does with a command
+#[derive(Debug, Clone, PartialEq)]
+pub enum Decision {
+    Run,
+    Ask,
+    Refuse(String),
+}
+
+impl Confirmation {
+    /// Parse `--yes[=<level>]`, `--deny-risky` or `--force-dangerous`;
+    /// `None` for any other argument
+    pub fn parse_flag(arg: &str) -> Result<Option<Self>, Box<dyn std::error::Error>> {
+        let level = match arg.split_once('=') {
+            Some(("--yes", level)) => level,
+            _ => match arg {
+                "--yes" => "medium",
+                "--deny-risky" => return Ok(Some(Confirmation::Deny)),
+                "--force-dangerous" => return Ok(Some(Confirmation::ForceDangerous)),
+                _ => return Ok(None),
+            },
+        };
+        match level.parse()? {
+            RiskLevel::Critical => Err("--yes stops at high; use --force-dangerous to run critical commands".into()),
+            level => Ok(Some(Confirmation::AutoApprove(level))),
+        }
+    }
+
+    /// What to do with a command of this risk. `terminal` says whether
+    /// there's someone at stdin to ask.
+    pub fn decide(&self, level: &RiskLevel, terminal: bool) -> Decision {
+        if *level == RiskLevel::Safe {
+            return Decision::Run;
+        }
+        match self {
+            Confirmation::ForceDangerous => Decision::Run,
+            Confirmation::AutoApprove(limit) if level <= limit => Decision::Run,
+            Confirmation::AutoApprove(limit) => Decision::Refuse(format!(
+                "{:?} risk is above the --yes limit of {:?}", level, limit
+            )),
+            Confirmation::Deny => Decision::Refuse(format!("{:?} risk commands are refused with --deny-risky", level)),
+            Confirmation::Interactive if *level == RiskLevel::Critical => Decision::Refuse(
+                "Critical command blocked for safety. Use --force-dangerous to override.".to_string()
+            ),
+            Confirmation::Interactive if terminal => Decision::Ask,
+            Confirmation::Interactive => Decision::Refuse(format!(
+                "{:?} risk command needs confirmation, but stdin isn't a terminal. Use --yes[=<level>] or --force-dangerous",
+                level
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Confirmation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Confirmation::Interactive => write!(f, "interactive"),
+            Confirmation::AutoApprove(level) => write!(f, "auto-approve up to {}", format!("{:?}", level).to_lowercase()),
+            Confirmation::Deny => write!(f, "deny"),
+            Confirmation::ForceDangerous => write!(f, "force-dangerous"),
+        }
+    }
+}
+
+fn current_dir() -> PathBuf {
+    std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."))
+}
+
 pub struct ProcessExecutor {
     safety_checker: SafetyChecker,
     shell: Shell,
+    confirmation: Confirmation,
+    initiator: Initiator,
+    audit: Option<AuditLog>,
 }
 
 impl ProcessExecutor {
     pub fn new() -> Self {
+        let audit = AuditLog::configured();
         ProcessExecutor {
-            safety_checker: SafetyChecker::new(),
-            shell: Shell::new(),
+            safety_checker: SafetyChecker::configured(&current_dir()),
+            shell: Shell::new().with_audit_log(audit.clone()),
+            confirmation: Confirmation::Interactive,
+            initiator: Initiator::User,
+            audit,
         }
     }
 
     pub fn with_allowed_commands(commands: Vec<String>) -> Self {
         ProcessExecutor {
-            safety_checker: SafetyChecker::new().with_allowed_commands(commands),
-            shell: Shell::new(),
+            safety_checker: SafetyChecker::configured(&current_dir()).with_allowed_commands(commands),
+            ..ProcessExecutor::new()
         }
     }
 
+    pub fn with_confirmation(mut self, confirmation: Confirmation) -> Self {
+        self.shell = self.shell.with_confirmation(confirmation.clone());
+        self.confirmation = confirmation;
+        self
+    }
+
+    pub fn confirmation(&self) -> &Confirmation {
+        &self.confirmation
+    }
+
+    pub fn safety_checker(&self) -> &SafetyChecker {
+        &self.safety_checker
+    }
+
+    /// Who the audit log says asked for the commands
+    pub fn with_initiator(mut self, initiator: Initiator) -> Self {
+        self.initiator = initiator;
+        self
+    }
+
+    /// Where commands are recorded; `None` records nothing
+    pub fn with_audit_log(mut self, audit: Option<AuditLog>) -> Self {
+        self.shell = self.shell.with_audit_log(audit.clone());
+        self.audit = audit;
+        self
+    }
+
+    /// Offer a diagnosis and fix when a command in the interactive shell fails
+    pub fn with_explain_failures(mut self, explain: bool) -> Self {
+        self.shell = self.shell.with_explain_failures(explain);
+        self
+    }
+
+    /// Check and run a command, recording it in the audit log whether it
+    /// runs or is refused
     pub fn execute(&mut self, command: &str, options: ExecutionOptions) -> Result<CommandResult, Box<dyn std::error::Error>> {
-        if options.safety_check {
-            self.perform_safety_check(command)?;
-        }
+        let (risk, approval) = if options.safety_check {
+            let (risk, approval) = self.confirm(command);
+            match approval {
+                Ok(approval) => (risk, approval),
+                Err(e) => {
+                    self.audit(self.audit_entry(command, options.working_directory.as_deref(), &risk, Approval::Refused));
+                    return Err(e);
+                }
+            }
+        } else {
+            (self.safety_checker.assess_command(command), Approval::Earlier)
+        };
 
-        if options.show_progress {
+        let entry = self.audit_entry(command, options.working_directory.as_deref(), &risk, approval);
+        let start = Instant::now();
+        let result = if options.show_progress {
             self.execute_with_progress(command, options)
         } else {
             self.execute_direct(command, options)
+        };
+        match &result {
+            Ok(result) => self.audit(entry.finished(result, start.elapsed())),
+            Err(_) => self.audit(entry.not_started(start.elapsed())),
         }
+        result
     }
 
     pub fn execute_batch(&mut self, commands: Vec<&str>, options: ExecutionOptions) -> Result<Vec<CommandResult>, Box<dyn std::error::Error>> {
@@ -107,97 +249,104 @@ impl ProcessExecutor {
             println!("{}", info_text("Executing pipeline..."));
         }
 
-        self.shell.execute_pipeline(commands)
+        let pipeline = commands.join(" | ");
+        let risk = self.safety_checker.assess_command(&pipeline);
+        let entry = self.audit_entry(&pipeline, options.working_directory.as_deref(), &risk, Approval::Earlier);
+        let start = Instant::now();
+        let result = self.shell.execute_with_options(&pipeline, &options);
+        match &result {
+            Ok(result) => self.audit(entry.finished(result, start.elapsed())),
+            Err(_) => self.audit(entry.not_started(start.elapsed())),
+        }
+        result
     }
 
-    fn perform_safety_check(&self, command: &str) -> Result<(), Box<dyn std::error::Error>> {
+    /// Show what's risky about a command and approve or refuse it the way
+    /// the confirmation strategy says. Every decision on a command that
+    /// isn't safe is logged with the strategy that made it.
+    /// A refusal is recorded in the audit log.
+    pub fn perform_safety_check(&self, command: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let (risk, approval) = self.confirm(command);
+        if approval.is_err() {
+            self.audit(self.audit_entry(command, None, &risk, Approval::Refused));
+        }
+        approval.map(|_| ())
+    }
+
+    fn confirm(&self, command: &str) -> (CommandRisk, Result<Approval, Box<dyn std::error::Error>>) {
         let risk = self.safety_checker.assess_command(command);
-        
+        if risk.level == RiskLevel::Safe {
+            return (risk, Ok(Approval::NotNeeded));
+        }
+
         match risk.level {
-            RiskLevel::Safe => Ok(()),
-            RiskLevel::Low => {
-                println!("{}", warning_text(&format!("⚠️  Low risk: {}", risk.reason)));
-                for suggestion in &risk.suggestions {
-                    println!("  💡 {}", suggestion);
-                }
-                self.prompt_user_confirmation("Proceed with execution? (y/N): ")
-            }
-            RiskLevel::Medium => {
-                println!("{}", warning_text(&format!("⚠️  Medium risk: {}", risk.reason)));
-                for suggestion in &risk.suggestions {
-                    println!("  💡 {}", suggestion);
-                }
-                
-                let alternatives = self.safety_checker.get_safe_alternatives(command);
-                if !alternatives.is_empty() {
-                    println!("Safe alternatives:");
-                    for alt in alternatives {
-                        println!("  ✅ {}", alt);
-                    }
-                }
-                
-                self.prompt_user_confirmation("Are you sure you want to proceed? (y/N): ")
-            }
-            RiskLevel::High => {
-                println!("{}", error_text(&format!("🚨 High risk: {}", risk.reason)));
-                for suggestion in &risk.suggestions {
-                    println!("  💡 {}", suggestion);
-                }
-                
-                let alternatives = self.safety_checker.get_safe_alternatives(command);
-                if !alternatives.is_empty() {
-                    println!("Safe alternatives:");
-                    for alt in alternatives {
-                        println!("  ✅ {}", alt);
-                    }
-                }
-                
-                self.prompt_user_confirmation("This is dangerous! Are you absolutely sure? (type 'YES' to confirm): ")
-                    .and_then(|_| {
-                        let mut input = String::new();
-                        io::stdin().read_line(&mut input)?;
-                        if input.trim() == "YES" {
-                            Ok(())
-                        } else {
-                            Err("Command execution cancelled for safety".into())
-                        }
-                    })
-            }
+            RiskLevel::Low => println!("{}", warning_text(&format!("⚠️  Low risk: {}", risk.reason))),
+            RiskLevel::Medium => println!("{}", warning_text(&format!("⚠️  Medium risk: {}", risk.reason))),
+            RiskLevel::High => println!("{}", error_text(&format!("🚨 High risk: {}", risk.reason))),
             RiskLevel::Critical => {
                 println!("{}", error_text(&format!("🛑 CRITICAL DANGER: {}", risk.reason)));
                 println!("{}", error_text("This command could cause irreversible damage!"));
-                for suggestion in &risk.suggestions {
-                    println!("  💡 {}", suggestion);
-                }
-                
-                let alternatives = self.safety_checker.get_safe_alternatives(command);
-                if !alternatives.is_empty() {
-                    println!("Safe alternatives:");
-                    for alt in alternatives {
-                        println!("  ✅ {}", alt);
-                    }
+            }
+            RiskLevel::Safe => {}
+        }
+        for suggestion in &risk.suggestions {
+            println!("  💡 {}", suggestion);
+        }
+        if risk.level >= RiskLevel::Medium {
+            let alternatives = self.safety_checker.get_safe_alternatives(command);
+            if !alternatives.is_empty() {
+                println!("Safe alternatives:");
+                for alt in alternatives {
+                    println!("  ✅ {}", alt);
                 }
-                
-                Err("Critical command blocked for safety. Use --force-dangerous to override.".into())
             }
         }
+
+        let decision = self.confirmation.decide(&risk.level, io::stdin().is_terminal());
+        let result = match decision {
+            Decision::Run => Ok(Approval::Automatic),
+            Decision::Refuse(reason) => Err(reason.into()),
+            Decision::Ask => match risk.level {
+                RiskLevel::High => self.prompt_user_confirmation("This is dangerous! Are you absolutely sure? (type 'YES' to confirm): ", "YES"),
+                RiskLevel::Medium => self.prompt_user_confirmation("Are you sure you want to proceed? (y/N): ", "y"),
+                _ => self.prompt_user_confirmation("Proceed with execution? (y/N): ", "y"),
+            }.map(|_| Approval::Confirmed),
+        };
+        let outcome = if result.is_ok() { "approved" } else { "refused" };
+        println!("{}", dim_text(&format!("Confirmation ({}): {:?} risk command {}", self.confirmation, risk.level, outcome)));
+        (risk, result)
+    }
+
+    fn audit_entry(&self, command: &str, working_directory: Option<&str>, risk: &CommandRisk, approval: Approval) -> AuditEntry {
+        let cwd = match working_directory {
+            Some(dir) => dir.to_string(),
+            None => current_dir().display().to_string(),
+        };
+        AuditEntry::new(command, &cwd, self.initiator, risk, &self.confirmation.to_string(), approval)
     }
 
-    fn prompt_user_confirmation(&self, prompt: &str) -> Result<(), Box<dyn std::error::Error>> {
+    fn audit(&self, entry: AuditEntry) {
+        if let Some(log) = &self.audit {
+            log.record(&entry);
+        }
+    }
+
+    fn prompt_user_confirmation(&self, prompt: &str, expected: &str) -> Result<(), Box<dyn std::error::Error>> {
         print!("{}", prompt);
         io::stdout().flush()?;
         
         let mut input = String::new();
         io::stdin().read_line(&mut input)?;
         
-        if input.trim().to_lowercase() == "y" {
+        let confirmed = if expected == "y" { input.trim().eq_ignore_ascii_case("y") } else { input.trim() == expected };
+        if confirmed {
             Ok(())
         } else {
             Err("Command execution cancelled by user".into())
         }
     }
 
-    fn execute_with_progress(&mut self, command: &str, _options: ExecutionOptions) -> Result<CommandResult, Box<dyn std::error::Error>> {
+    fn execute_with_progress(&mut self, command: &str, options: ExecutionOptions) -> Result<CommandResult, Box<dyn std::error::Error>> {
         let spinner = Spinner::new().with_title(&format!("Executing: {}", command));
         
         // Show spinner in a separate thread (simplified version)
@@ -205,7 +354,7 @@ impl ProcessExecutor {
         
         println!("{} {}", spinner.render(), command);
         
-        let result = self.shell.execute_command(command)?;
+        let result = self.shell.execute_with_options(command, &options)?;
         
         let duration = start_time.elapsed();
         
@@ -220,8 +369,8 @@ impl ProcessExecutor {
         Ok(result)
     }
 
-    fn execute_direct(&mut self, command: &str, _options: ExecutionOptions) -> Result<CommandResult, Box<dyn std::error::Error>> {
-        self.shell.execute_command(command)
+    fn execute_direct(&mut self, command: &str, options: ExecutionOptions) -> Result<CommandResult, Box<dyn std::error::Error>> {
+        self.shell.execute_with_options(command, &options)
     }
 
     pub fn run_interactive_shell(&mut self) -> Result<(), Box<dyn std::error::Error>> {
@@ -378,6 +527,7 @@ impl LongRunningExecutor {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::forge_process::audit::Status;
 
     #[test]
     fn test_executor_creation() {
@@ -400,6 +550,32 @@ mod tests {
         assert!(options.safety_check);
     }
 
+    #[test]
+    fn test_confirmation_strategies() {
+        use RiskLevel::*;
+        let medium = Confirmation::AutoApprove(Medium);
+        assert_eq!(medium.decide(&Safe, false), Decision::Run);
+        assert_eq!(medium.decide(&Medium, false), Decision::Run);
+        assert!(matches!(medium.decide(&High, true), Decision::Refuse(_)));
+
+        assert_eq!(Confirmation::Interactive.decide(&Low, true), Decision::Ask);
+        assert!(matches!(Confirmation::Interactive.decide(&Low, false), Decision::Refuse(reason) if reason.contains("isn't a terminal")));
+        assert!(matches!(Confirmation::Interactive.decide(&Critical, true), Decision::Refuse(_)));
+        assert!(matches!(Confirmation::Deny.decide(&Low, true), Decision::Refuse(_)));
+        assert_eq!(Confirmation::Deny.decide(&Safe, true), Decision::Run);
+        assert_eq!(Confirmation::ForceDangerous.decide(&Critical, false), Decision::Run);
+
+        let parse = |arg: &str| Confirmation::parse_flag(arg).map_err(|e| e.to_string());
+        assert_eq!(parse("--yes"), Ok(Some(medium)));
+        assert_eq!(parse("--yes=high"), Ok(Some(Confirmation::AutoApprove(High))));
+        assert_eq!(parse("--force-dangerous"), Ok(Some(Confirmation::ForceDangerous)));
+        assert_eq!(parse("--deny-risky"), Ok(Some(Confirmation::Deny)));
+        assert_eq!(parse("ls"), Ok(None));
+        assert!(parse("--yes=critical").unwrap_err().contains("--force-dangerous"));
+        assert!(parse("--yes=loud").is_err());
+        assert_eq!(Confirmation::AutoApprove(Low).to_string(), "auto-approve up to low");
+    }
+
     #[test]
     fn test_command_suggestions() {
         let executor = ProcessExecutor::new();
@@ -407,4 +583,74 @@ mod tests {
         
         assert!(suggestions.contains(&"git".to_string()));
     }
-}
\ No newline at end of file
+
+    fn quiet(options: ExecutionOptions) -> ExecutionOptions {
+        ExecutionOptions { show_progress: false, safety_check: false, ..options }
+    }
+
+    #[test]
+    fn test_timeout_kills_the_process_group() {
+        let mut executor = ProcessExecutor::new();
+        let options = quiet(ExecutionOptions { timeout: Some(Duration::from_millis(300)), ..Default::default() });
+
+        // The background sleep holds the output pipe, so this only returns
+        // quickly if the whole group is killed
+        let start = Instant::now();
+        let result = executor.execute("sh -c 'sleep 30 & wait; echo late'", options.clone()).unwrap();
+        assert_eq!(result.exit_code, 124);
+        assert!(result.stderr.contains("timed out after 0.3s"));
+        assert!(!result.stdout.contains("late"));
+        assert!(start.elapsed() < Duration::from_secs(3));
+
+        // Ignoring SIGTERM only lasts until the grace period is over
+        let start = Instant::now();
+        let result = executor.execute("sh -c 'trap \"\" TERM; sleep 30'", options.clone()).unwrap();
+        assert_eq!(result.exit_code, 124);
+        assert!(start.elapsed() < Duration::from_secs(10));
+
+        let result = executor.execute("echo fast && true", options).unwrap();
+        assert!(result.success);
+    }
+
+    #[test]
+    fn test_working_directory_and_capture() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("marker.txt"), "").unwrap();
+        let mut executor = ProcessExecutor::new();
+        let before = executor.shell.get_environment().working_directory.clone();
+
+        let options = quiet(ExecutionOptions { working_directory: Some(dir.path().display().to_string()), ..Default::default() });
+        let result = executor.execute("ls", options).unwrap();
+        assert_eq!(result.stdout, "marker.txt\n");
+        assert_eq!(executor.shell.get_environment().working_directory, before);
+
+        let options = quiet(ExecutionOptions { working_directory: Some("/no/such/dir".to_string()), ..Default::default() });
+        assert!(!executor.execute("ls", options).unwrap().success);
+
+        let options = quiet(ExecutionOptions { capture_output: false, ..Default::default() });
+        let result = executor.execute("true", options).unwrap();
+        assert!(result.success);
+        assert!(result.stdout.is_empty());
+    }
+
+    #[test]
+    fn test_commands_that_cannot_start_are_audited() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = AuditLog::new(dir.path().join("audit.jsonl"));
+        // Unknown commands are low risk; run them without asking
+        let mut executor = ProcessExecutor::new().with_audit_log(Some(log.clone())).with_confirmation(Confirmation::ForceDangerous);
+
+        let missing_binary = executor.execute("forge-no-such-binary", quiet(ExecutionOptions::default()));
+        assert!(missing_binary.is_err() || !missing_binary.unwrap().success);
+        let options = quiet(ExecutionOptions { working_directory: Some("/no/such/dir".to_string()), ..Default::default() });
+        let missing_dir = executor.execute("ls", options);
+        assert!(missing_dir.is_err() || !missing_dir.unwrap().success);
+
+        let (entries, errors) = log.read();
+        assert!(errors.is_empty());
+        let commands: Vec<(&str, &str)> = entries.iter().map(|entry| (entry.command.as_str(), entry.cwd.as_str())).collect();
+        assert_eq!(commands[0].0, "forge-no-such-binary");
+        assert_eq!(commands[1], ("ls", "/no/such/dir"));
+        assert!(entries.iter().all(|entry| entry.status() == Status::Failed && entry.approval != Approval::Refused));
+    }
+}
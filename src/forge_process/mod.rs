@@ -1,7 +1,22 @@
 pub mod executor;
 pub mod shell;
+pub mod shell_parser;
+pub mod shell_completion;
+#[cfg(unix)]
+pub mod shell_jobs;
 pub mod safety;
+pub mod safety_policy;
+pub mod audit;
+pub mod records;
 pub mod workflow;
+pub mod workflow_file;
+pub mod workflow_graph;
+pub mod workflow_template;
+pub mod workflow_expr;
+pub mod workflow_history;
+pub mod workflow_generate;
+pub mod command_suggest;
+pub mod regex;
 
 pub use executor::*;
 pub use shell::*;
@@ -0,0 +1,135 @@
+//! Helpers for the JSON records forge keeps, the workflow run history and
+//! the audit log: reading their fields and their UTC timestamps.
+
+use std::collections::HashMap;
+
+use crate::http::json::JsonValue;
+
+/// Read access to a JSON object's fields, with errors naming the field
+pub struct Fields<'a> {
+    object: &'a HashMap<String, JsonValue>,
+    context: String,
+}
+
+impl<'a> Fields<'a> {
+    pub fn of(value: &'a JsonValue, context: &str) -> Result<Self, String> {
+        let object = value.as_object().ok_or_else(|| format!("{} must be an object", context))?;
+        Ok(Fields { object, context: context.to_string() })
+    }
+
+    pub fn get(&self, key: &str) -> Result<&'a JsonValue, String> {
+        self.object.get(key).ok_or_else(|| format!("{} is missing '{}'", self.context, key))
+    }
+
+    fn wrong_type(&self, key: &str, expected: &str) -> String {
+        format!("{}.{} must be {}", self.context, key, expected)
+    }
+
+    pub fn string(&self, key: &str) -> Result<String, String> {
+        self.get(key)?.as_string().cloned().ok_or_else(|| self.wrong_type(key, "a string"))
+    }
+
+    pub fn optional_string(&self, key: &str) -> Result<Option<String>, String> {
+        match self.object.get(key) {
+            None | Some(JsonValue::Null) => Ok(None),
+            Some(_) => self.string(key).map(Some),
+        }
+    }
+
+    pub fn number(&self, key: &str) -> Result<f64, String> {
+        self.get(key)?.as_f64().ok_or_else(|| self.wrong_type(key, "a number"))
+    }
+
+    pub fn optional_number(&self, key: &str) -> Result<Option<f64>, String> {
+        match self.object.get(key) {
+            None | Some(JsonValue::Null) => Ok(None),
+            Some(_) => self.number(key).map(Some),
+        }
+    }
+
+    pub fn boolean(&self, key: &str) -> Result<bool, String> {
+        match self.get(key)? {
+            JsonValue::Boolean(b) => Ok(*b),
+            _ => Err(self.wrong_type(key, "true or false")),
+        }
+    }
+
+    pub fn array(&self, key: &str) -> Result<&'a Vec<JsonValue>, String> {
+        self.get(key)?.as_array().ok_or_else(|| self.wrong_type(key, "an array"))
+    }
+
+    pub fn object(&self, key: &str) -> Result<&'a HashMap<String, JsonValue>, String> {
+        self.get(key)?.as_object().ok_or_else(|| self.wrong_type(key, "an object"))
+    }
+}
+
+/// ISO 8601 in UTC, like `2026-10-18T15:30:12Z`
+pub fn format_utc(secs: u64) -> String {
+    let days = (secs / 86_400) as i64;
+    let (year, month, day) = civil_from_days(days);
+    let time = secs % 86_400;
+    format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z", year, month, day, time / 3600, time % 3600 / 60, time % 60)
+}
+
+/// The seconds since the epoch of a `format_utc` timestamp
+pub fn parse_utc(s: &str) -> Option<u64> {
+    let (date, time) = s.strip_suffix('Z')?.split_once('T')?;
+    let date: Vec<i64> = date.split('-').map(|part| part.parse().ok()).collect::<Option<_>>()?;
+    let time: Vec<u64> = time.split(':').map(|part| part.parse().ok()).collect::<Option<_>>()?;
+    if date.len() != 3 || time.len() != 3 {
+        return None;
+    }
+    let days = u64::try_from(days_from_civil(date[0], date[1], date[2])).ok()?;
+    Some(days * 86_400 + time[0] * 3600 + time[1] * 60 + time[2])
+}
+
+// Conversions between days since 1970-01-01 and proleptic Gregorian dates,
+// after Howard Hinnant's `chrono`-compatible algorithms
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    (yoe + era * 400 + i64::from(month <= 2), month, day)
+}
+
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let mp = if month > 2 { month - 3 } else { month + 9 };
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::json::parse_json;
+
+    #[test]
+    fn test_utc_dates() {
+        assert_eq!(format_utc(0), "1970-01-01T00:00:00Z");
+        assert_eq!(format_utc(951_782_400), "2000-02-29T00:00:00Z");
+        for secs in [0, 951_782_400, 1_792_337_412, 4_102_444_799] {
+            assert_eq!(parse_utc(&format_utc(secs)), Some(secs));
+        }
+        assert_eq!(parse_utc("2026-10-18 15:30:12"), None);
+    }
+
+    #[test]
+    fn test_fields_name_what_is_wrong() {
+        let document = parse_json(r#"{"name": "x", "count": "3", "note": null}"#).unwrap();
+        let fields = Fields::of(&document, "entry").unwrap();
+        assert_eq!(fields.string("name"), Ok("x".to_string()));
+        assert_eq!(fields.optional_string("note"), Ok(None));
+        assert_eq!(fields.number("count"), Err("entry.count must be a number".to_string()));
+        assert_eq!(fields.boolean("missing"), Err("entry is missing 'missing'".to_string()));
+        assert!(Fields::of(&JsonValue::Null, "entry").is_err());
+    }
+}
@@ -0,0 +1,607 @@
+use std::fmt;
+
+/// A small regular expression engine, enough to pull values out of command
+/// output without a regex dependency. Patterns compile to a program that a
+/// backtracking matcher runs with an explicit stack, remembering where it
+/// has already failed, so matching takes time linear in the text.
+///
+/// Supports literals, `.`, classes (`[a-z]`, `[^,]`), `\d \w \s` and their
+/// negations, groups (`(...)`, `(?:...)`), alternation, the quantifiers
+/// `* + ? {n} {n,} {n,m}` with lazy `?` variants, and `^`/`$`, which match
+/// at line boundaries. `.` doesn't match a newline.
+#[derive(Debug, Clone)]
+pub struct Regex {
+    program: Vec<Inst>,
+    groups: usize,
+    /// Number of `Split` instructions, each with its own row of the
+    /// visited table
+    splits: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RegexError {
+    pub position: usize,
+    pub message: String,
+}
+
+impl fmt::Display for RegexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid regex at {}: {}", self.position, self.message)
+    }
+}
+
+impl std::error::Error for RegexError {}
+
+/// Instructions a pattern may compile to; counted repetitions like `{2,500}`
+/// are written out, so this bounds how far they can go
+const MAX_PROGRAM: usize = 20_000;
+
+/// Bits the matcher may use to remember failed (branch, position) pairs,
+/// which bounds the text length for patterns with many branches
+const MAX_VISITED: usize = 1 << 26;
+
+/// The text was too long to match against the pattern within `MAX_VISITED`
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatchError {
+    /// Length of the text in chars
+    pub length: usize,
+}
+
+impl fmt::Display for MatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "regex gave up: {} chars of text are too many for a pattern with this many repetitions", self.length)
+    }
+}
+
+impl std::error::Error for MatchError {}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Char(char),
+    Any,
+    Class { items: Vec<ClassItem>, negated: bool },
+    LineStart,
+    LineEnd,
+    /// `index` is the capture group number, `None` for `(?:...)`
+    Group { index: Option<usize>, alternatives: Vec<Vec<Node>> },
+    Repeat { node: Box<Node>, min: usize, max: Option<usize>, greedy: bool },
+}
+
+#[derive(Debug, Clone)]
+enum ClassItem {
+    Range(char, char),
+    Digit(bool),
+    Word(bool),
+    Space(bool),
+}
+
+impl ClassItem {
+    fn matches(&self, c: char) -> bool {
+        match *self {
+            ClassItem::Range(from, to) => from <= c && c <= to,
+            ClassItem::Digit(negated) => c.is_ascii_digit() != negated,
+            ClassItem::Word(negated) => (c.is_alphanumeric() || c == '_') != negated,
+            ClassItem::Space(negated) => c.is_whitespace() != negated,
+        }
+    }
+}
+
+type Captures = Vec<Option<(usize, usize)>>;
+
+#[derive(Debug, Clone)]
+enum Inst {
+    Char(char),
+    Any,
+    Class { items: Vec<ClassItem>, negated: bool },
+    LineStart,
+    LineEnd,
+    /// Record the position in a capture slot: group `n` starts in slot
+    /// `2n` and ends in `2n + 1`
+    Save(usize),
+    /// Try `first`, and `second` if that fails. `id` picks the row in the
+    /// visited table.
+    Split { first: usize, second: usize, id: usize },
+    Jump(usize),
+    Match,
+}
+
+/// Work left for the matcher: a thread to run, or a capture slot to put
+/// back when backtracking past where it was set
+enum Job {
+    Run(usize, usize),
+    Restore(usize, Option<usize>),
+}
+
+impl Regex {
+    pub fn new(pattern: &str) -> Result<Self, RegexError> {
+        let mut parser = Parser { chars: pattern.chars().collect(), pos: 0, groups: 0 };
+        let alternatives = parser.parse_alternatives()?;
+        if parser.pos < parser.chars.len() {
+            return Err(parser.error("unmatched ')'"));
+        }
+        let mut compiler = Compiler { program: Vec::new(), splits: 0 };
+        compiler.emit(Inst::Save(0))?;
+        compiler.alternatives(&alternatives)?;
+        compiler.emit(Inst::Save(1))?;
+        compiler.emit(Inst::Match)?;
+        Ok(Regex { program: compiler.program, groups: parser.groups, splits: compiler.splits })
+    }
+
+    /// Number of capture groups, not counting the whole match
+    pub fn group_count(&self) -> usize {
+        self.groups
+    }
+
+    /// The leftmost match and its groups; index 0 is the whole match and
+    /// groups that didn't take part are `None`. Errors when the text is too
+    /// long for the pattern.
+    pub fn captures<'t>(&self, text: &'t str) -> Result<Option<Vec<Option<&'t str>>>, MatchError> {
+        let chars: Vec<char> = text.chars().collect();
+        // Byte offset of every char index, plus the end
+        let offsets: Vec<usize> = text.char_indices().map(|(i, _)| i).chain(std::iter::once(text.len())).collect();
+
+        Ok(self.find(&chars)?.map(|spans| spans.iter()
+            .map(|span| span.map(|(from, to)| &text[offsets[from]..offsets[to]]))
+            .collect()))
+    }
+
+    /// The char spans of the leftmost match and its groups
+    fn find(&self, chars: &[char]) -> Result<Option<Captures>, MatchError> {
+        let positions = chars.len() + 1;
+        let bits = self.splits.checked_mul(positions).filter(|&bits| bits <= MAX_VISITED)
+            .ok_or(MatchError { length: chars.len() })?;
+        let mut matcher = Matcher {
+            program: &self.program,
+            chars,
+            visited: vec![0; bits.div_ceil(64)],
+            slots: vec![None; 2 * (self.groups + 1)],
+            jobs: Vec::new(),
+        };
+
+        // A branch that failed from one start fails from any other, so the
+        // visited table carries over
+        for start in 0..positions {
+            if matcher.run(start) {
+                let captures = matcher.slots.chunks(2).map(|slot| slot[0].zip(slot[1])).collect();
+                return Ok(Some(captures));
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Turns the parsed pattern into a program
+struct Compiler {
+    program: Vec<Inst>,
+    splits: usize,
+}
+
+impl Compiler {
+    fn emit(&mut self, inst: Inst) -> Result<usize, RegexError> {
+        if self.program.len() >= MAX_PROGRAM {
+            return Err(RegexError { position: 0, message: "pattern repeats too much".to_string() });
+        }
+        self.program.push(inst);
+        Ok(self.program.len() - 1)
+    }
+
+    /// A split whose targets are filled in by `patch`
+    fn split(&mut self) -> Result<usize, RegexError> {
+        self.splits += 1;
+        self.emit(Inst::Split { first: 0, second: 0, id: self.splits - 1 })
+    }
+
+    fn patch(&mut self, pc: usize, to_first: usize, to_second: usize) {
+        match &mut self.program[pc] {
+            Inst::Split { first, second, .. } => (*first, *second) = (to_first, to_second),
+            Inst::Jump(target) => *target = to_first,
+            _ => {}
+        }
+    }
+
+    fn alternatives(&mut self, alternatives: &[Vec<Node>]) -> Result<(), RegexError> {
+        let mut jumps = Vec::new();
+        for (i, sequence) in alternatives.iter().enumerate() {
+            if i + 1 == alternatives.len() {
+                self.sequence(sequence)?;
+                break;
+            }
+            let split = self.split()?;
+            self.sequence(sequence)?;
+            jumps.push(self.emit(Inst::Jump(0))?);
+            let next = self.program.len();
+            self.patch(split, split + 1, next);
+        }
+        let end = self.program.len();
+        for jump in jumps {
+            self.patch(jump, end, end);
+        }
+        Ok(())
+    }
+
+    fn sequence(&mut self, sequence: &[Node]) -> Result<(), RegexError> {
+        sequence.iter().try_for_each(|node| self.node(node))
+    }
+
+    fn node(&mut self, node: &Node) -> Result<(), RegexError> {
+        match node {
+            Node::Char(c) => self.emit(Inst::Char(*c)).map(drop),
+            Node::Any => self.emit(Inst::Any).map(drop),
+            Node::Class { items, negated } => self.emit(Inst::Class { items: items.clone(), negated: *negated }).map(drop),
+            Node::LineStart => self.emit(Inst::LineStart).map(drop),
+            Node::LineEnd => self.emit(Inst::LineEnd).map(drop),
+            Node::Group { index, alternatives } => {
+                if let Some(index) = index {
+                    self.emit(Inst::Save(2 * index))?;
+                }
+                self.alternatives(alternatives)?;
+                if let Some(index) = index {
+                    self.emit(Inst::Save(2 * index + 1))?;
+                }
+                Ok(())
+            }
+            Node::Repeat { node, min, max, greedy } => {
+                for _ in 0..*min {
+                    self.node(node)?;
+                }
+                // Each further repetition is optional: the split either
+                // enters it or skips to the end
+                let order = |split: usize, end: usize| if *greedy { (split + 1, end) } else { (end, split + 1) };
+                match max {
+                    None => {
+                        let split = self.split()?;
+                        self.node(node)?;
+                        self.emit(Inst::Jump(split))?;
+                        let (first, second) = order(split, self.program.len());
+                        self.patch(split, first, second);
+                    }
+                    Some(max) => {
+                        let mut splits = Vec::new();
+                        for _ in *min..*max {
+                            splits.push(self.split()?);
+                            self.node(node)?;
+                        }
+                        let end = self.program.len();
+                        for split in splits {
+                            let (first, second) = order(split, end);
+                            self.patch(split, first, second);
+                        }
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    groups: usize,
+}
+
+impl Parser {
+    fn error(&self, message: &str) -> RegexError {
+        RegexError { position: self.pos, message: message.to_string() }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn parse_alternatives(&mut self) -> Result<Vec<Vec<Node>>, RegexError> {
+        let mut alternatives = vec![self.parse_sequence()?];
+        while self.eat('|') {
+            alternatives.push(self.parse_sequence()?);
+        }
+        Ok(alternatives)
+    }
+
+    fn parse_sequence(&mut self) -> Result<Vec<Node>, RegexError> {
+        let mut sequence = Vec::new();
+        while let Some(c) = self.peek() {
+            if c == '|' || c == ')' {
+                break;
+            }
+            let atom = self.parse_atom()?;
+            sequence.push(self.parse_quantifier(atom)?);
+        }
+        Ok(sequence)
+    }
+
+    fn parse_atom(&mut self) -> Result<Node, RegexError> {
+        let c = self.peek().ok_or_else(|| self.error("unexpected end of pattern"))?;
+        self.pos += 1;
+        match c {
+            '.' => Ok(Node::Any),
+            '^' => Ok(Node::LineStart),
+            '$' => Ok(Node::LineEnd),
+            '(' => {
+                let index = if self.eat('?') {
+                    if !self.eat(':') {
+                        return Err(self.error("only (?:...) groups are supported"));
+                    }
+                    None
+                } else {
+                    self.groups += 1;
+                    Some(self.groups)
+                };
+                let alternatives = self.parse_alternatives()?;
+                if !self.eat(')') {
+                    return Err(self.error("missing ')'"));
+                }
+                Ok(Node::Group { index, alternatives })
+            }
+            '[' => self.parse_class(),
+            '\\' => match self.parse_escape()? {
+                ClassItem::Range(c, _) => Ok(Node::Char(c)),
+                item => Ok(Node::Class { items: vec![item], negated: false }),
+            },
+            '*' | '+' | '?' => Err(RegexError { position: self.pos - 1, message: format!("'{}' has nothing to repeat", c) }),
+            c => Ok(Node::Char(c)),
+        }
+    }
+
+    /// After a `\`: a class shorthand, or a literal returned as a one-char range
+    fn parse_escape(&mut self) -> Result<ClassItem, RegexError> {
+        let c = self.peek().ok_or_else(|| self.error("pattern ends with '\\'"))?;
+        self.pos += 1;
+        Ok(match c {
+            'd' => ClassItem::Digit(false),
+            'D' => ClassItem::Digit(true),
+            'w' => ClassItem::Word(false),
+            'W' => ClassItem::Word(true),
+            's' => ClassItem::Space(false),
+            'S' => ClassItem::Space(true),
+            'n' => ClassItem::Range('\n', '\n'),
+            't' => ClassItem::Range('\t', '\t'),
+            'r' => ClassItem::Range('\r', '\r'),
+            c if c.is_alphanumeric() => return Err(RegexError { position: self.pos - 1, message: format!("unsupported escape '\\{}'", c) }),
+            c => ClassItem::Range(c, c),
+        })
+    }
+
+    fn parse_class(&mut self) -> Result<Node, RegexError> {
+        let negated = self.eat('^');
+        let mut items = Vec::new();
+        let mut first = true;
+
+        loop {
+            let c = self.peek().ok_or_else(|| self.error("missing ']'"))?;
+            self.pos += 1;
+            if c == ']' && !first {
+                break;
+            }
+            first = false;
+
+            let item = if c == '\\' { self.parse_escape()? } else { ClassItem::Range(c, c) };
+            match item {
+                ClassItem::Range(from, _) if self.peek() == Some('-') && self.chars.get(self.pos + 1).is_some_and(|&c| c != ']') => {
+                    self.pos += 1;
+                    let to = match self.peek() {
+                        Some('\\') => {
+                            self.pos += 1;
+                            match self.parse_escape()? {
+                                ClassItem::Range(to, _) => to,
+                                _ => return Err(self.error("a class can't end a range")),
+                            }
+                        }
+                        Some(to) => {
+                            self.pos += 1;
+                            to
+                        }
+                        None => return Err(self.error("missing ']'")),
+                    };
+                    if to < from {
+                        return Err(self.error("range out of order"));
+                    }
+                    items.push(ClassItem::Range(from, to));
+                }
+                item => items.push(item),
+            }
+        }
+        Ok(Node::Class { items, negated })
+    }
+
+    fn parse_quantifier(&mut self, node: Node) -> Result<Node, RegexError> {
+        let start = self.pos;
+        let (min, max) = match self.peek() {
+            Some('*') => (0, None),
+            Some('+') => (1, None),
+            Some('?') => (0, Some(1)),
+            Some('{') => match self.parse_braces() {
+                Some(bounds) => bounds,
+                // Not a valid repetition, so a literal '{'
+                None => {
+                    self.pos = start;
+                    return Ok(node);
+                }
+            },
+            _ => return Ok(node),
+        };
+        if self.pos == start {
+            self.pos += 1;
+        }
+        if matches!(node, Node::LineStart | Node::LineEnd) {
+            return Err(RegexError { position: start, message: "an anchor can't be repeated".to_string() });
+        }
+        if max.is_some_and(|max| max < min) {
+            return Err(RegexError { position: start, message: "repetition range out of order".to_string() });
+        }
+        let greedy = !self.eat('?');
+        Ok(Node::Repeat { node: Box::new(node), min, max, greedy })
+    }
+
+    /// `{n}`, `{n,}` or `{n,m}`, leaving `pos` after the `}`
+    fn parse_braces(&mut self) -> Option<(usize, Option<usize>)> {
+        self.pos += 1;
+        let number = |parser: &mut Parser| {
+            let start = parser.pos;
+            while parser.peek().is_some_and(|c| c.is_ascii_digit()) {
+                parser.pos += 1;
+            }
+            parser.chars[start..parser.pos].iter().collect::<String>().parse::<usize>().ok()
+        };
+        let min = number(self)?;
+        let max = if self.eat(',') {
+            if self.peek() == Some('}') { None } else { Some(number(self)?) }
+        } else {
+            Some(min)
+        };
+        if self.eat('}') { Some((min, max)) } else { None }
+    }
+}
+
+struct Matcher<'a> {
+    program: &'a [Inst],
+    chars: &'a [char],
+    /// A bit per split and position, set once the split has been tried
+    /// there. Since nothing depends on the path taken to get there, trying
+    /// again would fail again, or loop when a repetition matched nothing.
+    visited: Vec<u64>,
+    slots: Vec<Option<usize>>,
+    jobs: Vec<Job>,
+}
+
+impl Matcher<'_> {
+    /// Whether the program matches starting at `start`, leaving the capture
+    /// slots of the match
+    fn run(&mut self, start: usize) -> bool {
+        self.slots.iter_mut().for_each(|slot| *slot = None);
+        self.jobs.clear();
+        self.jobs.push(Job::Run(0, start));
+        while let Some(job) = self.jobs.pop() {
+            let (mut pc, mut pos) = match job {
+                Job::Run(pc, pos) => (pc, pos),
+                Job::Restore(slot, value) => {
+                    self.slots[slot] = value;
+                    continue;
+                }
+            };
+            loop {
+                match &self.program[pc] {
+                    Inst::Char(_) | Inst::Any | Inst::Class { .. } => {
+                        if !self.char_matches(&self.program[pc], pos) {
+                            break;
+                        }
+                        pos += 1;
+                    }
+                    Inst::LineStart => {
+                        if pos != 0 && self.chars[pos - 1] != '\n' {
+                            break;
+                        }
+                    }
+                    Inst::LineEnd => {
+                        if pos != self.chars.len() && self.chars[pos] != '\n' {
+                            break;
+                        }
+                    }
+                    Inst::Save(slot) => {
+                        self.jobs.push(Job::Restore(*slot, self.slots[*slot]));
+                        self.slots[*slot] = Some(pos);
+                    }
+                    Inst::Split { first, second, id } => {
+                        let bit = id * (self.chars.len() + 1) + pos;
+                        if self.visited[bit / 64] & (1 << (bit % 64)) != 0 {
+                            break;
+                        }
+                        self.visited[bit / 64] |= 1 << (bit % 64);
+                        self.jobs.push(Job::Run(*second, pos));
+                        pc = *first;
+                        continue;
+                    }
+                    Inst::Jump(target) => {
+                        pc = *target;
+                        continue;
+                    }
+                    Inst::Match => return true,
+                }
+                pc += 1;
+            }
+        }
+        false
+    }
+
+    /// Whether the single char at `pos` matches a one-char instruction
+    fn char_matches(&self, inst: &Inst, pos: usize) -> bool {
+        let c = match self.chars.get(pos) {
+            Some(&c) => c,
+            None => return false,
+        };
+        match inst {
+            Inst::Char(expected) => c == *expected,
+            Inst::Any => c != '\n',
+            Inst::Class { items, negated } => items.iter().any(|item| item.matches(c)) != *negated,
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn first_group(pattern: &str, text: &str) -> Option<String> {
+        let captures = Regex::new(pattern).unwrap().captures(text).unwrap()?;
+        captures.get(1).copied().flatten().or(captures[0]).map(str::to_string)
+    }
+
+    #[test]
+    fn test_matching() {
+        let cases = [
+            (r"version (\d+\.\d+\.\d+)", "forge version 1.22.3 (abc)", Some("1.22.3")),
+            (r"^commit ([0-9a-f]{7,})$", "log\ncommit 3fa9c01\nAuthor", Some("3fa9c01")),
+            (r"name = (?:'|`)?([\w-]+)", "name = `forge-cli`", Some("forge-cli")),
+            (r"(a|ab)(c|bcd)", "abcd", Some("a")),
+            (r"<(.+?)>", "<one> <two>", Some("one")),
+            (r"<(.+)>", "<one> <two>", Some("one> <two")),
+            (r"([^,]*),", "first,second", Some("first")),
+            (r"(x*)*y", "xxy", Some("xx")),
+            (r"^$", "a\n\nb", Some("")),
+            (r"\s+(\S+)$", "tag  v2", Some("v2")),
+            (r"a{2}", "aaa", Some("aa")),
+            (r"x{,3}", "x{,3}", Some("x{,3}")),
+            (r"ab+", "a", None),
+            (r"a.c", "a\nc", None),
+        ];
+        for (pattern, text, expected) in cases {
+            assert_eq!(first_group(pattern, text).as_deref(), expected, "{} against {:?}", pattern, text);
+        }
+    }
+
+    #[test]
+    fn test_long_input_and_errors() {
+        let long = format!("start {} end", "x".repeat(200_000));
+        assert_eq!(first_group("start (.*) end", &long).map(|s| s.len()), Some(200_000));
+
+        // Repeated groups and nested alternatives take linear time
+        let pairs = "ab".repeat(200_000);
+        assert_eq!(first_group("((ab))*", &pairs).map(|s| s.len()), Some(2));
+        assert_eq!(Regex::new("((?:ab)*)").unwrap().captures(&pairs).unwrap().unwrap()[0].map(str::len), Some(400_000));
+        assert!(Regex::new("x((ab))*").unwrap().captures(&pairs).unwrap().is_none());
+        assert_eq!(first_group("((?:ab)+)$", &"ab".repeat(2_000)).map(|s| s.len()), Some(4_000));
+        assert!(Regex::new("(a|aa)*c").unwrap().captures(&"a".repeat(40)).unwrap().is_none());
+        assert!(Regex::new("(a*)*b").unwrap().captures(&"a".repeat(5_000)).unwrap().is_none());
+
+        // Too many branches for the text, or repetitions for the program
+        let error = Regex::new("(?:a?){500}").unwrap().captures(&"a".repeat(200_000)).unwrap_err();
+        assert_eq!(error.length, 200_000);
+        assert_eq!(Regex::new("(?:ab){30000}").unwrap_err().message, "pattern repeats too much");
+
+        assert_eq!(Regex::new("(ab").unwrap_err().message, "missing ')'");
+        assert_eq!(Regex::new("ab)").unwrap_err().message, "unmatched ')'");
+        assert_eq!(Regex::new("*a").unwrap_err().position, 0);
+        assert!(Regex::new("[z-a]").is_err());
+        assert!(Regex::new(r"\q").is_err());
+        assert_eq!(Regex::new(r"(a)(?:b)(c)").unwrap().group_count(), 2);
+    }
+}
@@ -1,9 +1,37 @@
 #![allow(dead_code)]
 
-use std::collections::HashSet;
-use std::path::Path;
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::path::{Path, PathBuf};
 
-#[derive(Debug, Clone, PartialEq)]
+use crate::config::{ForgeConfig, SafetyConfig};
+use crate::terminal::output::error_text;
+use super::safety_policy::{self, Action, Policy, Rule};
+use super::shell_parser::{self, Command, ParseError, Pipeline, Redirect, RedirectTarget, Script};
+
+/// How deep `sh -c` scripts and substitutions are followed
+const MAX_DEPTH: usize = 8;
+
+/// Stands in for the output of a `$(...)` substitution
+const SUBSTITUTED: &str = "__forge_substitution__";
+
+/// Stands in for the output of a substitution that downloads something
+const DOWNLOADED: &str = "__forge_download__";
+
+/// Shells whose `-c` argument is a script we can check
+const SHELLS: [&str; 6] = ["sh", "bash", "zsh", "dash", "ksh", "fish"];
+
+/// Programs that run a script read from their input
+const INTERPRETERS: [&str; 11] = ["sh", "bash", "zsh", "dash", "ksh", "fish", "python", "python3", "perl", "ruby", "node"];
+
+const DOWNLOADERS: [&str; 3] = ["curl", "wget", "fetch"];
+
+/// Devices that are fine to write to
+const HARMLESS_DEVICES: [&str; 6] = ["/dev/null", "/dev/zero", "/dev/stdout", "/dev/stderr", "/dev/tty", "/dev/fd/"];
+
+/// Ordered from least to most risky, so the worst of several can be taken
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum RiskLevel {
     Safe,
     Low,
@@ -12,6 +40,21 @@ pub enum RiskLevel {
     Critical,
 }
 
+impl std::str::FromStr for RiskLevel {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_lowercase().as_str() {
+            "safe" => Ok(RiskLevel::Safe),
+            "low" => Ok(RiskLevel::Low),
+            "medium" => Ok(RiskLevel::Medium),
+            "high" => Ok(RiskLevel::High),
+            "critical" => Ok(RiskLevel::Critical),
+            _ => Err(format!("Unknown risk level '{}' (expected safe, low, medium, high or critical)", name)),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct CommandRisk {
     pub level: RiskLevel,
@@ -19,11 +62,23 @@ pub struct CommandRisk {
     pub suggestions: Vec<String>,
 }
 
+#[derive(Clone)]
 pub struct SafetyChecker {
     dangerous_patterns: Vec<&'static str>,
     destructive_commands: HashSet<&'static str>,
-    system_paths: Vec<&'static str>,
+    system_paths: Vec<String>,
     allowed_commands: Option<HashSet<String>>,
+    variables: HashMap<String, String>,
+    /// The built-in rules; policy rules and the allowlist apply either way
+    builtin_checks: bool,
+    /// Rate `sudo`, power control and service management one level lower
+    allow_system_commands: bool,
+    policy: Policy,
+    /// What relative paths are relative to, when not the current directory
+    working_directory: Option<PathBuf>,
+    /// Why the config or a policy couldn't be loaded; every command is
+    /// refused until it's fixed rather than run without the user's rules
+    load_error: Option<String>,
 }
 
 impl SafetyChecker {
@@ -40,6 +95,13 @@ impl SafetyChecker {
         destructive_commands.insert("halt");
         destructive_commands.insert("poweroff");
         destructive_commands.insert("systemctl");
+        destructive_commands.insert("init");
+        destructive_commands.insert("erase");
+        destructive_commands.insert("sfdisk");
+        destructive_commands.insert("parted");
+        destructive_commands.insert("wipefs");
+        destructive_commands.insert("mkswap");
+        destructive_commands.insert("shred");
 
         SafetyChecker {
             dangerous_patterns: vec![
@@ -76,14 +138,80 @@ impl SafetyChecker {
                 "/boot",
                 "/dev",
                 "/etc",
+                "/lib",
+                "/lib64",
                 "/proc",
                 "/sys",
+                "/var/lib",
                 "C:\\Windows",
                 "C:\\Program Files",
                 "C:\\System32",
-            ],
+            ].into_iter().map(String::from).collect(),
             allowed_commands: None,
+            variables: HashMap::new(),
+            builtin_checks: true,
+            allow_system_commands: false,
+            policy: Policy::default(),
+            working_directory: None,
+            load_error: None,
+        }
+    }
+
+    /// The checker for commands run in `dir`: the `[safety]` settings from
+    /// the config file, then the project's `.forge/policy.toml` and the
+    /// user's `~/.config/forge/policy.toml`
+    pub fn for_directory(dir: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let config = ForgeConfig::load()?;
+        let project_file = safety_policy::project_policy_file(dir);
+        let policy = Policy::load(project_file.as_deref(), safety_policy::user_policy_file().as_deref())?;
+        Ok(SafetyChecker::new()
+            .with_config(&config.safety)
+            .with_policy(policy)
+            .with_working_directory(dir))
+    }
+
+    /// `for_directory`, or if the config or a policy is broken, a checker
+    /// that rates every command critical so none run without the rules
+    pub fn configured(dir: &Path) -> Self {
+        SafetyChecker::for_directory(dir).unwrap_or_else(|e| {
+            eprintln!("{}", error_text(&format!("❌ Refusing to run commands until the safety settings load: {}", e)));
+            SafetyChecker::new().with_working_directory(dir).with_load_error(e.to_string())
+        })
+    }
+
+    fn with_load_error(mut self, error: String) -> Self {
+        self.load_error = Some(error);
+        self
+    }
+
+    pub fn with_config(mut self, config: &SafetyConfig) -> Self {
+        self.builtin_checks = config.enable_safety_checks;
+        self.allow_system_commands = config.allow_system_commands;
+        for path in &config.restricted_paths {
+            if !self.system_paths.contains(path) {
+                self.system_paths.push(path.clone());
+            }
         }
+        self
+    }
+
+    pub fn with_policy(mut self, policy: Policy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    pub fn with_working_directory(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.working_directory = Some(dir.into());
+        self
+    }
+
+    /// The policy files in effect, project first
+    pub fn policy_sources(&self) -> &[PathBuf] {
+        &self.policy.sources
+    }
+
+    pub fn policy(&self) -> &Policy {
+        &self.policy
     }
 
     pub fn with_allowed_commands(mut self, commands: Vec<String>) -> Self {
@@ -91,235 +219,917 @@ impl SafetyChecker {
         self
     }
 
+    /// Assess a command line by parsing it and checking every command it
+    /// would run, including pipeline stages, subshells, `$(...)`, `sh -c`
+    /// scripts and commands run through `sudo` or `xargs`. The riskiest
+    /// finding wins.
     pub fn assess_command(&self, command: &str) -> CommandRisk {
-        let command_lower = command.to_lowercase();
-        let parts: Vec<&str> = command.split_whitespace().collect();
-        
-        if parts.is_empty() {
+        if command.trim().is_empty() {
             return CommandRisk {
                 level: RiskLevel::Safe,
                 reason: "Empty command".to_string(),
                 suggestions: vec!["Specify a command to execute".to_string()],
             };
         }
+        if let Some(error) = &self.load_error {
+            return CommandRisk {
+                level: RiskLevel::Critical,
+                reason: format!("The safety settings couldn't be loaded: {}", error),
+                suggestions: vec![
+                    "Fix the file, then check it with forge policy check".to_string(),
+                    "Until then no command is run without --force-dangerous".to_string(),
+                ],
+            };
+        }
+
+        let mut analyzer = Analyzer { checker: self, risks: Vec::new(), allowance: None };
+        analyzer.command_line(command, Context::default());
+        analyzer.risks.into_iter()
+            .reduce(|worst, risk| if risk.level > worst.level { risk } else { worst })
+            .unwrap_or_else(|| CommandRisk {
+                level: RiskLevel::Safe,
+                reason: if self.builtin_checks { "Command appears safe" } else { "Safety checks are disabled" }.to_string(),
+                suggestions: vec![],
+            })
+    }
+
+    /// An operand as an absolute path, relative to the working directory.
+    /// `~` and Windows paths are left as they are.
+    fn resolve(&self, path: &str) -> String {
+        if path.starts_with(['/', '~']) || path.get(1..2) == Some(":") {
+            return normalize(path);
+        }
+        let dir = self.working_directory.clone().or_else(|| env::current_dir().ok()).unwrap_or_default();
+        normalize(&dir.join(path).to_string_lossy())
+    }
+
+    /// Resolve `$NAME` and `~` from these instead of the environment
+    pub fn with_variables(mut self, variables: HashMap<String, String>) -> Self {
+        self.variables = variables;
+        self
+    }
+
+    fn lookup(&self, name: &str) -> Option<String> {
+        self.variables.get(name).cloned().or_else(|| env::var(name).ok())
+    }
+
+    /// Where `path` points, once it's made absolute and `.`, `..`, trailing
+    /// slashes and a trailing `*` are resolved
+    fn classify(&self, path: &str) -> Target {
+        if path.contains(SUBSTITUTED) || path.contains(DOWNLOADED) {
+            return Target::Unknown;
+        }
+        let path = self.resolve(path);
+        let home = self.lookup("HOME").map(|home| normalize(&home));
+        if path == "/" {
+            Target::Root
+        } else if path == "~" || home.as_deref().is_some_and(|home| home == path || home.strip_prefix(&path).is_some_and(|rest| rest.starts_with('/'))) {
+            Target::Home
+        } else if let Some(system) = self.system_paths.iter().find(|system| is_system_path(&path, system)) {
+            Target::System(system.clone())
+        } else {
+            Target::Other
+        }
+    }
+
+    /// Critical patterns found by searching the raw text, for command lines
+    /// that don't parse
+    fn assess_unparsed(&self, command: &str, error: &ParseError) -> CommandRisk {
+        let command_lower = command.to_lowercase();
+        if let Some(pattern) = self.dangerous_patterns.iter().find(|pattern| command_lower.contains(*pattern)) {
+            return risk(RiskLevel::Critical, format!("Contains dangerous pattern: {}", pattern), &[
+                "This command could cause irreversible system damage",
+                "Consider using safer alternatives or be extremely careful",
+                "Always have backups before running destructive commands",
+            ]);
+        }
+        if self.has_network_risk(command) {
+            return network_risk(false);
+        }
+        risk(RiskLevel::Low, format!("Couldn't parse the command to check it: {}", error), &[
+            "Review the command carefully before running it",
+        ])
+    }
+
+    fn has_network_risk(&self, command: &str) -> bool {
+        (command.contains("curl") || command.contains("wget")) &&
+        (command.contains("| sh") || command.contains("| bash") || 
+         command.contains("|sh") || command.contains("|bash"))
+    }
+
+    pub fn is_command_allowed(&self, command: &str) -> bool {
+        let risk = self.assess_command(command);
+        matches!(risk.level, RiskLevel::Safe | RiskLevel::Low)
+    }
+
+    pub fn get_safe_alternatives(&self, command: &str) -> Vec<String> {
+        let mut alternatives = Vec::new();
+        let command_lower = command.to_lowercase();
+
+        if command_lower.starts_with("rm -rf") {
+            alternatives.push("Use 'rm -i' for interactive deletion".to_string());
+            alternatives.push("Move files to trash instead of permanent deletion".to_string());
+            alternatives.push("List files first with 'ls' to verify targets".to_string());
+        }
+
+        if command_lower.contains("curl") && command_lower.contains("| sh") {
+            alternatives.push("Download the script first: curl <url> -o script.sh".to_string());
+            alternatives.push("Review the script: cat script.sh".to_string());
+            alternatives.push("Then execute if safe: bash script.sh".to_string());
+        }
+
+        if command_lower.starts_with("sudo") {
+            alternatives.push("Try running without sudo first if possible".to_string());
+            alternatives.push("Use specific sudo commands instead of sudo su".to_string());
+        }
+
+        alternatives
+    }
+}
+
+/// What a command line is nested in while it's analyzed
+#[derive(Debug, Clone, Copy, Default)]
+struct Context {
+    /// Run through `sudo` or `doas`
+    privileged: bool,
+    /// Run by `xargs`, so more arguments come from its input
+    from_input: bool,
+    /// How many `sh -c` scripts and substitutions deep
+    depth: usize,
+}
+
+impl Context {
+    fn nested(self) -> Self {
+        Context { depth: self.depth + 1, ..self }
+    }
+}
+
+/// Where a path argument points
+#[derive(Debug, Clone, PartialEq)]
+enum Target {
+    Root,
+    /// The home directory, or a directory containing it
+    Home,
+    /// One of the checker's system paths, something under one, or a
+    /// directory containing one
+    System(String),
+    /// Only known when the command runs
+    Unknown,
+    Other,
+}
+
+impl Target {
+    fn is_vital(&self) -> bool {
+        matches!(self, Target::Root | Target::Home | Target::System(_))
+    }
+}
+
+/// A command's arguments with combined short flags like `-rf` split apart
+struct Arguments {
+    short: HashSet<char>,
+    long: HashSet<String>,
+    operands: Vec<String>,
+}
+
+impl Arguments {
+    fn parse(args: &[String]) -> Self {
+        let mut arguments = Arguments { short: HashSet::new(), long: HashSet::new(), operands: Vec::new() };
+        let mut rest = args.iter();
+        for arg in rest.by_ref() {
+            if arg == "--" {
+                break;
+            } else if let Some(long) = arg.strip_prefix("--") {
+                arguments.long.insert(long.split('=').next().unwrap_or(long).to_string());
+            } else if arg.len() > 1 && arg.starts_with('-') {
+                arguments.short.extend(arg.chars().skip(1));
+            } else {
+                arguments.operands.push(arg.clone());
+            }
+        }
+        arguments.operands.extend(rest.cloned());
+        arguments
+    }
+
+    fn has(&self, short: char, long: &str) -> bool {
+        self.short.contains(&short) || self.long.contains(long)
+    }
+
+    fn recursive(&self) -> bool {
+        self.has('r', "recursive") || self.short.contains(&'R')
+    }
+}
+
+/// Walks a command line, collecting the risk of everything it runs
+struct Analyzer<'a> {
+    checker: &'a SafetyChecker,
+    risks: Vec<CommandRisk>,
+    /// The allow rule for the command being checked, which lowers its own
+    /// findings but not those of the commands it runs
+    allowance: Option<&'a Rule>,
+}
+
+impl Analyzer<'_> {
+    /// Check a command line as text. Returns whether it downloads something.
+    fn command_line(&mut self, text: &str, context: Context) -> bool {
+        let outer = self.allowance.take();
+        let downloads = self.nested_command_line(text, context);
+        self.allowance = outer;
+        downloads
+    }
+
+    fn nested_command_line(&mut self, text: &str, context: Context) -> bool {
+        if context.depth > MAX_DEPTH {
+            self.push(RiskLevel::High, "Command is nested too deeply to check", &["Run the inner commands on their own"]);
+            return false;
+        }
+        let compact: String = text.chars().filter(|c| !c.is_whitespace()).collect();
+        if compact.contains(":(){:|:&};:") {
+            self.push(RiskLevel::Critical, "Fork bomb", &["This starts processes until the system stops responding"]);
+            return false;
+        }
+
+        // The parser doesn't know substitutions, so check them separately
+        // and leave a marker for what they print
+        let (mut outer, substitutions) = extract_substitutions(text);
+        let mut downloads = false;
+        for (i, inner) in substitutions.iter().enumerate() {
+            let inner_downloads = self.command_line(inner, context.nested());
+            downloads |= inner_downloads;
+            outer = outer.replace(&placeholder(i), if inner_downloads { DOWNLOADED } else { SUBSTITUTED });
+        }
+
+        match shell_parser::parse(&outer) {
+            Ok(script) => self.script(&script, context) || downloads,
+            Err(error) => {
+                let risk = self.checker.assess_unparsed(text, &error);
+                self.add(risk);
+                downloads
+            }
+        }
+    }
+
+    fn script(&mut self, script: &Script, context: Context) -> bool {
+        let mut downloads = false;
+        for list in &script.lists {
+            for pipeline in std::iter::once(&list.first).chain(list.rest.iter().map(|(_, pipeline)| pipeline)) {
+                downloads |= self.pipeline(pipeline, context);
+            }
+        }
+        downloads
+    }
+
+    /// Check each stage of a pipeline, noting when a later stage runs what
+    /// an earlier one downloaded
+    fn pipeline(&mut self, pipeline: &Pipeline, context: Context) -> bool {
+        let mut downloaded = false;
+        for command in &pipeline.commands {
+            let downloads = match command {
+                Command::Subshell { script, redirects } => {
+                    self.redirects(redirects);
+                    self.script(script, context)
+                }
+                Command::Simple(simple) => {
+                    self.redirects(&simple.redirects);
+                    let words: Vec<String> = simple.words.iter()
+                        .map(|word| word.expand(|name| self.checker.lookup(name)))
+                        .collect();
+                    self.simple(&words, context, downloaded)
+                }
+            };
+            downloaded |= downloads;
+        }
+        downloaded
+    }
 
-        let base_command = parts[0];
+    fn redirects(&mut self, redirects: &[Redirect]) {
+        for redirect in redirects {
+            if let RedirectTarget::Write(word) | RedirectTarget::Append(word) = &redirect.target {
+                let path = word.expand(|name| self.checker.lookup(name));
+                self.written(&path);
+            }
+        }
+    }
 
-        // Check if command is in allowlist (if configured)
-        if let Some(allowed) = &self.allowed_commands {
-            if !allowed.contains(base_command) {
-                return CommandRisk {
+    /// Check one command. `piped` says whether an earlier pipeline stage
+    /// downloads something. Returns whether this one does.
+    fn simple(&mut self, words: &[String], context: Context, piped: bool) -> bool {
+        // `NAME=value command`: the assignments don't run anything
+        let words = &words[words.iter().take_while(|word| is_assignment(word)).count()..];
+        let Some(first) = words.first() else {
+            return false;
+        };
+        let name = first.rsplit(['/', '\\']).next().unwrap_or(first);
+        let args = &words[1..];
+
+        if let Some(allowed) = &self.checker.allowed_commands {
+            if !allowed.contains(name) {
+                self.risks.push(CommandRisk {
                     level: RiskLevel::High,
-                    reason: format!("Command '{}' is not in the allowed list", base_command),
+                    reason: format!("Command '{}' is not in the allowed list", name),
                     suggestions: vec![
                         "Only pre-approved commands are allowed in this environment".to_string(),
                         format!("Allowed commands: {}", allowed.iter().cloned().collect::<Vec<_>>().join(", ")),
                     ],
-                };
+                });
             }
         }
+        if name.contains(SUBSTITUTED) || name.contains(DOWNLOADED) {
+            self.push(RiskLevel::Low, "Runs a command whose name is only known when it runs", &[
+                "Check what the substitution prints before running it",
+            ]);
+            return false;
+        }
 
-        // Check for critical patterns
-        for pattern in &self.dangerous_patterns {
-            if command_lower.contains(pattern) {
-                return CommandRisk {
-                    level: RiskLevel::Critical,
-                    reason: format!("Contains dangerous pattern: {}", pattern),
-                    suggestions: vec![
-                        "This command could cause irreversible system damage".to_string(),
-                        "Consider using safer alternatives or be extremely careful".to_string(),
-                        "Always have backups before running destructive commands".to_string(),
-                    ],
-                };
+        // A deny rule adds its risk; an allow rule lowers what the built-in
+        // checks find for this command
+        let paths: Vec<String> = Arguments::parse(args).operands.iter()
+            .filter(|operand| !operand.contains(SUBSTITUTED) && !operand.contains(DOWNLOADED))
+            .map(|operand| self.checker.resolve(operand))
+            .collect();
+        let home = self.checker.lookup("HOME");
+        let rule = self.checker.policy.find(name, args, &paths, home.as_deref());
+        if let Some(rule) = rule {
+            self.risks.push(rule.risk());
+        }
+        let outer = std::mem::replace(&mut self.allowance, rule.filter(|rule| rule.action == Action::Allow));
+        let downloads = self.builtin(name, args, context, piped);
+        self.allowance = outer;
+        downloads
+    }
+
+    /// The built-in checks for one command
+    fn builtin(&mut self, name: &str, args: &[String], context: Context, piped: bool) -> bool {
+        if let Some(start) = wrapped_command(name, args) {
+            let privileged = matches!(name, "sudo" | "doas");
+            if privileged {
+                self.system(RiskLevel::Medium, "Command requires elevated privileges", &[
+                    "Ensure you understand what the command does with elevated privileges",
+                    "Consider running without sudo first if possible",
+                ]);
             }
+            let context = Context {
+                privileged: context.privileged || privileged,
+                from_input: context.from_input || name == "xargs",
+                ..context
+            };
+            return self.simple(args.get(start..).unwrap_or_default(), context, piped);
         }
 
-        // Check base command for destructive potential
-        if self.destructive_commands.contains(base_command) {
-            let risk = self.assess_destructive_command(command, parts);
-            if risk.level != RiskLevel::Safe {
-                return risk;
+        match name {
+            "eval" => self.command_line(&args.join(" "), context.nested()),
+            _ if INTERPRETERS.contains(&name) => {
+                self.interpreter(name, args, context, piped);
+                false
+            }
+            _ if DOWNLOADERS.contains(&name) => true,
+            "find" => {
+                self.find(args, context);
+                false
+            }
+            "chmod" | "chown" | "chgrp" => {
+                self.permissions(name, args);
+                false
+            }
+            "kill" | "killall" | "pkill" => {
+                self.kill(name, args);
+                false
+            }
+            "git" => {
+                self.git(args);
+                false
+            }
+            _ if self.checker.destructive_commands.contains(name) || name.starts_with("mkfs.") => {
+                self.destructive(name, args, context);
+                false
+            }
+            _ => {
+                self.other(name, args);
+                false
             }
         }
+    }
 
-        // Check for system path manipulation
-        for sys_path in &self.system_paths {
-            if command_lower.contains(&sys_path.to_lowercase()) {
-                return CommandRisk {
-                    level: RiskLevel::High,
-                    reason: format!("Attempts to modify system path: {}", sys_path),
-                    suggestions: vec![
-                        "Modifying system paths can break your system".to_string(),
-                        "Use extreme caution when working with system directories".to_string(),
-                    ],
-                };
+    /// `sh -c '...'` runs a script we can check; a shell or interpreter
+    /// after a download in the pipeline runs whatever was downloaded
+    fn interpreter(&mut self, name: &str, args: &[String], context: Context, piped: bool) {
+        let script_flag = args.iter().position(|arg| arg.starts_with('-') && !arg.starts_with("--") && arg.contains('c'));
+        if let (Some(i), true) = (script_flag, SHELLS.contains(&name)) {
+            if let Some(script) = args.get(i + 1) {
+                if script.contains(DOWNLOADED) {
+                    self.add(network_risk(context.privileged));
+                }
+                self.command_line(script, context.nested());
             }
+            return;
         }
 
-        // Check for suspicious network activity
-        if self.has_network_risk(command) {
-            return CommandRisk {
-                level: RiskLevel::Medium,
-                reason: "Command downloads and executes content from the internet".to_string(),
-                suggestions: vec![
-                    "Review the source and content before executing".to_string(),
-                    "Consider downloading and inspecting the script first".to_string(),
-                ],
-            };
+        let reads_input = args.iter().all(|arg| arg.starts_with('-'));
+        if (piped && reads_input) || args.iter().any(|arg| arg.contains(DOWNLOADED)) {
+            self.add(network_risk(context.privileged));
         }
+    }
 
-        // Check for privilege escalation
-        if command_lower.starts_with("sudo ") || command_lower.contains("sudo ") {
-            return CommandRisk {
-                level: RiskLevel::Medium,
-                reason: "Command requires elevated privileges".to_string(),
-                suggestions: vec![
-                    "Ensure you understand what the command does with elevated privileges".to_string(),
-                    "Consider running without sudo first if possible".to_string(),
-                ],
-            };
+    /// `find -delete` and `find -exec`, with `{}` standing for the paths
+    /// searched
+    fn find(&mut self, args: &[String], context: Context) {
+        let paths_end = args.iter().position(|arg| arg.starts_with(['-', '(', '!'])).unwrap_or(args.len());
+        let mut paths: Vec<&str> = args[..paths_end].iter().map(String::as_str).collect();
+        if paths.is_empty() {
+            paths.push(".");
         }
+        let expression = &args[paths_end..];
 
-        // Check for recursive operations
-        if command_lower.contains(" -r") || command_lower.contains(" --recursive") {
-            return CommandRisk {
-                level: RiskLevel::Low,
-                reason: "Command performs recursive operations".to_string(),
-                suggestions: vec![
-                    "Be careful with recursive operations on large directory trees".to_string(),
-                    "Consider testing on a small subset first".to_string(),
-                ],
-            };
+        if expression.iter().any(|arg| arg == "-delete") {
+            match paths.iter().map(|path| (path, self.checker.classify(path))).find(|(_, target)| target.is_vital()) {
+                Some((path, _)) => self.push(RiskLevel::Critical, format!("Deletes everything find matches under {}", path), &[
+                    "This could destroy your system or all your files",
+                    "Run the find without -delete first to see what it matches",
+                ]),
+                None => self.push(RiskLevel::High, "Deletes the files find matches", &[
+                    "Run the find without -delete first to see what it matches",
+                ]),
+            }
         }
 
-        CommandRisk {
-            level: RiskLevel::Safe,
-            reason: "Command appears safe".to_string(),
-            suggestions: vec![],
+        let mut rest = expression.iter();
+        while let Some(arg) = rest.next() {
+            if !matches!(arg.as_str(), "-exec" | "-execdir" | "-ok" | "-okdir") {
+                continue;
+            }
+            let command: Vec<&String> = rest.by_ref().take_while(|arg| !matches!(arg.as_str(), ";" | "+")).collect();
+            for path in &paths {
+                let words: Vec<String> = command.iter().map(|word| word.replace("{}", path)).collect();
+                self.simple(&words, context.nested(), false);
+            }
         }
     }
 
-    fn assess_destructive_command(&self, command: &str, parts: Vec<&str>) -> CommandRisk {
-        let base_command = parts[0];
-        
-        match base_command {
-            "rm" => {
-                if parts.iter().any(|&arg| arg == "-rf" || arg == "-r") {
-                    CommandRisk {
-                        level: RiskLevel::Critical,
-                        reason: "Recursive file deletion".to_string(),
-                        suggestions: vec![
-                            "This will delete files and directories recursively".to_string(),
-                            "Make sure you have backups".to_string(),
-                            "Double-check the target path".to_string(),
-                        ],
-                    }
-                } else if parts.iter().any(|&arg| arg.starts_with('/') && arg.len() < 4) {
-                    CommandRisk {
-                        level: RiskLevel::Critical,
-                        reason: "Attempting to delete system root directories".to_string(),
-                        suggestions: vec![
-                            "This could destroy your entire system".to_string(),
-                            "Never delete root system directories".to_string(),
-                        ],
-                    }
-                } else {
-                    CommandRisk {
-                        level: RiskLevel::Low,
-                        reason: "File deletion command".to_string(),
-                        suggestions: vec!["Ensure the target files are correct".to_string()],
-                    }
+    fn permissions(&mut self, name: &str, args: &[String]) {
+        let arguments = Arguments::parse(args);
+        let recursive = arguments.short.contains(&'R') || arguments.long.contains("recursive");
+        // The first operand is the mode or owner
+        let targets = arguments.operands.iter().skip(1);
+        for path in targets {
+            match self.checker.classify(path) {
+                target if target.is_vital() && recursive => {
+                    return self.push(RiskLevel::Critical, format!("Changes the {} of everything under {}", permission_noun(name), path), &[
+                        "This can leave the system unable to boot or you unable to log in",
+                        "Double-check the target path",
+                    ]);
                 }
+                Target::Root | Target::System(_) => {
+                    return self.push(RiskLevel::High, format!("Attempts to modify system path: {}", path), &[
+                        "Modifying system paths can break your system",
+                        "Use extreme caution when working with system directories",
+                    ]);
+                }
+                _ => {}
+            }
+        }
+        let mode = arguments.operands.first().map(String::as_str);
+        if name == "chmod" && matches!(mode, Some("000" | "0000" | "a-rwx")) {
+            self.push(RiskLevel::High, "Removes all permissions", &["Nobody will be able to read or run the files"]);
+        } else if recursive {
+            self.push(RiskLevel::Low, "Command performs recursive operations", &[
+                "Be careful with recursive operations on large directory trees",
+            ]);
+        }
+    }
+
+    fn kill(&mut self, name: &str, args: &[String]) {
+        let forced = args.iter().any(|arg| matches!(arg.as_str(), "-9" | "-KILL" | "-SIGKILL" | "-s9"));
+        if name == "kill" {
+            // `kill -1` alone is a signal number, but a last argument of
+            // -1 or 1 is every process or init
+            if args.len() > 1 && args.last().is_some_and(|arg| arg == "-1" || arg == "1") {
+                self.push(RiskLevel::High, "Signals every process, or init", &["This can take down the whole system"]);
             }
-            "dd" => {
-                if command.contains("/dev/") {
-                    CommandRisk {
-                        level: RiskLevel::Critical,
-                        reason: "Direct disk access with dd".to_string(),
-                        suggestions: vec![
-                            "This can overwrite disk data directly".to_string(),
-                            "Wrong usage can destroy all data on the disk".to_string(),
-                            "Verify the input/output devices carefully".to_string(),
-                        ],
+        } else if forced {
+            self.push(RiskLevel::High, "Kills processes by name without letting them clean up", &[
+                "Check which processes match first with pgrep",
+            ]);
+        } else {
+            self.push(RiskLevel::Low, "Kills processes by name", &["Check which processes match first with pgrep"]);
+        }
+    }
+
+    /// Force pushes, which replace history on the remote, and cleaning
+    /// untracked files
+    fn git(&mut self, args: &[String]) {
+        let mut rest = args.iter();
+        while let Some(arg) = rest.next() {
+            match arg.as_str() {
+                // Global options that take a value
+                "-C" | "-c" => {
+                    rest.next();
+                }
+                option if option.starts_with('-') => {}
+                "push" => {
+                    let arguments = Arguments::parse(&rest.cloned().collect::<Vec<_>>());
+                    let forced = arguments.has('f', "force")
+                        || arguments.long.contains("force-with-lease")
+                        || arguments.operands.iter().any(|refspec| refspec.starts_with('+'));
+                    if forced {
+                        self.push(RiskLevel::Medium, "Force push can overwrite commits on the remote", &[
+                            "Check what the remote has first with git fetch and git log",
+                            "Prefer --force-with-lease, and never force push shared branches",
+                        ]);
                     }
-                } else {
-                    CommandRisk {
-                        level: RiskLevel::Medium,
-                        reason: "Data copying with dd".to_string(),
-                        suggestions: vec!["Verify source and destination paths".to_string()],
+                    return;
+                }
+                "clean" => {
+                    let arguments = Arguments::parse(&rest.cloned().collect::<Vec<_>>());
+                    // Without --force git refuses to clean
+                    if arguments.has('f', "force") && !arguments.has('n', "dry-run") {
+                        let ignored = arguments.short.contains(&'x') || arguments.short.contains(&'X');
+                        let reason = if ignored { "Deletes untracked and ignored files" } else { "Deletes untracked files" };
+                        self.push(RiskLevel::Medium, reason, &[
+                            "Git can't bring these files back",
+                            "See what would go first with git clean -n",
+                        ]);
                     }
+                    return;
                 }
+                _ => return,
             }
-            "shutdown" | "reboot" | "halt" | "poweroff" => {
-                CommandRisk {
-                    level: RiskLevel::Medium,
-                    reason: "System power control".to_string(),
-                    suggestions: vec![
-                        "This will shut down or restart the system".to_string(),
-                        "Save your work before proceeding".to_string(),
-                    ],
+        }
+    }
+
+    fn destructive(&mut self, name: &str, args: &[String], context: Context) {
+        let arguments = Arguments::parse(args);
+        match name {
+            "rm" => self.rm(&arguments, context),
+            "dd" => self.dd(args),
+            "shutdown" | "reboot" | "halt" | "poweroff" => self.power(),
+            "init" if matches!(args.first().map(String::as_str), Some("0" | "6")) => self.power(),
+            "systemctl" => {
+                if args.iter().any(|arg| matches!(arg.as_str(), "stop" | "disable" | "mask")) {
+                    self.system(RiskLevel::High, "Stopping or disabling system services", &[
+                        "This may affect system functionality",
+                        "Make sure you understand the service's purpose",
+                    ]);
+                } else if args.iter().any(|arg| matches!(arg.as_str(), "poweroff" | "reboot" | "halt")) {
+                    self.power();
+                } else {
+                    self.system(RiskLevel::Low, "System service management", &["Review the service and action carefully"]);
                 }
             }
-            "systemctl" => {
-                if parts.iter().any(|&arg| arg == "stop" || arg == "disable" || arg == "mask") {
-                    CommandRisk {
-                        level: RiskLevel::High,
-                        reason: "Stopping or disabling system services".to_string(),
-                        suggestions: vec![
-                            "This may affect system functionality".to_string(),
-                            "Make sure you understand the service's purpose".to_string(),
-                        ],
-                    }
+            "fdisk" | "sfdisk" | "parted" if arguments.has('l', "list") => {}
+            "format" if !args.iter().any(|arg| arg.len() == 2 && arg.ends_with(':')) => {}
+            "fdisk" | "sfdisk" | "parted" | "wipefs" | "mkswap" | "mkfs" | "format" => self.format_disk(),
+            _ if name.starts_with("mkfs.") => self.format_disk(),
+            "shred" => self.push(RiskLevel::High, "Overwrites files so they can't be recovered", &[
+                "Make sure you have backups",
+            ]),
+            "del" | "erase" => {
+                if args.iter().any(|arg| arg.eq_ignore_ascii_case("/s")) {
+                    self.push(RiskLevel::Critical, "Recursive file deletion", &[
+                        "This will delete files in every subdirectory",
+                        "Make sure you have backups",
+                    ]);
                 } else {
-                    CommandRisk {
-                        level: RiskLevel::Low,
-                        reason: "System service management".to_string(),
-                        suggestions: vec!["Review the service and action carefully".to_string()],
-                    }
+                    self.push(RiskLevel::Low, "File deletion command", &["Ensure the target files are correct"]);
                 }
             }
-            _ => CommandRisk {
-                level: RiskLevel::Safe,
-                reason: "Standard command usage".to_string(),
-                suggestions: vec![],
+            _ => {}
+        }
+    }
+
+    fn rm(&mut self, arguments: &Arguments, context: Context) {
+        if arguments.long.contains("no-preserve-root") {
+            return self.push(RiskLevel::Critical, "Deletion with --no-preserve-root", &["This is only needed to delete /"]);
+        }
+        let targets: Vec<(&String, Target)> = arguments.operands.iter().map(|path| (path, self.checker.classify(path))).collect();
+        let vital = targets.iter().find(|(_, target)| target.is_vital());
+
+        if arguments.recursive() {
+            if let Some((path, _)) = vital {
+                self.push(RiskLevel::Critical, format!("Recursive deletion of {}", path), &[
+                    "This could destroy your system or all your files",
+                    "Double-check the target path",
+                ]);
+            } else if targets.iter().any(|(_, target)| *target == Target::Unknown) || (targets.is_empty() && context.from_input) {
+                self.push(RiskLevel::High, "Recursive deletion of paths only known when it runs", &[
+                    "Print the paths first to see what would be deleted",
+                    "Make sure you have backups",
+                ]);
+            } else {
+                self.push(RiskLevel::High, "Recursive file deletion", &[
+                    "This will delete files and directories recursively",
+                    "Make sure you have backups",
+                    "Double-check the target path",
+                ]);
             }
+        } else if let Some((path, Target::Root | Target::System(_))) = vital {
+            self.push(RiskLevel::High, format!("Attempts to delete system files: {}", path), &[
+                "This could break your system",
+                "Never delete system files unless you know exactly why",
+            ]);
+        } else {
+            self.push(RiskLevel::Low, "File deletion command", &["Ensure the target files are correct"]);
         }
     }
 
-    fn has_network_risk(&self, command: &str) -> bool {
-        (command.contains("curl") || command.contains("wget")) &&
-        (command.contains("| sh") || command.contains("| bash") || 
-         command.contains("|sh") || command.contains("|bash"))
+    fn dd(&mut self, args: &[String]) {
+        let output = args.iter().find_map(|arg| arg.strip_prefix("of="));
+        match output {
+            Some(device) if device.starts_with("/dev/") && !is_harmless_device(device) => {
+                self.push(RiskLevel::Critical, format!("Writes straight to the device {}", device), &[
+                    "This can overwrite disk data directly",
+                    "Wrong usage can destroy all data on the disk",
+                    "Verify the input/output devices carefully",
+                ]);
+            }
+            Some(path) => {
+                self.written(path);
+                self.push(RiskLevel::Medium, "Data copying with dd", &["Verify source and destination paths"]);
+            }
+            None => self.push(RiskLevel::Medium, "Data copying with dd", &["Verify source and destination paths"]),
+        }
     }
 
-    pub fn is_command_allowed(&self, command: &str) -> bool {
-        let risk = self.assess_command(command);
-        matches!(risk.level, RiskLevel::Safe | RiskLevel::Low)
+    fn power(&mut self) {
+        self.system(RiskLevel::Medium, "System power control", &[
+            "This will shut down or restart the system",
+            "Save your work before proceeding",
+        ]);
     }
 
-    pub fn get_safe_alternatives(&self, command: &str) -> Vec<String> {
-        let mut alternatives = Vec::new();
-        let command_lower = command.to_lowercase();
+    fn format_disk(&mut self) {
+        self.push(RiskLevel::Critical, "Formats or repartitions a disk", &[
+            "This destroys everything on the disk or partition",
+            "Verify the device carefully",
+        ]);
+    }
 
-        if command_lower.starts_with("rm -rf") {
-            alternatives.push("Use 'rm -i' for interactive deletion".to_string());
-            alternatives.push("Move files to trash instead of permanent deletion".to_string());
-            alternatives.push("List files first with 'ls' to verify targets".to_string());
+    /// Commands without their own rules: writes to system paths, then
+    /// recursion
+    fn other(&mut self, name: &str, args: &[String]) {
+        let arguments = Arguments::parse(args);
+        let written: &[String] = match name {
+            "cp" | "install" | "rsync" | "scp" | "ln" => arguments.operands.last().map(std::slice::from_ref).unwrap_or_default(),
+            "mv" | "touch" | "mkdir" | "rmdir" | "tee" | "truncate" | "unlink" => &arguments.operands,
+            "sed" if arguments.has('i', "in-place") => arguments.operands.get(1..).unwrap_or_default(),
+            _ => &[],
+        };
+        for path in written {
+            self.written(path);
+        }
+        if arguments.recursive() {
+            self.push(RiskLevel::Low, "Command performs recursive operations", &[
+                "Be careful with recursive operations on large directory trees",
+                "Consider testing on a small subset first",
+            ]);
         }
+    }
 
-        if command_lower.contains("curl") && command_lower.contains("| sh") {
-            alternatives.push("Download the script first: curl <url> -o script.sh".to_string());
-            alternatives.push("Review the script: cat script.sh".to_string());
-            alternatives.push("Then execute if safe: bash script.sh".to_string());
+    /// Something writes to `path`
+    fn written(&mut self, path: &str) {
+        if path.starts_with("/dev/") {
+            if !is_harmless_device(path) {
+                self.push(RiskLevel::Critical, format!("Writes straight to the device {}", path), &[
+                    "This can overwrite disk data directly",
+                ]);
+            }
+            return;
+        }
+        if let Target::Root | Target::System(_) = self.checker.classify(path) {
+            self.push(RiskLevel::High, format!("Attempts to modify system path: {}", path), &[
+                "Modifying system paths can break your system",
+                "Use extreme caution when working with system directories",
+            ]);
         }
+    }
 
-        if command_lower.starts_with("sudo") {
-            alternatives.push("Try running without sudo first if possible".to_string());
-            alternatives.push("Use specific sudo commands instead of sudo su".to_string());
+    fn push(&mut self, level: RiskLevel, reason: impl Into<String>, suggestions: &[&str]) {
+        self.add(risk(level, reason, suggestions));
+    }
+
+    /// A finding about system administration, which `allow_system_commands`
+    /// rates one level lower
+    fn system(&mut self, level: RiskLevel, reason: impl Into<String>, suggestions: &[&str]) {
+        let level = match level {
+            RiskLevel::Medium if self.checker.allow_system_commands => RiskLevel::Low,
+            RiskLevel::High if self.checker.allow_system_commands => RiskLevel::Medium,
+            level => level,
+        };
+        self.push(level, reason, suggestions);
+    }
+
+    /// A finding of the built-in rules. An allow rule can lower Medium and
+    /// High findings, never Critical ones.
+    fn add(&mut self, mut risk: CommandRisk) {
+        if !self.checker.builtin_checks {
+            return;
         }
+        if let Some(rule) = self.allowance {
+            if matches!(risk.level, RiskLevel::Medium | RiskLevel::High) && rule.level < risk.level {
+                risk.level = rule.level.clone();
+                risk.suggestions.push(format!("Lowered by the policy in {}", rule.source.display()));
+            }
+        }
+        self.risks.push(risk);
+    }
+}
 
-        alternatives
+fn risk(level: RiskLevel, reason: impl Into<String>, suggestions: &[&str]) -> CommandRisk {
+    CommandRisk {
+        level,
+        reason: reason.into(),
+        suggestions: suggestions.iter().map(|suggestion| suggestion.to_string()).collect(),
+    }
+}
+
+fn network_risk(privileged: bool) -> CommandRisk {
+    let suggestions = [
+        "Review the source and content before executing",
+        "Consider downloading and inspecting the script first",
+    ];
+    if privileged {
+        risk(RiskLevel::Critical, "Command runs content from the internet with elevated privileges", &suggestions)
+    } else {
+        risk(RiskLevel::High, "Command downloads and executes content from the internet", &suggestions)
+    }
+}
+
+fn permission_noun(command: &str) -> &'static str {
+    match command {
+        "chmod" => "permissions",
+        "chgrp" => "group",
+        _ => "owner",
+    }
+}
+
+/// Where the command run by a wrapper like `sudo`, `env` or `xargs` starts
+/// in its arguments, or `None` if `name` isn't a wrapper
+fn wrapped_command(name: &str, args: &[String]) -> Option<usize> {
+    let takes_value: &[&str] = match name {
+        "sudo" | "doas" => &["-u", "-g", "-p", "-C", "-D", "-h", "-r", "-t", "-U"],
+        "env" => &["-u", "-C", "--unset", "--chdir"],
+        "nice" => &["-n", "--adjustment"],
+        "xargs" => &["-I", "-n", "-P", "-d", "-a", "-L", "-s", "-E"],
+        "timeout" => &["-s", "-k", "--signal", "--kill-after"],
+        "nohup" | "time" | "command" | "exec" | "busybox" => &[],
+        _ => return None,
+    };
+    let mut start = 0;
+    while let Some(arg) = args.get(start) {
+        if arg == "--" {
+            start += 1;
+            break;
+        } else if takes_value.contains(&arg.as_str()) {
+            start += 2;
+        } else if arg.starts_with('-') || (name == "env" && is_assignment(arg)) {
+            start += 1;
+        } else {
+            break;
+        }
+    }
+    // `timeout 10 command`: the duration comes first
+    if name == "timeout" {
+        start += 1;
+    }
+    Some(start)
+}
+
+fn is_assignment(word: &str) -> bool {
+    word.split_once('=').is_some_and(|(name, _)| {
+        !name.is_empty() && !name.starts_with(|c: char| c.is_ascii_digit()) && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+    })
+}
+
+fn is_harmless_device(path: &str) -> bool {
+    HARMLESS_DEVICES.iter().any(|device| path == *device || (device.ends_with('/') && path.starts_with(device)))
+}
+
+/// Resolve `.`, `..`, repeated and trailing slashes and a trailing `/*` in
+/// an absolute path. Relative paths only lose trailing slashes.
+fn normalize(path: &str) -> String {
+    if !path.starts_with('/') {
+        let trimmed = path.trim_end_matches('/');
+        return if trimmed.is_empty() { path.to_string() } else { trimmed.to_string() };
+    }
+    let mut parts: Vec<&str> = Vec::new();
+    for part in path.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            part => parts.push(part),
+        }
+    }
+    while parts.last() == Some(&"*") {
+        parts.pop();
+    }
+    format!("/{}", parts.join("/"))
+}
+
+/// Whether `path` is `system`, inside it, or a directory containing it
+fn is_system_path(path: &str, system: &str) -> bool {
+    if system.contains('\\') {
+        let (path, system) = (path.to_lowercase().replace('/', "\\"), system.to_lowercase());
+        return path.starts_with(&system);
+    }
+    path == system
+        || path.strip_prefix(system).is_some_and(|rest| rest.starts_with('/'))
+        || system.strip_prefix(path).is_some_and(|rest| rest.starts_with('/'))
+}
+
+fn placeholder(index: usize) -> String {
+    format!("__forge_substitution_{}__", index)
+}
+
+/// Pull `$(...)`, `` `...` ``, `<(...)` and `>(...)` out of a command line,
+/// leaving numbered placeholders, and mark `${...}` references the parser
+/// wouldn't accept. Single quotes hide them.
+fn extract_substitutions(text: &str) -> (String, Vec<String>) {
+    let chars: Vec<char> = text.chars().collect();
+    let (mut outer, mut inner) = (String::new(), Vec::new());
+    let (mut single, mut double) = (false, false);
+    let mut i = 0;
+    while let Some(&c) = chars.get(i) {
+        let substitution = match c {
+            _ if single => {
+                single = c != '\'';
+                None
+            }
+            '\\' => {
+                outer.push(c);
+                i += 1;
+                if let Some(&escaped) = chars.get(i) {
+                    outer.push(escaped);
+                }
+                i += 1;
+                continue;
+            }
+            '\'' if !double => {
+                single = true;
+                None
+            }
+            '"' => {
+                double = !double;
+                None
+            }
+            '`' => {
+                let end = (i + 1..chars.len()).find(|&j| chars[j] == '`' && chars[j - 1] != '\\').unwrap_or(chars.len());
+                Some((i + 1, end))
+            }
+            // `${steps.build.outputs.value}` and the like are filled in
+            // later by workflows
+            '$' if chars.get(i + 1) == Some(&'{') => {
+                let end = (i + 2..chars.len()).find(|&j| chars[j] == '}').unwrap_or(chars.len());
+                let name: String = chars[i + 2..end].iter().collect();
+                if !name.is_empty() && name != "?" && !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                    outer.push_str(SUBSTITUTED);
+                    i = end + 1;
+                    continue;
+                }
+                None
+            }
+            '$' | '<' | '>' if chars.get(i + 1) == Some(&'(') && (c == '$' || !double) => {
+                Some((i + 2, closing_paren(&chars, i + 2)))
+            }
+            _ => None,
+        };
+        match substitution {
+            Some((start, end)) => {
+                outer.push_str(&placeholder(inner.len()));
+                inner.push(chars[start..end].iter().collect());
+                i = end + 1;
+            }
+            None => {
+                outer.push(c);
+                i += 1;
+            }
+        }
+    }
+    (outer, inner)
+}
+
+/// The index of the `)` closing a parenthesis opened just before `start`,
+/// or the end of the text if it isn't closed
+fn closing_paren(chars: &[char], start: usize) -> usize {
+    let (mut depth, mut single, mut double) = (1, false, false);
+    let mut i = start;
+    while let Some(&c) = chars.get(i) {
+        match c {
+            _ if single => single = c != '\'',
+            '\\' => i += 1,
+            '\'' if !double => single = true,
+            '"' => double = !double,
+            '(' if !double => depth += 1,
+            ')' if !double => {
+                depth -= 1;
+                if depth == 0 {
+                    return i;
+                }
+            }
+            _ => {}
+        }
+        i += 1;
     }
+    chars.len()
 }
 
 // File system safety checks
@@ -392,7 +1202,287 @@ mod tests {
     fn test_network_risk() {
         let checker = SafetyChecker::new();
         let risk = checker.assess_command("curl https://example.com/script.sh | sh");
-        assert_eq!(risk.level, RiskLevel::Medium);
+        assert_eq!(risk.level, RiskLevel::High);
+    }
+
+    #[test]
+    fn test_corpus() {
+        use RiskLevel::*;
+        let corpus = [
+            // Everyday commands
+            ("ls -la", Safe),
+            ("git status && git diff --stat", Safe),
+            ("cargo build --release 2>&1 | tail -n 20", Safe),
+            ("cat /etc/hosts", Safe),
+            ("grep -n TODO src/main.rs > /dev/null", Safe),
+            ("echo 'rm -rf /'", Safe),
+            ("echo \"sudo rm -rf /\" | wc -c", Safe),
+            ("chmod +x build.sh", Safe),
+            ("FOO=bar env", Safe),
+            ("fdisk -l", Safe),
+            ("find . -name '*.rs'", Safe),
+            ("cp README.md /tmp/", Safe),
+            ("(cd docs && ls) | sort", Safe),
+            ("echo $(date)", Safe),
+            ("echo '$(rm -rf /)'", Safe),
+            ("", Safe),
+            // rm, however the flags and targets are written
+            ("rm notes.txt", Low),
+            ("rm -rf /", Critical),
+            ("rm -fr /", Critical),
+            ("rm -r -f /", Critical),
+            ("rm -R --force /", Critical),
+            ("rm --recursive --force /", Critical),
+            ("rm   -rf    /", Critical),
+            ("rm -rf /*", Critical),
+            ("rm -rf //", Critical),
+            ("rm -rf /tmp/../", Critical),
+            ("rm -rf -- /", Critical),
+            ("/bin/rm -rf /", Critical),
+            ("rm -r -f ~", Critical),
+            ("rm -rf ~/", Critical),
+            ("rm -rf ~/*", Critical),
+            ("rm -rf $HOME", Critical),
+            ("rm -rf \"$HOME\"/", Critical),
+            ("rm -rf $FORGE_SAFETY_UNSET_VARIABLE/", Critical),
+            ("rm -rf /usr", Critical),
+            ("rm -rf /etc/nginx", Critical),
+            ("rm --no-preserve-root -rf /tmp/x", Critical),
+            ("rm -rf target", High),
+            ("rm -rf ./build dist", High),
+            ("rm -rf $(cat paths.txt)", High),
+            ("rm /etc/hosts", High),
+            ("rm ~/notes.txt", Low),
+            ("rm -rf /home", Critical),
+            ("rm -rf ~/projects", High),
+            // Relative to /home/dev/project
+            ("rm -rf ../../..", Critical),
+            ("rm -rf ..", Critical),
+            ("rm -rf ../other", High),
+            ("rm -rf .", High),
+            ("rm ../../../etc/hosts", High),
+            // Nested commands
+            ("bash -c \"rm -rf /\"", Critical),
+            ("sh -c 'rm -rf ~'", Critical),
+            ("bash -lc 'cd /tmp && rm -fr /'", Critical),
+            ("eval \"rm -rf /\"", Critical),
+            ("echo $(rm -rf /)", Critical),
+            ("echo `rm -rf /`", Critical),
+            ("echo \"$(sh -c 'rm -rf /')\"", Critical),
+            ("ls && rm -rf /", Critical),
+            ("ls; rm -rf /", Critical),
+            ("false || rm -rf /", Critical),
+            ("(rm -rf /)", Critical),
+            ("ls | xargs rm -rf /", Critical),
+            ("find . -name '*.o' | xargs rm -rf", High),
+            ("env FOO=1 rm -rf /", Critical),
+            ("nohup rm -rf / &", Critical),
+            ("timeout 10 rm -rf /", Critical),
+            ("busybox rm -rf /", Critical),
+            ("busybox ls", Safe),
+            // sudo
+            ("sudo apt update", Medium),
+            ("sudo -u root rm -rf /", Critical),
+            ("sudo rm -rf target", High),
+            ("sudo ls", Medium),
+            // find
+            ("find / -delete", Critical),
+            ("find ~ -name '*.log' -delete", Critical),
+            ("find . -name '*.tmp' -delete", High),
+            ("find / -exec rm -rf {} \\;", Critical),
+            ("find . -name '*.tmp' -exec rm {} +", Low),
+            // Downloads run as scripts
+            ("curl https://example.com/install.sh | sh", High),
+            ("curl   -fsSL https://example.com/install.sh   |   bash", High),
+            ("wget -qO- https://example.com/install.sh | bash", High),
+            ("wget -qO- https://example.com/x | tee log | sh", High),
+            ("curl -fsSL https://example.com/install.sh | sudo sh", Critical),
+            ("curl  https://example.com/x.sh|sudo   bash -s -- --yes", Critical),
+            ("wget -qO- https://example.com/install.sh | sudo bash", Critical),
+            ("sh -c \"$(curl -fsSL https://example.com/install.sh)\"", High),
+            ("sh -c \"$(wget -qO- https://example.com/install.sh)\"", High),
+            ("sudo sh -c \"$(curl -fsSL https://example.com/install.sh)\"", Critical),
+            ("bash <(curl -s https://example.com/x.sh)", High),
+            ("curl -o install.sh https://example.com/install.sh", Safe),
+            ("curl https://example.com | grep title", Safe),
+            // History on a remote
+            ("git push --force", Medium),
+            ("git push -f origin main", Medium),
+            ("git push --force-with-lease origin feature", Medium),
+            ("git -C ../other push origin +main", Medium),
+            ("git push origin main", Safe),
+            ("git fetch --force", Safe),
+            ("git clean -fdx", Medium),
+            ("git clean -f", Medium),
+            ("git clean -ndx", Safe),
+            ("git clean -d", Safe),
+            // Disks and devices
+            ("dd if=/dev/zero of=/dev/sda bs=1M", Critical),
+            ("dd if=disk.img of=backup.img", Medium),
+            ("dd if=/dev/urandom of=/dev/null count=1", Medium),
+            ("echo hi > /dev/sda", Critical),
+            ("mkfs.ext4 /dev/sdb1", Critical),
+            ("wipefs -a /dev/sdb", Critical),
+            ("shred -u secrets.txt", High),
+            ("format c:", Critical),
+            ("del /s build", Critical),
+            // System paths
+            ("echo 'nameserver 1.1.1.1' > /etc/resolv.conf", High),
+            ("echo x >> /etc/profile", High),
+            ("cp evil /usr/bin/ls", High),
+            ("mv /etc/passwd /tmp", High),
+            ("sed -i s/a/b/ /etc/hosts", High),
+            ("sed s/a/b/ /etc/hosts", Safe),
+            ("chmod -R 777 /", Critical),
+            ("chown -R nobody ~", Critical),
+            ("chmod 000 script.sh", High),
+            ("chmod 644 /etc/shadow", High),
+            ("chmod -R 777 ../../../etc", Critical),
+            ("find ../.. -delete", Critical),
+            ("echo x > ../../../etc/profile", High),
+            // Processes and power
+            ("kill -9 -1", High),
+            ("kill -9 1234", Safe),
+            ("killall -9 firefox", High),
+            ("pkill node", Low),
+            ("shutdown -h now", Medium),
+            ("init 0", Medium),
+            ("systemctl stop nginx", High),
+            ("systemctl status nginx", Low),
+            (":(){ :|:& };:", Critical),
+            (":() { : | : & } ; :", Critical),
+            // Recursion elsewhere
+            ("cp -r src backup", Low),
+            ("grep -R TODO .", Low),
+        ];
+
+        let mut variables = HashMap::new();
+        variables.insert("HOME".to_string(), "/home/dev".to_string());
+        let checker = SafetyChecker::new().with_variables(variables).with_working_directory("/home/dev/project");
+        let failures: Vec<String> = corpus.iter()
+            .map(|(command, expected)| (command, expected, checker.assess_command(command)))
+            .filter(|(_, expected, risk)| risk.level != **expected)
+            .map(|(command, expected, risk)| format!("{:?}: expected {:?}, got {:?} ({})", command, expected, risk.level, risk.reason))
+            .collect();
+        assert!(failures.is_empty(), "{}", failures.join("\n"));
+    }
+
+    #[test]
+    fn test_substitutions_and_paths() {
+        let (outer, inner) = extract_substitutions("echo \"$(date +%s)\" '$(not)' `id` <(ls (x)) ${steps.a.exit_code} ${HOME}");
+        assert_eq!(outer, "echo \"__forge_substitution_0__\" '$(not)' __forge_substitution_1__ __forge_substitution_2__ __forge_substitution__ ${HOME}");
+        assert_eq!(inner, ["date +%s", "id", "ls (x)"]);
+
+        assert_eq!(normalize("/usr/./bin//"), "/usr/bin");
+        assert_eq!(normalize("/tmp/../etc/*"), "/etc");
+        assert_eq!(normalize("build/"), "build");
+        assert!(is_system_path("/usr", "/usr/bin"));
+        assert!(is_system_path("/etc/nginx", "/etc"));
+        assert!(!is_system_path("/etcetera", "/etc"));
+        assert!(is_system_path("c:/windows/system32", "C:\\Windows"));
+
+        let args: Vec<String> = ["-rf", "--verbose", "a", "--", "-b"].iter().map(|arg| arg.to_string()).collect();
+        let arguments = Arguments::parse(&args);
+        assert!(arguments.recursive() && arguments.has('f', "force") && arguments.long.contains("verbose"));
+        assert_eq!(arguments.operands, ["a", "-b"]);
+    }
+
+    #[test]
+    fn test_policy_and_config() {
+        let policy = r#"
+[[rule]]
+action = "allow"
+command = "rm"
+paths = ["/work/target"]
+
+[[rule]]
+action = "deny"
+command = "git"
+args = ["push", "--force"]
+level = "critical"
+message = "Force pushes go through review"
+"#;
+        let rules = safety_policy::parse(policy, Path::new("policy.toml")).unwrap();
+        let checker = SafetyChecker::new()
+            .with_policy(Policy { rules, sources: Vec::new() })
+            .with_working_directory("/work");
+
+        assert_eq!(checker.assess_command("rm -rf target").level, RiskLevel::Safe);
+        assert_eq!(checker.assess_command("rm -rf target/debug ./target").level, RiskLevel::Safe);
+        assert_eq!(checker.assess_command("rm -rf target src").level, RiskLevel::High);
+        let risk = checker.assess_command("git push --force origin main");
+        assert_eq!(risk.level, RiskLevel::Critical);
+        assert_eq!(risk.reason, "Force pushes go through review");
+        // Rules apply wherever the command runs
+        assert_eq!(checker.assess_command("sudo -u ci git push origin --force").level, RiskLevel::Critical);
+        assert_eq!(checker.assess_command("git push origin main").level, RiskLevel::Safe);
+
+        let config = SafetyConfig {
+            enable_safety_checks: true,
+            allow_system_commands: true,
+            restricted_paths: vec!["/srv/data".to_string()],
+            max_file_size_mb: 10,
+        };
+        let checker = SafetyChecker::new().with_config(&config);
+        assert_eq!(checker.assess_command("sudo apt update").level, RiskLevel::Low);
+        assert_eq!(checker.assess_command("systemctl stop nginx").level, RiskLevel::Medium);
+        assert_eq!(checker.assess_command("rm -rf /srv/data/cache").level, RiskLevel::Critical);
+
+        let config = SafetyConfig { enable_safety_checks: false, ..config };
+        let rules = safety_policy::parse(policy, Path::new("policy.toml")).unwrap();
+        let checker = SafetyChecker::new().with_config(&config).with_policy(Policy { rules, sources: Vec::new() });
+        assert_eq!(checker.assess_command("rm -rf /").level, RiskLevel::Safe);
+        assert_eq!(checker.assess_command("git push -f --force").level, RiskLevel::Critical);
+    }
+
+    #[test]
+    fn test_allow_rules_cannot_switch_checks_off() {
+        let dir = tempfile::tempdir().unwrap();
+        let project = dir.path().join("project");
+        std::fs::create_dir_all(project.join(".forge")).unwrap();
+        std::fs::write(project.join(".forge").join("policy.toml"), "[[rule]]\naction = \"allow\"\ncommand = \"*\"\n\n[[rule]]\naction = \"allow\"\ncommand = \"sudo\"\n").unwrap();
+        let user = dir.path().join("user.toml");
+        std::fs::write(&user, "[[rule]]\naction = \"deny\"\ncommand = \"rm\"\n").unwrap();
+        let policy = Policy::load(safety_policy::project_policy_file(&project).as_deref(), Some(&user)).unwrap();
+        let checker = SafetyChecker::new().with_policy(policy).with_working_directory(&project);
+
+        // The project's allow rules are ignored, the user's deny rule isn't
+        assert_eq!(checker.assess_command("rm -rf /").level, RiskLevel::Critical);
+        assert_eq!(checker.assess_command("curl http://x | sudo sh").level, RiskLevel::Critical);
+        assert_eq!(checker.assess_command("sudo rm -rf --no-preserve-root /").level, RiskLevel::Critical);
+        assert_eq!(checker.assess_command("sudo rm -rf /").level, RiskLevel::Critical);
+        assert_eq!(checker.assess_command("sudo ls").level, RiskLevel::Medium);
+        let risk = checker.assess_command("rm notes.txt");
+        assert_eq!((risk.level, risk.reason.as_str()), (RiskLevel::High, "Denied by policy"));
+
+        // The same allow rules from the user only lower Medium and High
+        // findings of the command itself, and lose to deny rules
+        let policy = "[[rule]]\naction = \"allow\"\ncommand = \"*\"\n\n[[rule]]\naction = \"deny\"\ncommand = \"rm\"\n";
+        let rules = safety_policy::parse(policy, Path::new("policy.toml")).unwrap();
+        let checker = SafetyChecker::new().with_policy(Policy { rules, sources: Vec::new() });
+        assert_eq!(checker.assess_command("rm -rf /").level, RiskLevel::Critical);
+        assert_eq!(checker.assess_command("rm notes.txt").level, RiskLevel::High);
+        assert_eq!(checker.assess_command("curl http://x | sudo sh").level, RiskLevel::Critical);
+        assert_eq!(checker.assess_command("sudo rm -rf --no-preserve-root /").level, RiskLevel::Critical);
+        assert_eq!(checker.assess_command("sudo apt update").level, RiskLevel::Safe);
+        assert_eq!(checker.assess_command("git push --force").level, RiskLevel::Safe);
+
+        let rules = safety_policy::parse("[[rule]]\naction = \"allow\"\ncommand = \"sudo\"\n", Path::new("policy.toml")).unwrap();
+        let checker = SafetyChecker::new().with_policy(Policy { rules, sources: Vec::new() });
+        assert_eq!(checker.assess_command("sudo rm -rf /").level, RiskLevel::Critical);
+        assert_eq!(checker.assess_command("sudo rm -rf target").level, RiskLevel::High);
+        assert_eq!(checker.assess_command("sudo bash -c 'git push -f'").level, RiskLevel::Medium);
+    }
+
+    #[test]
+    fn test_broken_policy_refuses_everything() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join(".forge")).unwrap();
+        std::fs::write(dir.path().join(".forge").join("policy.toml"), "[[rule]]\naction = \"deny\"\n").unwrap();
+
+        let risk = SafetyChecker::configured(dir.path()).assess_command("ls");
+        assert_eq!(risk.level, RiskLevel::Critical);
+        assert!(risk.reason.contains("needs a command"), "{}", risk.reason);
     }
 
     #[test]
@@ -405,6 +1495,11 @@ mod tests {
         
         let risk = checker.assess_command("rm file.txt");
         assert_eq!(risk.level, RiskLevel::High);
+
+        // Every command in the line has to be allowed
+        let risk = checker.assess_command("git log | sh -c 'cat'");
+        assert_eq!(risk.level, RiskLevel::High);
+        assert!(risk.reason.contains("'sh'"));
     }
 
     #[test]
@@ -0,0 +1,313 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::config;
+use crate::fs::glob::GlobMatcher;
+use super::safety::{CommandRisk, RiskLevel};
+
+/// Allow and deny rules from `policy.toml` files: project rules, then user
+/// rules. Deny rules from either apply; allow rules only from the user's
+/// policy, so a cloned project can't switch the checks off.
+#[derive(Debug, Clone, Default)]
+pub struct Policy {
+    pub rules: Vec<Rule>,
+    /// The files the rules came from, in precedence order
+    pub sources: Vec<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    /// Lower the built-in Medium and High findings for matching commands
+    /// to the rule's level. Critical findings and the commands they run
+    /// are still checked as usual.
+    Allow,
+    /// Rate matching commands at least at the rule's level
+    Deny,
+}
+
+#[derive(Debug, Clone)]
+pub struct Rule {
+    pub action: Action,
+    /// Glob for the command name, like `terraform` or `kubectl*`
+    pub command: Option<String>,
+    /// Globs that each have to match one of the arguments
+    pub args: Vec<String>,
+    /// Globs for paths the command works on. A deny rule matches when an
+    /// operand is inside one or contains one; an allow rule only when every
+    /// operand is inside one.
+    pub paths: Vec<String>,
+    pub level: RiskLevel,
+    pub message: String,
+    pub source: PathBuf,
+    /// From a project's policy, which can only make things stricter
+    pub project: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PolicyFile {
+    #[serde(default)]
+    rule: Vec<TomlRule>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TomlRule {
+    action: Action,
+    command: Option<String>,
+    #[serde(default)]
+    args: Vec<String>,
+    #[serde(default)]
+    paths: Vec<String>,
+    level: Option<RiskLevel>,
+    message: Option<String>,
+}
+
+impl Policy {
+    /// Load the project policy, then the user policy. Files that don't
+    /// exist are skipped. Relative `paths` in the project policy are
+    /// relative to the project; in the user policy they match anywhere.
+    pub fn load(project_file: Option<&Path>, user_file: Option<&Path>) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut policy = Policy::default();
+        // The directory holding `.forge`
+        let project_root = project_file.and_then(Path::parent).and_then(Path::parent);
+        for (path, base) in [(project_file, project_root), (user_file, None)] {
+            let Some(path) = path.filter(|path| path.is_file()) else {
+                continue;
+            };
+            let content = fs::read_to_string(path)
+                .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+            let mut rules = parse(&content, path)?;
+            for rule in &mut rules {
+                rule.project = base.is_some();
+                for pattern in &mut rule.paths {
+                    *pattern = anchor(pattern, base);
+                }
+            }
+            policy.rules.extend(rules);
+            policy.sources.push(path.to_path_buf());
+        }
+        Ok(policy)
+    }
+
+    /// The rule deciding a command: the strictest matching deny rule, or
+    /// failing that the first matching allow rule from the user's policy.
+    /// `paths` are its operands, already resolved to absolute paths.
+    pub fn find(&self, name: &str, args: &[String], paths: &[String], home: Option<&str>) -> Option<&Rule> {
+        let matching = || self.rules.iter().filter(|rule| rule.matches(name, args, paths, home));
+        matching()
+            .filter(|rule| rule.action == Action::Deny)
+            .reduce(|strictest, rule| if rule.level > strictest.level { rule } else { strictest })
+            .or_else(|| matching().find(|rule| rule.action == Action::Allow && !rule.project))
+    }
+
+    /// Allow rules from project policies, which are ignored
+    pub fn ignored(&self) -> impl Iterator<Item = &Rule> {
+        self.rules.iter().filter(|rule| rule.action == Action::Allow && rule.project)
+    }
+}
+
+impl Rule {
+    fn matches(&self, name: &str, args: &[String], paths: &[String], home: Option<&str>) -> bool {
+        // `-rf` matches patterns for `-r` and `-f` as well
+        let mut words: Vec<String> = args.to_vec();
+        for arg in args {
+            if arg.len() > 2 && arg.starts_with('-') && !arg.starts_with("--") {
+                words.extend(arg.chars().skip(1).map(|flag| format!("-{}", flag)));
+            }
+        }
+
+        self.command.as_ref().is_none_or(|command| GlobMatcher::new(command).matches_str(name))
+            && self.args.iter().all(|pattern| {
+                let matcher = GlobMatcher::new(pattern);
+                words.iter().any(|word| matcher.matches_str(word))
+            })
+            && (self.paths.is_empty() || self.matches_paths(paths, home))
+    }
+
+    fn matches_paths(&self, paths: &[String], home: Option<&str>) -> bool {
+        let patterns: Vec<String> = self.paths.iter().map(|pattern| expand_home(pattern, home)).collect();
+        match self.action {
+            Action::Allow => !paths.is_empty() && paths.iter().all(|path| patterns.iter().any(|pattern| is_inside(pattern, path))),
+            Action::Deny => paths.iter().any(|path| patterns.iter().any(|pattern| is_inside(pattern, path) || contains(path, pattern))),
+        }
+    }
+
+    pub fn risk(&self) -> CommandRisk {
+        CommandRisk {
+            level: self.level.clone(),
+            reason: self.message.clone(),
+            suggestions: vec![format!("From the policy in {}", self.source.display())],
+        }
+    }
+}
+
+/// Parse the rules of a policy file
+pub fn parse(content: &str, source: &Path) -> Result<Vec<Rule>, Box<dyn std::error::Error>> {
+    let file: PolicyFile = toml::from_str(content)
+        .map_err(|e| format!("Invalid policy {}: {}", source.display(), e))?;
+    file.rule.into_iter().enumerate().map(|(i, rule)| {
+        if rule.command.is_none() && rule.args.is_empty() && rule.paths.is_empty() {
+            return Err(format!("Invalid policy {}: rule {} needs a command, args or paths to match", source.display(), i + 1).into());
+        }
+        let (level, message) = match rule.action {
+            Action::Allow => (rule.level.unwrap_or(RiskLevel::Safe), rule.message.unwrap_or_else(|| "Allowed by policy".to_string())),
+            Action::Deny => (rule.level.unwrap_or(RiskLevel::High), rule.message.unwrap_or_else(|| "Denied by policy".to_string())),
+        };
+        Ok(Rule {
+            action: rule.action,
+            command: rule.command,
+            args: rule.args,
+            paths: rule.paths,
+            level,
+            message,
+            source: source.to_path_buf(),
+            project: false,
+        })
+    }).collect()
+}
+
+/// `.forge/policy.toml` in `start` or its nearest ancestor that has one
+pub fn project_policy_file(start: &Path) -> Option<PathBuf> {
+    start
+        .ancestors()
+        .map(|dir| dir.join(".forge").join("policy.toml"))
+        .find(|path| path.is_file())
+}
+
+pub fn user_policy_file() -> Option<PathBuf> {
+    config::config_dir().map(|dir| dir.join("policy.toml"))
+}
+
+/// A relative path pattern joined to `base`, or made to match in any
+/// directory without one
+fn anchor(pattern: &str, base: Option<&Path>) -> String {
+    if pattern.starts_with('/') || pattern.starts_with('~') {
+        return pattern.to_string();
+    }
+    let relative = pattern.trim_start_matches("./");
+    match base {
+        Some(base) => format!("{}/{}", base.display().to_string().trim_end_matches('/'), relative),
+        None => format!("**/{}", relative),
+    }
+}
+
+fn expand_home(pattern: &str, home: Option<&str>) -> String {
+    match (pattern.strip_prefix('~'), home) {
+        (Some(rest), Some(home)) if rest.is_empty() || rest.starts_with('/') => format!("{}{}", home.trim_end_matches('/'), rest),
+        _ => pattern.to_string(),
+    }
+}
+
+/// Whether `pattern` matches `path` or a directory containing it
+fn is_inside(pattern: &str, path: &str) -> bool {
+    let matcher = GlobMatcher::new(pattern.trim_end_matches('/'));
+    Path::new(path).ancestors().any(|ancestor| matcher.matches_str(&ancestor.to_string_lossy()))
+}
+
+/// Whether the directory `path` contains what `pattern` names
+fn contains(path: &str, pattern: &str) -> bool {
+    // The directories of the pattern before any wildcard
+    let prefix = pattern.split(['*', '?', '[']).next().unwrap_or_default();
+    let literal = if prefix.len() == pattern.len() { prefix } else { &prefix[..prefix.rfind('/').unwrap_or(0)] };
+    literal.strip_prefix(path.trim_end_matches('/')).is_some_and(|rest| rest.starts_with('/'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POLICY: &str = r#"
+[[rule]]
+action = "allow"
+command = "rm"
+paths = ["/work/project/target"]
+message = "Build output can always go"
+
+[[rule]]
+action = "deny"
+command = "terraform"
+args = ["destroy"]
+level = "critical"
+message = "Destroy infrastructure from CI, not a shell"
+
+[[rule]]
+action = "deny"
+paths = ["~/.ssh"]
+"#;
+
+    fn strings(words: &[&str]) -> Vec<String> {
+        words.iter().map(|word| word.to_string()).collect()
+    }
+
+    #[test]
+    fn test_rules() {
+        let rules = parse(POLICY, Path::new("policy.toml")).unwrap();
+        let policy = Policy { rules, sources: Vec::new() };
+        let find = |name: &str, args: &[&str], paths: &[&str]| {
+            policy.find(name, &strings(args), &strings(paths), Some("/home/dev")).map(|rule| (rule.action, rule.level.clone()))
+        };
+
+        assert_eq!(find("rm", &["-rf", "target"], &["/work/project/target"]), Some((Action::Allow, RiskLevel::Safe)));
+        assert_eq!(find("rm", &["-rf", "target/debug"], &["/work/project/target/debug"]), Some((Action::Allow, RiskLevel::Safe)));
+        assert_eq!(find("rm", &["-rf", "src"], &["/work/project/src"]), None);
+        assert_eq!(find("rm", &["-rf", "/work"], &["/work"]), None);
+        assert_eq!(find("rm", &["-rf", "target", "src"], &["/work/project/target", "/work/project/src"]), None);
+        assert_eq!(find("terraform", &["destroy", "-auto-approve"], &[]), Some((Action::Deny, RiskLevel::Critical)));
+        assert_eq!(find("terraform", &["plan"], &[]), None);
+        assert_eq!(find("cat", &["id_rsa"], &["/home/dev/.ssh/id_rsa"]), Some((Action::Deny, RiskLevel::High)));
+        // Removing a directory that contains a protected path
+        assert_eq!(find("rm", &["-rf", "/home/dev"], &["/home/dev"]), Some((Action::Deny, RiskLevel::High)));
+    }
+
+    #[test]
+    fn test_invalid_policies() {
+        let error = parse("[[rule]]\naction = \"deny\"\n", Path::new("p.toml")).unwrap_err().to_string();
+        assert!(error.contains("needs a command"), "{}", error);
+        let error = parse("[[rule]]\naction = \"block\"\ncommand = \"x\"\n", Path::new("p.toml")).unwrap_err().to_string();
+        assert!(error.contains("p.toml"), "{}", error);
+        assert!(parse("[[rule]]\naction = \"deny\"\ncommand = \"x\"\nlevel = \"severe\"\n", Path::new("p.toml")).is_err());
+    }
+
+    #[test]
+    fn test_deny_rules_win() {
+        let rules = parse("[[rule]]\naction = \"allow\"\ncommand = \"*\"\n\n[[rule]]\naction = \"deny\"\ncommand = \"rm\"\n\n[[rule]]\naction = \"deny\"\nargs = [\"-f\"]\nlevel = \"critical\"\n", Path::new("policy.toml")).unwrap();
+        let policy = Policy { rules, sources: Vec::new() };
+        let find = |name: &str, args: &[&str]| policy.find(name, &strings(args), &[], None).map(|rule| (rule.action, rule.level.clone()));
+
+        assert_eq!(find("ls", &[]), Some((Action::Allow, RiskLevel::Safe)));
+        assert_eq!(find("rm", &["notes.txt"]), Some((Action::Deny, RiskLevel::High)));
+        // The strictest deny rule, not the first
+        assert_eq!(find("rm", &["-rf", "target"]), Some((Action::Deny, RiskLevel::Critical)));
+    }
+
+    #[test]
+    fn test_project_policies_only_deny() {
+        let dir = tempfile::tempdir().unwrap();
+        let project = dir.path().join("project");
+        fs::create_dir_all(project.join(".forge")).unwrap();
+        fs::create_dir_all(project.join("src")).unwrap();
+        fs::write(project.join(".forge").join("policy.toml"), "[[rule]]\naction = \"allow\"\ncommand = \"*\"\n\n[[rule]]\naction = \"deny\"\npaths = [\"./release\"]\n").unwrap();
+        let user = dir.path().join("user.toml");
+        fs::write(&user, "[[rule]]\naction = \"allow\"\ncommand = \"make\"\n\n[[rule]]\naction = \"deny\"\npaths = [\"secrets\"]\n").unwrap();
+
+        let project_file = project_policy_file(&project.join("src")).unwrap();
+        let policy = Policy::load(Some(&project_file), Some(&user)).unwrap();
+        assert_eq!(policy.sources, [project_file.clone(), user.clone()]);
+        assert_eq!(policy.ignored().count(), 1);
+
+        // The project's allow rule doesn't apply, the user's does
+        assert!(policy.find("cargo", &[], &[], None).is_none());
+        assert_eq!(policy.find("make", &[], &[], None).unwrap().source, user);
+        let release = project.join("release").join("v1").display().to_string();
+        assert_eq!(policy.find("rm", &[], &[release], None).unwrap().source, project_file);
+        assert!(policy.find("rm", &[], &["/elsewhere/release".to_string()], None).is_none());
+        assert_eq!(policy.find("cat", &[], &["/any/where/secrets/key".to_string()], None).unwrap().source, user);
+
+        let policy = Policy::load(None, Some(&dir.path().join("missing.toml"))).unwrap();
+        assert!(policy.rules.is_empty());
+    }
+}
@@ -2,10 +2,32 @@
 
 use std::collections::HashMap;
 use std::env;
-use std::process::Command;
-use std::io::{self, Write};
-use std::path::Path;
-use crate::terminal::output::{success_text, error_text, warning_text, Spinner};
+use std::fs::{File, OpenOptions};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::io::{self, IsTerminal, PipeReader, PipeWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use crate::terminal::editor::LineEditor;
+use crate::terminal::input::{InputReader, Key};
+use crate::terminal::raw::RawTerminal;
+use crate::config::{self, client};
+use crate::terminal::output::{success_text, error_text, warning_text, info_text, dim_text, prompt_yes_no, Color, Spinner, StyledText};
+use super::audit::{Approval, AuditEntry, AuditLog, Initiator};
+use super::command_suggest::{self, CommandContext, Failure};
+use super::executor::{Confirmation, Decision, ExecutionOptions};
+use super::safety::{CommandRisk, RiskLevel, SafetyChecker};
+use super::shell_completion::{self, Completer};
+use super::shell_parser::{AndOrList, Command as ShellCommand, Connector, Parser, Pipeline, Redirect, RedirectTarget, Script, Word};
+#[cfg(unix)]
+use super::shell_jobs::{self, Job, JobState, Outcome, STOPPED_STATUS};
+#[cfg(unix)]
+use crate::terminal::output::GLOBAL_TASK_MONITOR;
+#[cfg(unix)]
+use nix::sys::signal::{self as unix_signal, Signal as UnixSignal};
+#[cfg(unix)]
+use nix::unistd::{self, Pid};
 
 #[derive(Debug, Clone)]
 pub struct ShellEnvironment {
@@ -79,7 +101,6 @@ impl ShellEnvironment {
 
         if absolute_path.exists() && absolute_path.is_dir() {
             self.working_directory = absolute_path.to_string_lossy().to_string();
-            env::set_current_dir(&absolute_path)?;
             Ok(())
         } else {
             Err(format!("Directory does not exist: {}", expanded_path).into())
@@ -87,30 +108,322 @@ impl ShellEnvironment {
     }
 }
 
+/// Options for commands typed at the interactive shell
+const INTERACTIVE: ExecutionOptions = ExecutionOptions {
+    timeout: None,
+    show_progress: false,
+    capture_output: false,
+    interactive: true,
+    safety_check: false,
+    working_directory: None,
+};
+
+/// Commands the shell runs itself rather than as a process
+pub const BUILTINS: [&str; 11] = ["cd", "pwd", "echo", "set", "alias", "history", "exit", "jobs", "fg", "bg", "kill"];
+
+/// Exit code of a command line stopped by its timeout, as with timeout(1)
+const TIMEOUT_STATUS: i32 = 124;
+
+/// How long a timed out process group gets between SIGTERM and SIGKILL
+const KILL_GRACE: Duration = Duration::from_secs(3);
+
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// Bytes of copied stderr kept for explaining a failure
+const STDERR_TAIL: usize = 8192;
+
+/// How long to wait for copied stderr to reach its end
+const TEE_GRACE: Duration = Duration::from_millis(200);
+
+/// Exit codes of commands stopped with Ctrl+C or Ctrl+Z, which don't need
+/// explaining
+const USER_STOPPED: [i32; 2] = [130, 148];
+
+#[derive(Clone)]
 pub struct Shell {
     environment: ShellEnvironment,
     history: Vec<String>,
     aliases: HashMap<String, String>,
+    /// Exit code of the last pipeline, for `$?`
+    last_status: i32,
+    exit_requested: bool,
+    /// When the running command line's timeout runs out. Its processes
+    /// then get a process group of their own, so all of them can be killed.
+    deadline: Option<Instant>,
+    /// Hand the terminal to the running command line's process groups
+    foreground: bool,
+    /// An interactive shell on a terminal: every pipeline gets a process
+    /// group of its own, and Ctrl+Z stops it and makes it a job
+    job_control: bool,
+    #[cfg(unix)]
+    jobs: Vec<Job>,
+    /// The background job this copy of the shell runs
+    #[cfg(unix)]
+    job: Option<Job>,
+    /// Copy stderr through a pipe when it goes to the terminal, so a
+    /// failed command can be explained
+    explain_failures: bool,
+    /// The end of what the last command line wrote to stderr, when copied
+    last_stderr: String,
+    /// Where commands run at the prompt are recorded
+    audit: Option<AuditLog>,
+    /// How suggested commands and fixes that aren't safe get approved
+    confirmation: Confirmation,
+    /// The safety rules to use instead of the working directory's
+    checker: Option<SafetyChecker>,
+}
+
+/// Where a command reads its input from
+enum Input {
+    Null,
+    /// Forge's own stdin
+    Inherit,
+    Pipe(PipeReader),
+    File(File),
+}
+
+/// Where a command writes its output or errors to
+enum Output {
+    /// Forge's own stdout
+    Stdout,
+    /// Forge's own stderr
+    Stderr,
+    Pipe(PipeWriter),
+    File(File),
+}
+
+impl Input {
+    fn try_clone(&self) -> io::Result<Input> {
+        Ok(match self {
+            Input::Null => Input::Null,
+            Input::Inherit => Input::Inherit,
+            Input::Pipe(pipe) => Input::Pipe(pipe.try_clone()?),
+            Input::File(file) => Input::File(file.try_clone()?),
+        })
+    }
+
+    fn into_stdio(self) -> Stdio {
+        match self {
+            Input::Null => Stdio::null(),
+            Input::Inherit => Stdio::inherit(),
+            Input::Pipe(pipe) => pipe.into(),
+            Input::File(file) => file.into(),
+        }
+    }
+}
+
+impl Output {
+    fn try_clone(&self) -> io::Result<Output> {
+        Ok(match self {
+            Output::Stdout => Output::Stdout,
+            Output::Stderr => Output::Stderr,
+            Output::Pipe(pipe) => Output::Pipe(pipe.try_clone()?),
+            Output::File(file) => Output::File(file.try_clone()?),
+        })
+    }
+
+    fn into_stdio(self) -> Stdio {
+        match self {
+            Output::Stdout => io::stdout().into(),
+            Output::Stderr => io::stderr().into(),
+            Output::Pipe(pipe) => pipe.into(),
+            Output::File(file) => file.into(),
+        }
+    }
+
+    fn write_all(&mut self, text: &str) -> io::Result<()> {
+        match self {
+            Output::Stdout => io::stdout().write_all(text.as_bytes()).and_then(|_| io::stdout().flush()),
+            Output::Stderr => io::stderr().write_all(text.as_bytes()),
+            Output::Pipe(pipe) => pipe.write_all(text.as_bytes()),
+            Output::File(file) => file.write_all(text.as_bytes()),
+        }
+    }
+}
+
+/// The standard input, output and error of a command
+struct Streams {
+    stdin: Input,
+    stdout: Output,
+    stderr: Output,
+}
+
+impl Streams {
+    fn try_clone(&self) -> io::Result<Streams> {
+        Ok(Streams {
+            stdin: self.stdin.try_clone()?,
+            stdout: self.stdout.try_clone()?,
+            stderr: self.stderr.try_clone()?,
+        })
+    }
+}
+
+/// A pipeline stage that has been started
+enum Running {
+    /// A process, and the process group it leads or joined
+    Process(Child, Option<u32>),
+    /// A builtin or subshell running on its own thread
+    Thread(JoinHandle<i32>),
+    Done(i32),
+}
+
+impl Running {
+    /// Wait for the stage to finish, killing its process group once
+    /// `deadline` passes
+    fn wait(self, deadline: Option<Instant>) -> i32 {
+        match self {
+            Running::Process(mut child, group) => match deadline {
+                Some(deadline) => wait_until(&mut child, group, deadline),
+                None => child.wait().map_or(1, exit_code),
+            },
+            Running::Thread(handle) => handle.join().unwrap_or(1),
+            Running::Done(status) => status,
+        }
+    }
+}
+
+fn wait_until(child: &mut Child, group: Option<u32>, deadline: Instant) -> i32 {
+    loop {
+        match child.try_wait() {
+            Ok(Some(status)) => return exit_code(status),
+            Ok(None) => {}
+            Err(_) => return 1,
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            break;
+        }
+        thread::sleep(POLL_INTERVAL.min(deadline - now));
+    }
+
+    // SIGTERM gives the group a chance to clean up; whatever is left after
+    // the grace period, or after the leader exits, gets SIGKILL
+    signal_group(child, group, false);
+    let grace = Instant::now() + KILL_GRACE;
+    let mut status = None;
+    while status.is_none() && Instant::now() < grace {
+        status = child.try_wait().ok().flatten();
+        thread::sleep(POLL_INTERVAL);
+    }
+    signal_group(child, group, true);
+    status.or_else(|| child.wait().ok()).map_or(1, exit_code)
+}
+
+/// SIGTERM, or SIGKILL if `kill`, to the process group
+#[cfg(unix)]
+fn signal_group(child: &mut Child, group: Option<u32>, kill: bool) {
+    let signal = if kill { UnixSignal::SIGKILL } else { UnixSignal::SIGTERM };
+    shell_jobs::signal_group(child.id(), group, signal);
+}
+
+#[cfg(not(unix))]
+fn signal_group(child: &mut Child, _group: Option<u32>, _kill: bool) {
+    let _ = child.kill();
+}
+
+/// A process's exit code, or 128 plus the signal that killed it
+fn exit_code(status: ExitStatus) -> i32 {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return 128 + signal;
+        }
+    }
+    status.code().unwrap_or(-1)
+}
+
+fn read_in_background(mut pipe: PipeReader) -> JoinHandle<String> {
+    thread::spawn(move || {
+        let mut bytes = Vec::new();
+        let _ = pipe.read_to_end(&mut bytes);
+        String::from_utf8_lossy(&bytes).into_owned()
+    })
+}
+
+/// Copy a pipe to stderr, keeping its last `STDERR_TAIL` bytes
+fn tee_in_background(mut pipe: PipeReader) -> (JoinHandle<()>, Arc<Mutex<Vec<u8>>>) {
+    let tail = Arc::new(Mutex::new(Vec::new()));
+    let kept = Arc::clone(&tail);
+    let handle = thread::spawn(move || {
+        let mut buffer = [0; 4096];
+        while let Ok(n @ 1..) = pipe.read(&mut buffer) {
+            let _ = io::stderr().write_all(&buffer[..n]);
+            let mut tail = kept.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            tail.extend_from_slice(&buffer[..n]);
+            let excess = tail.len().saturating_sub(STDERR_TAIL);
+            tail.drain(..excess);
+        }
+    });
+    (handle, tail)
 }
 
 impl Shell {
     pub fn new() -> Self {
         let mut aliases = HashMap::new();
-        
+
         // Common aliases
         aliases.insert("ll".to_string(), "ls -la".to_string());
         aliases.insert("la".to_string(), "ls -a".to_string());
         aliases.insert("..".to_string(), "cd ..".to_string());
         aliases.insert("...".to_string(), "cd ../..".to_string());
-        
+
         Shell {
             environment: ShellEnvironment::new(),
             history: Vec::new(),
             aliases,
+            last_status: 0,
+            exit_requested: false,
+            deadline: None,
+            foreground: false,
+            job_control: false,
+            #[cfg(unix)]
+            jobs: Vec::new(),
+            #[cfg(unix)]
+            job: None,
+            explain_failures: false,
+            last_stderr: String::new(),
+            audit: None,
+            confirmation: Confirmation::Interactive,
+            checker: None,
         }
     }
 
+    /// Offer the model's diagnosis and fix when an interactive command fails
+    pub fn with_explain_failures(mut self, explain: bool) -> Self {
+        self.explain_failures = explain;
+        self
+    }
+
+    /// Record the commands run at the prompt, suggested ones included
+    pub fn with_audit_log(mut self, audit: Option<AuditLog>) -> Self {
+        self.audit = audit;
+        self
+    }
+
+    /// How suggested commands and fixes that aren't safe get approved.
+    /// Critical ones are refused unless this is `ForceDangerous`.
+    pub fn with_confirmation(mut self, confirmation: Confirmation) -> Self {
+        self.confirmation = confirmation;
+        self
+    }
+
+    /// Rate commands with `checker` rather than the rules configured for
+    /// the working directory
+    pub fn with_safety_checker(mut self, checker: SafetyChecker) -> Self {
+        self.checker = Some(checker);
+        self
+    }
+
+    /// Parse and run a command line, capturing what it writes
     pub fn execute_command(&mut self, command: &str) -> Result<CommandResult, Box<dyn std::error::Error>> {
+        self.execute_with_options(command, &ExecutionOptions::default())
+    }
+
+    /// Parse and run a command line. `timeout` stops it, `working_directory`
+    /// applies to this call only, and without `capture_output`, or with
+    /// `interactive`, its output goes straight to the terminal.
+    pub fn execute_with_options(&mut self, command: &str, options: &ExecutionOptions) -> Result<CommandResult, Box<dyn std::error::Error>> {
         let command = command.trim();
         if command.is_empty() {
             return Ok(CommandResult::empty());
@@ -119,26 +432,28 @@ impl Shell {
         // Add to history
         self.history.push(command.to_string());
 
-        // Expand variables and aliases
-        let expanded_command = self.environment.expand_variables(command);
-        let processed_command = self.resolve_aliases(&expanded_command);
-
-        // Handle built-in commands
-        if let Some(result) = self.handle_builtin(&processed_command)? {
-            return Ok(result);
-        }
+        let script = match Parser::new(command).with_aliases(&self.aliases).parse() {
+            Ok(script) => script,
+            Err(e) => return Ok(CommandResult::error(format!("Syntax error: {}", e))),
+        };
 
-        // Parse command and arguments
-        let parts = self.parse_command_line(&processed_command);
-        if parts.is_empty() {
-            return Ok(CommandResult::empty());
+        let saved_directory = self.environment.working_directory.clone();
+        if let Some(directory) = &options.working_directory {
+            if let Err(e) = self.environment.change_directory(directory) {
+                return Ok(CommandResult::error(e.to_string()));
+            }
         }
+        self.deadline = options.timeout.map(|timeout| Instant::now() + timeout);
+        self.foreground = options.interactive;
 
-        let program = &parts[0];
-        let args = &parts[1..];
+        let result = self.run_script_with(&script, options);
 
-        // Execute external command
-        self.execute_external_command(program, args)
+        self.deadline = None;
+        self.foreground = false;
+        if options.working_directory.is_some() {
+            self.environment.working_directory = saved_directory;
+        }
+        result
     }
 
     pub fn execute_with_progress<F>(&mut self, command: &str, mut progress_callback: F) -> Result<CommandResult, Box<dyn std::error::Error>>
@@ -150,108 +465,516 @@ impl Shell {
             return Ok(CommandResult::empty());
         }
 
-        progress_callback("Parsing command...");
-        
-        let expanded_command = self.environment.expand_variables(command);
-        let processed_command = self.resolve_aliases(&expanded_command);
-
         progress_callback("Executing command...");
 
         // For demonstration, show a spinner during execution
         let _spinner = Spinner::new().with_title("Running");
-        
-        // Execute the command (simplified for progress demo)
-        let result = self.execute_command(&processed_command)?;
-        
+
+        let result = self.execute_command(command)?;
+
         progress_callback("Command completed");
-        
+
         Ok(result)
     }
 
+    /// Run commands with each one's output piped into the next
     pub fn execute_pipeline(&mut self, commands: &[&str]) -> Result<CommandResult, Box<dyn std::error::Error>> {
         if commands.is_empty() {
             return Ok(CommandResult::empty());
         }
+        self.execute_command(&commands.join(" | "))
+    }
+
+    fn run_script_with(&mut self, script: &Script, options: &ExecutionOptions) -> Result<CommandResult, Box<dyn std::error::Error>> {
+        let capture = options.capture_output && !options.interactive;
+        let (mut stdout, mut stderr, mut tee) = (None, None, None);
+        let streams = if capture {
+            let (stdout_reader, stdout_writer) = io::pipe()?;
+            let (stderr_reader, stderr_writer) = io::pipe()?;
+            stdout = Some(read_in_background(stdout_reader));
+            stderr = Some(read_in_background(stderr_reader));
+            Streams { stdin: Input::Null, stdout: Output::Pipe(stdout_writer), stderr: Output::Pipe(stderr_writer) }
+        } else {
+            let stdin = if options.interactive { Input::Inherit } else { Input::Null };
+            let stderr = if self.explain_failures {
+                let (reader, writer) = io::pipe()?;
+                tee = Some(tee_in_background(reader));
+                Output::Pipe(writer)
+            } else {
+                Output::Stderr
+            };
+            Streams { stdin, stdout: Output::Stdout, stderr }
+        };
+
+        self.exit_requested = false;
+        let status = self.run_script(script, &streams);
+        let mut errors = streams.stderr.try_clone()?;
+        // Our copies of the write ends must close before the readers can finish
+        drop(streams);
+
+        let mut status = status?;
+        if self.timed_out() {
+            status = TIMEOUT_STATUS;
+            let timeout = options.timeout.unwrap_or_default();
+            errors.write_all(&format!("forge-shell: timed out after {}s\n", timeout.as_secs_f64()))?;
+        }
+        drop(errors);
 
-        if commands.len() == 1 {
-            return self.execute_command(commands[0]);
+        if let Some((copier, tail)) = tee {
+            // Background and stopped jobs keep the pipe open, so don't wait
+            // for the end of it for long
+            let give_up = Instant::now() + TEE_GRACE;
+            while !copier.is_finished() && Instant::now() < give_up {
+                thread::sleep(POLL_INTERVAL);
+            }
+            let tail = tail.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            self.last_stderr = String::from_utf8_lossy(&tail).into_owned();
         }
 
-        println!("Executing pipeline: {}", commands.join(" | "));
+        Ok(CommandResult {
+            success: status == 0,
+            exit_code: status,
+            stdout: stdout.map(|reader| reader.join().unwrap_or_default()).unwrap_or_default(),
+            stderr: stderr.map(|reader| reader.join().unwrap_or_default()).unwrap_or_default(),
+            is_exit: self.exit_requested,
+        })
+    }
 
-        // For a simple implementation, execute commands in sequence
-        // In a full shell, this would involve actual piping
-        let mut final_result = CommandResult::empty();
+    fn timed_out(&self) -> bool {
+        self.deadline.is_some_and(|deadline| Instant::now() >= deadline)
+    }
 
-        for (i, command) in commands.iter().enumerate() {
-            println!("Step {}: {}", i + 1, command);
-            let result = self.execute_command(command)?;
-            
-            if !result.success {
-                println!("{}", error_text(&format!("Pipeline failed at step {}", i + 1)));
-                return Ok(result);
+    fn run_script(&mut self, script: &Script, streams: &Streams) -> io::Result<i32> {
+        for list in &script.lists {
+            #[cfg(unix)]
+            if list.background {
+                self.start_background(list, streams)?;
+                continue;
             }
-            
-            final_result = result;
+            self.run_and_or(list, streams)?;
+            if self.exit_requested || self.timed_out() {
+                break;
+            }
+        }
+        Ok(self.last_status)
+    }
+
+    fn run_and_or(&mut self, list: &AndOrList, streams: &Streams) -> io::Result<i32> {
+        self.last_status = self.run_pipeline(&list.first, streams, &list.text)?;
+        for (connector, pipeline) in &list.rest {
+            if self.exit_requested || self.timed_out() {
+                break;
+            }
+            let run = match connector {
+                Connector::And => self.last_status == 0,
+                Connector::Or => self.last_status != 0,
+            };
+            if run {
+                self.last_status = self.run_pipeline(pipeline, streams, &list.text)?;
+            }
+        }
+        Ok(self.last_status)
+    }
+
+    /// Run an and-or list ending in `&` as a job, on a thread with a copy
+    /// of the shell
+    #[cfg(unix)]
+    fn start_background(&mut self, list: &AndOrList, streams: &Streams) -> io::Result<()> {
+        let job = Job::new(self.next_job_id(), &list.text);
+        let mut shell = self.clone();
+        shell.jobs.clear();
+        shell.job = Some(job.clone());
+        let mut streams = streams.try_clone()?;
+        // Without job control nothing would stop it stealing the shell's input
+        if !self.job_control {
+            streams.stdin = Input::Null;
+        }
+
+        let list = list.clone();
+        let runner = job.clone();
+        thread::spawn(move || {
+            let code = shell.run_and_or(&list, &streams).unwrap_or(1);
+            runner.finish(code);
+        });
+
+        if self.job_control {
+            eprintln!("[{}] {}", job.id, job.command);
+        }
+        self.jobs.push(job);
+        self.last_status = 0;
+        Ok(())
+    }
+
+    /// Start every stage with OS pipes between them, then wait for all of
+    /// them; the pipeline's status is the last stage's. A lone builtin runs
+    /// in this shell so `cd` and `set` stick.
+    fn run_pipeline(&mut self, pipeline: &Pipeline, streams: &Streams, text: &str) -> io::Result<i32> {
+        let mut group = None;
+        let mut stages = Vec::new();
+        if let [command] = pipeline.commands.as_slice() {
+            stages.push(self.start(command, streams.try_clone()?, false, &mut group)?);
+        } else {
+            let mut stdin = streams.stdin.try_clone()?;
+            for (i, command) in pipeline.commands.iter().enumerate() {
+                let (stdout, next_stdin) = if i + 1 == pipeline.commands.len() {
+                    (streams.stdout.try_clone()?, Input::Null)
+                } else {
+                    let (reader, writer) = io::pipe()?;
+                    (Output::Pipe(writer), Input::Pipe(reader))
+                };
+                let stage = Streams {
+                    stdin: std::mem::replace(&mut stdin, next_stdin),
+                    stdout,
+                    stderr: streams.stderr.try_clone()?,
+                };
+                stages.push(self.start(command, stage, true, &mut group)?);
+            }
+        }
+
+        let status = self.wait_stages(stages, group, text);
+        #[cfg(unix)]
+        if group.is_some() && self.owns_terminal() {
+            shell_jobs::give_terminal(unistd::getpgrp());
+        }
+        Ok(status)
+    }
+
+    fn wait_stages(&mut self, stages: Vec<Running>, group: Option<u32>, text: &str) -> i32 {
+        #[cfg(unix)]
+        if self.job_control || self.job.is_some() {
+            return self.wait_job_stages(stages, group, text);
+        }
+        let _ = (group, text);
+        let deadline = self.deadline;
+        stages.into_iter().map(|stage| stage.wait(deadline)).last().unwrap_or(0)
+    }
+
+    /// Wait for a pipeline with job control: a background job tracks its
+    /// processes stopping and continuing, and a foreground pipeline stopped
+    /// with Ctrl+Z becomes a job
+    #[cfg(unix)]
+    fn wait_job_stages(&mut self, stages: Vec<Running>, group: Option<u32>, text: &str) -> i32 {
+        let last_is_process = matches!(stages.last(), Some(Running::Process(..)));
+        let mut pids = Vec::new();
+        let mut others = Vec::new();
+        for stage in stages {
+            match stage {
+                Running::Process(child, _) => pids.push(Pid::from_raw(child.id() as i32)),
+                other => others.push(other),
+            }
+        }
+        let group = group.filter(|_| self.own_groups()).map(|group| Pid::from_raw(group as i32));
+
+        let process_status = match &self.job {
+            Some(job) => {
+                job.set_pipeline(group, pids.clone(), JobState::Running);
+                job.wait(&pids)
+            }
+            None => match shell_jobs::wait_foreground(&pids) {
+                Outcome::Exited(code) => code,
+                Outcome::Stopped(remaining) => {
+                    self.stop_job(group, remaining, text);
+                    STOPPED_STATUS
+                }
+            },
+        };
+        let other_status = others.into_iter().map(|stage| stage.wait(None)).last();
+        if last_is_process {
+            process_status
+        } else {
+            other_status.unwrap_or(process_status)
+        }
+    }
+
+    /// Make a pipeline stopped with Ctrl+Z a job, with a thread to wait for it
+    #[cfg(unix)]
+    fn stop_job(&mut self, group: Option<Pid>, pids: Vec<Pid>, text: &str) {
+        let job = Job::new(self.next_job_id(), text);
+        job.set_pipeline(group, pids.clone(), JobState::Stopped);
+        let waiter = job.clone();
+        thread::spawn(move || {
+            let code = waiter.wait(&pids);
+            waiter.finish(code);
+        });
+        eprintln!("\n[{}]+  Stopped    {}", job.id, job.command);
+        self.jobs.push(job);
+    }
+
+    #[cfg(unix)]
+    fn next_job_id(&self) -> usize {
+        self.jobs.iter().map(|job| job.id).max().unwrap_or(0) + 1
+    }
+
+    /// Print the jobs that finished since the last prompt, as reported to
+    /// the task monitor, and forget them
+    #[cfg(unix)]
+    pub fn report_finished_jobs(&mut self) {
+        for notification in GLOBAL_TASK_MONITOR.get_unread_notifications() {
+            if Job::is_job_notification(&notification.id) {
+                println!("{}", notification.message);
+                GLOBAL_TASK_MONITOR.mark_notification_read(&notification.id);
+            }
+        }
+        self.jobs.retain(|job| !matches!(job.state(), JobState::Done(_)));
+    }
+
+    /// The job `spec` names: `%2` or `2`, or the latest job
+    #[cfg(unix)]
+    fn find_job(&self, spec: Option<&str>) -> Result<Job, String> {
+        let Some(spec) = spec else {
+            return self.jobs.last().cloned().ok_or_else(|| "no current job".to_string());
+        };
+        let id: usize = spec.trim_start_matches('%').parse().map_err(|_| format!("{}: no such job", spec))?;
+        self.jobs.iter().find(|job| job.id == id).cloned().ok_or_else(|| format!("{}: no such job", spec))
+    }
+
+    /// Start one command. Builtins and subshells in a pipeline get a copy
+    /// of the shell and a thread of their own, like a forked shell would.
+    fn start(&mut self, command: &ShellCommand, mut streams: Streams, in_pipeline: bool, group: &mut Option<u32>) -> io::Result<Running> {
+        let redirects = match command {
+            ShellCommand::Simple(simple) => &simple.redirects,
+            ShellCommand::Subshell { redirects, .. } => redirects,
+        };
+        if let Err(message) = self.redirect(redirects, &mut streams) {
+            streams.stderr.write_all(&format!("forge-shell: {}\n", message))?;
+            return Ok(Running::Done(1));
+        }
+
+        match command {
+            ShellCommand::Subshell { script, .. } => {
+                let mut shell = self.clone();
+                if in_pipeline {
+                    let script = script.clone();
+                    Ok(Running::Thread(thread::spawn(move || shell.run_script(&script, &streams).unwrap_or(1))))
+                } else {
+                    Ok(Running::Done(shell.run_script(script, &streams)?))
+                }
+            }
+            ShellCommand::Simple(simple) => {
+                let words = self.expand_words(&simple.words);
+                match words.first() {
+                    None => Ok(Running::Done(0)),
+                    Some(name) if BUILTINS.contains(&name.as_str()) => {
+                        if in_pipeline {
+                            let mut shell = self.clone();
+                            Ok(Running::Thread(thread::spawn(move || shell.run_builtin(&words, streams))))
+                        } else {
+                            Ok(Running::Done(self.run_builtin(&words, streams)))
+                        }
+                    }
+                    Some(_) => self.spawn(&words, streams, group),
+                }
+            }
+        }
+    }
+
+    /// Whether pipelines get process groups of their own: with job control,
+    /// and with a timeout so the whole pipeline can be stopped
+    fn own_groups(&self) -> bool {
+        self.job_control || self.deadline.is_some()
+    }
+
+    /// Whether processes should get the terminal: a foreground command
+    /// line with process groups of its own, or a job brought back with `fg`
+    fn owns_terminal(&self) -> bool {
+        if !self.foreground || !io::stdin().is_terminal() {
+            return false;
+        }
+        #[cfg(unix)]
+        if let Some(job) = &self.job {
+            return job.is_foreground();
+        }
+        self.own_groups()
+    }
+
+    /// Spawn a process. With process groups the first process of a
+    /// pipeline starts a new group and the rest join it.
+    fn spawn(&self, words: &[String], streams: Streams, group: &mut Option<u32>) -> io::Result<Running> {
+        let mut errors = streams.stderr.try_clone()?;
+        let mut cmd = Command::new(&words[0]);
+        cmd.args(&words[1..])
+            .current_dir(&self.environment.working_directory)
+            .envs(&self.environment.variables)
+            .stdin(streams.stdin.into_stdio())
+            .stdout(streams.stdout.into_stdio())
+            .stderr(streams.stderr.into_stdio());
+
+        #[cfg(unix)]
+        if self.own_groups() {
+            use std::os::unix::process::CommandExt;
+            cmd.process_group(group.unwrap_or(0) as libc::pid_t);
+            let take_terminal = group.is_none() && self.owns_terminal();
+            let job_control = self.job_control;
+            // The child's pgid is set before this runs
+            unsafe {
+                cmd.pre_exec(move || {
+                    if take_terminal {
+                        shell_jobs::give_terminal(unistd::getpgrp());
+                    }
+                    if job_control {
+                        shell_jobs::ignore_job_signals(false);
+                    }
+                    Ok(())
+                });
+            }
+        }
+
+        match cmd.spawn() {
+            Ok(child) => {
+                let leader = *group.get_or_insert(child.id());
+                #[cfg(unix)]
+                if leader == child.id() && self.owns_terminal() {
+                    shell_jobs::give_terminal(Pid::from_raw(leader as i32));
+                }
+                let group = if self.own_groups() { Some(leader) } else { None };
+                Ok(Running::Process(child, group))
+            }
+            Err(e) => {
+                let (status, reason) = match e.kind() {
+                    io::ErrorKind::NotFound => (127, "command not found".to_string()),
+                    _ => (126, e.to_string()),
+                };
+                errors.write_all(&format!("forge-shell: {}: {}\n", words[0], reason))?;
+                Ok(Running::Done(status))
+            }
+        }
+    }
+
+    /// Point the streams where the redirections say, left to right
+    fn redirect(&self, redirects: &[Redirect], streams: &mut Streams) -> Result<(), String> {
+        for redirect in redirects {
+            let bad_descriptor = |fd: u32| format!("{}: unsupported file descriptor", fd);
+            let output = match &redirect.target {
+                RedirectTarget::Duplicate(source) => match source {
+                    1 => streams.stdout.try_clone(),
+                    2 => streams.stderr.try_clone(),
+                    fd => return Err(bad_descriptor(*fd)),
+                }.map_err(|e| e.to_string())?,
+                RedirectTarget::Read(word) => {
+                    let name = word.expand(|name| self.lookup(name));
+                    let file = File::open(self.resolve_path(&name)).map_err(|e| format!("{}: {}", name, e))?;
+                    match redirect.fd {
+                        0 => streams.stdin = Input::File(file),
+                        fd => return Err(bad_descriptor(fd)),
+                    }
+                    continue;
+                }
+                RedirectTarget::Write(word) | RedirectTarget::Append(word) => {
+                    let name = word.expand(|name| self.lookup(name));
+                    let append = matches!(redirect.target, RedirectTarget::Append(_));
+                    let file = OpenOptions::new()
+                        .write(true)
+                        .create(true)
+                        .append(append)
+                        .truncate(!append)
+                        .open(self.resolve_path(&name))
+                        .map_err(|e| format!("{}: {}", name, e))?;
+                    Output::File(file)
+                }
+            };
+            match redirect.fd {
+                1 => streams.stdout = output,
+                2 => streams.stderr = output,
+                fd => return Err(bad_descriptor(fd)),
+            }
+        }
+        Ok(())
+    }
+
+    fn resolve_path(&self, path: &str) -> PathBuf {
+        Path::new(&self.environment.working_directory).join(path)
+    }
+
+    fn lookup(&self, name: &str) -> Option<String> {
+        match name {
+            "?" => Some(self.last_status.to_string()),
+            _ => self.environment.get_variable(name).cloned(),
         }
+    }
+
+    fn expand_words(&self, words: &[Word]) -> Vec<String> {
+        words.iter().map(|word| word.expand(|name| self.lookup(name))).collect()
+    }
 
-        println!("{}", success_text("Pipeline completed successfully"));
-        Ok(final_result)
+    /// Run a builtin and write what it printed, with a final newline
+    fn run_builtin(&mut self, args: &[String], mut streams: Streams) -> i32 {
+        let Some(result) = self.handle_builtin(args) else {
+            return 127;
+        };
+        for (text, output) in [(&result.stdout, &mut streams.stdout), (&result.stderr, &mut streams.stderr)] {
+            if !text.is_empty() {
+                let newline = if text.ends_with('\n') { "" } else { "\n" };
+                // A reader that went away, like `head`, isn't the builtin's failure
+                let _ = output.write_all(&format!("{}{}", text, newline));
+            }
+        }
+        if result.is_exit {
+            self.exit_requested = true;
+        }
+        result.exit_code
     }
 
-    fn handle_builtin(&mut self, command: &str) -> Result<Option<CommandResult>, Box<dyn std::error::Error>> {
-        let parts: Vec<&str> = command.split_whitespace().collect();
+    fn handle_builtin(&mut self, args: &[String]) -> Option<CommandResult> {
+        let parts: Vec<&str> = args.iter().map(|s| s.as_str()).collect();
         if parts.is_empty() {
-            return Ok(None);
+            return None;
         }
 
         match parts[0] {
             "cd" => {
+                let home = self.environment.get_variable("HOME").cloned().unwrap_or_else(|| "/".to_string());
                 let path = if parts.len() > 1 {
                     parts[1]
                 } else {
-                    "~" // Home directory
+                    home.as_str()
                 };
-                
+
                 match self.environment.change_directory(path) {
-                    Ok(()) => Ok(Some(CommandResult::success(format!("Changed directory to {}", path)))),
-                    Err(e) => Ok(Some(CommandResult::error(e.to_string()))),
+                    Ok(()) => Some(CommandResult::success(format!("Changed directory to {}", path))),
+                    Err(e) => Some(CommandResult::error(e.to_string())),
                 }
             }
             "pwd" => {
-                Ok(Some(CommandResult::success(self.environment.working_directory.clone())))
+                Some(CommandResult::success(self.environment.working_directory.clone()))
             }
             "echo" => {
                 let output = parts[1..].join(" ");
-                Ok(Some(CommandResult::success(output)))
+                Some(CommandResult::success(output))
             }
             "set" => {
                 if parts.len() == 3 {
                     self.environment.set_variable(parts[1], parts[2]);
-                    Ok(Some(CommandResult::success(format!("Set {}={}", parts[1], parts[2]))))
+                    Some(CommandResult::success(format!("Set {}={}", parts[1], parts[2])))
                 } else if parts.len() == 1 {
                     // Show all variables
                     let mut output = String::new();
                     for (key, value) in &self.environment.variables {
                         output.push_str(&format!("{}={}\n", key, value));
                     }
-                    Ok(Some(CommandResult::success(output)))
+                    Some(CommandResult::success(output))
                 } else {
-                    Ok(Some(CommandResult::error("Usage: set [VAR VALUE]".to_string())))
+                    Some(CommandResult::error("Usage: set [VAR VALUE]".to_string()))
                 }
             }
             "alias" => {
-                if parts.len() == 3 {
-                    self.aliases.insert(parts[1].to_string(), parts[2].to_string());
-                    Ok(Some(CommandResult::success(format!("Alias set: {} -> {}", parts[1], parts[2]))))
+                // `alias ll='ls -la'` or `alias ll ls -la`
+                let definition = match parts.len() {
+                    1 => None,
+                    2 => parts[1].split_once('=').map(|(name, command)| (name.to_string(), command.to_string())),
+                    _ => Some((parts[1].to_string(), parts[2..].join(" "))),
+                };
+                if let Some((name, command)) = definition {
+                    let message = format!("Alias set: {} -> {}", name, command);
+                    self.aliases.insert(name, command);
+                    Some(CommandResult::success(message))
                 } else if parts.len() == 1 {
                     let mut output = String::new();
                     for (alias, command) in &self.aliases {
                         output.push_str(&format!("{}='{}'\n", alias, command));
                     }
-                    Ok(Some(CommandResult::success(output)))
+                    Some(CommandResult::success(output))
                 } else {
-                    Ok(Some(CommandResult::error("Usage: alias [NAME COMMAND]".to_string())))
+                    Some(CommandResult::error("Usage: alias [NAME=COMMAND]".to_string()))
                 }
             }
             "history" => {
@@ -260,100 +983,129 @@ impl Shell {
                     .map(|(i, cmd)| format!("{}: {}", i + 1, cmd))
                     .collect::<Vec<_>>()
                     .join("\n");
-                Ok(Some(CommandResult::success(output)))
+                Some(CommandResult::success(output))
             }
             "exit" => {
-                Ok(Some(CommandResult::exit()))
+                Some(CommandResult::exit())
             }
-            _ => Ok(None),
+            "jobs" | "fg" | "bg" | "kill" => Some(self.job_builtin(&parts)),
+            _ => None,
         }
     }
 
-    fn resolve_aliases(&self, command: &str) -> String {
-        let parts: Vec<&str> = command.split_whitespace().collect();
-        if parts.is_empty() {
-            return command.to_string();
-        }
-
-        if let Some(alias_value) = self.aliases.get(parts[0]) {
-            if parts.len() > 1 {
-                format!("{} {}", alias_value, parts[1..].join(" "))
-            } else {
-                alias_value.clone()
-            }
-        } else {
-            command.to_string()
-        }
+    #[cfg(not(unix))]
+    fn job_builtin(&mut self, parts: &[&str]) -> CommandResult {
+        CommandResult::error(format!("{}: job control isn't supported on this platform", parts[0]))
     }
 
-    fn parse_command_line(&self, command: &str) -> Vec<String> {
-        // Simple command line parsing (doesn't handle all shell features)
-        let mut parts = Vec::new();
-        let mut current = String::new();
-        let mut in_quotes = false;
-        let mut chars = command.chars().peekable();
-
-        while let Some(ch) = chars.next() {
-            match ch {
-                '"' => in_quotes = !in_quotes,
-                ' ' if !in_quotes => {
-                    if !current.is_empty() {
-                        parts.push(current.clone());
-                        current.clear();
+    #[cfg(unix)]
+    fn job_builtin(&mut self, parts: &[&str]) -> CommandResult {
+        match parts[0] {
+            "jobs" => {
+                let last = self.jobs.len().saturating_sub(1);
+                let output = self.jobs.iter()
+                    .enumerate()
+                    .map(|(i, job)| {
+                        let current = if i == last { '+' } else { ' ' };
+                        format!("[{}]{}  {:<10} {}", job.id, current, job.state().to_string(), job.command)
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                CommandResult::success(output)
+            }
+            "fg" => {
+                let job = match self.find_job(parts.get(1).copied()) {
+                    Ok(job) => job,
+                    Err(e) => return CommandResult::error(format!("fg: {}", e)),
+                };
+                if self.job_control {
+                    println!("{}", job.command);
+                }
+                let terminal = self.job_control && io::stdin().is_terminal();
+                match job.wait_in_foreground(terminal) {
+                    JobState::Done(code) => {
+                        job.dismiss();
+                        self.jobs.retain(|other| other.id != job.id);
+                        CommandResult { success: code == 0, exit_code: code, ..CommandResult::empty() }
+                    }
+                    _ => {
+                        eprintln!("\n[{}]+  Stopped    {}", job.id, job.command);
+                        CommandResult { success: false, exit_code: STOPPED_STATUS, ..CommandResult::empty() }
                     }
                 }
-                _ => current.push(ch),
             }
+            "bg" => {
+                let job = match self.find_job(parts.get(1).copied()) {
+                    Ok(job) => job,
+                    Err(e) => return CommandResult::error(format!("bg: {}", e)),
+                };
+                match job.signal(UnixSignal::SIGCONT) {
+                    Ok(()) => CommandResult::success(format!("[{}]+ {} &", job.id, job.command)),
+                    Err(e) => CommandResult::error(format!("bg: {}", e)),
+                }
+            }
+            _ => self.kill(&parts[1..]),
         }
-
-        if !current.is_empty() {
-            parts.push(current);
-        }
-
-        parts
     }
 
-    fn execute_external_command(&mut self, program: &str, args: &[String]) -> Result<CommandResult, Box<dyn std::error::Error>> {
-        let mut cmd = Command::new(program);
-        cmd.args(args);
-        cmd.current_dir(&self.environment.working_directory);
-        
-        // Set environment variables
-        for (key, value) in &self.environment.variables {
-            cmd.env(key, value);
+    /// `kill [-SIGNAL] %JOB|PID...`, SIGTERM by default
+    #[cfg(unix)]
+    fn kill(&self, args: &[&str]) -> CommandResult {
+        let (signal, targets) = match args.split_first() {
+            Some((flag, rest)) if flag.starts_with('-') => match shell_jobs::parse_signal(&flag[1..]) {
+                Some(signal) => (signal, rest),
+                None => return CommandResult::error(format!("kill: {}: invalid signal", &flag[1..])),
+            },
+            _ => (UnixSignal::SIGTERM, args),
+        };
+        if targets.is_empty() {
+            return CommandResult::error("Usage: kill [-SIGNAL] %JOB|PID...".to_string());
         }
 
-        match cmd.output() {
-            Ok(output) => {
-                let stdout = String::from_utf8_lossy(&output.stdout).to_string();
-                let stderr = String::from_utf8_lossy(&output.stderr).to_string();
-                let exit_code = output.status.code().unwrap_or(-1);
-                let success = output.status.success();
-
-                Ok(CommandResult {
-                    success,
-                    exit_code,
-                    stdout,
-                    stderr,
-                    is_exit: false,
-                })
-            }
-            Err(e) => {
-                Ok(CommandResult::error(format!("Failed to execute '{}': {}", program, e)))
+        let mut errors = Vec::new();
+        for target in targets {
+            let sent = if target.starts_with('%') {
+                self.find_job(Some(target))
+                    .and_then(|job| job.kill(signal).map_err(|e| format!("{}: {}", target, e)))
+            } else {
+                match target.parse() {
+                    Ok(pid) => unix_signal::kill(Pid::from_raw(pid), signal).map_err(|e| format!("{}: {}", target, e)),
+                    Err(_) => Err(format!("{}: arguments must be process or job IDs", target)),
+                }
+            };
+            if let Err(e) = sent {
+                errors.push(format!("kill: {}", e));
             }
         }
+        if errors.is_empty() {
+            CommandResult::empty()
+        } else {
+            CommandResult::error(errors.join("\n"))
+        }
     }
 
     pub fn run_interactive(&mut self) -> Result<(), Box<dyn std::error::Error>> {
         println!("{}", success_text("Forge Shell Interactive Mode"));
         println!("Type 'help' for available commands, 'exit' to quit");
 
+        // Job control needs a terminal to hand to the jobs
+        #[cfg(unix)]
+        if io::stdin().is_terminal() {
+            self.job_control = true;
+            shell_jobs::ignore_job_signals(true);
+        }
+
+        // Piped input is read plainly, a line at a time
+        let mut editor = io::stdin().is_terminal().then(line_editor);
+
         loop {
-            print!("forge-shell:{}$ ", self.environment.working_directory);
-            io::stdout().flush()?;
+            #[cfg(unix)]
+            self.report_finished_jobs();
 
-            let mut input = String::new();
-            io::stdin().read_line(&mut input)?;
+            let Some(input) = self.read_input(editor.as_mut())? else {
+                println!("Goodbye!");
+                break;
+            };
             let input = input.trim();
 
             if input.is_empty() {
@@ -365,23 +1117,36 @@ impl Shell {
                 continue;
             }
 
-            match self.execute_command(input) {
+            let (command, result) = match input.strip_prefix('?') {
+                Some(request) => match self.suggest(request.trim(), editor.as_mut()) {
+                    Ok(Some(result)) => (None, Ok(result)),
+                    Ok(None) => continue,
+                    Err(e) => (None, Err(e)),
+                },
+                // Commands get the terminal, so editors and pagers work
+                None => (Some(input), self.run_audited(input, Initiator::User, Approval::Typed)),
+            };
+            match result {
                 Ok(result) => {
                     if result.is_exit {
                         println!("Goodbye!");
                         break;
                     }
-                    
-                    if !result.stdout.is_empty() {
-                        print!("{}", result.stdout);
-                    }
-                    
-                    if !result.stderr.is_empty() {
-                        print!("{}", error_text(&result.stderr));
+                    #[cfg(unix)]
+                    if result.exit_code == STOPPED_STATUS {
+                        continue;
                     }
+                    print_result(&result);
 
-                    if !result.success {
-                        println!("{}", warning_text(&format!("Command failed with exit code: {}", result.exit_code)));
+                    if let (Some(command), false) = (command, result.success) {
+                        if self.explain_failures && !USER_STOPPED.contains(&result.exit_code) {
+                            let stderr = if result.stderr.is_empty() { self.last_stderr.clone() } else { result.stderr.clone() };
+                            match self.explain_failure(command, result.exit_code, &stderr, editor.as_mut()) {
+                                Ok(Some(result)) => print_result(&result),
+                                Ok(None) => {}
+                                Err(e) => println!("{}", error_text(&format!("Couldn't explain the failure: {}", e))),
+                            }
+                        }
                     }
                 }
                 Err(e) => {
@@ -390,9 +1155,219 @@ impl Shell {
             }
         }
 
+        #[cfg(unix)]
+        if self.job_control {
+            self.job_control = false;
+            shell_jobs::ignore_job_signals(false);
+        }
         Ok(())
     }
 
+    /// Ask the model for a command that does `request` and show it with its
+    /// risk. With an editor the user can then run, edit or cancel it; a
+    /// command that is run goes into history. The confirmation strategy
+    /// has the last word, so critical commands need `--force-dangerous`.
+    pub fn suggest(&mut self, request: &str, editor: Option<&mut LineEditor>) -> Result<Option<CommandResult>, Box<dyn std::error::Error>> {
+        if request.is_empty() {
+            return Err("Say what the command should do, like `? find rust files changed this week`".into());
+        }
+        let (client, model) = client::create_ollama_client()?;
+        println!("{}", dim_text(&format!("🤖 Asking {}...", model)));
+        let context = CommandContext::from_environment(&self.environment);
+        let suggestion = command_suggest::suggest_command(&client, &model, request, &context, &self.safety_checker())?;
+        if suggestion.attempts > 1 {
+            println!("{}", dim_text(&format!("   Took {} attempts to get a command that parses", suggestion.attempts)));
+        }
+
+        println!("{} {}", StyledText::new("💡").fg(Color::BrightYellow), StyledText::new(&suggestion.command).bold());
+        if !suggestion.explanation.is_empty() {
+            println!("   {}", suggestion.explanation);
+        }
+        print_risk(&suggestion.risk);
+
+        let Some(editor) = editor else {
+            return Ok(None);
+        };
+        let mut command = suggestion.command;
+        loop {
+            print!("{} ", StyledText::new("Run, edit or cancel? [r/e/C]").fg(Color::BrightBlue));
+            io::stdout().flush()?;
+            let mut answer = String::new();
+            io::stdin().read_line(&mut answer)?;
+            match answer.trim().to_lowercase().as_str() {
+                "r" | "run" => break,
+                "e" | "edit" => {
+                    editor.set_prompt("edit> ");
+                    match editor.read_line_with(&command) {
+                        Ok(edited) if !edited.trim().is_empty() => command = edited.trim().to_string(),
+                        _ => return Ok(None),
+                    }
+                    print_risk(&self.safety_checker().assess_command(&command));
+                }
+                _ => return Ok(None),
+            }
+        }
+
+        let risk = self.safety_checker().assess_command(&command);
+        let run = match self.decide(&command, &risk) {
+            Decision::Refuse(_) => false,
+            Decision::Ask if matches!(risk.level, RiskLevel::High | RiskLevel::Critical) => {
+                prompt_yes_no(&format!("This command is {:?} risk. Run it anyway?", risk.level), false)?
+            }
+            _ => true,
+        };
+        if !run {
+            self.audit(self.audit_entry(&command, Initiator::Agent, &risk, Approval::Refused));
+            return Ok(None);
+        }
+        editor.add_history(&command);
+        self.run_audited(&command, Initiator::Agent, Approval::Confirmed).map(Some)
+    }
+
+    /// The safety rules for commands run in the shell's directory
+    fn safety_checker(&self) -> SafetyChecker {
+        match &self.checker {
+            Some(checker) => checker.clone(),
+            None => SafetyChecker::configured(Path::new(&self.environment.working_directory)),
+        }
+    }
+
+    /// Ask the model why `command` failed and show its diagnosis and fix.
+    /// With an editor the fix runs on a single keystroke, or after a yes
+    /// when it's high risk, and goes into history. Fixes the confirmation
+    /// strategy refuses, like critical ones without `--force-dangerous`,
+    /// don't run.
+    pub fn explain_failure(&mut self, command: &str, exit_code: i32, stderr: &str, editor: Option<&mut LineEditor>) -> Result<Option<CommandResult>, Box<dyn std::error::Error>> {
+        let (client, model) = client::create_ollama_client()?;
+        println!("{}", dim_text(&format!("🤖 Asking {} what went wrong...", model)));
+        let context = CommandContext::from_environment(&self.environment);
+        let failure = Failure { command, exit_code, stderr };
+        let diagnosis = command_suggest::diagnose_failure(&client, &model, &failure, &context, &self.safety_checker())?;
+
+        println!("{} {}", StyledText::new("🩺").fg(Color::BrightYellow), diagnosis.diagnosis);
+        let (Some(fix), Some(risk)) = (diagnosis.fix, diagnosis.risk) else {
+            return Ok(None);
+        };
+        println!("{} {}", StyledText::new("💡 Fix:").fg(Color::BrightYellow), StyledText::new(&fix).bold());
+        print_risk(&risk);
+
+        let Some(editor) = editor else {
+            return Ok(None);
+        };
+        let run = match self.decide(&fix, &risk) {
+            Decision::Refuse(_) => false,
+            Decision::Ask if matches!(risk.level, RiskLevel::High | RiskLevel::Critical) => {
+                prompt_yes_no(&format!("The fix is {:?} risk. Run it anyway?", risk.level), false)?
+            }
+            _ => {
+                print!("{} ", StyledText::new("Press r to run it, any other key to skip").fg(Color::BrightBlue));
+                io::stdout().flush()?;
+                let key = read_keystroke()?;
+                println!();
+                if !matches!(key, Key::Char('r') | Key::Char('R')) {
+                    return Ok(None);
+                }
+                true
+            }
+        };
+        if !run {
+            self.audit(self.audit_entry(&fix, Initiator::Agent, &risk, Approval::Refused));
+            return Ok(None);
+        }
+        editor.add_history(&fix);
+        self.run_audited(&fix, Initiator::Agent, Approval::Confirmed).map(Some)
+    }
+
+    /// What the confirmation strategy does with a command the model came
+    /// up with. A refusal is explained here; the caller records it.
+    fn decide(&self, command: &str, risk: &CommandRisk) -> Decision {
+        let decision = self.confirmation.decide(&risk.level, io::stdin().is_terminal());
+        if let Decision::Refuse(reason) = &decision {
+            println!("{} {}", error_text("Not running"), StyledText::new(command).bold());
+            println!("   {}", reason);
+        }
+        decision
+    }
+
+    /// Run a command with the terminal and record it in the audit log
+    fn run_audited(&mut self, command: &str, initiator: Initiator, approval: Approval) -> Result<CommandResult, Box<dyn std::error::Error>> {
+        if self.audit.is_none() {
+            return self.execute_with_options(command, &INTERACTIVE);
+        }
+        let risk = self.safety_checker().assess_command(command);
+        let entry = self.audit_entry(command, initiator, &risk, approval);
+        let start = Instant::now();
+        let result = self.execute_with_options(command, &INTERACTIVE);
+        match &result {
+            Ok(result) if result.is_exit => {}
+            Ok(result) => self.audit(entry.finished(result, start.elapsed())),
+            Err(_) => self.audit(entry.not_started(start.elapsed())),
+        }
+        result
+    }
+
+    fn audit_entry(&self, command: &str, initiator: Initiator, risk: &CommandRisk, approval: Approval) -> AuditEntry {
+        AuditEntry::new(command, &self.environment.working_directory, initiator, risk, &self.confirmation.to_string(), approval)
+    }
+
+    fn audit(&self, entry: AuditEntry) {
+        if let Some(log) = &self.audit {
+            log.record(&entry);
+        }
+    }
+
+    /// Read a command line, or `None` at end of input
+    fn read_input(&self, editor: Option<&mut LineEditor>) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        let Some(editor) = editor else {
+            print!("{}", self.prompt());
+            io::stdout().flush()?;
+            let mut input = String::new();
+            return Ok((io::stdin().read_line(&mut input)? > 0).then_some(input));
+        };
+
+        editor.set_prompt(&self.prompt());
+        let completer = self.completer();
+        editor.set_completion_callback(move |line| completer.complete(line));
+        match editor.read_line() {
+            Ok(input) => Ok(Some(input)),
+            Err(e) if e.to_string() == "Interrupted" => Ok(Some(String::new())),
+            Err(e) if e.to_string() == "EOF" => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// `forge-shell:~/project (main) [1]$ `: the working directory, the git
+    /// branch and the last command's status when it failed
+    pub fn prompt(&self) -> String {
+        let directory = Path::new(&self.environment.working_directory);
+        let home = self.environment.get_variable("HOME").filter(|home| !home.is_empty() && home.as_str() != "/");
+        let shown = match home.and_then(|home| directory.strip_prefix(home).ok()) {
+            Some(rest) if rest.as_os_str().is_empty() => "~".to_string(),
+            Some(rest) => format!("~/{}", rest.display()),
+            None => directory.display().to_string(),
+        };
+
+        let mut prompt = format!("forge-shell:{}", info_text(&shown));
+        if let Some(branch) = shell_completion::current_branch(directory) {
+            prompt.push_str(&format!(" {}", dim_text(&format!("({})", branch))));
+        }
+        if self.last_status != 0 {
+            prompt.push_str(&format!(" {}", error_text(&format!("[{}]", self.last_status))));
+        }
+        prompt.push_str("$ ");
+        prompt
+    }
+
+    /// Tab completion for the shell's directory, path and aliases as they are now
+    fn completer(&self) -> Completer {
+        let completer = Completer::new(&self.environment.working_directory, self.environment.get_variable("PATH").cloned())
+            .with_aliases(self.aliases.keys().cloned());
+        match self.environment.get_variable("HOME") {
+            Some(home) => completer.with_home(home),
+            None => completer,
+        }
+    }
+
     fn show_help(&self) {
         println!("Forge Shell Built-in Commands:");
         println!("  cd <path>        - Change directory");
@@ -401,8 +1376,17 @@ impl Shell {
         println!("  set [var value]  - Set/show environment variables");
         println!("  alias [name cmd] - Set/show command aliases");
         println!("  history          - Show command history");
+        println!("  jobs             - List background and stopped jobs");
+        println!("  fg [%n]          - Bring a job to the foreground");
+        println!("  bg [%n]          - Continue a stopped job in the background");
+        println!("  kill [-SIG] %n   - Send a signal to a job or process");
         println!("  exit             - Exit shell");
         println!();
+        println!("Commands can use 'quotes', \\ escapes, $VARS, |, &&, ||, ;, >, >>, <, 2>&1 and ( subshells ).");
+        println!("End a command with & to run it in the background; Ctrl+Z stops the foreground command.");
+        println!("Tab completes commands, paths and git branches; Ctrl+R searches history.");
+        println!("Start a line with ? to have the model suggest a command, like: ? find large log files");
+        println!();
         println!("Current aliases:");
         for (alias, command) in &self.aliases {
             println!("  {} -> {}", alias, command);
@@ -418,6 +1402,59 @@ impl Shell {
     }
 }
 
+/// Where the interactive shell keeps its history
+fn history_path() -> Option<PathBuf> {
+    config::config_dir().map(|dir| dir.join("shell_history"))
+}
+
+/// A line editor with the shell's history, that continues lines the
+/// parser finds incomplete
+pub fn line_editor() -> LineEditor {
+    let mut editor = LineEditor::new().with_continuation_check(|text| {
+        matches!(Parser::new(text).parse(), Err(e) if e.incomplete)
+    });
+    if let Some(path) = history_path() {
+        editor = editor.with_history_file(path);
+    }
+    editor.enable_multiline();
+    editor
+}
+
+/// Show what an interactive command printed that wasn't already shown,
+/// and its exit code when it failed
+pub fn print_result(result: &CommandResult) {
+    if !result.stdout.is_empty() {
+        print!("{}", result.stdout);
+    }
+    if !result.stderr.is_empty() {
+        print!("{}", error_text(&result.stderr));
+    }
+    if !result.success {
+        println!("{}", warning_text(&format!("Command failed with exit code: {}", result.exit_code)));
+    }
+}
+
+/// Wait for a single key press
+fn read_keystroke() -> Result<Key, Box<dyn std::error::Error>> {
+    let mut terminal = RawTerminal::new()?;
+    terminal.enable_raw_mode()?;
+    InputReader::new().read_key()
+}
+
+/// The risk level, coloured by severity, and why unless it's safe
+pub fn print_risk(risk: &CommandRisk) {
+    let line = format!("Risk: {:?}", risk.level);
+    match risk.level {
+        RiskLevel::Safe => println!("   {}", success_text(&line)),
+        RiskLevel::Low => println!("   {}", dim_text(&line)),
+        RiskLevel::Medium => println!("   {}", warning_text(&line)),
+        RiskLevel::High | RiskLevel::Critical => println!("   {}", error_text(&line)),
+    }
+    if risk.level != RiskLevel::Safe {
+        println!("   {}", dim_text(&risk.reason));
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct CommandResult {
     pub success: bool,
@@ -472,6 +1509,7 @@ impl CommandResult {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::forge_process::shell_parser;
 
     #[test]
     fn test_environment_variable_expansion() {
@@ -488,7 +1526,7 @@ mod tests {
         let result = shell.execute_command("echo Hello World").unwrap();
         
         assert!(result.success);
-        assert_eq!(result.stdout, "Hello World");
+        assert_eq!(result.stdout, "Hello World\n");
     }
 
     #[test]
@@ -499,19 +1537,158 @@ mod tests {
         let result = shell.execute_command("alias ll ls -la").unwrap();
         assert!(result.success);
         
-        // Test alias resolution
-        let resolved = shell.resolve_aliases("ll /tmp");
-        assert_eq!(resolved, "ls -la /tmp");
+        // The alias is expanded when the command line is parsed
+        shell.execute_command("alias greet echo hello").unwrap();
+        assert_eq!(shell.execute_command("greet world").unwrap().stdout, "hello world\n");
     }
 
     #[test]
     fn test_command_parsing() {
         let shell = Shell::new();
-        let parts = shell.parse_command_line("echo \"hello world\" test");
+        let script = shell_parser::parse("echo \"hello world\" test").unwrap();
+        let ShellCommand::Simple(command) = &script.lists[0].first.commands[0] else {
+            panic!("expected a simple command");
+        };
+        let parts = shell.expand_words(&command.words);
         
         assert_eq!(parts.len(), 3);
         assert_eq!(parts[0], "echo");
         assert_eq!(parts[1], "hello world");
         assert_eq!(parts[2], "test");
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_pipes_and_operators() {
+        let mut shell = Shell::new();
+
+        let result = shell.execute_command("printf 'apple\\nbanana\\ncherry\\n' | grep an | tr a-z A-Z").unwrap();
+        assert_eq!(result.stdout, "BANANA\n");
+
+        let result = shell.execute_command("false && echo no || echo yes; echo $?").unwrap();
+        assert_eq!(result.stdout, "yes\n0\n");
+
+        let result = shell.execute_command("echo one; false").unwrap();
+        assert_eq!((result.stdout.as_str(), result.exit_code), ("one\n", 1));
+
+        let result = shell.execute_command("no-such-command-here | cat").unwrap();
+        assert!(result.stderr.contains("no-such-command-here: command not found"));
+
+        // More output than a pipe buffers doesn't deadlock
+        let result = shell.execute_command("seq 1 100000 | tail -n 1 | cat").unwrap();
+        assert_eq!(result.stdout, "100000\n");
+        // Builtins write into pipes too
+        assert_eq!(shell.execute_command("echo hi | wc -l").unwrap().stdout.trim(), "1");
+
+        let result = shell.execute_command("echo 'unclosed").unwrap();
+        assert!(!result.success);
+        assert!(result.stderr.starts_with("Syntax error: unterminated single quote"));
+    }
+
+    #[test]
+    fn test_redirects_and_subshells() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut shell = Shell::new();
+        shell.execute_command(&format!("cd {}", dir.path().display())).unwrap();
+
+        shell.execute_command("echo first > out.txt; echo second >> out.txt").unwrap();
+        assert_eq!(std::fs::read_to_string(dir.path().join("out.txt")).unwrap(), "first\nsecond\n");
+        assert_eq!(shell.execute_command("sort -r < out.txt").unwrap().stdout, "second\nfirst\n");
+
+        let result = shell.execute_command("ls missing-file 2>&1 | grep -c missing-file").unwrap();
+        assert_eq!((result.stdout.as_str(), result.stderr.as_str()), ("1\n", ""));
+        let result = shell.execute_command("ls missing-file 2> err.txt").unwrap();
+        assert!(result.stderr.is_empty());
+        assert!(std::fs::read_to_string(dir.path().join("err.txt")).unwrap().contains("missing-file"));
+        assert!(shell.execute_command("cat < nowhere.txt").unwrap().stderr.contains("nowhere.txt"));
+
+        // A subshell gets a copy of the shell's state
+        let result = shell.execute_command("(set GREETING hi; echo $GREETING) > sub.txt; echo [$GREETING]").unwrap();
+        assert_eq!(result.stdout, "[]\n");
+        assert_eq!(std::fs::read_to_string(dir.path().join("sub.txt")).unwrap(), "Set GREETING=hi\nhi\n");
+        assert_eq!(shell.execute_command("(echo b; echo a) | sort").unwrap().stdout, "a\nb\n");
+    }
+
+    #[test]
+    fn test_prompt() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("project").join(".git")).unwrap();
+        std::fs::write(dir.path().join("project").join(".git").join("HEAD"), "ref: refs/heads/main\n").unwrap();
+        let mut shell = Shell::new();
+        shell.environment.set_variable("HOME", &dir.path().display().to_string());
+
+        shell.execute_command("cd ~/project").unwrap();
+        let prompt = shell.prompt();
+        assert!(prompt.starts_with("forge-shell:") && prompt.ends_with("$ "), "{}", prompt);
+        assert!(prompt.contains("~/project") && prompt.contains("(main)"), "{}", prompt);
+        assert!(!prompt.contains("[1]"));
+
+        shell.execute_command("false").unwrap();
+        assert!(shell.prompt().contains("[1]"));
+        assert_eq!(shell.completer().complete("cd ../"), ["../project/"]);
+        assert_eq!(shell.completer().complete("cat ~/pro"), ["~/project/"]);
+        assert!(shell.completer().complete("ll").contains(&"ll".to_string()));
+    }
+
+    #[test]
+    fn test_prompt_commands_are_audited() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = AuditLog::new(dir.path().join("audit.jsonl"));
+        let mut shell = Shell::new().with_audit_log(Some(log.clone()));
+
+        let result = shell.run_audited("forge-no-such-binary", Initiator::User, Approval::Typed);
+        assert!(result.is_err() || !result.unwrap().success);
+        shell.run_audited("cd /no/such/dir", Initiator::Agent, Approval::Confirmed).unwrap();
+        assert!(shell.run_audited("exit", Initiator::User, Approval::Typed).unwrap().is_exit);
+
+        let (entries, _) = log.read();
+        let audited: Vec<(&str, Initiator, bool)> = entries.iter()
+            .map(|entry| (entry.command.as_str(), entry.initiator, entry.exit_code.is_some_and(|code| code != 0)))
+            .collect();
+        assert_eq!(audited, [("forge-no-such-binary", Initiator::User, true), ("cd /no/such/dir", Initiator::Agent, true)]);
+    }
+
+    #[test]
+    fn test_suggested_critical_commands_need_force_dangerous() {
+        let shell = Shell::new();
+        let risk = shell.safety_checker().assess_command("rm -rf /");
+        assert_eq!(risk.level, RiskLevel::Critical);
+        assert!(matches!(shell.decide("rm -rf /", &risk), Decision::Refuse(_)));
+
+        let shell = Shell::new().with_confirmation(Confirmation::AutoApprove(RiskLevel::High));
+        assert!(matches!(shell.decide("rm -rf /", &risk), Decision::Refuse(_)));
+        let shell = Shell::new().with_confirmation(Confirmation::ForceDangerous);
+        assert_eq!(shell.decide("rm -rf /", &risk), Decision::Run);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_background_jobs() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut shell = Shell::new();
+        shell.execute_command(&format!("cd {}", dir.path().display())).unwrap();
+        // A job keeps its output streams, so it mustn't hold capture pipes
+        let options = ExecutionOptions { capture_output: false, ..Default::default() };
+
+        let result = shell.execute_with_options("sleep 30 &", &options).unwrap();
+        assert_eq!(result.exit_code, 0);
+        let jobs = shell.execute_command("jobs").unwrap().stdout;
+        assert!(jobs.starts_with("[1]+  Running    sleep 30"), "{}", jobs);
+        // Give the job's thread time to start the process
+        thread::sleep(Duration::from_millis(100));
+        assert!(shell.execute_command("kill %1").unwrap().success);
+        // Listed as finished straight away, not still running
+        let jobs = shell.execute_command("jobs").unwrap().stdout;
+        assert!(jobs.starts_with("[1]+  Exit 143   sleep 30"), "{}", jobs);
+        assert_eq!(shell.execute_command("fg").unwrap().exit_code, 143);
+        assert!(shell.jobs.is_empty());
+
+        // The rest of an and-or list runs in the job too
+        shell.execute_with_options("sleep 0.1 && echo done > job.txt &", &options).unwrap();
+        assert_eq!(shell.jobs[0].id, 1);
+        assert_eq!(shell.execute_command("fg %1").unwrap().exit_code, 0);
+        assert_eq!(std::fs::read_to_string(dir.path().join("job.txt")).unwrap(), "done\n");
+
+        assert!(shell.execute_command("fg %7").unwrap().stderr.contains("%7: no such job"));
+        assert!(shell.execute_command("kill -NOPE %1").unwrap().stderr.contains("invalid signal"));
+    }
+}
@@ -0,0 +1,245 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use super::shell::BUILTINS;
+use super::shell_parser;
+
+/// Git subcommands whose arguments are usually branches
+const GIT_BRANCH_COMMANDS: [&str; 10] = ["checkout", "switch", "merge", "rebase", "branch", "diff", "log", "push", "pull", "cherry-pick"];
+
+/// Tab completion for the interactive shell: commands in command position,
+/// git branches after `git checkout` and friends, and file paths elsewhere
+#[derive(Debug, Clone)]
+pub struct Completer {
+    working_directory: PathBuf,
+    home: Option<PathBuf>,
+    path: Option<String>,
+    aliases: Vec<String>,
+}
+
+impl Completer {
+    /// Complete relative to `working_directory`, finding commands on `path`
+    /// (a `$PATH` value)
+    pub fn new(working_directory: impl Into<PathBuf>, path: Option<String>) -> Self {
+        Completer {
+            working_directory: working_directory.into(),
+            home: dirs::home_dir(),
+            path,
+            aliases: Vec::new(),
+        }
+    }
+
+    pub fn with_aliases<I: IntoIterator<Item = String>>(mut self, aliases: I) -> Self {
+        self.aliases.extend(aliases);
+        self
+    }
+
+    pub fn with_home(mut self, home: impl Into<PathBuf>) -> Self {
+        self.home = Some(home.into());
+        self
+    }
+
+    /// What the word at the end of `line` could become, sorted. Words are
+    /// split the way the shell splits them, so `my\ dir/` is one word.
+    pub fn complete(&self, line: &str) -> Vec<String> {
+        // Inside an open quote, fall back to splitting on whitespace
+        let (before, word_start) = shell_parser::completion_words(line).unwrap_or_else(|| {
+            let word_start = line.rfind(char::is_whitespace).map_or(0, |i| i + 1);
+            (line[..word_start].split_whitespace().map(String::from).collect(), word_start)
+        });
+        let word = &line[word_start..];
+        // The words of the command being typed
+        let args: Vec<&str> = before.iter().map(String::as_str).collect();
+
+        let mut candidates = match args.as_slice() {
+            [] if !word.contains('/') => self.commands(word),
+            ["cd", ..] => self.paths(word, true),
+            ["git", subcommand, ..] if GIT_BRANCH_COMMANDS.contains(subcommand) && !word.starts_with('-') => {
+                let mut candidates = self.branches(word);
+                candidates.extend(self.paths(word, false));
+                candidates
+            }
+            _ => self.paths(word, false),
+        };
+        candidates.sort();
+        candidates.dedup();
+        candidates
+    }
+
+    /// Builtins, aliases and executables on the path
+    fn commands(&self, prefix: &str) -> Vec<String> {
+        let mut commands: Vec<String> = BUILTINS
+            .iter()
+            .map(|builtin| builtin.to_string())
+            .chain(self.aliases.iter().cloned())
+            .filter(|command| command.starts_with(prefix))
+            .collect();
+        for dir in env::split_paths(self.path.as_deref().unwrap_or_default()) {
+            let Ok(entries) = fs::read_dir(&dir) else {
+                continue;
+            };
+            for entry in entries.filter_map(|entry| entry.ok()) {
+                let name = entry.file_name().to_string_lossy().into_owned();
+                if name.starts_with(prefix) && is_executable(&entry.path()) {
+                    commands.push(name);
+                }
+            }
+        }
+        commands
+    }
+
+    /// Entries of the directory `word` points into whose names start with
+    /// the rest of it. Directories end in `/`.
+    fn paths(&self, word: &str, directories_only: bool) -> Vec<String> {
+        let (dir_part, name_prefix) = match word.rfind('/') {
+            Some(i) => (&word[..=i], &word[i + 1..]),
+            None => ("", word),
+        };
+        let dir = match (dir_part.strip_prefix("~/"), &self.home) {
+            (Some(rest), Some(home)) => home.join(unescape(rest)),
+            _ => self.working_directory.join(unescape(dir_part)),
+        };
+        let name_prefix = unescape(name_prefix);
+
+        let Ok(entries) = fs::read_dir(&dir) else {
+            return Vec::new();
+        };
+        entries
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let name = entry.file_name().to_string_lossy().into_owned();
+                // Hidden files only when asked for
+                if !name.starts_with(&name_prefix) || (name.starts_with('.') && !name_prefix.starts_with('.')) {
+                    return None;
+                }
+                let is_dir = entry.path().is_dir();
+                if directories_only && !is_dir {
+                    return None;
+                }
+                let suffix = if is_dir { "/" } else { "" };
+                Some(format!("{}{}{}", dir_part, name.replace(' ', "\\ "), suffix))
+            })
+            .collect()
+    }
+
+    /// Local and remote-tracking branches of the repository we're in
+    fn branches(&self, prefix: &str) -> Vec<String> {
+        let output = Command::new("git")
+            .args(["for-each-ref", "--format=%(refname:short)", "refs/heads", "refs/remotes"])
+            .current_dir(&self.working_directory)
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .output();
+        match output {
+            Ok(output) if output.status.success() => String::from_utf8_lossy(&output.stdout)
+                .lines()
+                .filter(|branch| branch.starts_with(prefix) && !branch.ends_with("/HEAD"))
+                .map(|branch| branch.to_string())
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// `word` with its backslash escapes removed
+fn unescape(word: &str) -> String {
+    let mut text = String::new();
+    let mut chars = word.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => text.extend(chars.next()),
+            c => text.push(c),
+        }
+    }
+    text
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    fs::metadata(path).is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+/// The branch checked out in the repository containing `dir`, or a short
+/// commit hash when HEAD is detached. Reads `.git/HEAD` rather than
+/// running git, since the prompt asks every time.
+pub fn current_branch(dir: &Path) -> Option<String> {
+    let git = dir.ancestors().map(|ancestor| ancestor.join(".git")).find(|git| git.exists())?;
+    let git_dir = if git.is_file() {
+        // A worktree or submodule: `gitdir: <path>`
+        let content = fs::read_to_string(&git).ok()?;
+        let target = PathBuf::from(content.strip_prefix("gitdir:")?.trim());
+        git.parent()?.join(target)
+    } else {
+        git
+    };
+
+    let head = fs::read_to_string(git_dir.join("HEAD")).ok()?;
+    let head = head.trim();
+    match head.strip_prefix("ref: ") {
+        Some(reference) => Some(reference.strip_prefix("refs/heads/").unwrap_or(reference).to_string()),
+        None => Some(head.chars().take(7).collect()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_completion() {
+        let dir = tempfile::tempdir().unwrap();
+        let bin = dir.path().join("bin");
+        fs::create_dir_all(dir.path().join("src").join("cli")).unwrap();
+        fs::create_dir(&bin).unwrap();
+        fs::write(dir.path().join("src").join("main.rs"), "").unwrap();
+        fs::write(dir.path().join("my notes.txt"), "").unwrap();
+        fs::write(dir.path().join(".hidden"), "").unwrap();
+        fs::create_dir_all(dir.path().join("some dir").join("sub dir")).unwrap();
+        fs::write(dir.path().join("some dir").join("sub dir").join("été.txt"), "").unwrap();
+        fs::write(bin.join("forge-tool"), "").unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(bin.join("forge-tool"), fs::Permissions::from_mode(0o755)).unwrap();
+        }
+
+        let completer = Completer::new(dir.path(), Some(bin.display().to_string()))
+            .with_aliases(["fl".to_string()])
+            .with_home(dir.path());
+        assert_eq!(completer.complete("f"), ["fg", "fl", "forge-tool"]);
+        assert_eq!(completer.complete("ls | forge-"), ["forge-tool"]);
+        assert_eq!(completer.complete("cat src/"), ["src/cli/", "src/main.rs"]);
+        assert_eq!(completer.complete("cd src/"), ["src/cli/"]);
+        assert_eq!(completer.complete("cat ~/my"), ["~/my\\ notes.txt"]);
+        assert_eq!(completer.complete("cat my\\ n"), ["my\\ notes.txt"]);
+        assert_eq!(completer.complete("cd some\\ dir/"), ["some\\ dir/sub\\ dir/"]);
+        assert_eq!(completer.complete("cat some\\ dir/sub\\ dir/é"), ["some\\ dir/sub\\ dir/été.txt"]);
+        assert_eq!(completer.complete("echo x && cd some\\ d"), ["some\\ dir/"]);
+        assert_eq!(completer.complete("cat ."), [".hidden"]);
+        assert!(completer.complete("cat ").iter().all(|path| !path.starts_with('.')));
+        assert!(completer.complete("cat nothing-here").is_empty());
+    }
+
+    #[test]
+    fn test_current_branch() {
+        let dir = tempfile::tempdir().unwrap();
+        let nested = dir.path().join("a").join("b");
+        fs::create_dir_all(&nested).unwrap();
+        assert_eq!(current_branch(&nested), None);
+
+        fs::create_dir(dir.path().join(".git")).unwrap();
+        fs::write(dir.path().join(".git").join("HEAD"), "ref: refs/heads/feature/x\n").unwrap();
+        assert_eq!(current_branch(&nested).as_deref(), Some("feature/x"));
+
+        fs::write(dir.path().join(".git").join("HEAD"), "0123456789abcdef\n").unwrap();
+        assert_eq!(current_branch(dir.path()).as_deref(), Some("0123456"));
+    }
+}
@@ -0,0 +1,322 @@
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::Duration;
+
+use nix::errno::Errno;
+use nix::sys::signal::{self, SigHandler, Signal};
+use nix::sys::wait::{waitpid, WaitPidFlag, WaitStatus};
+use nix::unistd::{self, Pid};
+
+use crate::terminal::output::GLOBAL_TASK_MONITOR;
+
+/// Signals a job-control shell ignores so it isn't stopped itself. Children
+/// get the default handlers back before they exec.
+const JOB_SIGNALS: [Signal; 3] = [Signal::SIGTSTP, Signal::SIGTTIN, Signal::SIGTTOU];
+
+/// Exit code of a pipeline stopped with Ctrl+Z
+pub const STOPPED_STATUS: i32 = 128 + Signal::SIGTSTP as i32;
+
+/// How long `kill` waits for a signalled job's processes to be reaped
+const SIGNAL_SETTLE: Duration = Duration::from_millis(200);
+
+/// Signals that don't end a process by default, so aren't waited on
+const NON_FATAL_SIGNALS: [Signal; 7] = [
+    Signal::SIGCONT,
+    Signal::SIGSTOP,
+    Signal::SIGTSTP,
+    Signal::SIGTTIN,
+    Signal::SIGTTOU,
+    Signal::SIGCHLD,
+    Signal::SIGWINCH,
+];
+
+/// Task monitor ids, which unlike job numbers are never reused
+static NEXT_TASK: AtomicUsize = AtomicUsize::new(1);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum JobState {
+    Running,
+    Stopped,
+    Done(i32),
+}
+
+impl fmt::Display for JobState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JobState::Running => write!(f, "Running"),
+            JobState::Stopped => write!(f, "Stopped"),
+            JobState::Done(0) => write!(f, "Done"),
+            JobState::Done(code) => write!(f, "Exit {}", code),
+        }
+    }
+}
+
+/// What the job's thread and the shell share
+#[derive(Debug)]
+struct Status {
+    state: JobState,
+    /// Process group of the pipeline the job is running
+    group: Option<Pid>,
+    /// That pipeline's processes that haven't exited
+    pids: Vec<Pid>,
+    /// The shell is waiting for the job with the terminal handed over
+    foreground: bool,
+}
+
+/// A command line running in the background, or stopped with Ctrl+Z. A
+/// thread waits for its processes and runs the rest of its commands.
+#[derive(Debug, Clone)]
+pub struct Job {
+    pub id: usize,
+    pub command: String,
+    task_id: String,
+    status: Arc<(Mutex<Status>, Condvar)>,
+}
+
+impl Job {
+    pub fn new(id: usize, command: &str) -> Self {
+        let task = format!("job-{}", NEXT_TASK.fetch_add(1, Ordering::Relaxed));
+        let task_id = GLOBAL_TASK_MONITOR.start_task(&task, &format!("[{}] {}", id, command));
+        let status = Status { state: JobState::Running, group: None, pids: Vec::new(), foreground: false };
+        Job {
+            id,
+            command: command.to_string(),
+            task_id,
+            status: Arc::new((Mutex::new(status), Condvar::new())),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Status> {
+        self.status.0.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn update(&self, change: impl FnOnce(&mut Status)) {
+        change(&mut self.lock());
+        self.status.1.notify_all();
+    }
+
+    pub fn state(&self) -> JobState {
+        self.lock().state
+    }
+
+    pub fn is_foreground(&self) -> bool {
+        self.lock().foreground
+    }
+
+    /// Note the pipeline the job has started
+    pub fn set_pipeline(&self, group: Option<Pid>, pids: Vec<Pid>, state: JobState) {
+        self.update(|status| {
+            status.group = group;
+            status.pids = pids;
+            status.state = state;
+        });
+    }
+
+    /// Wait for the job's processes, tracking when they stop and continue.
+    /// Returns the last one's exit code.
+    pub fn wait(&self, pids: &[Pid]) -> i32 {
+        let mut code = 0;
+        for &pid in pids {
+            loop {
+                match waitpid(pid, Some(WaitPidFlag::WUNTRACED | WaitPidFlag::WCONTINUED)) {
+                    Ok(WaitStatus::Stopped(..)) => self.update(|status| status.state = JobState::Stopped),
+                    Ok(WaitStatus::Continued(_)) => self.update(|status| status.state = JobState::Running),
+                    Ok(WaitStatus::Exited(_, exit_code)) => {
+                        code = exit_code;
+                        break;
+                    }
+                    Ok(WaitStatus::Signaled(_, signal, _)) => {
+                        code = 128 + signal as i32;
+                        break;
+                    }
+                    Ok(_) | Err(Errno::EINTR) => {}
+                    Err(_) => {
+                        code = 1;
+                        break;
+                    }
+                }
+            }
+            self.update(|status| status.pids.retain(|&p| p != pid));
+        }
+        code
+    }
+
+    /// Record the job's exit code and notify the task monitor
+    pub fn finish(&self, code: i32) {
+        self.update(|status| {
+            status.state = JobState::Done(code);
+            status.pids.clear();
+        });
+        if code == 0 {
+            GLOBAL_TASK_MONITOR.complete_task(&self.task_id, "done");
+        } else {
+            GLOBAL_TASK_MONITOR.fail_task(&self.task_id, &format!("exit {}", code));
+        }
+    }
+
+    /// Mark the completion notification read, for a job that was waited for
+    pub fn dismiss(&self) {
+        for kind in ["task-complete", "task-failed"] {
+            GLOBAL_TASK_MONITOR.mark_notification_read(&format!("{}-{}", kind, self.task_id));
+        }
+    }
+
+    /// Whether a notification from the task monitor is about a job
+    pub fn is_job_notification(id: &str) -> bool {
+        id.starts_with("task-complete-job-") || id.starts_with("task-failed-job-")
+    }
+
+    /// Send a signal to the job's process group, or to its processes when
+    /// it has no group of its own
+    pub fn signal(&self, signal: Signal) -> nix::Result<()> {
+        let status = self.lock();
+        match status.group {
+            Some(group) => signal::killpg(group, signal),
+            None => status.pids.iter().try_for_each(|&pid| signal::kill(pid, signal)),
+        }
+    }
+
+    /// Send a signal for `kill %n`, continuing a stopped job so it acts on
+    /// it. If the signal ends the pipeline, gives the job's thread a moment
+    /// to reap it so a `jobs` straight after doesn't list it as running.
+    pub fn kill(&self, signal: Signal) -> nix::Result<()> {
+        let (state, pids) = {
+            let status = self.lock();
+            (status.state, status.pids.clone())
+        };
+        self.signal(signal)?;
+        if state == JobState::Stopped && signal != Signal::SIGCONT {
+            let _ = self.signal(Signal::SIGCONT);
+        }
+        if pids.is_empty() || NON_FATAL_SIGNALS.contains(&signal) {
+            return Ok(());
+        }
+        // Over once the job finishes or starts its next pipeline
+        let _ = self.status.1.wait_timeout_while(self.lock(), SIGNAL_SETTLE, |status| {
+            !matches!(status.state, JobState::Done(_)) && status.pids.iter().all(|pid| pids.contains(pid))
+        });
+        Ok(())
+    }
+
+    /// Continue the job, handing it the terminal if `terminal`, and wait
+    /// until it stops or finishes
+    pub fn wait_in_foreground(&self, terminal: bool) -> JobState {
+        let group = {
+            let mut status = self.lock();
+            status.foreground = true;
+            // Set here so a stop that was reported earlier doesn't end the wait
+            if status.state == JobState::Stopped {
+                status.state = JobState::Running;
+            }
+            status.group
+        };
+        if let (true, Some(group)) = (terminal, group) {
+            give_terminal(group);
+        }
+        let _ = self.signal(Signal::SIGCONT);
+
+        let mut status = self.status.1
+            .wait_while(self.lock(), |status| status.state == JobState::Running)
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        status.foreground = false;
+        let state = status.state;
+        drop(status);
+
+        if terminal {
+            give_terminal(unistd::getpgrp());
+        }
+        state
+    }
+}
+
+/// How a foreground pipeline's processes ended
+pub enum Outcome {
+    /// The last process's exit code
+    Exited(i32),
+    /// Ctrl+Z stopped it; these processes are left
+    Stopped(Vec<Pid>),
+}
+
+/// Wait for the processes of a foreground pipeline
+pub fn wait_foreground(pids: &[Pid]) -> Outcome {
+    let mut code = 0;
+    for (i, &pid) in pids.iter().enumerate() {
+        loop {
+            match waitpid(pid, Some(WaitPidFlag::WUNTRACED)) {
+                Ok(WaitStatus::Stopped(..)) => return Outcome::Stopped(pids[i..].to_vec()),
+                Ok(WaitStatus::Exited(_, exit_code)) => {
+                    code = exit_code;
+                    break;
+                }
+                Ok(WaitStatus::Signaled(_, signal, _)) => {
+                    code = 128 + signal as i32;
+                    break;
+                }
+                Ok(_) | Err(Errno::EINTR) => {}
+                Err(_) => {
+                    code = 1;
+                    break;
+                }
+            }
+        }
+    }
+    Outcome::Exited(code)
+}
+
+/// Signal a process group, or one process when `group` is `None`. The
+/// process may be gone already.
+pub fn signal_group(pid: u32, group: Option<u32>, signal: Signal) {
+    let _ = match group {
+        Some(group) => signal::killpg(Pid::from_raw(group as i32), signal),
+        None => signal::kill(Pid::from_raw(pid as i32), signal),
+    };
+}
+
+/// Make `group` the terminal's foreground process group, so it can read
+/// the terminal and gets Ctrl+C and Ctrl+Z. Called from forge and from the
+/// child, since either may run first; SIGTTOU is ignored while a
+/// background process does it.
+pub fn give_terminal(group: Pid) {
+    unsafe {
+        let previous = signal::signal(Signal::SIGTTOU, SigHandler::SigIgn);
+        let _ = unistd::tcsetpgrp(libc::STDIN_FILENO, group);
+        if let Ok(previous) = previous {
+            let _ = signal::signal(Signal::SIGTTOU, previous);
+        }
+    }
+}
+
+/// Ignore the job-control signals, or restore their defaults
+pub fn ignore_job_signals(ignore: bool) {
+    let handler = if ignore { SigHandler::SigIgn } else { SigHandler::SigDfl };
+    for signal in JOB_SIGNALS {
+        // Only fails for signals that can't be caught, which these can
+        let _ = unsafe { signal::signal(signal, handler) };
+    }
+}
+
+/// Parse a signal for `kill`: `9`, `KILL` or `SIGKILL`
+pub fn parse_signal(name: &str) -> Option<Signal> {
+    if let Ok(number) = name.parse::<i32>() {
+        return Signal::try_from(number).ok();
+    }
+    let name = name.to_uppercase();
+    let name = if name.starts_with("SIG") { name } else { format!("SIG{}", name) };
+    name.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_signal() {
+        assert_eq!(parse_signal("9"), Some(Signal::SIGKILL));
+        assert_eq!(parse_signal("term"), Some(Signal::SIGTERM));
+        assert_eq!(parse_signal("SIGCONT"), Some(Signal::SIGCONT));
+        assert_eq!(parse_signal("NOPE"), None);
+        assert_eq!(STOPPED_STATUS, 148);
+        assert_eq!(JobState::Done(2).to_string(), "Exit 2");
+    }
+}
@@ -0,0 +1,862 @@
+use std::collections::HashMap;
+use std::fmt;
+
+/// A parsed command line: and-or lists that run one after another
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Script {
+    pub lists: Vec<AndOrList>,
+}
+
+/// Pipelines joined by `&&` and `||`
+#[derive(Debug, Clone, PartialEq)]
+pub struct AndOrList {
+    pub first: Pipeline,
+    pub rest: Vec<(Connector, Pipeline)>,
+    /// Ended with `&`: runs as a background job
+    pub background: bool,
+    /// The list as typed, for job listings
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Connector {
+    /// `&&`: run if the previous pipeline succeeded
+    And,
+    /// `||`: run if the previous pipeline failed
+    Or,
+}
+
+/// Commands whose output feeds the next one's input
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pipeline {
+    pub commands: Vec<Command>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Simple(SimpleCommand),
+    /// `( ... )`: runs with a copy of the shell's state
+    Subshell { script: Script, redirects: Vec<Redirect> },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimpleCommand {
+    pub words: Vec<Word>,
+    pub redirects: Vec<Redirect>,
+}
+
+/// Where one of a command's file descriptors goes, applied left to right
+#[derive(Debug, Clone, PartialEq)]
+pub struct Redirect {
+    pub fd: u32,
+    pub target: RedirectTarget,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RedirectTarget {
+    /// `> file`
+    Write(Word),
+    /// `>> file`
+    Append(Word),
+    /// `< file`
+    Read(Word),
+    /// `>&N`: wherever descriptor N goes at that point
+    Duplicate(u32),
+}
+
+/// A word as typed, with its expansions still to be done
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Word {
+    pub parts: Vec<WordPart>,
+    /// Whether any of it was quoted or escaped
+    pub quoted: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum WordPart {
+    Text(String),
+    /// `$NAME`, `${NAME}` or `$?`
+    Variable(String),
+    /// A leading unquoted `~`
+    Home,
+}
+
+impl Word {
+    /// The word's text, if it has nothing to expand
+    pub fn literal(&self) -> Option<String> {
+        self.parts.iter().map(|part| match part {
+            WordPart::Text(text) => Some(text.as_str()),
+            WordPart::Variable(_) | WordPart::Home => None,
+        }).collect()
+    }
+
+    /// The word with variables filled in by `lookup`; unset ones are empty
+    pub fn expand(&self, lookup: impl Fn(&str) -> Option<String>) -> String {
+        self.parts.iter().map(|part| match part {
+            WordPart::Text(text) => text.clone(),
+            WordPart::Variable(name) => lookup(name).unwrap_or_default(),
+            WordPart::Home => lookup("HOME").unwrap_or_else(|| "~".to_string()),
+        }).collect()
+    }
+
+    fn push_text(&mut self, c: char) {
+        match self.parts.last_mut() {
+            Some(WordPart::Text(text)) => text.push(c),
+            _ => self.parts.push(WordPart::Text(c.to_string())),
+        }
+    }
+}
+
+/// A command line that couldn't be parsed
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub message: String,
+    /// Byte offset in the input
+    pub position: usize,
+    /// The input stopped short, so another line could complete it
+    pub incomplete: bool,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (column {})", self.message, self.position + 1)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Parse a command line without alias expansion
+#[allow(dead_code)]
+pub fn parse(input: &str) -> Result<Script, ParseError> {
+    Parser::new(input).parse()
+}
+
+pub struct Parser<'a> {
+    input: &'a str,
+    aliases: Option<&'a HashMap<String, String>>,
+}
+
+impl<'a> Parser<'a> {
+    pub fn new(input: &'a str) -> Self {
+        Parser { input, aliases: None }
+    }
+
+    /// Replace an unquoted command name that matches an alias with its value
+    pub fn with_aliases(mut self, aliases: &'a HashMap<String, String>) -> Self {
+        self.aliases = Some(aliases);
+        self
+    }
+
+    pub fn parse(self) -> Result<Script, ParseError> {
+        let mut tokens = Tokens {
+            input: self.input,
+            tokens: tokenize(self.input)?,
+            next: 0,
+            end: self.input.len(),
+            aliases: self.aliases,
+            expanded: Vec::new(),
+        };
+        tokens.expanded = vec![Vec::new(); tokens.tokens.len()];
+        let script = tokens.script(false)?;
+        match tokens.peek() {
+            None => Ok(script),
+            Some(_) => Err(tokens.unexpected()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(Word),
+    Pipe,
+    And,
+    Or,
+    Semi,
+    Amp,
+    Newline,
+    Open,
+    Close,
+    Redirect(u32, RedirectOp),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum RedirectOp {
+    Write,
+    Append,
+    Read,
+    Duplicate(u32),
+}
+
+fn describe(token: &Token) -> String {
+    match token {
+        Token::Word(word) => format!("'{}'", word.literal().unwrap_or_else(|| word.expand(|name| Some(format!("${}", name))))),
+        Token::Pipe => "'|'".to_string(),
+        Token::And => "'&&'".to_string(),
+        Token::Or => "'||'".to_string(),
+        Token::Semi => "';'".to_string(),
+        Token::Amp => "'&'".to_string(),
+        Token::Newline => "a newline".to_string(),
+        Token::Open => "'('".to_string(),
+        Token::Close => "')'".to_string(),
+        Token::Redirect(_, RedirectOp::Write) => "'>'".to_string(),
+        Token::Redirect(_, RedirectOp::Append) => "'>>'".to_string(),
+        Token::Redirect(_, RedirectOp::Read) => "'<'".to_string(),
+        Token::Redirect(_, RedirectOp::Duplicate(fd)) => format!("'>&{}'", fd),
+    }
+}
+
+fn is_operator_char(c: char) -> bool {
+    matches!(c, ' ' | '\t' | '\n' | '|' | '&' | ';' | '(' | ')' | '<' | '>')
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+fn tokenize(input: &str) -> Result<Vec<(usize, Token)>, ParseError> {
+    let mut lexer = Lexer::new(input);
+    lexer.run()?;
+    Ok(lexer.tokens)
+}
+
+/// For completing the last word of a partly typed line: the words of the
+/// command it's in, after any `|`, `&&`, `;` or `(`, with quotes and escapes
+/// removed, and the byte offset the last word starts at (the end of the
+/// line when it ends in whitespace). `None` while the line doesn't
+/// tokenize, like inside an open quote.
+pub fn completion_words(input: &str) -> Option<(Vec<String>, usize)> {
+    let mut lexer = Lexer::new(input);
+    lexer.run().ok()?;
+    let mut words = Vec::new();
+    let mut last = None;
+    for (start, token) in &lexer.tokens {
+        last = None;
+        match token {
+            Token::Word(word) => {
+                words.push(word.expand(|_| None));
+                last = Some(*start);
+            }
+            Token::Redirect(..) => {}
+            _ => words.clear(),
+        }
+    }
+    match last.filter(|_| lexer.word_end == input.len()) {
+        Some(start) => {
+            words.pop();
+            Some((words, start))
+        }
+        None => Some((words, input.len())),
+    }
+}
+
+struct Lexer<'a> {
+    input: &'a str,
+    chars: Vec<(usize, char)>,
+    next: usize,
+    tokens: Vec<(usize, Token)>,
+    /// Where the last word ended
+    word_end: usize,
+}
+
+impl<'a> Lexer<'a> {
+    fn new(input: &'a str) -> Self {
+        Lexer { input, chars: input.char_indices().collect(), next: 0, tokens: Vec::new(), word_end: 0 }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.next).map(|&(_, c)| c)
+    }
+
+    fn peek_second(&self) -> Option<char> {
+        self.chars.get(self.next + 1).map(|&(_, c)| c)
+    }
+
+    fn offset(&self) -> usize {
+        self.chars.get(self.next).map_or(self.input.len(), |&(i, _)| i)
+    }
+
+    fn error(&self, message: impl Into<String>, position: usize) -> ParseError {
+        ParseError { message: message.into(), position, incomplete: false }
+    }
+
+    fn incomplete(&self, message: impl Into<String>, position: usize) -> ParseError {
+        ParseError { message: message.into(), position, incomplete: true }
+    }
+
+    fn run(&mut self) -> Result<(), ParseError> {
+        while let Some(c) = self.peek() {
+            let start = self.offset();
+            let token = match c {
+                ' ' | '\t' => None,
+                '\n' => Some(Token::Newline),
+                '#' => {
+                    while self.peek().is_some_and(|c| c != '\n') {
+                        self.next += 1;
+                    }
+                    continue;
+                }
+                '|' if self.peek_second() == Some('|') => {
+                    self.next += 1;
+                    Some(Token::Or)
+                }
+                '|' => Some(Token::Pipe),
+                '&' if self.peek_second() == Some('&') => {
+                    self.next += 1;
+                    Some(Token::And)
+                }
+                '&' => Some(Token::Amp),
+                ';' => Some(Token::Semi),
+                '(' => Some(Token::Open),
+                ')' => Some(Token::Close),
+                '<' | '>' => {
+                    self.redirect(start, None)?;
+                    continue;
+                }
+                _ => {
+                    self.word()?;
+                    continue;
+                }
+            };
+            self.next += 1;
+            if let Some(token) = token {
+                self.tokens.push((start, token));
+            }
+        }
+        Ok(())
+    }
+
+    /// A redirection operator starting at the current `<` or `>`
+    fn redirect(&mut self, start: usize, fd: Option<u32>) -> Result<(), ParseError> {
+        let op = match (self.peek(), self.peek_second()) {
+            (Some('<'), _) => RedirectOp::Read,
+            (Some('>'), Some('>')) => {
+                self.next += 1;
+                RedirectOp::Append
+            }
+            (Some('>'), Some('&')) => {
+                self.next += 2;
+                let digits_start = self.next;
+                while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+                    self.next += 1;
+                }
+                let digits: String = self.chars[digits_start..self.next].iter().map(|&(_, c)| c).collect();
+                let target = digits.parse().map_err(|_| self.error("expected a file descriptor number after '>&'", start))?;
+                self.tokens.push((start, Token::Redirect(fd.unwrap_or(1), RedirectOp::Duplicate(target))));
+                return Ok(());
+            }
+            _ => RedirectOp::Write,
+        };
+        self.next += 1;
+        let default_fd = if op == RedirectOp::Read { 0 } else { 1 };
+        self.tokens.push((start, Token::Redirect(fd.unwrap_or(default_fd), op)));
+        Ok(())
+    }
+
+    fn word(&mut self) -> Result<(), ParseError> {
+        let start = self.offset();
+        let mut word = Word::default();
+        if self.peek() == Some('~') && self.peek_second().is_none_or(|c| c == '/' || is_operator_char(c)) {
+            self.next += 1;
+            word.parts.push(WordPart::Home);
+        }
+
+        while let Some(c) = self.peek() {
+            if is_operator_char(c) {
+                // `2>file`: a number right before a redirection is its descriptor
+                if matches!(c, '<' | '>') && !word.quoted {
+                    if let [WordPart::Text(digits)] = word.parts.as_slice() {
+                        if let Ok(fd) = digits.parse() {
+                            if digits.chars().all(|c| c.is_ascii_digit()) {
+                                return self.redirect(start, Some(fd));
+                            }
+                        }
+                    }
+                }
+                break;
+            }
+
+            let position = self.offset();
+            self.next += 1;
+            match c {
+                '\\' => match self.peek() {
+                    None => return Err(self.incomplete("expected a character after '\\'", position)),
+                    Some('\n') => self.next += 1,
+                    Some(escaped) => {
+                        self.next += 1;
+                        word.quoted = true;
+                        word.push_text(escaped);
+                    }
+                },
+                '\'' => {
+                    word.quoted = true;
+                    if word.parts.is_empty() {
+                        word.parts.push(WordPart::Text(String::new()));
+                    }
+                    loop {
+                        match self.peek() {
+                            None => return Err(self.incomplete("unterminated single quote", position)),
+                            Some('\'') => break,
+                            Some(quoted) => word.push_text(quoted),
+                        }
+                        self.next += 1;
+                    }
+                    self.next += 1;
+                }
+                '"' => {
+                    word.quoted = true;
+                    if word.parts.is_empty() {
+                        word.parts.push(WordPart::Text(String::new()));
+                    }
+                    self.double_quoted(&mut word, position)?;
+                }
+                '$' => self.dollar(&mut word, position)?,
+                _ => word.push_text(c),
+            }
+        }
+
+        // A line continuation on its own isn't a word
+        if !word.parts.is_empty() {
+            self.tokens.push((start, Token::Word(word)));
+            self.word_end = self.offset();
+        }
+        Ok(())
+    }
+
+    /// The rest of a `"..."` string, after the opening quote
+    fn double_quoted(&mut self, word: &mut Word, open: usize) -> Result<(), ParseError> {
+        loop {
+            let position = self.offset();
+            let Some(c) = self.peek() else {
+                return Err(self.incomplete("unterminated double quote", open));
+            };
+            self.next += 1;
+            match c {
+                '"' => return Ok(()),
+                '\\' => match self.peek() {
+                    Some('\n') => self.next += 1,
+                    Some(escaped @ ('$' | '`' | '"' | '\\')) => {
+                        self.next += 1;
+                        word.push_text(escaped);
+                    }
+                    _ => word.push_text('\\'),
+                },
+                '$' => self.dollar(word, position)?,
+                _ => word.push_text(c),
+            }
+        }
+    }
+
+    /// A variable after `$`; a `$` that doesn't start one is kept as text
+    fn dollar(&mut self, word: &mut Word, position: usize) -> Result<(), ParseError> {
+        match self.peek() {
+            Some('?') => {
+                self.next += 1;
+                word.parts.push(WordPart::Variable("?".to_string()));
+            }
+            Some('{') => {
+                self.next += 1;
+                let name_start = self.next;
+                while self.peek().is_some_and(|c| c != '}') {
+                    self.next += 1;
+                }
+                if self.peek().is_none() {
+                    return Err(self.incomplete("unterminated '${'", position));
+                }
+                let name: String = self.chars[name_start..self.next].iter().map(|&(_, c)| c).collect();
+                self.next += 1;
+                if name != "?" && (name.is_empty() || !name.chars().all(is_name_char)) {
+                    return Err(self.error(format!("bad substitution '${{{}}}'", name), position));
+                }
+                word.parts.push(WordPart::Variable(name));
+            }
+            Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+                let name_start = self.next;
+                while self.peek().is_some_and(is_name_char) {
+                    self.next += 1;
+                }
+                let name = self.chars[name_start..self.next].iter().map(|&(_, c)| c).collect();
+                word.parts.push(WordPart::Variable(name));
+            }
+            _ => word.push_text('$'),
+        }
+        Ok(())
+    }
+}
+
+struct Tokens<'a> {
+    input: &'a str,
+    tokens: Vec<(usize, Token)>,
+    next: usize,
+    /// Length of the input, where errors at the end point
+    end: usize,
+    aliases: Option<&'a HashMap<String, String>>,
+    /// For each token, the aliases it came out of
+    expanded: Vec<Vec<String>>,
+}
+
+impl Tokens<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.next).map(|(_, token)| token)
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        if self.peek() == Some(token) {
+            self.next += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn skip_newlines(&mut self) {
+        while self.eat(&Token::Newline) {}
+    }
+
+    fn unexpected(&self) -> ParseError {
+        match self.tokens.get(self.next) {
+            Some((position, token)) => ParseError {
+                message: format!("unexpected {}", describe(token)),
+                position: *position,
+                incomplete: false,
+            },
+            None => self.expected("a command"),
+        }
+    }
+
+    /// An error for input that ends where `what` should follow
+    fn expected(&self, what: &str) -> ParseError {
+        if self.peek().is_some() {
+            let mut error = self.unexpected();
+            error.message = format!("expected {}, found {}", what, error.message.trim_start_matches("unexpected "));
+            return error;
+        }
+        ParseError { message: format!("expected {}", what), position: self.end, incomplete: true }
+    }
+
+    fn script(&mut self, nested: bool) -> Result<Script, ParseError> {
+        let mut script = Script::default();
+        loop {
+            while self.eat(&Token::Semi) || self.eat(&Token::Newline) {}
+            match self.peek() {
+                None => break,
+                Some(Token::Close) if nested => break,
+                _ => {}
+            }
+            script.lists.push(self.and_or()?);
+            match self.peek() {
+                None | Some(Token::Semi) | Some(Token::Newline) => {}
+                Some(Token::Amp) => {
+                    self.next += 1;
+                    if let Some(list) = script.lists.last_mut() {
+                        list.background = true;
+                    }
+                }
+                Some(Token::Close) if nested => break,
+                Some(_) => return Err(self.unexpected()),
+            }
+        }
+        Ok(script)
+    }
+
+    /// Where the next token starts, or the end of the input
+    fn position(&self) -> usize {
+        self.tokens.get(self.next).map_or(self.end, |(position, _)| *position)
+    }
+
+    fn and_or(&mut self) -> Result<AndOrList, ParseError> {
+        let start = self.position();
+        let first = self.pipeline()?;
+        let mut rest = Vec::new();
+        loop {
+            let connector = match self.peek() {
+                Some(Token::And) => Connector::And,
+                Some(Token::Or) => Connector::Or,
+                _ => break,
+            };
+            self.next += 1;
+            self.skip_newlines();
+            rest.push((connector, self.pipeline()?));
+        }
+        let text = self.input.get(start..self.position()).unwrap_or_default().trim().to_string();
+        Ok(AndOrList { first, rest, background: false, text })
+    }
+
+    fn pipeline(&mut self) -> Result<Pipeline, ParseError> {
+        let mut commands = vec![self.command()?];
+        while self.eat(&Token::Pipe) {
+            self.skip_newlines();
+            commands.push(self.command()?);
+        }
+        Ok(Pipeline { commands })
+    }
+
+    fn command(&mut self) -> Result<Command, ParseError> {
+        self.expand_alias();
+        match self.peek() {
+            Some(Token::Open) => {
+                let open = self.tokens[self.next].0;
+                self.next += 1;
+                let script = self.script(true)?;
+                if !self.eat(&Token::Close) {
+                    return Err(self.expected("')'"));
+                }
+                if script.lists.is_empty() {
+                    return Err(ParseError { message: "empty subshell".to_string(), position: open, incomplete: false });
+                }
+                let mut redirects = Vec::new();
+                while let Some(Token::Redirect(..)) = self.peek() {
+                    redirects.push(self.redirect()?);
+                }
+                if let Some(Token::Word(_)) | Some(Token::Open) = self.peek() {
+                    return Err(self.unexpected());
+                }
+                Ok(Command::Subshell { script, redirects })
+            }
+            Some(Token::Word(_)) | Some(Token::Redirect(..)) => {
+                let mut simple = SimpleCommand { words: Vec::new(), redirects: Vec::new() };
+                loop {
+                    match self.peek() {
+                        Some(Token::Word(word)) => {
+                            simple.words.push(word.clone());
+                            self.next += 1;
+                        }
+                        Some(Token::Redirect(..)) => simple.redirects.push(self.redirect()?),
+                        _ => break,
+                    }
+                }
+                Ok(Command::Simple(simple))
+            }
+            _ => Err(self.expected("a command")),
+        }
+    }
+
+    fn redirect(&mut self) -> Result<Redirect, ParseError> {
+        let Some((_, Token::Redirect(fd, op))) = self.tokens.get(self.next).cloned() else {
+            return Err(self.expected("a redirection"));
+        };
+        self.next += 1;
+        if let RedirectOp::Duplicate(target) = op {
+            return Ok(Redirect { fd, target: RedirectTarget::Duplicate(target) });
+        }
+        let Some(Token::Word(file)) = self.peek().cloned() else {
+            return Err(self.expected(&format!("a file name after {}", describe(&Token::Redirect(fd, op)))));
+        };
+        self.next += 1;
+        let target = match op {
+            RedirectOp::Write => RedirectTarget::Write(file),
+            RedirectOp::Append => RedirectTarget::Append(file),
+            RedirectOp::Read => RedirectTarget::Read(file),
+            RedirectOp::Duplicate(_) => unreachable!(),
+        };
+        Ok(Redirect { fd, target })
+    }
+
+    /// Replace the command name with its alias, repeatedly. Tokens remember
+    /// the aliases they came out of, and those aren't expanded in them
+    /// again, so `ls='ls -F'` and `a='echo; a'` terminate.
+    fn expand_alias(&mut self) {
+        let Some(aliases) = self.aliases else {
+            return;
+        };
+        while let Some((position, Token::Word(word))) = self.tokens.get(self.next) {
+            let position = *position;
+            let Some(name) = word.literal().filter(|_| !word.quoted) else {
+                break;
+            };
+            let mut expanded = self.expanded[self.next].clone();
+            let Some(value) = aliases.get(&name).filter(|_| !expanded.contains(&name)) else {
+                break;
+            };
+            // An alias that doesn't parse is left as a command name
+            let Ok(replacement) = tokenize(value) else {
+                break;
+            };
+            let count = replacement.len();
+            self.tokens.splice(self.next..self.next + 1, replacement.into_iter().map(|(_, token)| (position, token)));
+            expanded.push(name);
+            self.expanded.splice(self.next..self.next + 1, std::iter::repeat_n(expanded, count));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(command: &Command) -> Vec<String> {
+        match command {
+            Command::Simple(simple) => simple.words.iter().map(|w| w.expand(|name| Some(format!("<{}>", name)))).collect(),
+            Command::Subshell { .. } => vec!["(...)".to_string()],
+        }
+    }
+
+    fn single(input: &str) -> SimpleCommand {
+        let script = parse(input).unwrap();
+        match &script.lists[0].first.commands[0] {
+            Command::Simple(simple) => simple.clone(),
+            other => panic!("not a simple command: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_quotes_escapes_and_variables() {
+        let command = Command::Simple(single(r#"echo 'a  $b' "c $HOME ${X}\"" d\ e\$f $? ~/src "" x$ # comment"#));
+        assert_eq!(words(&command), vec!["echo", "a  $b", "c <HOME> <X>\"", "d e$f", "<?>", "<HOME>/src", "", "x$"]);
+
+        let simple = single("echo '~' a~ \"$\"");
+        assert!(simple.words[1].quoted);
+        assert_eq!(simple.words[1].literal().unwrap(), "~");
+        assert_eq!(simple.words[2].literal().unwrap(), "a~");
+        assert_eq!(simple.words[3].literal().unwrap(), "$");
+        assert!(!simple.words[0].quoted);
+
+        assert_eq!(single("echo one \\\n two").words.len(), 3);
+    }
+
+    #[test]
+    fn test_operators_and_structure() {
+        let script = parse("cd src && cargo build || echo failed; ls | grep rs | wc -l\npwd").unwrap();
+        assert_eq!(script.lists.len(), 3);
+
+        let first = &script.lists[0];
+        assert_eq!(words(&first.first.commands[0]), vec!["cd", "src"]);
+        assert_eq!(first.rest.iter().map(|(c, _)| *c).collect::<Vec<_>>(), vec![Connector::And, Connector::Or]);
+        assert_eq!(words(&first.rest[1].1.commands[0]), vec!["echo", "failed"]);
+
+        let pipeline = &script.lists[1].first;
+        assert_eq!(pipeline.commands.iter().map(|c| words(c)[0].clone()).collect::<Vec<_>>(), vec!["ls", "grep", "wc"]);
+
+        // Operators don't need spaces around them
+        let shape = |input: &str| parse(input).unwrap().lists.into_iter().map(|l| (l.first, l.rest)).collect::<Vec<_>>();
+        assert_eq!(shape("a&&b||c;d|e"), shape("a && b || c ; d | e"));
+        assert_eq!(script.lists[0].text, "cd src && cargo build || echo failed");
+        assert_eq!(parse("  ;; \n ").unwrap().lists.len(), 0);
+        assert_eq!(parse("build &&\n  test").unwrap().lists.len(), 1);
+    }
+
+    #[test]
+    fn test_redirects() {
+        let simple = single("sort < in.txt > out.txt 2>>err.log 2>&1 >&2 3>x");
+        assert_eq!(words(&Command::Simple(simple.clone())), vec!["sort"]);
+        let word = |s: &str| Word { parts: vec![WordPart::Text(s.to_string())], quoted: false };
+        assert_eq!(simple.redirects, vec![
+            Redirect { fd: 0, target: RedirectTarget::Read(word("in.txt")) },
+            Redirect { fd: 1, target: RedirectTarget::Write(word("out.txt")) },
+            Redirect { fd: 2, target: RedirectTarget::Append(word("err.log")) },
+            Redirect { fd: 2, target: RedirectTarget::Duplicate(1) },
+            Redirect { fd: 1, target: RedirectTarget::Duplicate(2) },
+            Redirect { fd: 3, target: RedirectTarget::Write(word("x")) },
+        ]);
+
+        // Only an unquoted number right before the operator is a descriptor
+        assert_eq!(words(&Command::Simple(single("echo 2 >x"))), vec!["echo", "2"]);
+        assert_eq!(words(&Command::Simple(single("echo '2'>x"))), vec!["echo", "2"]);
+        assert_eq!(single("> empty.txt").words.len(), 0);
+    }
+
+    #[test]
+    fn test_subshells() {
+        let script = parse("(cd /tmp && ls) > files.txt; (echo a; (echo b)) | sort").unwrap();
+        let Command::Subshell { script: inner, redirects } = &script.lists[0].first.commands[0] else {
+            panic!("expected a subshell");
+        };
+        assert_eq!(inner.lists[0].rest.len(), 1);
+        assert_eq!(redirects.len(), 1);
+
+        let pipeline = &script.lists[1].first;
+        assert_eq!(pipeline.commands.len(), 2);
+        let Command::Subshell { script: inner, .. } = &pipeline.commands[0] else {
+            panic!("expected a subshell");
+        };
+        assert_eq!(inner.lists.len(), 2);
+        assert!(matches!(inner.lists[1].first.commands[0], Command::Subshell { .. }));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let error = |input: &str| parse(input).unwrap_err();
+
+        for (input, message, position) in [
+            ("echo a | | b", "expected a command, found '|'", 9),
+            ("&& ls", "expected a command, found '&&'", 0),
+            ("ls )", "unexpected ')'", 3),
+            ("()", "empty subshell", 0),
+            ("(ls) echo", "unexpected 'echo'", 5),
+            ("cat < | x", "expected a file name after '<', found '|'", 6),
+            ("echo ${a b}", "bad substitution '${a b}'", 5),
+            ("& ls", "expected a command, found '&'", 0),
+            ("ls & & ls", "expected a command, found '&'", 5),
+            ("ls >&x", "expected a file descriptor number after '>&'", 3),
+        ] {
+            let e = error(input);
+            assert_eq!((e.message.as_str(), e.position, e.incomplete), (message, position, false), "{}", input);
+        }
+
+        for input in ["echo 'open", "echo \"open", "ls &&", "ls |", "(ls", "cat >", "echo ${HOME", "echo \\"] {
+            assert!(error(input).incomplete, "{}", input);
+        }
+        assert_eq!(error("echo 'open").to_string(), "unterminated single quote (column 6)");
+    }
+
+    #[test]
+    fn test_background_lists() {
+        let script = parse("server --port 80 > log & sleep 1 && ls& (cd x; make) &\nwait").unwrap();
+        let lists: Vec<(&str, bool)> = script.lists.iter().map(|l| (l.text.as_str(), l.background)).collect();
+        assert_eq!(lists, vec![
+            ("server --port 80 > log", true),
+            ("sleep 1 && ls", true),
+            ("(cd x; make)", true),
+            ("wait", false),
+        ]);
+    }
+
+    #[test]
+    fn test_aliases() {
+        let aliases: HashMap<String, String> = [
+            ("ll", "ls -la"),
+            ("ls", "ls --color"),
+            ("up", "cd .. && ll"),
+        ].into_iter().map(|(a, b)| (a.to_string(), b.to_string())).collect();
+        let parse = |input: &str| Parser::new(input).with_aliases(&aliases).parse().unwrap();
+
+        let script = parse("ll /tmp | ll");
+        let commands = &script.lists[0].first.commands;
+        assert_eq!(words(&commands[0]), vec!["ls", "--color", "-la", "/tmp"]);
+        assert_eq!(words(&commands[1]), vec!["ls", "--color", "-la"]);
+
+        let script = parse("up; echo ll");
+        assert_eq!(words(&script.lists[0].rest[0].1.commands[0]), vec!["ls", "--color", "-la"]);
+        assert_eq!(words(&script.lists[1].first.commands[0]), vec!["echo", "ll"]);
+
+        // Quoting the name skips the alias
+        assert_eq!(words(&parse("'ll'").lists[0].first.commands[0]), vec!["ll"]);
+    }
+
+    #[test]
+    fn test_recursive_aliases() {
+        let aliases: HashMap<String, String> = [
+            ("a", "echo hi; a"),
+            ("p", "ls | p"),
+            ("x", "y && x"),
+            ("y", "x"),
+        ].into_iter().map(|(a, b)| (a.to_string(), b.to_string())).collect();
+        let parse = |input: &str| Parser::new(input).with_aliases(&aliases).parse().unwrap();
+
+        let script = parse("a");
+        assert_eq!(script.lists.len(), 2);
+        assert_eq!(words(&script.lists[0].first.commands[0]), vec!["echo", "hi"]);
+        assert_eq!(words(&script.lists[1].first.commands[0]), vec!["a"]);
+
+        let script = parse("p | p");
+        let commands: Vec<Vec<String>> = script.lists[0].first.commands.iter().map(words).collect();
+        assert_eq!(commands, [vec!["ls"], vec!["p"], vec!["ls"], vec!["p"]]);
+
+        // Back through another alias; an `x` typed later still expands
+        let script = parse("x; x");
+        assert_eq!(script.lists.len(), 2);
+        for list in &script.lists {
+            assert_eq!(words(&list.first.commands[0]), vec!["x"]);
+            assert_eq!(words(&list.rest[0].1.commands[0]), vec!["x"]);
+        }
+    }
+}
@@ -1,12 +1,22 @@
 #![allow(dead_code)]
 
-use std::collections::HashMap;
-use std::time::{Duration, Instant};
+use std::cell::OnceCell;
+use std::collections::{BTreeMap, HashMap, VecDeque};
 use std::fs;
-use crate::terminal::output::{ProgressBar, success_text, error_text, warning_text, info_text};
-use super::executor::{ProcessExecutor, ExecutionOptions};
+use std::io::{self, IsTerminal, Write};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+use std::path::{Path, PathBuf};
+use crate::terminal::output::{MultiStageProgress, TerminalControl, success_text, error_text, warning_text, info_text, dim_text};
+use super::audit::Initiator;
+use super::executor::{Confirmation, ProcessExecutor, ExecutionOptions};
+use super::workflow_file::load_workflow;
+use super::workflow_expr::{self, Expr, Scope};
+use super::workflow_graph::StepGraph;
+use super::workflow_template::{self, Reference};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct WorkflowStep {
     pub name: String,
     pub command: String,
@@ -15,6 +25,73 @@ pub struct WorkflowStep {
     pub timeout: Option<Duration>,
     pub retry_count: u32,
     pub conditions: Vec<WorkflowCondition>,
+    /// Steps that must finish first; `None` means the step before this one
+    pub depends_on: Option<Vec<String>>,
+    /// Command that undoes this step, run if the workflow rolls back
+    pub rollback: Option<String>,
+    /// Values captured from stdout for later steps
+    pub outputs: Vec<StepOutput>,
+    /// Expression that must hold for the step to run, alongside `conditions`
+    pub when: Option<String>,
+    /// Run the step once per combination of these values, available as
+    /// `${matrix.<key>}`
+    pub matrix: BTreeMap<String, Vec<String>>,
+    /// Run the step once per item, available as `${item}`
+    pub for_each: Option<ForEach>,
+}
+
+impl WorkflowStep {
+    /// Whether the step runs as several iterations
+    pub fn is_looping(&self) -> bool {
+        !self.matrix.is_empty() || self.for_each.is_some()
+    }
+}
+
+/// Where a `for_each` step gets its items, worked out when the step starts
+#[derive(Debug, Clone, PartialEq)]
+pub enum ForEach {
+    List(Vec<String>),
+    /// Files matching a glob, relative to the working directory
+    Glob(String),
+    /// The non-empty lines of a template like `${steps.Find.outputs.files}`
+    Lines(String),
+}
+
+/// One run of a `matrix` or `for_each` step
+#[derive(Debug, Clone, PartialEq)]
+pub struct Iteration {
+    pub matrix: Vec<(String, String)>,
+    pub item: Option<String>,
+}
+
+impl Iteration {
+    /// The iteration's name, like `Build [x86_64, --all-features]`
+    pub fn step_name(&self, step_name: &str) -> String {
+        let values: Vec<String> = self.matrix.iter()
+            .map(|(_, value)| value)
+            .chain(&self.item)
+            .map(|value| if value.is_empty() { "\"\"".to_string() } else { value.clone() })
+            .collect();
+        format!("{} [{}]", step_name, values.join(", "))
+    }
+}
+
+/// A value captured from a step's stdout, available to later steps as
+/// `${steps.<step>.outputs.<name>}`
+#[derive(Debug, Clone)]
+pub struct StepOutput {
+    pub name: String,
+    pub source: OutputSource,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum OutputSource {
+    /// The whole of stdout, trimmed
+    Stdout,
+    /// The first capture group of a regex, or the whole match if it has none
+    Regex(String),
+    /// A path into stdout parsed as JSON, like `release.assets[0].name`
+    JsonPath(String),
 }
 
 #[derive(Debug, Clone)]
@@ -39,7 +116,12 @@ pub struct Workflow {
     pub name: String,
     pub description: Option<String>,
     pub steps: Vec<WorkflowStep>,
+    /// Cleanup steps run in order after the steps, whatever their outcome
+    pub finally: Vec<WorkflowStep>,
+    /// Defaults, overridden per run with `WorkflowRunner::with_variables`
     pub variables: HashMap<String, String>,
+    /// Variables without a default, which every run has to set
+    pub required_variables: Vec<String>,
     pub on_failure: FailureAction,
 }
 
@@ -53,38 +135,124 @@ pub enum FailureAction {
 #[derive(Debug)]
 pub struct WorkflowExecution {
     pub workflow_name: String,
+    /// Wall-clock time the run started, for its record
+    pub started_at: SystemTime,
     pub start_time: Instant,
     pub end_time: Option<Instant>,
     pub step_results: Vec<StepResult>,
+    pub skipped_steps: Vec<SkippedStep>,
+    /// Compensating commands run after a failure, in the order they ran
+    pub rollback_results: Vec<StepResult>,
+    pub finally_results: Vec<StepResult>,
     pub overall_success: bool,
 }
 
+impl WorkflowExecution {
+    /// Result of a step or cleanup step that has run
+    pub fn find_result(&self, step_name: &str) -> Option<&StepResult> {
+        self.step_results.iter()
+            .chain(&self.finally_results)
+            .find(|result| result.step_name == step_name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SkipReason {
+    ConditionsNotMet,
+    /// A `for_each` step found nothing to run over
+    NoItems,
+    /// A step this one depends on failed
+    Cancelled { failed_step: String },
+}
+
+impl std::fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SkipReason::ConditionsNotMet => write!(f, "conditions not met"),
+            SkipReason::NoItems => write!(f, "no items"),
+            SkipReason::Cancelled { failed_step } => write!(f, "cancelled: '{}' failed", failed_step),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct SkippedStep {
+    pub step_name: String,
+    pub reason: SkipReason,
+}
+
 #[derive(Debug)]
 pub struct StepResult {
     pub step_name: String,
     pub success: bool,
     pub duration: Duration,
     pub output: String,
+    pub stderr: String,
     pub error: Option<String>,
     pub retry_attempts: u32,
+    /// `None` if the command couldn't be run or didn't exit normally
+    pub exit_code: Option<i32>,
+    pub outputs: HashMap<String, String>,
+    /// Results of a `matrix` or `for_each` step's iterations, in order
+    pub iterations: Vec<StepResult>,
+    /// Set on the result of one iteration
+    pub iteration: Option<Iteration>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum StepState {
+    Pending,
+    Running,
+    Succeeded,
+    Failed,
+    Skipped,
+    /// Holds the index of the failed step that caused the cancellation
+    Cancelled(usize),
+}
+
+enum Readiness {
+    Ready,
+    Waiting,
+    Cancelled(usize),
 }
 
 pub struct WorkflowRunner {
     executor: ProcessExecutor,
     workflows: HashMap<String, Workflow>,
+    jobs: usize,
+    variables: HashMap<String, String>,
 }
 
 impl WorkflowRunner {
     pub fn new() -> Self {
         WorkflowRunner {
-            executor: ProcessExecutor::new(),
+            executor: ProcessExecutor::new().with_initiator(Initiator::Workflow),
             workflows: HashMap::new(),
+            jobs: thread::available_parallelism().map(|n| n.get()).unwrap_or(4),
+            variables: HashMap::new(),
         }
     }
 
+    /// How risky steps get approved before the workflow starts
+    pub fn with_confirmation(mut self, confirmation: Confirmation) -> Self {
+        self.executor = self.executor.with_confirmation(confirmation);
+        self
+    }
+
+    /// Run at most `jobs` independent steps at once
+    pub fn with_jobs(mut self, jobs: usize) -> Self {
+        self.jobs = jobs.max(1);
+        self
+    }
+
+    /// Values for workflow variables, as given with `--set NAME=value`
+    pub fn with_variables(mut self, variables: HashMap<String, String>) -> Self {
+        self.variables = variables;
+        self
+    }
+
     pub fn load_workflow_from_file(&mut self, file_path: &str) -> Result<(), Box<dyn std::error::Error>> {
-        let content = fs::read_to_string(file_path)?;
-        let workflow = self.parse_workflow_yaml(&content)?;
+        let workflow = load_workflow(Path::new(file_path))?;
         self.workflows.insert(workflow.name.clone(), workflow);
         Ok(())
     }
@@ -93,10 +261,17 @@ impl WorkflowRunner {
         self.workflows.insert(workflow.name.clone(), workflow);
     }
 
+    /// Run a workflow's steps as a dependency graph, up to `jobs` at a time.
+    ///
+    /// When a step fails, the steps that depend on it are cancelled (unless
+    /// it has `continue_on_failure` or the workflow's failure action is
+    /// `Continue`) while unrelated branches run to completion.
     pub fn execute_workflow(&mut self, workflow_name: &str) -> Result<WorkflowExecution, Box<dyn std::error::Error>> {
         let workflow = self.workflows.get(workflow_name)
             .ok_or_else(|| format!("Workflow '{}' not found", workflow_name))?
             .clone();
+        let graph = StepGraph::new(&workflow.steps)?;
+        let variables = self.resolve_variables(&workflow)?;
 
         println!("{}", info_text(&format!("🚀 Starting workflow: {}", workflow.name)));
         if let Some(desc) = &workflow.description {
@@ -104,74 +279,226 @@ impl WorkflowRunner {
         }
         println!();
 
+        // Steps run concurrently and can't stop to ask, so confirm risky
+        // commands before anything starts. References to other steps are
+        // filled in once those steps have run.
+        let commands = workflow.steps.iter()
+            .map(|step| render_command(&step.command, &variables, None, None))
+            .collect::<Result<Vec<String>, String>>()?;
+        let cleanup_commands = workflow.steps.iter()
+            .filter_map(|step| step.rollback.as_ref())
+            .chain(workflow.finally.iter().map(|step| &step.command))
+            .map(|command| render_command(command, &variables, None, None))
+            .collect::<Result<Vec<String>, String>>()?;
+        for command in commands.iter().chain(&cleanup_commands) {
+            self.executor.perform_safety_check(command)?;
+        }
+
         let start_time = Instant::now();
         let mut execution = WorkflowExecution {
             workflow_name: workflow.name.clone(),
+            started_at: SystemTime::now(),
             start_time,
             end_time: None,
             step_results: Vec::new(),
+            skipped_steps: Vec::new(),
+            rollback_results: Vec::new(),
+            finally_results: Vec::new(),
             overall_success: true,
         };
 
-        let total_steps = workflow.steps.len();
-        let mut progress = ProgressBar::new(total_steps, 50)
-            .with_title("Workflow Progress");
+        // Outside git, `changed()` looks at files modified since the last
+        // successful run
+        let uses_changed = uses_changed(&workflow);
+        let changed_files = ChangedFiles {
+            since: if uses_changed { fs::metadata(success_stamp(&workflow)).and_then(|m| m.modified()).ok() } else { None },
+            files: OnceCell::new(),
+        };
 
-        for (step_index, step) in workflow.steps.iter().enumerate() {
-            progress.set_progress(step_index);
-            println!("\r{}", progress.render());
-            println!();
+        let mut display = StepDisplay::new(&workflow);
+        let mut states = vec![StepState::Pending; workflow.steps.len()];
+        let (sender, receiver) = mpsc::channel::<(usize, StepResult)>();
+        let mut running = 0;
+        // Iterations of `matrix` and `for_each` steps share the job slots
+        // with ordinary steps
+        let mut loops: HashMap<usize, Loop> = HashMap::new();
+        let mut queued: VecDeque<(usize, usize)> = VecDeque::new();
 
-            println!("{}", info_text(&format!("📋 Step {}/{}: {}", step_index + 1, total_steps, step.name)));
-            if let Some(desc) = &step.description {
-                println!("   {}", desc);
-            }
+        loop {
+            // Settle every pending step whose dependencies are decided, until nothing changes
+            let mut changed = true;
+            while changed {
+                changed = false;
+                for i in 0..workflow.steps.len() {
+                    if states[i] != StepState::Pending {
+                        continue;
+                    }
+                    let step = &workflow.steps[i];
 
-            // Check conditions
-            if !self.check_conditions(step, &execution)? {
-                println!("{}", warning_text("⏭️  Skipping step due to unmet conditions"));
-                continue;
+                    match readiness(&workflow, &graph, &states, i) {
+                        Readiness::Waiting => {}
+                        Readiness::Cancelled(failed_step) => {
+                            states[i] = StepState::Cancelled(failed_step);
+                            let reason = SkipReason::Cancelled { failed_step: workflow.steps[failed_step].name.clone() };
+                            display.skipped(i, &reason);
+                            execution.skipped_steps.push(SkippedStep { step_name: step.name.clone(), reason });
+                            changed = true;
+                        }
+                        Readiness::Ready => {
+                            let holds = conditions_hold(step, &RunScope { variables: &variables, execution: &execution, changed_files: &changed_files });
+                            if holds == Ok(false) {
+                                states[i] = StepState::Skipped;
+                                display.skipped(i, &SkipReason::ConditionsNotMet);
+                                execution.skipped_steps.push(SkippedStep { step_name: step.name.clone(), reason: SkipReason::ConditionsNotMet });
+                                changed = true;
+                            } else if step.is_looping() {
+                                let iterations = holds.and_then(|_| iterations(step, &variables, &execution));
+                                match iterations {
+                                    Ok(iterations) if iterations.is_empty() => {
+                                        states[i] = StepState::Skipped;
+                                        display.skipped(i, &SkipReason::NoItems);
+                                        execution.skipped_steps.push(SkippedStep { step_name: step.name.clone(), reason: SkipReason::NoItems });
+                                    }
+                                    Ok(iterations) => {
+                                        states[i] = StepState::Running;
+                                        display.started(i);
+                                        display.iterations(i, 0, iterations.len());
+                                        queued.extend((0..iterations.len()).map(|n| (i, n)));
+                                        loops.insert(i, Loop { start_time: Instant::now(), results: iterations.iter().map(|_| None).collect(), iterations });
+                                    }
+                                    Err(error) => {
+                                        let result = failed_result(step, error);
+                                        states[i] = StepState::Failed;
+                                        execution.overall_success = false;
+                                        display.started(i);
+                                        display.finished(i, &result);
+                                        execution.step_results.push(result);
+                                    }
+                                }
+                                changed = true;
+                            } else if running < self.jobs {
+                                display.started(i);
+                                let command = holds.and_then(|_| self.prepare_command(step, &commands[i], &variables, &execution, None));
+                                let command = match command {
+                                    Ok(command) => command,
+                                    Err(error) => {
+                                        let result = failed_result(step, error);
+                                        states[i] = StepState::Failed;
+                                        execution.overall_success = false;
+                                        display.finished(i, &result);
+                                        execution.step_results.push(result);
+                                        changed = true;
+                                        continue;
+                                    }
+                                };
+                                states[i] = StepState::Running;
+                                running += 1;
+
+                                let step = step.clone();
+                                let sender = sender.clone();
+                                thread::spawn(move || {
+                                    let result = run_step(&step, &command);
+                                    let _ = sender.send((i, result));
+                                });
+                            }
+                        }
+                    }
+                }
             }
 
-            let step_result = self.execute_step(step, &workflow.variables)?;
-            let step_success = step_result.success;
+            while running < self.jobs {
+                let Some((i, n)) = queued.pop_front() else {
+                    break;
+                };
+                let iteration = loops[&i].iterations[n].clone();
+                let step = iteration_step(&workflow.steps[i], Some(&iteration));
+                running += 1;
 
-            execution.step_results.push(step_result);
+                let sender = sender.clone();
+                match self.prepare_command(&workflow.steps[i], &commands[i], &variables, &execution, Some(&iteration)) {
+                    Ok(command) => {
+                        thread::spawn(move || {
+                            let result = run_step(&step, &command);
+                            let _ = sender.send((i, StepResult { iteration: Some(iteration), ..result }));
+                        });
+                    }
+                    Err(error) => {
+                        let _ = sender.send((i, StepResult { iteration: Some(iteration), ..failed_result(&step, error) }));
+                    }
+                }
+            }
 
-            if !step_success {
-                execution.overall_success = false;
-                
-                if !step.continue_on_failure {
-                    match workflow.on_failure {
-                        FailureAction::Stop => {
-                            println!("{}", error_text("🛑 Workflow stopped due to step failure"));
-                            break;
-                        }
-                        FailureAction::Continue => {
-                            println!("{}", warning_text("⚠️  Continuing despite step failure"));
-                        }
-                        FailureAction::Rollback => {
-                            println!("{}", warning_text("🔄 Attempting rollback..."));
-                            self.rollback_workflow(&workflow, &execution)?;
-                            break;
+            if running == 0 {
+                break;
+            }
+
+            match receiver.recv_timeout(Duration::from_millis(100)) {
+                Ok((i, result)) => {
+                    running -= 1;
+                    let result = match loops.remove(&i) {
+                        Some(mut looping) => {
+                            display.iteration_finished(&result);
+                            looping.record(result);
+                            display.iterations(i, looping.done(), looping.results.len());
+                            if looping.done() < looping.results.len() {
+                                loops.insert(i, looping);
+                                continue;
+                            }
+                            looping.finish(&workflow.steps[i])
                         }
+                        None => result,
+                    };
+
+                    states[i] = if result.success { StepState::Succeeded } else { StepState::Failed };
+                    if !result.success {
+                        execution.overall_success = false;
                     }
+                    display.finished(i, &result);
+                    execution.step_results.push(result);
                 }
+                Err(mpsc::RecvTimeoutError::Timeout) => {}
+                Err(mpsc::RecvTimeoutError::Disconnected) => break,
             }
+            display.tick();
         }
+        display.finish();
 
-        progress.set_progress(total_steps);
-        println!("\r{}", progress.render());
-        println!();
+        let unrecovered_failure = workflow.steps.iter().zip(&states)
+            .any(|(step, state)| *state == StepState::Failed && !step.continue_on_failure);
+        if unrecovered_failure {
+            match workflow.on_failure {
+                FailureAction::Stop => {
+                    if execution.skipped_steps.iter().any(|s| matches!(s.reason, SkipReason::Cancelled { .. })) {
+                        println!("{}", error_text("🛑 Steps depending on the failure were cancelled"));
+                    }
+                }
+                FailureAction::Continue => {
+                    println!("{}", warning_text("⚠️  Continued despite step failure"));
+                }
+                FailureAction::Rollback => {
+                    self.rollback_workflow(&workflow, &variables, &mut execution);
+                }
+            }
+        }
+
+        self.run_finally(&workflow, &variables, &changed_files, &mut execution);
+
+        if uses_changed && execution.overall_success && !workflow_expr::in_git_repository() {
+            let stamp = success_stamp(&workflow);
+            let written = stamp.parent().map_or(Ok(()), fs::create_dir_all).and_then(|_| fs::write(&stamp, ""));
+            if let Err(e) = written {
+                println!("{}", warning_text(&format!("⚠️  Couldn't record this run in {}: {}", stamp.display(), e)));
+            }
+        }
 
         execution.end_time = Some(Instant::now());
         let duration = execution.end_time.unwrap() - execution.start_time;
 
         if execution.overall_success {
-            println!("{}", success_text(&format!("✅ Workflow '{}' completed successfully in {:.2}s", 
+            println!("{}", success_text(&format!("✅ Workflow '{}' completed successfully in {:.2}s",
                 workflow.name, duration.as_secs_f64())));
         } else {
-            println!("{}", error_text(&format!("❌ Workflow '{}' failed after {:.2}s", 
+            println!("{}", error_text(&format!("❌ Workflow '{}' failed after {:.2}s",
                 workflow.name, duration.as_secs_f64())));
         }
 
@@ -179,145 +506,121 @@ impl WorkflowRunner {
         Ok(execution)
     }
 
-    fn execute_step(&mut self, step: &WorkflowStep, variables: &HashMap<String, String>) -> Result<StepResult, Box<dyn std::error::Error>> {
-        let step_start = Instant::now();
-        let mut command = step.command.clone();
+    /// The workflow's variables for this run: its defaults, with
+    /// `${env.NAME}` filled in, overridden by `with_variables`
+    fn resolve_variables(&self, workflow: &Workflow) -> Result<HashMap<String, String>, Box<dyn std::error::Error>> {
+        let mut declared: Vec<&String> = workflow.variables.keys().chain(&workflow.required_variables).collect();
+        declared.sort();
+        let mut unknown: Vec<&String> = self.variables.keys().filter(|name| !declared.contains(name)).collect();
+        unknown.sort();
+        if let Some(name) = unknown.first() {
+            let known = if declared.is_empty() {
+                "it has no variables".to_string()
+            } else {
+                format!("it has {}", declared.iter().map(|name| name.as_str()).collect::<Vec<_>>().join(", "))
+            };
+            return Err(format!("Workflow '{}' has no variable '{}' ({})", workflow.name, name, known).into());
+        }
 
-        // Variable substitution
-        for (var, value) in variables {
-            command = command.replace(&format!("${{{}}}", var), value);
+        let missing: Vec<&str> = workflow.required_variables.iter()
+            .filter(|name| !self.variables.contains_key(*name))
+            .map(|name| name.as_str())
+            .collect();
+        if !missing.is_empty() {
+            return Err(format!("Workflow '{}' requires {}; pass --set NAME=value",
+                workflow.name, missing.join(", ")).into());
         }
 
-        let mut retry_attempts = 0;
-        let mut last_error = None;
+        let mut variables = HashMap::new();
+        for (name, value) in &workflow.variables {
+            let value = workflow_template::render(value, |reference| match reference {
+                Reference::Env(_) => resolve_reference(reference, &HashMap::new(), None, None),
+                _ => Ok(None),
+            }).map_err(|e| format!("Variable '{}': {}", name, e))?;
+            variables.insert(name.clone(), value);
+        }
+        variables.extend(self.variables.iter().map(|(name, value)| (name.clone(), value.clone())));
+        Ok(variables)
+    }
 
-        while retry_attempts <= step.retry_count {
-            if retry_attempts > 0 {
-                println!("{}", warning_text(&format!("🔄 Retry attempt {} of {}", retry_attempts, step.retry_count)));
-                std::thread::sleep(Duration::from_secs(1)); // Brief delay between retries
-            }
+    /// Fill in a step's references to earlier steps. If that changes the
+    /// command, it gets the safety check the preflight couldn't give it.
+    fn prepare_command(&self, step: &WorkflowStep, checked: &str, variables: &HashMap<String, String>, execution: &WorkflowExecution, iteration: Option<&Iteration>) -> Result<String, String> {
+        let command = render_command(&step.command, variables, Some(execution), iteration)?;
+        if command != checked {
+            self.executor.perform_safety_check(&command).map_err(|e| e.to_string())?;
+        }
+        Ok(command)
+    }
 
-            let execution_options = ExecutionOptions {
-                timeout: step.timeout,
-                show_progress: false, // We're showing workflow progress
-                capture_output: true,
-                interactive: false,
-                safety_check: true,
-                working_directory: None,
+    /// Run the rollback commands of the steps that succeeded, most recently
+    /// finished first. Each iteration of a `matrix` or `for_each` step that
+    /// succeeded is undone, even if others failed. A failing rollback is
+    /// recorded and the rest still run.
+    fn rollback_workflow(&mut self, workflow: &Workflow, variables: &HashMap<String, String>, execution: &mut WorkflowExecution) {
+        let mut to_undo: Vec<(&WorkflowStep, Option<Iteration>)> = Vec::new();
+        for result in execution.step_results.iter().rev() {
+            let Some(step) = workflow.steps.iter().find(|step| step.name == result.step_name && step.rollback.is_some()) else {
+                continue;
             };
-
-            match self.executor.execute(&command, execution_options) {
-                Ok(result) => {
-                    let duration = step_start.elapsed();
-                    
-                    if result.success {
-                        println!("{}", success_text(&format!("✅ Step '{}' completed", step.name)));
-                        return Ok(StepResult {
-                            step_name: step.name.clone(),
-                            success: true,
-                            duration,
-                            output: result.stdout,
-                            error: if result.stderr.is_empty() { None } else { Some(result.stderr) },
-                            retry_attempts,
-                        });
-                    } else {
-                        last_error = Some(format!("Command failed: {}", result.stderr));
-                        retry_attempts += 1;
-                        
-                        if retry_attempts > step.retry_count {
-                            println!("{}", error_text(&format!("❌ Step '{}' failed after {} retries", step.name, step.retry_count)));
-                        }
-                    }
-                }
-                Err(e) => {
-                    last_error = Some(e.to_string());
-                    retry_attempts += 1;
-                    
-                    if retry_attempts > step.retry_count {
-                        println!("{}", error_text(&format!("❌ Step '{}' failed: {}", step.name, e)));
-                    }
-                }
+            if result.iterations.is_empty() && result.success {
+                to_undo.push((step, None));
             }
+            for iteration in result.iterations.iter().rev().filter(|iteration| iteration.success) {
+                to_undo.push((step, iteration.iteration.clone()));
+            }
+        }
+        if to_undo.is_empty() {
+            println!("{}", warning_text("🔄 Nothing to roll back: no completed step has a rollback command"));
+            return;
         }
 
-        let duration = step_start.elapsed();
-        Ok(StepResult {
-            step_name: step.name.clone(),
-            success: false,
-            duration,
-            output: String::new(),
-            error: last_error,
-            retry_attempts,
-        })
-    }
+        println!("{}", info_text("🔄 Rolling back completed steps..."));
+        for (step, iteration) in to_undo {
+            let step = &iteration_step(step, iteration.as_ref());
+            let rollback = step.rollback.as_deref().unwrap_or_default();
+            let result = match render_command(rollback, variables, Some(execution), iteration.as_ref()) {
+                Ok(command) => run_step(step, &command),
+                Err(error) => failed_result(step, error),
+            };
+            print_step_line("↩️ ", &result);
+            execution.rollback_results.push(result);
+        }
 
-    fn check_conditions(&self, step: &WorkflowStep, execution: &WorkflowExecution) -> Result<bool, Box<dyn std::error::Error>> {
-        for condition in &step.conditions {
-            match condition.condition_type {
-                ConditionType::FileExists => {
-                    if !std::path::Path::new(&condition.value).exists() {
-                        return Ok(false);
-                    }
-                }
-                ConditionType::FileNotExists => {
-                    if std::path::Path::new(&condition.value).exists() {
-                        return Ok(false);
-                    }
-                }
-                ConditionType::DirectoryExists => {
-                    let path = std::path::Path::new(&condition.value);
-                    if !path.exists() || !path.is_dir() {
-                        return Ok(false);
-                    }
-                }
-                ConditionType::DirectoryNotExists => {
-                    let path = std::path::Path::new(&condition.value);
-                    if path.exists() && path.is_dir() {
-                        return Ok(false);
-                    }
-                }
-                ConditionType::EnvironmentVariable => {
-                    if std::env::var(&condition.value).is_err() {
-                        return Ok(false);
-                    }
-                }
-                ConditionType::PreviousStepSuccess => {
-                    if let Some(prev_step) = execution.step_results.iter().find(|s| s.step_name == condition.value) {
-                        if !prev_step.success {
-                            return Ok(false);
-                        }
-                    } else {
-                        return Ok(false);
-                    }
-                }
-                ConditionType::PreviousStepFailure => {
-                    if let Some(prev_step) = execution.step_results.iter().find(|s| s.step_name == condition.value) {
-                        if prev_step.success {
-                            return Ok(false);
-                        }
-                    } else {
-                        return Ok(false);
-                    }
-                }
-            }
+        if execution.rollback_results.iter().all(|result| result.success) {
+            println!("{}", success_text("Rollback completed"));
+        } else {
+            println!("{}", error_text("Rollback finished with errors; manual cleanup may be needed"));
         }
-        Ok(true)
     }
 
-    fn rollback_workflow(&mut self, _workflow: &Workflow, execution: &WorkflowExecution) -> Result<(), Box<dyn std::error::Error>> {
-        println!("{}", info_text("🔄 Starting workflow rollback..."));
-        
-        // Execute steps in reverse order (simplified rollback)
-        for step_result in execution.step_results.iter().rev() {
-            if step_result.success {
-                println!("{}", info_text(&format!("Rolling back: {}", step_result.step_name)));
-                // In a real implementation, you'd have rollback commands for each step
-                // For now, we'll just log the rollback intent
+    /// Run the workflow's `finally` steps one after another. They see the
+    /// results of every step through their conditions.
+    fn run_finally(&mut self, workflow: &Workflow, variables: &HashMap<String, String>, changed_files: &ChangedFiles, execution: &mut WorkflowExecution) {
+        if workflow.finally.is_empty() {
+            return;
+        }
+
+        println!("{}", info_text("🧹 Running cleanup steps..."));
+        for step in &workflow.finally {
+            let holds = conditions_hold(step, &RunScope { variables, execution, changed_files });
+            if holds == Ok(false) {
+                println!("{}", dim_text(&format!("⏭  {} ({})", step.name, SkipReason::ConditionsNotMet)));
+                execution.skipped_steps.push(SkippedStep { step_name: step.name.clone(), reason: SkipReason::ConditionsNotMet });
+                continue;
+            }
+
+            let command = holds.and_then(|_| render_command(&step.command, variables, Some(execution), None));
+            let result = match command {
+                Ok(command) => run_step(step, &command),
+                Err(error) => failed_result(step, error),
+            };
+            print_step_line("🧹", &result);
+            if !result.success && !step.continue_on_failure {
+                execution.overall_success = false;
             }
+            execution.finally_results.push(result);
         }
-        
-        println!("{}", success_text("Rollback completed"));
-        Ok(())
     }
 
     fn print_execution_summary(&self, execution: &WorkflowExecution) {
@@ -332,6 +635,9 @@ impl WorkflowRunner {
         let successful_steps = execution.step_results.iter().filter(|s| s.success).count();
         let total_steps = execution.step_results.len();
         println!("Steps: {}/{} successful", successful_steps, total_steps);
+        if !execution.skipped_steps.is_empty() {
+            println!("Skipped: {}", execution.skipped_steps.len());
+        }
 
         println!("\nStep Details:");
         for (i, step) in execution.step_results.iter().enumerate() {
@@ -345,9 +651,41 @@ impl WorkflowRunner {
             if let Some(error) = &step.error {
                 println!("     Error: {}", error);
             }
+            for iteration in &step.iterations {
+                let status = if iteration.success { "✅" } else { "❌" };
+                println!("     {} {} ({:.2}s)", status, iteration.step_name, iteration.duration.as_secs_f64());
+            }
+
+            let mut outputs: Vec<_> = step.outputs.iter().collect();
+            outputs.sort();
+            for (name, value) in outputs {
+                let value: String = value.lines().next().unwrap_or_default().chars().take(60).collect();
+                println!("     Output {} = {}", name, value);
+            }
+        }
+        for step in &execution.skipped_steps {
+            println!("  -: ⏭  {} ({})", step.step_name, step.reason);
+        }
+
+        for (title, results) in [("Rollback", &execution.rollback_results), ("Cleanup", &execution.finally_results)] {
+            if results.is_empty() {
+                continue;
+            }
+            println!("\n{}:", title);
+            for step in results {
+                let status = if step.success { "✅" } else { "❌" };
+                println!("  {} {} ({:.2}s)", status, step.step_name, step.duration.as_secs_f64());
+                if let (false, Some(error)) = (step.success, &step.error) {
+                    println!("     Error: {}", error);
+                }
+            }
         }
     }
 
+    pub fn get_workflow(&self, name: &str) -> Option<&Workflow> {
+        self.workflows.get(name)
+    }
+
     pub fn list_workflows(&self) -> Vec<&String> {
         self.workflows.keys().collect()
     }
@@ -358,10 +696,8 @@ impl WorkflowRunner {
                 name: format!("Step {}", i + 1),
                 command: cmd.to_string(),
                 description: Some(format!("Execute: {}", cmd)),
-                continue_on_failure: false,
                 timeout: Some(Duration::from_secs(300)), // 5 minutes default
-                retry_count: 0,
-                conditions: Vec::new(),
+                ..Default::default()
             }
         }).collect();
 
@@ -369,31 +705,500 @@ impl WorkflowRunner {
             name: name.to_string(),
             description: Some("Auto-generated workflow".to_string()),
             steps,
+            finally: Vec::new(),
             variables: HashMap::new(),
+            required_variables: Vec::new(),
             on_failure: FailureAction::Stop,
         }
     }
+}
 
-    // Simplified YAML parsing (in practice, you'd use a YAML library)
-    fn parse_workflow_yaml(&self, _yaml_content: &str) -> Result<Workflow, Box<dyn std::error::Error>> {
-        // This is a placeholder - in a real implementation you'd parse YAML
-        Ok(Workflow {
-            name: "example".to_string(),
-            description: Some("Example workflow".to_string()),
-            steps: vec![
-                WorkflowStep {
-                    name: "Hello".to_string(),
-                    command: "echo 'Hello from workflow'".to_string(),
-                    description: None,
-                    continue_on_failure: false,
-                    timeout: None,
-                    retry_count: 0,
-                    conditions: Vec::new(),
+/// Whether step `i` can start, must wait, or is cancelled by a failure
+/// upstream (reported as the index of the step that failed)
+fn readiness(workflow: &Workflow, graph: &StepGraph, states: &[StepState], i: usize) -> Readiness {
+    let mut waiting = false;
+    for &dep in graph.dependencies(i) {
+        match states[dep] {
+            StepState::Pending | StepState::Running => waiting = true,
+            StepState::Succeeded | StepState::Skipped => {}
+            StepState::Failed => {
+                let tolerated = workflow.steps[dep].continue_on_failure
+                    || matches!(workflow.on_failure, FailureAction::Continue);
+                if !tolerated {
+                    return Readiness::Cancelled(dep);
                 }
-            ],
-            variables: HashMap::new(),
-            on_failure: FailureAction::Stop,
-        })
+            }
+            StepState::Cancelled(failed_step) => return Readiness::Cancelled(failed_step),
+        }
+    }
+    if waiting { Readiness::Waiting } else { Readiness::Ready }
+}
+
+/// Whether a step's `conditions` and `when` expression all hold. An
+/// expression that can't be evaluated is an error, which fails the step.
+fn conditions_hold(step: &WorkflowStep, scope: &dyn Scope) -> Result<bool, String> {
+    for condition in &step.conditions {
+        if !Expr::from_condition(condition).is_true(scope)? {
+            return Ok(false);
+        }
+    }
+    match &step.when {
+        Some(when) => {
+            let expr = Expr::parse(when).map_err(|e| format!("when: {}", e))?;
+            expr.is_true(scope).map_err(|e| format!("when: {}", e))
+        }
+        None => Ok(true),
+    }
+}
+
+fn uses_changed(workflow: &Workflow) -> bool {
+    workflow.steps.iter()
+        .chain(&workflow.finally)
+        .filter_map(|step| step.when.as_deref())
+        .filter_map(|when| Expr::parse(when).ok())
+        .any(|expr| expr.uses_changed())
+}
+
+/// Marks the last successful run of a workflow that uses `changed()`
+/// outside a git repository
+fn success_stamp(workflow: &Workflow) -> PathBuf {
+    Path::new(".forge").join("state").join(format!("{}.last-success", workflow.name))
+}
+
+/// Files for `changed()`, found the first time an expression asks
+struct ChangedFiles {
+    since: Option<SystemTime>,
+    files: OnceCell<Result<Vec<String>, String>>,
+}
+
+/// What conditions and `when` expressions see of a running workflow
+struct RunScope<'a> {
+    variables: &'a HashMap<String, String>,
+    execution: &'a WorkflowExecution,
+    changed_files: &'a ChangedFiles,
+}
+
+impl Scope for RunScope<'_> {
+    fn step_status(&self, step: &str) -> Option<&'static str> {
+        if let Some(result) = self.execution.find_result(step) {
+            return Some(if result.success { "success" } else { "failure" });
+        }
+        self.execution.skipped_steps.iter()
+            .find(|skipped| skipped.step_name == step)
+            .map(|skipped| match skipped.reason {
+                SkipReason::ConditionsNotMet | SkipReason::NoItems => "skipped",
+                SkipReason::Cancelled { .. } => "cancelled",
+            })
+    }
+
+    fn step_exit_code(&self, step: &str) -> Option<i32> {
+        self.execution.find_result(step).and_then(|result| result.exit_code)
+    }
+
+    fn step_output(&self, step: &str, output: &str) -> Option<String> {
+        self.execution.find_result(step).and_then(|result| result.outputs.get(output).cloned())
+    }
+
+    fn variable(&self, name: &str) -> Option<String> {
+        self.variables.get(name).cloned()
+    }
+
+    fn changed_files(&self) -> Result<Vec<String>, String> {
+        self.changed_files.files.get_or_init(|| workflow_expr::changed_files(self.changed_files.since)).clone()
+    }
+}
+
+/// A `matrix` or `for_each` step whose iterations are queued or running
+struct Loop {
+    start_time: Instant,
+    iterations: Vec<Iteration>,
+    results: Vec<Option<StepResult>>,
+}
+
+impl Loop {
+    fn record(&mut self, result: StepResult) {
+        // Iterations that are equal are interchangeable
+        let slot = self.iterations.iter().zip(&self.results)
+            .position(|(iteration, slot)| slot.is_none() && result.iteration.as_ref() == Some(iteration));
+        if let Some(n) = slot {
+            self.results[n] = Some(result);
+        }
+    }
+
+    fn done(&self) -> usize {
+        self.results.iter().filter(|result| result.is_some()).count()
+    }
+
+    /// The step's result: a success if every iteration succeeded
+    fn finish(self, step: &WorkflowStep) -> StepResult {
+        let iterations: Vec<StepResult> = self.results.into_iter().flatten().collect();
+        let failed: Vec<&StepResult> = iterations.iter().filter(|result| !result.success).collect();
+        let error = match failed.as_slice() {
+            [] => None,
+            [only] => only.error.clone().map(|error| format!("{}: {}", only.step_name, error)),
+            _ => Some(format!("{} of {} iterations failed: {}", failed.len(), iterations.len(),
+                failed.iter().map(|result| result.step_name.as_str()).collect::<Vec<_>>().join(", "))),
+        };
+        StepResult {
+            step_name: step.name.clone(),
+            success: failed.is_empty(),
+            duration: self.start_time.elapsed(),
+            output: String::new(),
+            stderr: String::new(),
+            error,
+            retry_attempts: 0,
+            exit_code: failed.first().map_or(Some(0), |result| result.exit_code),
+            outputs: HashMap::new(),
+            iterations,
+            iteration: None,
+        }
+    }
+}
+
+/// Work out a looping step's iterations: every combination of its matrix
+/// values, once per `for_each` item
+fn iterations(step: &WorkflowStep, variables: &HashMap<String, String>, execution: &WorkflowExecution) -> Result<Vec<Iteration>, String> {
+    let mut combinations: Vec<Vec<(String, String)>> = vec![Vec::new()];
+    for (key, values) in &step.matrix {
+        combinations = combinations.iter()
+            .flat_map(|combination| values.iter().map(move |value| {
+                let mut combination = combination.clone();
+                combination.push((key.clone(), value.clone()));
+                combination
+            }))
+            .collect();
+    }
+
+    let items = match &step.for_each {
+        None => return Ok(combinations.into_iter().map(|matrix| Iteration { matrix, item: None }).collect()),
+        Some(ForEach::List(items)) => items.clone(),
+        Some(ForEach::Glob(pattern)) => {
+            let cwd = std::env::current_dir().map_err(|e| e.to_string())?;
+            crate::fs::glob::glob(pattern)
+                .map_err(|e| format!("for_each glob '{}': {}", pattern, e))?
+                .into_iter()
+                .filter(|path| path.is_file())
+                .map(|path| path.strip_prefix(&cwd).map(Path::to_path_buf).unwrap_or(path).to_string_lossy().replace('\\', "/"))
+                .collect()
+        }
+        Some(ForEach::Lines(template)) => render_command(template, variables, Some(execution), None)
+            .map_err(|e| format!("for_each: {}", e))?
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(str::to_string)
+            .collect(),
+    };
+    Ok(combinations.iter()
+        .flat_map(|matrix| items.iter().map(|item| Iteration { matrix: matrix.clone(), item: Some(item.clone()) }))
+        .collect())
+}
+
+/// The step as one iteration runs it, named after the iteration
+fn iteration_step(step: &WorkflowStep, iteration: Option<&Iteration>) -> WorkflowStep {
+    match iteration {
+        Some(iteration) => WorkflowStep { name: iteration.step_name(&step.name), ..step.clone() },
+        None => step.clone(),
+    }
+}
+
+/// Fill in a command's placeholders. Without an `execution`, references
+/// to steps and iterations are left for later.
+fn render_command(command: &str, variables: &HashMap<String, String>, execution: Option<&WorkflowExecution>, iteration: Option<&Iteration>) -> Result<String, String> {
+    workflow_template::render(command, |reference| resolve_reference(reference, variables, execution, iteration))
+}
+
+fn resolve_reference(reference: &Reference, variables: &HashMap<String, String>, execution: Option<&WorkflowExecution>, iteration: Option<&Iteration>) -> Result<Option<String>, String> {
+    let step_result = |step: &str| {
+        let execution = execution?;
+        Some(execution.find_result(step).ok_or_else(|| {
+            if execution.skipped_steps.iter().any(|skipped| skipped.step_name == step) {
+                format!("{} has no value: step '{}' was skipped", reference, step)
+            } else {
+                format!("{} has no value: step '{}' hasn't run", reference, step)
+            }
+        }))
+    };
+
+    match reference {
+        // Unknown names are left for the shell to expand from the environment
+        Reference::Variable(name) => Ok(variables.get(name).cloned()),
+        Reference::Env(name) => std::env::var(name)
+            .map(Some)
+            .map_err(|_| format!("environment variable {} is not set", name)),
+        Reference::StepExitCode(step) => match step_result(step) {
+            None => Ok(None),
+            Some(result) => {
+                let result = result?;
+                result.exit_code
+                    .map(|code| Some(code.to_string()))
+                    .ok_or_else(|| format!("{} has no value: step '{}' didn't exit normally", reference, step))
+            }
+        },
+        Reference::StepOutput { step, output } => match step_result(step) {
+            None => Ok(None),
+            Some(result) => {
+                let result = result?;
+                result.outputs.get(output).cloned().map(Some).ok_or_else(|| {
+                    let why = if result.success { "it has no such output" } else { "it failed" };
+                    format!("{} has no value: {}", reference, why)
+                })
+            }
+        },
+        Reference::Matrix(_) | Reference::Item if iteration.is_none() => match execution {
+            None => Ok(None),
+            Some(_) => Err(format!("{} only has a value in steps with matrix or for_each", reference)),
+        },
+        Reference::Matrix(key) => iteration.into_iter()
+            .flat_map(|iteration| &iteration.matrix)
+            .find(|(name, _)| name == key)
+            .map(|(_, value)| Some(value.clone()))
+            .ok_or_else(|| format!("{} has no value: the step's matrix has no '{}'", reference, key)),
+        Reference::Item => iteration.and_then(|iteration| iteration.item.clone())
+            .map(Some)
+            .ok_or_else(|| format!("{} has no value: the step has no for_each", reference)),
+    }
+}
+
+/// Run one step on a worker thread, retrying as configured. Prints nothing;
+/// the caller reports progress.
+fn run_step(step: &WorkflowStep, command: &str) -> StepResult {
+    let step_start = Instant::now();
+    let mut executor = ProcessExecutor::new().with_initiator(Initiator::Workflow);
+    let mut retry_attempts = 0;
+    let mut last_error = None;
+    let mut last_exit_code = None;
+    let mut last_output = (String::new(), String::new());
+
+    while retry_attempts <= step.retry_count {
+        if retry_attempts > 0 {
+            thread::sleep(Duration::from_secs(1)); // Brief delay between retries
+        }
+
+        let execution_options = ExecutionOptions {
+            timeout: step.timeout,
+            show_progress: false, // We're showing workflow progress
+            capture_output: true,
+            interactive: false,
+            safety_check: false, // Checked before the workflow started
+            working_directory: None,
+        };
+
+        match executor.execute(command, execution_options) {
+            Ok(result) if result.success => {
+                let mut outputs = HashMap::new();
+                for output in &step.outputs {
+                    match workflow_template::extract_output(&output.source, &result.stdout) {
+                        Ok(value) => {
+                            outputs.insert(output.name.clone(), value);
+                        }
+                        Err(e) => {
+                            // Retrying won't change how the output is read
+                            return StepResult {
+                                error: Some(format!("Output '{}': {}", output.name, e)),
+                                output: result.stdout,
+                                stderr: result.stderr,
+                                exit_code: Some(result.exit_code),
+                                retry_attempts,
+                                ..failed_result(step, String::new())
+                            };
+                        }
+                    }
+                }
+
+                return StepResult {
+                    step_name: step.name.clone(),
+                    success: true,
+                    duration: step_start.elapsed(),
+                    output: result.stdout,
+                    stderr: result.stderr,
+                    error: None,
+                    retry_attempts,
+                    exit_code: Some(result.exit_code),
+                    outputs,
+                    iterations: Vec::new(),
+                    iteration: None,
+                };
+            }
+            Ok(result) => {
+                last_error = Some(if result.stderr.trim().is_empty() {
+                    format!("Command exited with code {}", result.exit_code)
+                } else {
+                    format!("Command failed: {}", result.stderr.trim())
+                });
+                last_exit_code = Some(result.exit_code);
+                last_output = (result.stdout, result.stderr);
+            }
+            Err(e) => last_error = Some(e.to_string()),
+        }
+        retry_attempts += 1;
+    }
+
+    StepResult {
+        duration: step_start.elapsed(),
+        retry_attempts: retry_attempts - 1,
+        exit_code: last_exit_code,
+        output: last_output.0,
+        stderr: last_output.1,
+        ..failed_result(step, last_error.unwrap_or_default())
+    }
+}
+
+/// A step that failed before or without running its command
+fn failed_result(step: &WorkflowStep, error: String) -> StepResult {
+    StepResult {
+        step_name: step.name.clone(),
+        success: false,
+        duration: Duration::ZERO,
+        output: String::new(),
+        stderr: String::new(),
+        error: Some(error),
+        retry_attempts: 0,
+        exit_code: None,
+        outputs: HashMap::new(),
+        iterations: Vec::new(),
+        iteration: None,
+    }
+}
+
+/// Report a rollback or cleanup command, which run one at a time
+fn print_step_line(icon: &str, result: &StepResult) {
+    if result.success {
+        println!("{}", success_text(&format!("{} {} ({:.2}s)", icon, result.step_name, result.duration.as_secs_f64())));
+    } else {
+        let error = result.error.as_deref().unwrap_or("failed");
+        println!("{}", error_text(&format!("{} {} ({:.2}s): {}", icon, result.step_name, result.duration.as_secs_f64(), error)));
+    }
+}
+
+/// Per-step status while a workflow runs: a `MultiStageProgress` redrawn in
+/// place on a terminal, or a line per event when output is redirected
+struct StepDisplay {
+    progress: MultiStageProgress,
+    names: Vec<String>,
+    started: Vec<Option<Instant>>,
+    /// Iterations finished and in all, for looping steps
+    counts: Vec<Option<(usize, usize)>>,
+    live: bool,
+    drawn_lines: usize,
+}
+
+impl StepDisplay {
+    fn new(workflow: &Workflow) -> Self {
+        let mut progress = MultiStageProgress::new(&format!("📋 {}", workflow.name));
+        for step in &workflow.steps {
+            progress.add_stage(&step.name, 1.0);
+        }
+
+        let mut display = StepDisplay {
+            progress,
+            names: workflow.steps.iter().map(|step| step.name.clone()).collect(),
+            started: vec![None; workflow.steps.len()],
+            counts: vec![None; workflow.steps.len()],
+            live: io::stdout().is_terminal(),
+            drawn_lines: 0,
+        };
+        if display.live {
+            print!("{}", TerminalControl::hide_cursor());
+            display.redraw();
+        }
+        display
+    }
+
+    fn started(&mut self, i: usize) {
+        let _ = self.progress.begin_stage(i);
+        self.started[i] = Some(Instant::now());
+        if !self.live {
+            println!("{}", info_text(&format!("▶️  {}", self.names[i])));
+        }
+        self.redraw();
+    }
+
+    fn finished(&mut self, i: usize, result: &StepResult) {
+        self.started[i] = None;
+        let _ = self.progress.update_stage_progress(i, 1.0, Some(&format!("{:.1}s", result.duration.as_secs_f64())));
+        if result.success {
+            let _ = self.progress.complete_stage(i);
+            if !self.live {
+                println!("{}", success_text(&format!("✅ {} ({:.2}s)", self.names[i], result.duration.as_secs_f64())));
+            }
+        } else {
+            let error = result.error.as_deref().and_then(|e| e.lines().next()).unwrap_or("failed");
+            let error: String = error.chars().take(60).collect();
+            let _ = self.progress.fail_stage(i, &error);
+            if !self.live {
+                println!("{}", error_text(&format!("❌ {} ({:.2}s): {}", self.names[i], result.duration.as_secs_f64(), error)));
+            }
+        }
+        self.redraw();
+    }
+
+    /// Progress of a `matrix` or `for_each` step
+    fn iterations(&mut self, i: usize, done: usize, total: usize) {
+        self.counts[i] = Some((done, total));
+    }
+
+    fn iteration_finished(&mut self, result: &StepResult) {
+        if self.live {
+            return;
+        }
+        if result.success {
+            println!("{}", success_text(&format!("  ✅ {} ({:.2}s)", result.step_name, result.duration.as_secs_f64())));
+        } else {
+            let error = result.error.as_deref().and_then(|e| e.lines().next()).unwrap_or("failed");
+            println!("{}", error_text(&format!("  ❌ {} ({:.2}s): {}", result.step_name, result.duration.as_secs_f64(), error)));
+        }
+    }
+
+    fn skipped(&mut self, i: usize, reason: &SkipReason) {
+        let _ = self.progress.skip_stage(i, &reason.to_string());
+        if !self.live {
+            println!("{}", dim_text(&format!("⏭  {} ({})", self.names[i], reason)));
+        }
+        self.redraw();
+    }
+
+    /// Refresh the elapsed time of running steps
+    fn tick(&mut self) {
+        for (i, started) in self.started.iter().enumerate() {
+            if let Some(started) = started {
+                let elapsed = started.elapsed().as_secs_f64();
+                let _ = match self.counts[i] {
+                    Some((done, total)) => self.progress.update_stage_progress(i, done as f64 / total as f64,
+                        Some(&format!("{}/{} running {:.1}s", done, total, elapsed))),
+                    None => self.progress.update_stage_progress(i, 0.0, Some(&format!("running {:.1}s", elapsed))),
+                };
+            }
+        }
+        self.redraw();
+    }
+
+    fn finish(&mut self) {
+        if self.live {
+            print!("{}", TerminalControl::show_cursor());
+        }
+        println!();
+    }
+
+    fn redraw(&mut self) {
+        if !self.live {
+            return;
+        }
+
+        let rendered = self.progress.render(40);
+        let mut output = String::new();
+        if self.drawn_lines > 0 {
+            output.push_str(&TerminalControl::move_cursor_up(self.drawn_lines as u16));
+        }
+        for line in rendered.lines() {
+            output.push_str(TerminalControl::clear_line());
+            output.push_str(line);
+            output.push('\n');
+        }
+        self.drawn_lines = rendered.lines().count();
+
+        print!("{}", output);
+        let _ = io::stdout().flush();
     }
 }
 
@@ -412,29 +1217,28 @@ impl CommonWorkflows {
                     description: Some("Check code formatting".to_string()),
                     continue_on_failure: true,
                     timeout: Some(Duration::from_secs(60)),
-                    retry_count: 0,
-                    conditions: Vec::new(),
+                    ..Default::default()
                 },
                 WorkflowStep {
                     name: "Build".to_string(),
                     command: "cargo build".to_string(),
                     description: Some("Build the project".to_string()),
-                    continue_on_failure: false,
                     timeout: Some(Duration::from_secs(300)),
                     retry_count: 1,
-                    conditions: Vec::new(),
+                    ..Default::default()
                 },
                 WorkflowStep {
                     name: "Test".to_string(),
                     command: "cargo test".to_string(),
                     description: Some("Run tests".to_string()),
-                    continue_on_failure: false,
                     timeout: Some(Duration::from_secs(600)),
                     retry_count: 1,
-                    conditions: Vec::new(),
+                    ..Default::default()
                 },
             ],
+            finally: Vec::new(),
             variables: HashMap::new(),
+            required_variables: Vec::new(),
             on_failure: FailureAction::Stop,
         }
     }
@@ -451,40 +1255,35 @@ impl CommonWorkflows {
                     name: "Status Check".to_string(),
                     command: "git status --porcelain".to_string(),
                     description: Some("Check for changes".to_string()),
-                    continue_on_failure: false,
                     timeout: Some(Duration::from_secs(30)),
-                    retry_count: 0,
-                    conditions: Vec::new(),
+                    ..Default::default()
                 },
                 WorkflowStep {
                     name: "Add Changes".to_string(),
                     command: "git add .".to_string(),
                     description: Some("Stage all changes".to_string()),
-                    continue_on_failure: false,
                     timeout: Some(Duration::from_secs(60)),
-                    retry_count: 0,
-                    conditions: Vec::new(),
+                    ..Default::default()
                 },
                 WorkflowStep {
                     name: "Commit".to_string(),
                     command: "git commit -m \"${COMMIT_MESSAGE}\"".to_string(),
                     description: Some("Commit changes".to_string()),
-                    continue_on_failure: false,
                     timeout: Some(Duration::from_secs(60)),
-                    retry_count: 0,
-                    conditions: Vec::new(),
+                    ..Default::default()
                 },
                 WorkflowStep {
                     name: "Push".to_string(),
                     command: "git push".to_string(),
                     description: Some("Push to remote".to_string()),
-                    continue_on_failure: false,
                     timeout: Some(Duration::from_secs(120)),
                     retry_count: 2,
-                    conditions: Vec::new(),
+                    ..Default::default()
                 },
             ],
+            finally: Vec::new(),
             variables,
+            required_variables: Vec::new(),
             on_failure: FailureAction::Stop,
         }
     }
@@ -494,6 +1293,10 @@ impl CommonWorkflows {
 mod tests {
     use super::*;
 
+    fn step(name: &str, command: &str) -> WorkflowStep {
+        WorkflowStep { name: name.to_string(), command: command.to_string(), ..Default::default() }
+    }
+
     #[test]
     fn test_workflow_creation() {
         let mut runner = WorkflowRunner::new();
@@ -503,6 +1306,209 @@ mod tests {
         assert_eq!(workflow.steps.len(), 2);
     }
 
+    #[test]
+    fn test_failure_cancels_only_dependents() {
+        let after = |name: &str, command: &str, depends_on: &[&str]| WorkflowStep {
+            depends_on: Some(depends_on.iter().map(|d| d.to_string()).collect()),
+            ..step(name, command)
+        };
+        let workflow = Workflow {
+            name: "dag".to_string(),
+            description: None,
+            steps: vec![
+                after("build", "false", &[]),
+                after("test", "true", &["build"]),
+                after("package", "true", &["test"]),
+                after("lint", "true", &[]),
+            ],
+            finally: Vec::new(),
+            variables: HashMap::new(),
+            required_variables: Vec::new(),
+            on_failure: FailureAction::Stop,
+        };
+
+        let mut runner = WorkflowRunner::new().with_jobs(2);
+        runner.add_workflow(workflow);
+        let execution = runner.execute_workflow("dag").unwrap();
+
+        assert!(!execution.overall_success);
+        let lint = execution.step_results.iter().find(|r| r.step_name == "lint").unwrap();
+        assert!(lint.success);
+        let cancelled: Vec<&str> = execution.skipped_steps.iter().map(|s| s.step_name.as_str()).collect();
+        assert_eq!(cancelled, vec!["test", "package"]);
+        assert!(execution.skipped_steps.iter().all(|s| s.reason == SkipReason::Cancelled { failed_step: "build".to_string() }));
+    }
+
+    #[test]
+    fn test_rollback_and_finally() {
+        let undoable = |name: &str, command: &str, rollback: &str| WorkflowStep {
+            rollback: Some(rollback.to_string()),
+            ..step(name, command)
+        };
+        let mut report = step("report", "true");
+        report.conditions.push(WorkflowCondition { condition_type: ConditionType::PreviousStepFailure, value: "deploy".to_string() });
+
+        let workflow = Workflow {
+            name: "release".to_string(),
+            description: None,
+            steps: vec![
+                undoable("tag", "true", "true"),
+                step("notes", "true"),
+                undoable("upload", "true", "false"),
+                undoable("deploy", "false", "true"),
+            ],
+            finally: vec![step("cleanup", "true"), report],
+            variables: HashMap::new(),
+            required_variables: Vec::new(),
+            on_failure: FailureAction::Rollback,
+        };
+
+        let mut runner = WorkflowRunner::new();
+        runner.add_workflow(workflow);
+        let execution = runner.execute_workflow("release").unwrap();
+
+        // Most recent first; steps without a rollback, or that failed, are left alone
+        let rolled_back: Vec<(&str, bool)> = execution.rollback_results.iter()
+            .map(|r| (r.step_name.as_str(), r.success))
+            .collect();
+        assert_eq!(rolled_back, vec![("upload", false), ("tag", true)]);
+
+        let cleaned_up: Vec<&str> = execution.finally_results.iter().map(|r| r.step_name.as_str()).collect();
+        assert_eq!(cleaned_up, vec!["cleanup", "report"]);
+        assert!(!execution.overall_success);
+    }
+
+    #[test]
+    fn test_outputs_and_variables_reach_later_steps() {
+        let output = |name: &str, command: &str, source: OutputSource| WorkflowStep {
+            outputs: vec![StepOutput { name: "value".to_string(), source }],
+            ..step(name, command)
+        };
+        let mut variables = HashMap::new();
+        variables.insert("GREETING".to_string(), "hello".to_string());
+        let workflow = Workflow {
+            name: "outputs".to_string(),
+            description: None,
+            steps: vec![
+                output("version", "echo forge 1.4.2", OutputSource::Regex(r"(\d+\.\d+\.\d+)".to_string())),
+                output("use", "echo ${GREETING} ${TARGET} v${steps.version.outputs.value} ${steps.version.exit_code}", OutputSource::Stdout),
+            ],
+            finally: Vec::new(),
+            variables,
+            required_variables: vec!["TARGET".to_string()],
+            on_failure: FailureAction::Stop,
+        };
+
+        let mut runner = WorkflowRunner::new();
+        runner.add_workflow(workflow.clone());
+        let error = runner.execute_workflow("outputs").unwrap_err().to_string();
+        assert!(error.contains("requires TARGET"), "{}", error);
+
+        let set = |pairs: &[(&str, &str)]| pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        let mut runner = WorkflowRunner::new().with_variables(set(&[("TARGET", "prod"), ("TYPO", "x")]));
+        runner.add_workflow(workflow.clone());
+        assert!(runner.execute_workflow("outputs").unwrap_err().to_string().contains("no variable 'TYPO'"));
+
+        let mut runner = WorkflowRunner::new().with_variables(set(&[("TARGET", "prod"), ("GREETING", "hi")]));
+        runner.add_workflow(workflow);
+        let execution = runner.execute_workflow("outputs").unwrap();
+        assert!(execution.overall_success);
+        let used = execution.find_result("use").unwrap();
+        assert_eq!(used.outputs["value"], "hi prod v1.4.2 0");
+    }
+
+    #[test]
+    fn test_when_expressions_gate_steps() {
+        let gated = |name: &str, command: &str, when: &str| WorkflowStep {
+            continue_on_failure: true,
+            when: if when.is_empty() { None } else { Some(when.to_string()) },
+            ..step(name, command)
+        };
+        let mut count = gated("count", "echo 7", "");
+        count.outputs.push(StepOutput { name: "n".to_string(), source: OutputSource::Stdout });
+        let workflow = Workflow {
+            name: "gated".to_string(),
+            description: None,
+            steps: vec![
+                count,
+                gated("fails", "false", ""),
+                gated("enough", "true", "steps.count.outputs.n > 5 && failure('fails')"),
+                gated("never", "true", "vars.MODE == 'release' || steps.fails.exit_code == 0"),
+                gated("after-skip", "true", "steps.never.status == 'skipped'"),
+                gated("broken", "true", "steps.count.outputs.n < true"),
+            ],
+            finally: vec![gated("report", "true", "steps.broken.status == 'failure'")],
+            variables: HashMap::new(),
+            required_variables: Vec::new(),
+            on_failure: FailureAction::Continue,
+        };
+
+        let mut runner = WorkflowRunner::new();
+        runner.add_workflow(workflow);
+        let execution = runner.execute_workflow("gated").unwrap();
+
+        let ran: Vec<&str> = execution.step_results.iter().map(|r| r.step_name.as_str()).collect();
+        assert_eq!(ran, vec!["count", "fails", "enough", "after-skip", "broken"]);
+        let skipped: Vec<&str> = execution.skipped_steps.iter().map(|s| s.step_name.as_str()).collect();
+        assert_eq!(skipped, vec!["never"]);
+        let broken = execution.find_result("broken").unwrap();
+        assert!(!broken.success);
+        assert!(broken.error.as_deref().unwrap().starts_with("when: can't order"));
+        assert_eq!(execution.finally_results.len(), 1);
+    }
+
+    #[test]
+    fn test_matrix_and_for_each_run_iterations() {
+        let mut list = step("list", "printf 'a\\nb\\n'");
+        list.outputs.push(StepOutput { name: "files".to_string(), source: OutputSource::Stdout });
+        let mut build = step("build", "echo ${matrix.os}-${matrix.mode}");
+        build.matrix.insert("os".to_string(), vec!["linux".to_string(), "mac".to_string()]);
+        build.matrix.insert("mode".to_string(), vec!["debug".to_string(), "release".to_string()]);
+        build.rollback = Some("echo undo ${matrix.os}".to_string());
+        let mut each = step("each", "test ${item} = b");
+        each.for_each = Some(ForEach::Lines("${steps.list.outputs.files}".to_string()));
+        each.rollback = Some("echo undo ${item}".to_string());
+        let mut none = step("none", "true");
+        none.for_each = Some(ForEach::Lines("${EMPTY}".to_string()));
+        none.depends_on = Some(vec!["list".to_string()]);
+
+        let mut variables = HashMap::new();
+        variables.insert("EMPTY".to_string(), String::new());
+        let workflow = Workflow {
+            name: "loops".to_string(),
+            description: None,
+            steps: vec![list, build, each, step("after", "true"), none],
+            finally: Vec::new(),
+            variables,
+            required_variables: Vec::new(),
+            on_failure: FailureAction::Rollback,
+        };
+
+        let mut runner = WorkflowRunner::new().with_jobs(3);
+        runner.add_workflow(workflow);
+        let execution = runner.execute_workflow("loops").unwrap();
+
+        let build = execution.find_result("build").unwrap();
+        assert!(build.success);
+        let names: Vec<&str> = build.iterations.iter().map(|r| r.step_name.as_str()).collect();
+        assert_eq!(names, vec!["build [debug, linux]", "build [debug, mac]", "build [release, linux]", "build [release, mac]"]);
+        assert_eq!(build.iterations[3].output.trim(), "mac-release");
+
+        let each = execution.find_result("each").unwrap();
+        assert!(!each.success);
+        assert_eq!(each.iterations.iter().map(|r| r.success).collect::<Vec<_>>(), vec![false, true]);
+        assert!(each.error.as_deref().unwrap().starts_with("each [a]: "));
+        assert_eq!(each.exit_code, Some(1));
+
+        let skipped: Vec<(&str, &SkipReason)> = execution.skipped_steps.iter().map(|s| (s.step_name.as_str(), &s.reason)).collect();
+        assert!(skipped.contains(&("none", &SkipReason::NoItems)));
+        assert!(skipped.contains(&("after", &SkipReason::Cancelled { failed_step: "each".to_string() })));
+
+        // Each successful iteration is undone, the latest step first
+        let undone: Vec<String> = execution.rollback_results.iter().map(|r| r.output.trim().to_string()).collect();
+        assert_eq!(undone, vec!["undo b", "undo mac", "undo linux", "undo mac", "undo linux"]);
+    }
+
     #[test]
     fn test_common_workflows() {
         let rust_workflow = CommonWorkflows::rust_build_and_test();
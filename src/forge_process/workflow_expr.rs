@@ -0,0 +1,736 @@
+use std::fmt;
+use std::path::Path;
+use std::process::Command;
+use std::time::SystemTime;
+
+use crate::fs::glob::GlobMatcher;
+use crate::fs::walker::DirectoryWalker;
+use super::regex::Regex;
+use super::workflow::{ConditionType, WorkflowCondition};
+
+/// A parsed `when` expression, such as
+/// `success("Build") && (os() == "linux" || env("CI") != null)`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Literal(Value),
+    /// `steps.<name>.status`, `steps["Format Check"].exit_code`,
+    /// `steps.<name>.outputs.<output>` or `vars.<NAME>`
+    Path(Vec<String>),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Compare(CompareOp, Box<Expr>, Box<Expr>),
+    Call(Function, Vec<Expr>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Str(String),
+    Num(f64),
+    Bool(bool),
+    Null,
+}
+
+impl Value {
+    fn is_truthy(&self) -> bool {
+        match self {
+            Value::Str(s) => !s.is_empty(),
+            Value::Num(n) => *n != 0.0,
+            Value::Bool(b) => *b,
+            Value::Null => false,
+        }
+    }
+
+    fn as_number(&self) -> Option<f64> {
+        match self {
+            Value::Num(n) => Some(*n),
+            Value::Str(s) => s.trim().parse().ok(),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Str(s) => write!(f, "{}", s),
+            Value::Num(n) => write!(f, "{}", n),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::Null => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Function {
+    /// `exists(path)`: a file or directory is there
+    Exists,
+    FileExists,
+    DirExists,
+    /// `env(name)`: the variable's value, or null
+    Env,
+    /// `os()`: "linux", "macos", "windows", ...
+    Os,
+    /// `success(step)` / `failure(step)`: how a step that ran ended
+    Success,
+    Failure,
+    /// `changed(glob, ...)`: a file matching any glob changed
+    Changed,
+    Contains,
+    StartsWith,
+    EndsWith,
+    /// `matches(text, regex)`
+    Matches,
+}
+
+impl Function {
+    const ALL: [(&'static str, Function); 12] = [
+        ("exists", Function::Exists),
+        ("file_exists", Function::FileExists),
+        ("dir_exists", Function::DirExists),
+        ("env", Function::Env),
+        ("os", Function::Os),
+        ("success", Function::Success),
+        ("failure", Function::Failure),
+        ("changed", Function::Changed),
+        ("contains", Function::Contains),
+        ("starts_with", Function::StartsWith),
+        ("ends_with", Function::EndsWith),
+        ("matches", Function::Matches),
+    ];
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().find(|(n, _)| *n == name).map(|(_, f)| *f)
+    }
+
+    fn name(self) -> &'static str {
+        Self::ALL.iter().find(|(_, f)| *f == self).map(|(n, _)| *n).unwrap_or_default()
+    }
+
+    /// Smallest and largest number of arguments
+    fn arity(self) -> (usize, usize) {
+        match self {
+            Function::Os => (0, 0),
+            Function::Exists | Function::FileExists | Function::DirExists | Function::Env
+            | Function::Success | Function::Failure => (1, 1),
+            Function::Changed => (1, usize::MAX),
+            Function::Contains | Function::StartsWith | Function::EndsWith | Function::Matches => (2, 2),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExprError {
+    /// Byte offset into the expression
+    pub position: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ExprError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (at column {})", self.message, self.position + 1)
+    }
+}
+
+impl std::error::Error for ExprError {}
+
+/// What a `when` expression can see of the running workflow
+pub trait Scope {
+    /// "success", "failure", "skipped" or "cancelled"; `None` if the step
+    /// hasn't finished
+    fn step_status(&self, step: &str) -> Option<&'static str>;
+    fn step_exit_code(&self, step: &str) -> Option<i32>;
+    fn step_output(&self, step: &str, output: &str) -> Option<String>;
+    fn variable(&self, name: &str) -> Option<String>;
+    /// Paths, relative to the working directory, that changed
+    fn changed_files(&self) -> Result<Vec<String>, String>;
+}
+
+impl Expr {
+    pub fn parse(source: &str) -> Result<Expr, ExprError> {
+        let tokens = tokenize(source)?;
+        let mut parser = Parser { tokens, pos: 0, end: source.len() };
+        let expr = parser.parse_or()?;
+        match parser.tokens.get(parser.pos) {
+            Some((position, token)) => Err(ExprError { position: *position, message: format!("unexpected {}", token) }),
+            None => Ok(expr),
+        }
+    }
+
+    /// The expression for one of the older `conditions` entries
+    pub fn from_condition(condition: &WorkflowCondition) -> Expr {
+        let call = |function, value: &str| Expr::Call(function, vec![Expr::Literal(Value::Str(value.to_string()))]);
+        let not = |expr| Expr::Not(Box::new(expr));
+        let value = condition.value.as_str();
+        match condition.condition_type {
+            ConditionType::FileExists => call(Function::Exists, value),
+            ConditionType::FileNotExists => not(call(Function::Exists, value)),
+            ConditionType::DirectoryExists => call(Function::DirExists, value),
+            ConditionType::DirectoryNotExists => not(call(Function::DirExists, value)),
+            ConditionType::EnvironmentVariable => Expr::Compare(CompareOp::Ne, Box::new(call(Function::Env, value)), Box::new(Expr::Literal(Value::Null))),
+            ConditionType::PreviousStepSuccess => call(Function::Success, value),
+            ConditionType::PreviousStepFailure => call(Function::Failure, value),
+        }
+    }
+
+    /// Names of the steps the expression looks at
+    pub fn steps(&self) -> Vec<&str> {
+        let mut steps = Vec::new();
+        self.visit(&mut |expr| match expr {
+            Expr::Path(path) if path[0] == "steps" => steps.push(path[1].as_str()),
+            Expr::Call(Function::Success | Function::Failure, args) => {
+                if let Some(Expr::Literal(Value::Str(step))) = args.first() {
+                    steps.push(step.as_str());
+                }
+            }
+            _ => {}
+        });
+        steps
+    }
+
+    pub fn uses_changed(&self) -> bool {
+        let mut uses = false;
+        self.visit(&mut |expr| uses |= matches!(expr, Expr::Call(Function::Changed, _)));
+        uses
+    }
+
+    fn visit<'e>(&'e self, f: &mut impl FnMut(&'e Expr)) {
+        f(self);
+        match self {
+            Expr::Literal(_) | Expr::Path(_) => {}
+            Expr::Not(inner) => inner.visit(f),
+            Expr::And(a, b) | Expr::Or(a, b) | Expr::Compare(_, a, b) => {
+                a.visit(f);
+                b.visit(f);
+            }
+            Expr::Call(_, args) => args.iter().for_each(|arg| arg.visit(f)),
+        }
+    }
+
+    pub fn is_true(&self, scope: &dyn Scope) -> Result<bool, String> {
+        Ok(self.evaluate(scope)?.is_truthy())
+    }
+
+    pub fn evaluate(&self, scope: &dyn Scope) -> Result<Value, String> {
+        match self {
+            Expr::Literal(value) => Ok(value.clone()),
+            Expr::Path(path) => Ok(resolve_path(path, scope)),
+            Expr::Not(inner) => Ok(Value::Bool(!inner.is_true(scope)?)),
+            Expr::And(a, b) => Ok(Value::Bool(a.is_true(scope)? && b.is_true(scope)?)),
+            Expr::Or(a, b) => Ok(Value::Bool(a.is_true(scope)? || b.is_true(scope)?)),
+            Expr::Compare(op, a, b) => compare(*op, &a.evaluate(scope)?, &b.evaluate(scope)?).map(Value::Bool),
+            Expr::Call(function, args) => {
+                let args = args.iter().map(|arg| arg.evaluate(scope)).collect::<Result<Vec<_>, _>>()?;
+                call(*function, &args, scope)
+            }
+        }
+    }
+}
+
+fn resolve_path(path: &[String], scope: &dyn Scope) -> Value {
+    let string = |value: Option<String>| value.map(Value::Str).unwrap_or(Value::Null);
+    match path.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+        ["vars", name] => string(scope.variable(name)),
+        ["steps", step, "status"] => scope.step_status(step).map(|s| Value::Str(s.to_string())).unwrap_or(Value::Null),
+        ["steps", step, "exit_code"] => scope.step_exit_code(step).map(|code| Value::Num(code as f64)).unwrap_or(Value::Null),
+        ["steps", step, "outputs", output] => string(scope.step_output(step, output)),
+        _ => Value::Null,
+    }
+}
+
+/// `==` compares numbers numerically when either side is a number and both
+/// read as one, so `steps.x.outputs.count == 3` works on captured text
+fn compare(op: CompareOp, a: &Value, b: &Value) -> Result<bool, String> {
+    let numeric = match (a, b) {
+        (Value::Num(_), _) | (_, Value::Num(_)) => a.as_number().zip(b.as_number()),
+        _ => None,
+    };
+
+    match op {
+        CompareOp::Eq | CompareOp::Ne => {
+            let equal = match (numeric, a, b) {
+                (Some((x, y)), _, _) => x == y,
+                (None, Value::Null, Value::Null) => true,
+                (None, Value::Null, _) | (None, _, Value::Null) => false,
+                (None, Value::Bool(x), Value::Bool(y)) => x == y,
+                (None, a, b) => a.to_string() == b.to_string(),
+            };
+            Ok(equal == (op == CompareOp::Eq))
+        }
+        _ => {
+            let ordering = match (numeric, a, b) {
+                (Some((x, y)), _, _) => x.partial_cmp(&y),
+                (None, Value::Str(x), Value::Str(y)) => Some(x.cmp(y)),
+                _ => None,
+            }
+            .ok_or_else(|| format!("can't order {} and {}", describe(a), describe(b)))?;
+            Ok(match op {
+                CompareOp::Lt => ordering.is_lt(),
+                CompareOp::Le => ordering.is_le(),
+                CompareOp::Gt => ordering.is_gt(),
+                _ => ordering.is_ge(),
+            })
+        }
+    }
+}
+
+fn describe(value: &Value) -> String {
+    match value {
+        Value::Str(s) => format!("\"{}\"", s),
+        Value::Null => "null".to_string(),
+        value => value.to_string(),
+    }
+}
+
+fn call(function: Function, args: &[Value], scope: &dyn Scope) -> Result<Value, String> {
+    let text = |i: usize| args[i].to_string();
+    let value = match function {
+        Function::Exists => Value::Bool(Path::new(&text(0)).exists()),
+        Function::FileExists => Value::Bool(Path::new(&text(0)).is_file()),
+        Function::DirExists => Value::Bool(Path::new(&text(0)).is_dir()),
+        Function::Env => std::env::var(text(0)).map(Value::Str).unwrap_or(Value::Null),
+        Function::Os => Value::Str(std::env::consts::OS.to_string()),
+        Function::Success => Value::Bool(scope.step_status(&text(0)) == Some("success")),
+        Function::Failure => Value::Bool(scope.step_status(&text(0)) == Some("failure")),
+        Function::Changed => {
+            let globs: Vec<GlobMatcher> = args.iter().map(|arg| GlobMatcher::new(&arg.to_string())).collect();
+            let files = scope.changed_files()?;
+            Value::Bool(files.iter().any(|file| globs.iter().any(|glob| glob.matches_str(file))))
+        }
+        Function::Contains => Value::Bool(text(0).contains(&text(1))),
+        Function::StartsWith => Value::Bool(text(0).starts_with(&text(1))),
+        Function::EndsWith => Value::Bool(text(0).ends_with(&text(1))),
+        Function::Matches => {
+            let regex = Regex::new(&text(1)).map_err(|e| format!("matches(): {}", e))?;
+            Value::Bool(regex.captures(&text(0)).map_err(|e| format!("matches(): {}", e))?.is_some())
+        }
+    };
+    Ok(value)
+}
+
+/// Files that changed, relative to the working directory. Inside a git
+/// repository that means uncommitted and untracked files; elsewhere, files
+/// modified after `since` (all files when there's no `since`).
+pub fn changed_files(since: Option<SystemTime>) -> Result<Vec<String>, String> {
+    // Run records and the stamps of successful runs aren't changes of their own
+    let is_change = |path: &String| !path.starts_with(".forge/state/") && !path.starts_with(".forge/runs/");
+    if let Some(files) = git_changed_files() {
+        return Ok(files.into_iter().filter(is_change).collect());
+    }
+
+    let cwd = std::env::current_dir().map_err(|e| e.to_string())?;
+    let files = DirectoryWalker::new(&cwd)
+        .into_iter()
+        .files()
+        .filter(|file| match since {
+            Some(since) => file.metadata().and_then(|m| m.modified()).is_ok_and(|modified| modified > since),
+            None => true,
+        })
+        .filter_map(|file| file.strip_prefix(&cwd).ok().map(|path| path.to_string_lossy().replace('\\', "/")))
+        .filter(is_change)
+        .collect();
+    Ok(files)
+}
+
+pub fn in_git_repository() -> bool {
+    Command::new("git")
+        .args(["rev-parse", "--git-dir"])
+        .output()
+        .is_ok_and(|output| output.status.success())
+}
+
+/// `None` when not in a git repository or git isn't installed
+fn git_changed_files() -> Option<Vec<String>> {
+    let run = |args: &[&str]| {
+        let output = Command::new("git").args(args).output().ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        let text = String::from_utf8_lossy(&output.stdout).into_owned();
+        Some(text.split('\0').filter(|path| !path.is_empty()).map(str::to_string).collect::<Vec<_>>())
+    };
+
+    let mut files = run(&["diff", "--name-only", "--relative", "-z", "HEAD"])
+        // A repository without commits yet has nothing to diff against
+        .or_else(|| in_git_repository().then(Vec::new))?;
+    files.extend(run(&["ls-files", "--others", "--exclude-standard", "-z"]).unwrap_or_default());
+    Some(files)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Str(String),
+    Num(f64),
+    Op(&'static str),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Ident(name) => write!(f, "'{}'", name),
+            Token::Str(s) => write!(f, "string \"{}\"", s),
+            Token::Num(n) => write!(f, "number {}", n),
+            Token::Op(op) => write!(f, "'{}'", op),
+        }
+    }
+}
+
+const OPERATORS: [&str; 15] = ["==", "!=", "<=", ">=", "&&", "||", "<", ">", "!", "(", ")", ",", ".", "[", "]"];
+
+fn tokenize(source: &str) -> Result<Vec<(usize, Token)>, ExprError> {
+    let mut tokens = Vec::new();
+    let mut chars = source.char_indices().peekable();
+
+    while let Some(&(start, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '"' || c == '\'' {
+            chars.next();
+            let mut value = String::new();
+            loop {
+                match chars.next() {
+                    // Single-quoted strings are raw, which suits regexes
+                    Some((_, '\\')) if c == '"' => match chars.next() {
+                        Some((_, 'n')) => value.push('\n'),
+                        Some((_, 't')) => value.push('\t'),
+                        Some((_, escaped)) => value.push(escaped),
+                        None => return Err(ExprError { position: start, message: "unterminated string".to_string() }),
+                    },
+                    Some((_, quote)) if quote == c => break,
+                    Some((_, ch)) => value.push(ch),
+                    None => return Err(ExprError { position: start, message: "unterminated string".to_string() }),
+                }
+            }
+            tokens.push((start, Token::Str(value)));
+        } else if c.is_ascii_digit() || (c == '-' && source[start + 1..].starts_with(|d: char| d.is_ascii_digit())) {
+            let mut end = start + c.len_utf8();
+            chars.next();
+            while let Some(&(i, d)) = chars.peek() {
+                if !(d.is_ascii_digit() || d == '.') {
+                    break;
+                }
+                end = i + 1;
+                chars.next();
+            }
+            let number = source[start..end].parse().map_err(|_| ExprError { position: start, message: format!("bad number '{}'", &source[start..end]) })?;
+            tokens.push((start, Token::Num(number)));
+        } else if c.is_alphanumeric() || c == '_' {
+            let mut end = start;
+            while let Some(&(i, d)) = chars.peek() {
+                if !(d.is_alphanumeric() || d == '_' || d == '-') {
+                    break;
+                }
+                end = i + d.len_utf8();
+                chars.next();
+            }
+            tokens.push((start, Token::Ident(source[start..end].to_string())));
+        } else {
+            let op = OPERATORS.iter().find(|op| source[start..].starts_with(**op))
+                .ok_or_else(|| ExprError { position: start, message: format!("unexpected '{}'", c) })?;
+            for _ in 0..op.len() {
+                chars.next();
+            }
+            tokens.push((start, Token::Op(op)));
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    pos: usize,
+    /// Reported as the position of errors at the end of the input
+    end: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(_, token)| token)
+    }
+
+    fn position(&self) -> usize {
+        self.tokens.get(self.pos).map(|(position, _)| *position).unwrap_or(self.end)
+    }
+
+    fn error(&self, message: impl Into<String>) -> ExprError {
+        ExprError { position: self.position(), message: message.into() }
+    }
+
+    fn eat(&mut self, op: &str) -> bool {
+        if matches!(self.peek(), Some(Token::Op(o)) if *o == op) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, op: &str) -> Result<(), ExprError> {
+        if self.eat(op) {
+            Ok(())
+        } else {
+            let found = self.peek().map(|token| token.to_string()).unwrap_or_else(|| "end of expression".to_string());
+            Err(self.error(format!("expected '{}', found {}", op, found)))
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<Expr, ExprError> {
+        let mut expr = self.parse_and()?;
+        while self.eat("||") {
+            expr = Expr::Or(Box::new(expr), Box::new(self.parse_and()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr, ExprError> {
+        let mut expr = self.parse_unary()?;
+        while self.eat("&&") {
+            expr = Expr::And(Box::new(expr), Box::new(self.parse_unary()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr, ExprError> {
+        if self.eat("!") {
+            return Ok(Expr::Not(Box::new(self.parse_unary()?)));
+        }
+        let left = self.parse_primary()?;
+        let op = match self.peek() {
+            Some(Token::Op("==")) => CompareOp::Eq,
+            Some(Token::Op("!=")) => CompareOp::Ne,
+            Some(Token::Op("<")) => CompareOp::Lt,
+            Some(Token::Op("<=")) => CompareOp::Le,
+            Some(Token::Op(">")) => CompareOp::Gt,
+            Some(Token::Op(">=")) => CompareOp::Ge,
+            _ => return Ok(left),
+        };
+        self.pos += 1;
+        let right = self.parse_primary()?;
+        Ok(Expr::Compare(op, Box::new(left), Box::new(right)))
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr, ExprError> {
+        let start = self.position();
+        let token = self.peek().cloned().ok_or_else(|| self.error("expression ends too early"))?;
+        self.pos += 1;
+
+        match token {
+            Token::Str(s) => Ok(Expr::Literal(Value::Str(s))),
+            Token::Num(n) => Ok(Expr::Literal(Value::Num(n))),
+            Token::Op("(") => {
+                let expr = self.parse_or()?;
+                self.expect(")")?;
+                Ok(expr)
+            }
+            Token::Ident(name) => match name.as_str() {
+                "true" => Ok(Expr::Literal(Value::Bool(true))),
+                "false" => Ok(Expr::Literal(Value::Bool(false))),
+                "null" => Ok(Expr::Literal(Value::Null)),
+                _ if self.eat("(") => self.parse_call(start, &name),
+                _ => self.parse_path(start, name),
+            },
+            token => {
+                self.pos -= 1;
+                Err(self.error(format!("unexpected {}", token)))
+            }
+        }
+    }
+
+    fn parse_call(&mut self, start: usize, name: &str) -> Result<Expr, ExprError> {
+        let function = Function::from_name(name)
+            .ok_or_else(|| ExprError { position: start, message: format!("unknown function '{}'", name) })?;
+
+        let mut args = Vec::new();
+        if !self.eat(")") {
+            loop {
+                args.push(self.parse_or()?);
+                if self.eat(")") {
+                    break;
+                }
+                self.expect(",")?;
+            }
+        }
+
+        let (min, max) = function.arity();
+        if args.len() < min || args.len() > max {
+            let expected = match (min, max) {
+                (min, max) if min == max => format!("{}", min),
+                (min, usize::MAX) => format!("at least {}", min),
+                (min, max) => format!("{} to {}", min, max),
+            };
+            return Err(ExprError { position: start, message: format!("{}() takes {} argument(s), got {}", function.name(), expected, args.len()) });
+        }
+        if matches!(function, Function::Success | Function::Failure) && !matches!(args[0], Expr::Literal(Value::Str(_))) {
+            return Err(ExprError { position: start, message: format!("{}() takes a step name in quotes", function.name()) });
+        }
+        Ok(Expr::Call(function, args))
+    }
+
+    fn parse_path(&mut self, start: usize, root: String) -> Result<Expr, ExprError> {
+        let mut path = vec![root];
+        loop {
+            if self.eat(".") {
+                match self.peek().cloned() {
+                    Some(Token::Ident(name)) => {
+                        self.pos += 1;
+                        path.push(name);
+                    }
+                    _ => return Err(self.error("expected a name after '.'")),
+                }
+            } else if self.eat("[") {
+                match self.peek().cloned() {
+                    Some(Token::Str(name)) => {
+                        self.pos += 1;
+                        path.push(name);
+                    }
+                    _ => return Err(self.error("expected a quoted name after '['")),
+                }
+                self.expect("]")?;
+            } else {
+                break;
+            }
+        }
+
+        let segments: Vec<&str> = path.iter().map(String::as_str).collect();
+        let valid = matches!(
+            segments[..],
+            ["vars", _] | ["steps", _, "status" | "exit_code"] | ["steps", _, "outputs", _]
+        );
+        if !valid {
+            return Err(ExprError {
+                position: start,
+                message: format!(
+                    "unknown name '{}'; expected steps.<step>.status, steps.<step>.exit_code, steps.<step>.outputs.<name> or vars.<NAME>",
+                    segments.join(".")
+                ),
+            });
+        }
+        Ok(Expr::Path(path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[derive(Default)]
+    struct TestScope {
+        statuses: HashMap<&'static str, &'static str>,
+        outputs: HashMap<(&'static str, &'static str), &'static str>,
+        changed: Vec<String>,
+    }
+
+    impl Scope for TestScope {
+        fn step_status(&self, step: &str) -> Option<&'static str> {
+            self.statuses.get(step).copied()
+        }
+        fn step_exit_code(&self, step: &str) -> Option<i32> {
+            self.step_status(step).map(|status| if status == "success" { 0 } else { 2 })
+        }
+        fn step_output(&self, step: &str, output: &str) -> Option<String> {
+            self.outputs.iter().find(|((s, o), _)| *s == step && *o == output).map(|(_, v)| v.to_string())
+        }
+        fn variable(&self, name: &str) -> Option<String> {
+            (name == "PROFILE").then(|| "release".to_string())
+        }
+        fn changed_files(&self) -> Result<Vec<String>, String> {
+            Ok(self.changed.clone())
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        let expr = Expr::parse(r#"!success("Build") || steps["Format Check"].exit_code != 0 && os() == 'linux'"#).unwrap();
+        match &expr {
+            Expr::Or(left, right) => {
+                assert!(matches!(**left, Expr::Not(_)));
+                assert!(matches!(**right, Expr::And(_, _)));
+            }
+            other => panic!("expected ||, got {:?}", other),
+        }
+        assert_eq!(expr.steps(), vec!["Build", "Format Check"]);
+        assert!(!expr.uses_changed());
+        assert!(Expr::parse("changed('src/**', \"Cargo.*\")").unwrap().uses_changed());
+
+        let errors = [
+            ("success(\"a\") &&", 15, "expression ends too early"),
+            ("exists()", 0, "exists() takes 1 argument(s), got 0"),
+            ("nope(1)", 0, "unknown function 'nope'"),
+            ("steps.build.result", 0, "unknown name 'steps.build.result'"),
+            ("(os() == 'linux'", 16, "expected ')', found end of expression"),
+            ("os() == 'linux", 8, "unterminated string"),
+            ("os() = 'linux'", 5, "unexpected '='"),
+            ("success(vars.STEP)", 0, "success() takes a step name in quotes"),
+            ("1 2", 2, "unexpected number 2"),
+        ];
+        for (source, position, message) in errors {
+            let error = Expr::parse(source).unwrap_err();
+            assert!(error.message.starts_with(message), "{}: {}", source, error.message);
+            assert_eq!(error.position, position, "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let mut scope = TestScope::default();
+        scope.statuses.insert("build", "success");
+        scope.statuses.insert("lint", "failure");
+        scope.statuses.insert("docs", "skipped");
+        scope.outputs.insert(("build", "version"), "1.10.0");
+        scope.outputs.insert(("build", "warnings"), "12");
+        scope.changed = vec!["src/main.rs".to_string(), "README.md".to_string()];
+
+        let cases = [
+            ("success('build') && failure('lint')", true),
+            ("success('docs') || failure('docs')", false),
+            ("steps.docs.status == 'skipped'", true),
+            ("steps.missing.status == null", true),
+            ("steps.lint.exit_code > 0 && steps.build.exit_code == 0", true),
+            ("steps.build.outputs.warnings >= 10", true),
+            ("steps.build.outputs.warnings == 12.0", true),
+            // Strings order as text unless a number is involved
+            ("steps.build.outputs.version < '1.9'", true),
+            ("starts_with(steps.build.outputs.version, '1.') && ends_with('forge.tar.gz', '.gz')", true),
+            ("contains(vars.PROFILE, 'rel') && vars.UNKNOWN == null", true),
+            (r"matches(steps.build.outputs.version, '^\d+\.\d+\.0$')", true),
+            ("changed('src/**/*.rs')", true),
+            ("changed('tests/**', 'Cargo.toml')", false),
+            ("exists('/') && dir_exists('/') && !file_exists('/')", true),
+            ("env('FORGE_TEST_SURELY_UNSET') == null", true),
+            ("os() != ''", true),
+            ("!(true && false) && 0 == 0", true),
+            ("'' || null || 0", false),
+        ];
+        for (source, expected) in cases {
+            let expr = Expr::parse(source).unwrap_or_else(|e| panic!("{}: {}", source, e));
+            assert_eq!(expr.is_true(&scope).unwrap(), expected, "{}", source);
+        }
+
+        let error = Expr::parse("steps.build.outputs.version > true").unwrap().is_true(&scope).unwrap_err();
+        assert_eq!(error, "can't order \"1.10.0\" and true");
+    }
+
+    #[test]
+    fn test_conditions_become_functions() {
+        let condition = |condition_type, value: &str| WorkflowCondition { condition_type, value: value.to_string() };
+        let mut scope = TestScope::default();
+        scope.statuses.insert("build", "failure");
+
+        assert!(!Expr::from_condition(&condition(ConditionType::PreviousStepSuccess, "build")).is_true(&scope).unwrap());
+        assert!(Expr::from_condition(&condition(ConditionType::PreviousStepFailure, "build")).is_true(&scope).unwrap());
+        assert!(Expr::from_condition(&condition(ConditionType::DirectoryNotExists, "/surely/not/here")).is_true(&scope).unwrap());
+        assert!(!Expr::from_condition(&condition(ConditionType::EnvironmentVariable, "FORGE_TEST_SURELY_UNSET")).is_true(&scope).unwrap());
+    }
+}
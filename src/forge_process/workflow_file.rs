@@ -0,0 +1,1079 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use toml::Spanned;
+
+use crate::config;
+
+use super::regex::Regex;
+use super::workflow::{ConditionType, FailureAction, ForEach, OutputSource, StepOutput, Workflow, WorkflowCondition, WorkflowStep};
+use super::workflow_expr::Expr;
+use super::workflow_graph::{GraphError, StepGraph};
+use super::workflow_template::{self, Reference};
+
+/// A problem with a workflow file, pointing at the offending line
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorkflowFileError {
+    pub path: Option<PathBuf>,
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl WorkflowFileError {
+    fn at(content: &str, span: Range<usize>, message: impl Into<String>) -> Self {
+        let (line, column) = line_column(content, span.start);
+        Self { path: None, line, column, message: message.into() }
+    }
+
+    fn in_file(mut self, path: &Path) -> Self {
+        self.path = Some(path.to_path_buf());
+        self
+    }
+}
+
+impl fmt::Display for WorkflowFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(path) = &self.path {
+            write!(f, "{}:", path.display())?;
+        }
+        if self.line > 0 {
+            write!(f, "{}:{}: ", self.line, self.column)?;
+        } else if self.path.is_some() {
+            write!(f, " ")?;
+        }
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for WorkflowFileError {}
+
+/// Where a workflow was defined
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WorkflowSource {
+    Builtin,
+    User(PathBuf),
+    Project(PathBuf),
+}
+
+impl WorkflowSource {
+    pub fn label(&self) -> &'static str {
+        match self {
+            WorkflowSource::Builtin => "built-in",
+            WorkflowSource::User(_) => "user",
+            WorkflowSource::Project(_) => "project",
+        }
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            WorkflowSource::Builtin => None,
+            WorkflowSource::User(path) | WorkflowSource::Project(path) => Some(path),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct WorkflowEntry {
+    pub workflow: Workflow,
+    pub source: WorkflowSource,
+}
+
+/// Workflows found on disk, plus the files that failed to load
+#[derive(Debug, Default)]
+pub struct Discovery {
+    pub workflows: Vec<WorkflowEntry>,
+    pub errors: Vec<WorkflowFileError>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+struct TomlWorkflow {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<Spanned<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    on_failure: Option<TomlFailureAction>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    variables: BTreeMap<String, Spanned<TomlVariable>>,
+    steps: Option<Spanned<Vec<TomlStep>>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    finally: Vec<TomlStep>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(untagged)]
+enum TomlVariable {
+    /// `NAME = "default"`
+    Default(String),
+    /// `NAME = { required = true }`
+    Spec(TomlVariableSpec),
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+struct TomlVariableSpec {
+    required: bool,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum TomlFailureAction {
+    Stop,
+    Continue,
+    Rollback,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+struct TomlStep {
+    name: Spanned<String>,
+    command: Spanned<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+    #[serde(default, skip_serializing_if = "is_false")]
+    continue_on_failure: bool,
+    /// Seconds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    timeout: Option<Spanned<u64>>,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    retries: u32,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    conditions: Vec<TomlCondition>,
+    /// Expression that must hold for the step to run
+    #[serde(skip_serializing_if = "Option::is_none")]
+    when: Option<Spanned<String>>,
+    /// Omitted means "after the previous step"; `[]` means no dependencies
+    #[serde(skip_serializing_if = "Option::is_none")]
+    depends_on: Option<Vec<Spanned<String>>>,
+    /// Undoes the step if a later one fails under `on_failure = "rollback"`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rollback: Option<Spanned<String>>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    outputs: BTreeMap<String, Spanned<TomlOutput>>,
+    /// Runs the step once per combination of values
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    matrix: BTreeMap<String, Spanned<Vec<String>>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    for_each: Option<Spanned<TomlForEach>>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(untagged)]
+enum TomlForEach {
+    /// `for_each = ["a", "b"]`
+    List(Vec<String>),
+    /// `for_each = { glob = "..." }` or `for_each = { lines = "..." }`
+    Spec(TomlForEachSpec),
+}
+
+/// Unknown keys are left to the "must be a list, ..." error, which reads
+/// better than serde's for an untagged enum
+#[derive(Debug, Deserialize, Serialize)]
+struct TomlForEachSpec {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    glob: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    lines: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(untagged)]
+enum TomlOutput {
+    /// `name = "stdout"`
+    Keyword(String),
+    /// `name = { regex = "..." }` or `name = { json = "..." }`
+    Spec(TomlOutputSpec),
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+struct TomlOutputSpec {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    regex: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    json: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+struct TomlCondition {
+    #[serde(rename = "type")]
+    condition_type: TomlConditionType,
+    value: Spanned<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum TomlConditionType {
+    FileExists,
+    FileNotExists,
+    DirectoryExists,
+    DirectoryNotExists,
+    EnvironmentVariable,
+    PreviousStepSuccess,
+    PreviousStepFailure,
+}
+
+/// Where a step's parts are in the file, for errors found after parsing
+struct StepSpans {
+    name: Range<usize>,
+    command: Range<usize>,
+    rollback: Option<Range<usize>>,
+    when: Option<Range<usize>>,
+    /// The template of `for_each = { lines = "..." }`
+    for_each_lines: Option<Range<usize>>,
+    depends_on: Vec<Range<usize>>,
+    /// Conditions that name another step, by index into the step's conditions
+    step_conditions: Vec<(usize, Range<usize>)>,
+}
+
+/// Parse and validate a workflow definition: top-level `name`,
+/// `description`, `on_failure` and `[variables]`, then one `[[steps]]`
+/// table per step and any `[[finally]]` steps. `default_name` is used when the file doesn't set
+/// `name`, normally the file stem.
+pub fn parse_workflow(content: &str, default_name: &str) -> Result<Workflow, WorkflowFileError> {
+    let parsed: TomlWorkflow = toml::from_str(content).map_err(|e| match e.span() {
+        Some(span) => WorkflowFileError::at(content, span, e.message()),
+        None => WorkflowFileError { path: None, line: 0, column: 0, message: e.message().to_string() },
+    })?;
+
+    let name = match &parsed.name {
+        Some(name) => {
+            validate_name(name.get_ref()).map_err(|msg| WorkflowFileError::at(content, name.span(), msg))?;
+            name.get_ref().clone()
+        }
+        None => {
+            validate_name(default_name).map_err(|msg| WorkflowFileError { path: None, line: 0, column: 0, message: msg })?;
+            default_name.to_string()
+        }
+    };
+
+    let toml_steps = parsed.steps.ok_or_else(|| WorkflowFileError {
+        path: None,
+        line: 0,
+        column: 0,
+        message: "workflow has no [[steps]]".to_string(),
+    })?;
+    if toml_steps.get_ref().is_empty() {
+        return Err(WorkflowFileError::at(content, toml_steps.span(), "workflow has no steps"));
+    }
+
+    let mut variables = BTreeMap::new();
+    let mut required_variables = Vec::new();
+    for (var_name, value) in parsed.variables {
+        if var_name.is_empty() || !var_name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+            return Err(WorkflowFileError::at(content, value.span(),
+                format!("variable name '{}' may only contain letters, digits, '-' and '_'", var_name)));
+        }
+        if var_name == "item" {
+            return Err(WorkflowFileError::at(content, value.span(), "'item' is reserved for the current for_each item"));
+        }
+        match value.get_ref() {
+            TomlVariable::Default(default) => {
+                let references = workflow_template::references(default)
+                    .map_err(|msg| WorkflowFileError::at(content, value.span(), msg))?;
+                let fixed = |reference: &Reference| matches!(reference, Reference::Env(_) | Reference::Variable(_));
+                if let Some((_, reference)) = references.iter().find(|(_, reference)| !fixed(reference)) {
+                    return Err(WorkflowFileError::at(content, value.span(),
+                        format!("variable '{}' can't use {}; defaults may only refer to ${{env.NAME}}", var_name, reference)));
+                }
+                variables.insert(var_name, default.clone());
+            }
+            TomlVariable::Spec(TomlVariableSpec { required: true }) => required_variables.push(var_name),
+            TomlVariable::Spec(TomlVariableSpec { required: false }) => {
+                return Err(WorkflowFileError::at(content, value.span(),
+                    format!("variable '{}' needs a default value or `required = true`", var_name)));
+            }
+        }
+    }
+
+    let mut seen = HashSet::new();
+    let mut steps = Vec::new();
+    let mut step_spans = Vec::new();
+    for step in toml_steps.into_inner() {
+        let (step, spans) = convert_step(content, step, &mut seen)?;
+        steps.push(step);
+        step_spans.push(spans);
+    }
+
+    let graph = StepGraph::new(&steps).map_err(|e| {
+        let span = match &e {
+            GraphError::UnknownDependency { step, dependency, .. } => dependency_span(&steps[*step], &step_spans[*step], dependency),
+            GraphError::Cycle { steps: cycle, .. } => {
+                let dependency = &steps[cycle[1]].name;
+                dependency_span(&steps[cycle[0]], &step_spans[cycle[0]], dependency)
+            }
+        };
+        WorkflowFileError::at(content, span, e.to_string())
+    })?;
+
+    // Conditions can only look at steps that are certain to have finished
+    for (i, step) in steps.iter().enumerate() {
+        let ancestors = graph.ancestors(i);
+        for (condition, span) in &step_spans[i].step_conditions {
+            let value = &step.conditions[*condition].value;
+            let is_ancestor = steps.iter().position(|s| &s.name == value).is_some_and(|j| ancestors.contains(&j));
+            if !is_ancestor {
+                return Err(WorkflowFileError::at(content, span.clone(),
+                    format!("'{}' is not a step that '{}' depends on", value, step.name)));
+            }
+        }
+
+        // Rollbacks run after the step itself, so they may also use its outputs
+        let visible: Vec<&WorkflowStep> = steps.iter().enumerate()
+            .filter(|(j, _)| ancestors.contains(j))
+            .map(|(_, step)| step)
+            .collect();
+        let not_visible = |other: &str| format!("'{}' is not a step that '{}' depends on", other, step.name);
+        check_references(content, &step_spans[i].command, &step.command, &visible, not_visible)?;
+        if let (Some(when), Some(span)) = (&step.when, &step_spans[i].when) {
+            check_when(content, span, when, &visible, not_visible)?;
+        }
+        if let (Some(ForEach::Lines(lines)), Some(span)) = (&step.for_each, &step_spans[i].for_each_lines) {
+            check_references(content, span, lines, &visible, not_visible)?;
+        }
+        if let (Some(rollback), Some(span)) = (&step.rollback, &step_spans[i].rollback) {
+            let visible: Vec<&WorkflowStep> = visible.iter().copied().chain([step]).collect();
+            check_references(content, span, rollback, &visible, not_visible)?;
+        }
+    }
+
+    // Cleanup steps run one after another once every step has finished,
+    // so they may look at any step before them
+    let mut finally = Vec::new();
+    for toml_step in parsed.finally {
+        let depends_on_span = toml_step.depends_on.as_ref().map(|_| toml_step.name.span());
+        let (step, spans) = convert_step(content, toml_step, &mut seen)?;
+        if let Some(span) = depends_on_span {
+            return Err(WorkflowFileError::at(content, span, format!("finally step '{}' can't have depends_on", step.name)));
+        }
+        if step.rollback.is_some() {
+            return Err(WorkflowFileError::at(content, spans.name, format!("finally step '{}' can't have a rollback", step.name)));
+        }
+        if step.is_looping() {
+            return Err(WorkflowFileError::at(content, spans.name, format!("finally step '{}' can't have a matrix or for_each", step.name)));
+        }
+        for (condition, span) in &spans.step_conditions {
+            let value = &step.conditions[*condition].value;
+            let is_earlier = steps.iter().chain(&finally).any(|s: &WorkflowStep| &s.name == value);
+            if !is_earlier {
+                return Err(WorkflowFileError::at(content, span.clone(), format!("'{}' is not an earlier step", value)));
+            }
+        }
+        let visible: Vec<&WorkflowStep> = steps.iter().chain(&finally).collect();
+        let not_earlier = |other: &str| format!("'{}' is not an earlier step", other);
+        check_references(content, &spans.command, &step.command, &visible, not_earlier)?;
+        if let (Some(when), Some(span)) = (&step.when, &spans.when) {
+            check_when(content, span, when, &visible, not_earlier)?;
+        }
+        finally.push(step);
+    }
+
+    Ok(Workflow {
+        name,
+        description: parsed.description,
+        steps,
+        finally,
+        variables: variables.into_iter().collect(),
+        required_variables,
+        on_failure: match parsed.on_failure {
+            None | Some(TomlFailureAction::Stop) => FailureAction::Stop,
+            Some(TomlFailureAction::Continue) => FailureAction::Continue,
+            Some(TomlFailureAction::Rollback) => FailureAction::Rollback,
+        },
+    })
+}
+
+/// Validate one `[[steps]]` or `[[finally]]` table. `seen` holds the step
+/// names used so far, which must be unique across both.
+fn convert_step(content: &str, step: TomlStep, seen: &mut HashSet<String>) -> Result<(WorkflowStep, StepSpans), WorkflowFileError> {
+    let step_name = step.name.get_ref().trim();
+    if step_name.is_empty() {
+        return Err(WorkflowFileError::at(content, step.name.span(), "step name is empty"));
+    }
+    if !seen.insert(step_name.to_string()) {
+        return Err(WorkflowFileError::at(content, step.name.span(), format!("duplicate step name '{}'", step_name)));
+    }
+    if step.command.get_ref().trim().is_empty() {
+        return Err(WorkflowFileError::at(content, step.command.span(), format!("step '{}' has an empty command", step_name)));
+    }
+    if let Some(rollback) = &step.rollback {
+        if rollback.get_ref().trim().is_empty() {
+            return Err(WorkflowFileError::at(content, rollback.span(), format!("step '{}' has an empty rollback command", step_name)));
+        }
+    }
+
+    let timeout = match step.timeout {
+        Some(timeout) if *timeout.get_ref() == 0 => {
+            return Err(WorkflowFileError::at(content, timeout.span(), "timeout must be at least 1 second"));
+        }
+        Some(timeout) => Some(Duration::from_secs(timeout.into_inner())),
+        None => None,
+    };
+
+    let mut conditions = Vec::new();
+    let mut condition_spans = Vec::new();
+    for condition in step.conditions {
+        let condition_type = match condition.condition_type {
+            TomlConditionType::FileExists => ConditionType::FileExists,
+            TomlConditionType::FileNotExists => ConditionType::FileNotExists,
+            TomlConditionType::DirectoryExists => ConditionType::DirectoryExists,
+            TomlConditionType::DirectoryNotExists => ConditionType::DirectoryNotExists,
+            TomlConditionType::EnvironmentVariable => ConditionType::EnvironmentVariable,
+            TomlConditionType::PreviousStepSuccess => ConditionType::PreviousStepSuccess,
+            TomlConditionType::PreviousStepFailure => ConditionType::PreviousStepFailure,
+        };
+        if matches!(condition_type, ConditionType::PreviousStepSuccess | ConditionType::PreviousStepFailure) {
+            condition_spans.push((conditions.len(), condition.value.span()));
+        }
+        conditions.push(WorkflowCondition { condition_type, value: condition.value.into_inner() });
+    }
+
+    let mut outputs = Vec::new();
+    for (output_name, output) in &step.outputs {
+        if output_name.is_empty() || !output_name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+            return Err(WorkflowFileError::at(content, output.span(),
+                format!("output name '{}' may only contain letters, digits, '-' and '_'", output_name)));
+        }
+        let source = match output.get_ref() {
+            TomlOutput::Keyword(keyword) if keyword == "stdout" => OutputSource::Stdout,
+            TomlOutput::Spec(TomlOutputSpec { regex: Some(pattern), json: None }) => {
+                Regex::new(pattern).map_err(|e| WorkflowFileError::at(content, output.span(), format!("output '{}': {}", output_name, e)))?;
+                OutputSource::Regex(pattern.clone())
+            }
+            TomlOutput::Spec(TomlOutputSpec { regex: None, json: Some(path) }) => OutputSource::JsonPath(path.clone()),
+            _ => {
+                return Err(WorkflowFileError::at(content, output.span(),
+                    format!("output '{}' must be \"stdout\", {{ regex = \"...\" }} or {{ json = \"...\" }}", output_name)));
+            }
+        };
+        outputs.push(StepOutput { name: output_name.clone(), source });
+    }
+
+    let mut matrix = BTreeMap::new();
+    for (key, values) in step.matrix {
+        if key.is_empty() || !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+            return Err(WorkflowFileError::at(content, values.span(),
+                format!("matrix key '{}' may only contain letters, digits, '-' and '_'", key)));
+        }
+        if values.get_ref().is_empty() {
+            return Err(WorkflowFileError::at(content, values.span(), format!("matrix '{}' has no values", key)));
+        }
+        matrix.insert(key, values.into_inner());
+    }
+
+    let for_each_span = step.for_each.as_ref().map(|for_each| for_each.span());
+    let for_each = match step.for_each.map(Spanned::into_inner) {
+        None => None,
+        Some(TomlForEach::List(items)) if items.is_empty() => {
+            return Err(WorkflowFileError::at(content, for_each_span.unwrap_or_default(), format!("step '{}' has an empty for_each", step_name)));
+        }
+        Some(TomlForEach::List(items)) => Some(ForEach::List(items)),
+        Some(TomlForEach::Spec(TomlForEachSpec { glob: Some(pattern), lines: None })) => Some(ForEach::Glob(pattern)),
+        Some(TomlForEach::Spec(TomlForEachSpec { glob: None, lines: Some(lines) })) => Some(ForEach::Lines(lines)),
+        Some(TomlForEach::Spec(_)) => {
+            return Err(WorkflowFileError::at(content, for_each_span.unwrap_or_default(),
+                "for_each must be a list, { glob = \"...\" } or { lines = \"...\" }"));
+        }
+    };
+    if (!matrix.is_empty() || for_each.is_some()) && !outputs.is_empty() {
+        let span = step.outputs.values().next().map(|output| output.span()).unwrap_or_default();
+        return Err(WorkflowFileError::at(content, span,
+            format!("step '{}' runs once per matrix or for_each entry, so it can't have outputs", step_name)));
+    }
+
+    // `${matrix.KEY}` and `${item}` only mean something in a looping step's
+    // own commands
+    let for_each_lines = match &for_each {
+        Some(ForEach::Lines(_)) => for_each_span.clone(),
+        _ => None,
+    };
+    let iteration_texts = [
+        Some((step.command.span(), step.command.get_ref(), true)),
+        step.rollback.as_ref().map(|rollback| (rollback.span(), rollback.get_ref(), true)),
+        match (&for_each, &for_each_lines) {
+            (Some(ForEach::Lines(lines)), Some(span)) => Some((span.clone(), lines, false)),
+            _ => None,
+        },
+    ];
+    for (span, text, iterates) in iteration_texts.into_iter().flatten() {
+        let references = workflow_template::references(text).map_err(|msg| WorkflowFileError::at(content, span.clone(), msg))?;
+        for (range, reference) in references {
+            let problem = match &reference {
+                Reference::Matrix(_) | Reference::Item if !iterates => Some("for_each is worked out before the iterations run".to_string()),
+                Reference::Matrix(key) if !matrix.contains_key(key) => Some(format!("step '{}' has no matrix key '{}'", step_name, key)),
+                Reference::Item if for_each.is_none() => Some(format!("step '{}' has no for_each", step_name)),
+                _ => None,
+            };
+            if let Some(problem) = problem {
+                let at = value_span(content, &span, text, range);
+                return Err(WorkflowFileError::at(content, at, format!("{} can't be used here: {}", reference, problem)));
+            }
+        }
+    }
+
+    let spans = StepSpans {
+        name: step.name.span(),
+        command: step.command.span(),
+        rollback: step.rollback.as_ref().map(|rollback| rollback.span()),
+        when: step.when.as_ref().map(|when| when.span()),
+        for_each_lines,
+        depends_on: step.depends_on.iter().flatten().map(|dep| dep.span()).collect(),
+        step_conditions: condition_spans,
+    };
+    let step = WorkflowStep {
+        name: step_name.to_string(),
+        command: step.command.into_inner(),
+        description: step.description,
+        continue_on_failure: step.continue_on_failure,
+        timeout,
+        retry_count: step.retries,
+        conditions,
+        depends_on: step.depends_on.map(|deps| deps.into_iter().map(|dep| dep.into_inner().trim().to_string()).collect()),
+        rollback: step.rollback.map(Spanned::into_inner),
+        outputs,
+        when: step.when.map(Spanned::into_inner),
+        matrix,
+        for_each,
+    };
+    Ok((step, spans))
+}
+
+/// Check the `${...}` references in a command: they must parse, and any
+/// step they use must be in `visible` and declare the output used.
+/// `not_visible` words the error for a step that isn't.
+fn check_references(
+    content: &str,
+    span: &Range<usize>,
+    command: &str,
+    visible: &[&WorkflowStep],
+    not_visible: impl Fn(&str) -> String,
+) -> Result<(), WorkflowFileError> {
+    let references = workflow_template::references(command).map_err(|msg| WorkflowFileError::at(content, span.clone(), msg))?;
+    for (range, reference) in references {
+        let Some(step_name) = reference.step() else {
+            continue;
+        };
+        let at = value_span(content, span, command, range);
+        let step = visible.iter().find(|step| step.name == step_name)
+            .ok_or_else(|| WorkflowFileError::at(content, at.clone(), not_visible(step_name)))?;
+        if let Reference::StepOutput { output, .. } = &reference {
+            if !step.outputs.iter().any(|declared| &declared.name == output) {
+                return Err(WorkflowFileError::at(content, at, format!("step '{}' has no output '{}'", step_name, output)));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Parse a `when` expression and check that the steps it looks at are in
+/// `visible`
+fn check_when(
+    content: &str,
+    span: &Range<usize>,
+    source: &str,
+    visible: &[&WorkflowStep],
+    not_visible: impl Fn(&str) -> String,
+) -> Result<(), WorkflowFileError> {
+    let expr = Expr::parse(source).map_err(|e| {
+        let at = value_span(content, span, source, e.position..e.position);
+        WorkflowFileError::at(content, at, format!("invalid when: {}", e.message))
+    })?;
+    for step_name in expr.steps() {
+        if !visible.iter().any(|step| step.name == step_name) {
+            return Err(WorkflowFileError::at(content, span.clone(), not_visible(step_name)));
+        }
+    }
+    Ok(())
+}
+
+/// Span of `range` within a string value, when the value is written on one
+/// line without escapes; otherwise the span of the whole value
+fn value_span(content: &str, span: &Range<usize>, value: &str, range: Range<usize>) -> Range<usize> {
+    let start = span.start + 1;
+    match content.get(start..start + range.end) {
+        Some(written) if written == &value[..range.end] => start + range.start..start + range.end,
+        _ => span.clone(),
+    }
+}
+
+/// Span of the `depends_on` entry naming `dependency`, or of the step name
+/// when the dependency is the implicit previous step
+fn dependency_span(step: &WorkflowStep, spans: &StepSpans, dependency: &str) -> Range<usize> {
+    step.depends_on
+        .iter()
+        .flatten()
+        .position(|dep| dep == dependency)
+        .and_then(|i| spans.depends_on.get(i).cloned())
+        .unwrap_or_else(|| spans.name.clone())
+}
+
+/// Read and parse a workflow file, naming it after the file if it has no `name`
+pub fn load_workflow(path: &Path) -> Result<Workflow, WorkflowFileError> {
+    let content = fs::read_to_string(path).map_err(|e| WorkflowFileError {
+        path: Some(path.to_path_buf()),
+        line: 0,
+        column: 0,
+        message: e.to_string(),
+    })?;
+    let stem = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+    parse_workflow(&content, &stem).map_err(|e| e.in_file(path))
+}
+
+/// Render a workflow in the format `parse_workflow` reads
+pub fn to_toml(workflow: &Workflow) -> Result<String, Box<dyn std::error::Error>> {
+    // Spans only matter when parsing
+    let spanned = |value| Spanned::new(0..0, value);
+
+    let to_toml_step = |step: &WorkflowStep| TomlStep {
+        name: spanned(step.name.clone()),
+        command: spanned(step.command.clone()),
+        description: step.description.clone(),
+        continue_on_failure: step.continue_on_failure,
+        timeout: step.timeout.map(|timeout| Spanned::new(0..0, timeout.as_secs().max(1))),
+        retries: step.retry_count,
+        conditions: step
+            .conditions
+            .iter()
+            .map(|condition| TomlCondition {
+                condition_type: match condition.condition_type {
+                    ConditionType::FileExists => TomlConditionType::FileExists,
+                    ConditionType::FileNotExists => TomlConditionType::FileNotExists,
+                    ConditionType::DirectoryExists => TomlConditionType::DirectoryExists,
+                    ConditionType::DirectoryNotExists => TomlConditionType::DirectoryNotExists,
+                    ConditionType::EnvironmentVariable => TomlConditionType::EnvironmentVariable,
+                    ConditionType::PreviousStepSuccess => TomlConditionType::PreviousStepSuccess,
+                    ConditionType::PreviousStepFailure => TomlConditionType::PreviousStepFailure,
+                },
+                value: spanned(condition.value.clone()),
+            })
+            .collect(),
+        when: step.when.clone().map(spanned),
+        depends_on: step.depends_on.as_ref().map(|deps| deps.iter().map(|dep| spanned(dep.clone())).collect()),
+        rollback: step.rollback.clone().map(spanned),
+        outputs: step
+            .outputs
+            .iter()
+            .map(|output| {
+                let toml_output = match &output.source {
+                    OutputSource::Stdout => TomlOutput::Keyword("stdout".to_string()),
+                    OutputSource::Regex(pattern) => TomlOutput::Spec(TomlOutputSpec { regex: Some(pattern.clone()), json: None }),
+                    OutputSource::JsonPath(path) => TomlOutput::Spec(TomlOutputSpec { regex: None, json: Some(path.clone()) }),
+                };
+                (output.name.clone(), Spanned::new(0..0, toml_output))
+            })
+            .collect(),
+        matrix: step.matrix.iter().map(|(key, values)| (key.clone(), Spanned::new(0..0, values.clone()))).collect(),
+        for_each: step.for_each.as_ref().map(|for_each| Spanned::new(0..0, match for_each {
+            ForEach::List(items) => TomlForEach::List(items.clone()),
+            ForEach::Glob(pattern) => TomlForEach::Spec(TomlForEachSpec { glob: Some(pattern.clone()), lines: None }),
+            ForEach::Lines(lines) => TomlForEach::Spec(TomlForEachSpec { glob: None, lines: Some(lines.clone()) }),
+        })),
+    };
+
+    let toml_workflow = TomlWorkflow {
+        name: Some(spanned(workflow.name.clone())),
+        description: workflow.description.clone(),
+        on_failure: Some(match workflow.on_failure {
+            FailureAction::Stop => TomlFailureAction::Stop,
+            FailureAction::Continue => TomlFailureAction::Continue,
+            FailureAction::Rollback => TomlFailureAction::Rollback,
+        }),
+        variables: workflow
+            .variables
+            .iter()
+            .map(|(name, value)| (name.clone(), Spanned::new(0..0, TomlVariable::Default(value.clone()))))
+            .chain(workflow.required_variables.iter().map(|name| (name.clone(), Spanned::new(0..0, TomlVariable::Spec(TomlVariableSpec { required: true })))))
+            .collect(),
+        steps: Some(Spanned::new(0..0, workflow.steps.iter().map(to_toml_step).collect())),
+        finally: workflow.finally.iter().map(to_toml_step).collect(),
+    };
+
+    Ok(toml::to_string_pretty(&toml_workflow)?)
+}
+
+/// Write `workflow` to `<dir>/<name>.toml`, creating `dir` if needed
+pub fn save_workflow(dir: &Path, workflow: &Workflow) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    validate_name(&workflow.name)?;
+    fs::create_dir_all(dir)?;
+    let path = dir.join(format!("{}.toml", workflow.name));
+    fs::write(&path, to_toml(workflow)?)?;
+    Ok(path)
+}
+
+/// `.forge/workflows` in `start` or its nearest ancestor that has one
+pub fn project_workflow_dir(start: &Path) -> Option<PathBuf> {
+    start
+        .ancestors()
+        .map(|dir| dir.join(".forge").join("workflows"))
+        .find(|dir| dir.is_dir())
+}
+
+pub fn user_workflow_dir() -> Option<PathBuf> {
+    config::config_dir().map(|dir| dir.join("workflows"))
+}
+
+/// Load every workflow from the project (`.forge/workflows`) and user
+/// (`~/.config/forge/workflows`) directories. Project workflows take
+/// precedence over user workflows with the same name.
+pub fn discover(project_dir: Option<&Path>, user_dir: Option<&Path>) -> Discovery {
+    let mut discovery = Discovery::default();
+    let mut names = HashSet::new();
+
+    for (dir, is_project) in [(project_dir, true), (user_dir, false)] {
+        let dir = match dir {
+            Some(dir) => dir,
+            None => continue,
+        };
+        for path in workflow_files(dir) {
+            match load_workflow(&path) {
+                Ok(workflow) => {
+                    if names.insert(workflow.name.clone()) {
+                        let source = if is_project { WorkflowSource::Project(path) } else { WorkflowSource::User(path) };
+                        discovery.workflows.push(WorkflowEntry { workflow, source });
+                    }
+                }
+                Err(e) => discovery.errors.push(e),
+            }
+        }
+    }
+
+    discovery
+}
+
+/// `*.toml` files directly inside `dir`, sorted by name
+fn workflow_files(dir: &Path) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = match fs::read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext == "toml"))
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    files.sort();
+    files
+}
+
+fn is_false(value: &bool) -> bool {
+    !*value
+}
+
+fn is_zero(value: &u32) -> bool {
+    *value == 0
+}
+
+pub fn validate_name(name: &str) -> Result<(), String> {
+    if name.is_empty() {
+        return Err("workflow name is empty".to_string());
+    }
+    if !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.') {
+        return Err(format!("workflow name '{}' may only contain letters, digits, '-', '_' and '.'", name));
+    }
+    Ok(())
+}
+
+/// 1-based line and column of a byte offset
+fn line_column(content: &str, offset: usize) -> (usize, usize) {
+    let before = &content[..offset.min(content.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before.rfind('\n').map(|i| before.len() - i).unwrap_or(before.len() + 1);
+    (line, column)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    const RELEASE: &str = r#"
+description = "Build and test"
+on_failure = "continue"
+
+[variables]
+TARGET = "release"
+
+[[steps]]
+name = "Build"
+command = "cargo build --${TARGET}"
+timeout = 300
+retries = 2
+
+[[steps]]
+name = "Test"
+command = "cargo test"
+continue_on_failure = true
+conditions = [
+    { type = "file_exists", value = "Cargo.toml" },
+    { type = "previous_step_success", value = "Build" },
+]
+"#;
+
+    #[test]
+    fn test_parse_workflow() {
+        let workflow = parse_workflow(RELEASE, "release").unwrap();
+        assert_eq!(workflow.name, "release");
+        assert!(matches!(workflow.on_failure, FailureAction::Continue));
+        assert_eq!(workflow.variables.get("TARGET").map(String::as_str), Some("release"));
+        assert_eq!(workflow.steps.len(), 2);
+        assert_eq!(workflow.steps[0].timeout, Some(Duration::from_secs(300)));
+        assert_eq!(workflow.steps[0].retry_count, 2);
+        assert!(workflow.steps[1].continue_on_failure);
+        assert!(matches!(workflow.steps[1].conditions[1].condition_type, ConditionType::PreviousStepSuccess));
+    }
+
+    #[test]
+    fn test_errors_point_at_lines() {
+        let unknown_type = RELEASE.replace("\"file_exists\"", "\"file_present\"");
+        let error = parse_workflow(&unknown_type, "release").unwrap_err();
+        assert_eq!(error.line, 19);
+        assert!(error.message.contains("file_present"));
+
+        let duplicate = RELEASE.replace("name = \"Test\"", "name = \"Build\"");
+        let error = parse_workflow(&duplicate, "release").unwrap_err();
+        assert_eq!((error.line, error.column), (15, 8));
+        assert!(error.message.contains("duplicate step name"));
+
+        let forward = RELEASE.replace("value = \"Build\"", "value = \"Deploy\"");
+        assert_eq!(parse_workflow(&forward, "release").unwrap_err().line, 20);
+
+        let unknown_field = RELEASE.replace("retries = 2", "retry = 2");
+        assert_eq!(parse_workflow(&unknown_field, "release").unwrap_err().line, 12);
+
+        assert!(parse_workflow("description = \"nothing\"\n", "empty").is_err());
+    }
+
+    #[test]
+    fn test_dependency_errors() {
+        let cycle = "[[steps]]\nname = \"a\"\ncommand = \"true\"\ndepends_on = [\"b\"]\n\n[[steps]]\nname = \"b\"\ncommand = \"true\"\n";
+        let error = parse_workflow(cycle, "cycle").unwrap_err();
+        assert_eq!((error.line, error.column), (4, 15));
+        assert_eq!(error.message, "dependency cycle: a -> b -> a");
+
+        let unknown = RELEASE.replace("retries = 2", "depends_on = [\"Lint\"]");
+        let error = parse_workflow(&unknown, "release").unwrap_err();
+        assert_eq!(error.line, 12);
+        assert!(error.message.contains("unknown step 'Lint'"));
+
+        // Test no longer waits for Build, so it can't check how Build went
+        let unordered = RELEASE.replace("continue_on_failure = true", "depends_on = []");
+        let error = parse_workflow(&unordered, "release").unwrap_err();
+        assert!(error.message.contains("not a step that 'Test' depends on"));
+    }
+
+    #[test]
+    fn test_rollback_and_finally() {
+        let with_cleanup = format!("{}rollback = \"cargo clean\"\n\n[[finally]]\nname = \"Notify\"\ncommand = \"echo done\"\nconditions = [{{ type = \"previous_step_failure\", value = \"Test\" }}]\n", RELEASE);
+        let workflow = parse_workflow(&with_cleanup, "release").unwrap();
+        assert_eq!(workflow.steps[1].rollback.as_deref(), Some("cargo clean"));
+        assert_eq!(workflow.finally.len(), 1);
+        assert_eq!(workflow.finally[0].conditions[0].value, "Test");
+
+        let bad = with_cleanup.replace("name = \"Notify\"", "name = \"Notify\"\ndepends_on = []");
+        let error = parse_workflow(&bad, "release").unwrap_err();
+        assert_eq!(error.line, 25);
+        assert!(error.message.contains("can't have depends_on"));
+
+        let clash = with_cleanup.replace("name = \"Notify\"", "name = \"Build\"");
+        assert!(parse_workflow(&clash, "release").unwrap_err().message.contains("duplicate step name"));
+    }
+
+    const OUTPUTS: &str = r#"
+[variables]
+VERSION = { required = true }
+OWNER = "${env.USER}"
+
+[[steps]]
+name = "Build"
+command = "cargo build --release"
+
+[steps.outputs]
+version = { regex = 'Compiling forge v([\d.]+)' }
+binary = "stdout"
+
+[[steps]]
+name = "Publish"
+command = "gh release create v${steps.Build.outputs.version} --notes ${VERSION}"
+rollback = "gh release delete v${steps.Build.outputs.version}"
+
+[steps.outputs]
+url = { json = "release.url" }
+
+[[finally]]
+name = "Report"
+command = "echo ${steps.Publish.exit_code} ${steps.Publish.outputs.url}"
+"#;
+
+    #[test]
+    fn test_outputs_and_variables() {
+        let workflow = parse_workflow(OUTPUTS, "publish").unwrap();
+        assert_eq!(workflow.required_variables, vec!["VERSION"]);
+        assert_eq!(workflow.variables.get("OWNER").map(String::as_str), Some("${env.USER}"));
+        let outputs: Vec<(&str, &OutputSource)> = workflow.steps[0].outputs.iter().map(|o| (o.name.as_str(), &o.source)).collect();
+        assert_eq!(outputs, vec![("binary", &OutputSource::Stdout), ("version", &OutputSource::Regex(r"Compiling forge v([\d.]+)".to_string()))]);
+        assert_eq!(workflow.steps[1].outputs[0].source, OutputSource::JsonPath("release.url".to_string()));
+
+        let reloaded = parse_workflow(&to_toml(&workflow).unwrap(), "publish").unwrap();
+        assert_eq!(reloaded.required_variables, workflow.required_variables);
+        assert_eq!(reloaded.steps[0].outputs.len(), 2);
+
+        // References point at the placeholder itself
+        let undeclared = OUTPUTS.replace("outputs.version} --notes", "outputs.tag} --notes");
+        let error = parse_workflow(&undeclared, "publish").unwrap_err();
+        assert_eq!((error.line, error.column), (16, 31));
+        assert_eq!(error.message, "step 'Build' has no output 'tag'");
+
+        let independent = OUTPUTS.replace("rollback =", "depends_on = []\nrollback =");
+        let error = parse_workflow(&independent, "publish").unwrap_err();
+        assert_eq!(error.message, "'Build' is not a step that 'Publish' depends on");
+
+        let self_reference = OUTPUTS.replace("delete v${steps.Build.outputs.version}", "delete ${steps.Publish.outputs.url}");
+        assert!(parse_workflow(&self_reference, "publish").is_ok());
+
+        let bad_regex = OUTPUTS.replace("([\\d.]+)", "([\\d.]+");
+        assert!(parse_workflow(&bad_regex, "publish").unwrap_err().message.contains("missing ')'"));
+
+        let bad_output = OUTPUTS.replace("binary = \"stdout\"", "binary = \"stderr\"");
+        assert_eq!(parse_workflow(&bad_output, "publish").unwrap_err().line, 12);
+
+        let not_required = OUTPUTS.replace("required = true", "required = false");
+        assert!(parse_workflow(&not_required, "publish").unwrap_err().message.contains("needs a default"));
+
+        let step_default = OUTPUTS.replace("${env.USER}", "${steps.Build.exit_code}");
+        assert_eq!(parse_workflow(&step_default, "publish").unwrap_err().line, 4);
+    }
+
+    #[test]
+    fn test_when_expressions() {
+        let with_when = RELEASE.replace("continue_on_failure = true", "when = \"success('Build') && os() != 'plan9'\"");
+        let workflow = parse_workflow(&with_when, "release").unwrap();
+        assert_eq!(workflow.steps[1].when.as_deref(), Some("success('Build') && os() != 'plan9'"));
+        let reloaded = parse_workflow(&to_toml(&workflow).unwrap(), "release").unwrap();
+        assert_eq!(reloaded.steps[1].when, workflow.steps[1].when);
+
+        let typo = with_when.replace("os()", "oss()");
+        let error = parse_workflow(&typo, "release").unwrap_err();
+        assert_eq!((error.line, error.column), (17, 29));
+        assert_eq!(error.message, "invalid when: unknown function 'oss'");
+
+        let unordered = with_when.replace("success('Build')", "steps.Build.status == 'success'").replace("retries = 2", "depends_on = []");
+        let unordered = unordered.replace("name = \"Test\"", "name = \"Test\"\ndepends_on = []");
+        let error = parse_workflow(&unordered, "release").unwrap_err();
+        assert!(error.message.contains("'Build' is not a step that 'Test' depends on"), "{}", error.message);
+    }
+
+    const LOOPS: &str = r#"
+[[steps]]
+name = "Find"
+command = "git ls-files '*.rs'"
+outputs = { files = "stdout" }
+
+[[steps]]
+name = "Build"
+command = "cargo build --target ${matrix.target} ${matrix.features}"
+rollback = "cargo clean --target ${matrix.target}"
+matrix = { target = ["x86_64-unknown-linux-gnu", "aarch64-unknown-linux-gnu"], features = ["", "--all-features"] }
+
+[[steps]]
+name = "Format"
+command = "rustfmt --check ${item}"
+for_each = { lines = "${steps.Find.outputs.files}" }
+
+[[steps]]
+name = "Lint"
+command = "lint ${item}"
+for_each = { glob = "crates/*/Cargo.toml" }
+"#;
+
+    #[test]
+    fn test_matrix_and_for_each() {
+        let workflow = parse_workflow(LOOPS, "loops").unwrap();
+        assert_eq!(workflow.steps[1].matrix["features"], vec!["".to_string(), "--all-features".to_string()]);
+        assert_eq!(workflow.steps[2].for_each, Some(ForEach::Lines("${steps.Find.outputs.files}".to_string())));
+        assert_eq!(workflow.steps[3].for_each, Some(ForEach::Glob("crates/*/Cargo.toml".to_string())));
+        let listed = LOOPS.replace("{ glob = \"crates/*/Cargo.toml\" }", "[\"core\", \"cli\"]");
+        assert_eq!(parse_workflow(&listed, "loops").unwrap().steps[3].for_each, Some(ForEach::List(vec!["core".to_string(), "cli".to_string()])));
+
+        let reloaded = parse_workflow(&to_toml(&workflow).unwrap(), "loops").unwrap();
+        assert_eq!(reloaded.steps[1].matrix, workflow.steps[1].matrix);
+        assert_eq!(reloaded.steps[2].for_each, workflow.steps[2].for_each);
+        assert_eq!(reloaded.steps[3].for_each, workflow.steps[3].for_each);
+
+        let errors = [
+            (LOOPS.replace("${matrix.features}", "${matrix.feature}"), (9, 50), "${matrix.feature} can't be used here: step 'Build' has no matrix key 'feature'"),
+            (LOOPS.replace("git ls-files '*.rs'", "git ls-files ${item}"), (4, 25), "${item} can't be used here: step 'Find' has no for_each"),
+            (LOOPS.replace("outputs.files}", "outputs.files} ${item}"), (16, 12), "${item} can't be used here: for_each is worked out before the iterations run"),
+            (LOOPS.replace("features = [\"\", \"--all-features\"]", "features = []"), (11, 91), "matrix 'features' has no values"),
+            (LOOPS.replace("{ glob = ", "{ regex = "), (21, 12), "for_each must be a list, { glob = \"...\" } or { lines = \"...\" }"),
+            (LOOPS.replace("{ glob = \"crates/*/Cargo.toml\" }", "[]"), (21, 12), "step 'Lint' has an empty for_each"),
+            (LOOPS.replace("rustfmt --check ${item}\"", "rustfmt --check ${item}\"\noutputs = { diff = \"stdout\" }"), (16, 20), "step 'Format' runs once per matrix or for_each entry, so it can't have outputs"),
+        ];
+        for (content, position, message) in errors {
+            let error = parse_workflow(&content, "loops").unwrap_err();
+            assert!(error.message.starts_with(message), "{}", error.message);
+            assert_eq!((error.line, error.column), position, "{}", error.message);
+        }
+    }
+
+    #[test]
+    fn test_to_toml_round_trips() {
+        let workflow = parse_workflow(RELEASE, "release").unwrap();
+        let dir = tempdir().unwrap();
+        let path = save_workflow(dir.path(), &workflow).unwrap();
+        assert_eq!(path, dir.path().join("release.toml"));
+
+        let reloaded = load_workflow(&path).unwrap();
+        assert_eq!(to_toml(&reloaded).unwrap(), to_toml(&workflow).unwrap());
+        assert_eq!(reloaded.steps[1].conditions.len(), 2);
+        assert_eq!(reloaded.steps[0].retry_count, 2);
+        assert!(reloaded.steps[1].continue_on_failure);
+        assert_eq!(reloaded.variables, workflow.variables);
+
+        let mut workflow = workflow;
+        workflow.steps[0].rollback = Some("git tag -d v1".to_string());
+        workflow.finally.push(workflow.steps[1].clone());
+        workflow.finally[0].name = "Cleanup".to_string();
+        let reloaded = parse_workflow(&to_toml(&workflow).unwrap(), "release").unwrap();
+        assert_eq!(reloaded.steps[0].rollback.as_deref(), Some("git tag -d v1"));
+        assert_eq!(reloaded.finally[0].name, "Cleanup");
+    }
+
+    #[test]
+    fn test_discover_prefers_project_workflows() {
+        let project = tempdir().unwrap();
+        let user = tempdir().unwrap();
+        let project_dir = project.path().join(".forge").join("workflows");
+        fs::create_dir_all(&project_dir).unwrap();
+
+        let step = "[[steps]]\nname = \"Run\"\ncommand = \"echo {}\"\n";
+        fs::write(project_dir.join("build.toml"), step.replace("{}", "project")).unwrap();
+        fs::write(project_dir.join("broken.toml"), "[[steps]\n").unwrap();
+        fs::write(user.path().join("build.toml"), step.replace("{}", "user")).unwrap();
+        fs::write(user.path().join("lint.toml"), step.replace("{}", "lint")).unwrap();
+        fs::write(user.path().join("notes.txt"), "not a workflow").unwrap();
+
+        let nested = project.path().join("src").join("deep");
+        fs::create_dir_all(&nested).unwrap();
+        assert_eq!(project_workflow_dir(&nested), Some(project_dir.clone()));
+
+        let discovery = discover(Some(&project_dir), Some(user.path()));
+        let names: Vec<(&str, &str)> = discovery
+            .workflows
+            .iter()
+            .map(|entry| (entry.workflow.name.as_str(), entry.source.label()))
+            .collect();
+        assert_eq!(names, vec![("build", "project"), ("lint", "user")]);
+        assert_eq!(discovery.workflows[0].workflow.steps[0].command, "echo project");
+
+        assert_eq!(discovery.errors.len(), 1);
+        assert_eq!(discovery.errors[0].line, 1);
+        assert!(discovery.errors[0].to_string().starts_with(&project_dir.join("broken.toml").display().to_string()));
+    }
+}
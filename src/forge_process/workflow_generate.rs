@@ -0,0 +1,280 @@
+use std::fs;
+use std::path::Path;
+
+use crate::http::client::OllamaClient;
+use super::safety::{CommandRisk, RiskLevel, SafetyChecker};
+use super::workflow::Workflow;
+use super::workflow_file::{self, WorkflowFileError};
+
+/// Tries the model gets to produce an answer that validates
+pub const MAX_ATTEMPTS: usize = 3;
+
+/// Top-level entries of the project shown to the model
+const MAX_PROJECT_ENTRIES: usize = 40;
+
+/// The workflow file format, as the model is told it
+const FORMAT_GUIDE: &str = r#"A workflow is a TOML file:
+
+name = "kebab-case-name"
+description = "One line"
+on_failure = "stop"            # or "continue", or "rollback" to run the rollbacks of finished steps
+
+[variables]
+VERSION = { required = true }  # Passed with --set VERSION=1.2.3
+BRANCH = "main"                # Default value
+
+[[steps]]
+name = "Build"
+command = "cargo build --release"
+timeout = 600                  # Seconds, optional
+retries = 1                    # Optional
+
+[[steps]]
+name = "Tag"
+command = "git tag v${VERSION}"
+depends_on = ["Build"]         # Optional; a step runs after the one before it by default, [] runs it at once
+rollback = "git tag -d v${VERSION}"
+when = "success('Build') && env('CI') != null"   # Optional condition
+
+[[finally]]                     # Optional cleanup, always runs
+name = "Report"
+command = "git status --short"
+
+Rules:
+- Commands run in forge's own shell: quotes, $VARS, pipes, `&&`, `||`, `;`, `>`, `>>`, `<`, `2>&1` and ( subshells ) work; globs and $(command substitution) don't. Prefer separate steps to long chains.
+- Step names are unique. `depends_on` may only name earlier steps.
+- Use ${NAME} for variables. A step can read an earlier step's output with ${steps.<step>.outputs.<name>} if that step declares `outputs = { <name> = "stdout" }`.
+- A step can repeat with `matrix = { target = ["a", "b"] }` and ${matrix.target}, or with `for_each = ["a", "b"]` or `for_each = { glob = "src/*.rs" }` and ${item}.
+- Prefer the project's own tools and avoid destructive commands."#;
+
+/// A workflow drafted by the model, validated and ready for review
+#[derive(Debug)]
+pub struct Draft {
+    pub workflow: Workflow,
+    /// The workflow as it will be saved
+    pub content: String,
+    pub risks: Vec<StepRisk>,
+    /// Model calls it took to get a valid workflow
+    pub attempts: usize,
+}
+
+/// How risky one of a workflow's commands is
+#[derive(Debug)]
+pub struct StepRisk {
+    pub step: String,
+    /// `"command"` or `"rollback"`
+    pub kind: &'static str,
+    pub command: String,
+    pub risk: CommandRisk,
+}
+
+impl Draft {
+    /// The riskiest command's level
+    pub fn highest_risk(&self) -> RiskLevel {
+        self.risks.iter().map(|risk| risk.risk.level.clone()).max_by_key(risk_rank).unwrap_or(RiskLevel::Safe)
+    }
+}
+
+fn risk_rank(level: &RiskLevel) -> u8 {
+    match level {
+        RiskLevel::Safe => 0,
+        RiskLevel::Low => 1,
+        RiskLevel::Medium => 2,
+        RiskLevel::High => 3,
+        RiskLevel::Critical => 4,
+    }
+}
+
+/// Ask the model for a workflow that achieves `goal`. An answer that
+/// doesn't validate is sent back with its error, up to `MAX_ATTEMPTS` times.
+/// `name` overrides the name the model picks.
+pub fn draft_workflow(client: &OllamaClient, model: &str, goal: &str, name: Option<&str>, project: &Path) -> Result<Draft, Box<dyn std::error::Error>> {
+    let prompt = generate_prompt(goal, name, &project_summary(project));
+    let (mut workflow, attempts) = generate_until_valid(
+        client, model, &prompt, "produce a valid workflow", "Reply with the corrected file only.",
+        |response| workflow_file::parse_workflow(&extract_toml(response), name.unwrap_or("generated")).map_err(|e| describe(&e)),
+    )?;
+    if let Some(name) = name {
+        workflow.name = name.to_string();
+    }
+    Ok(Draft {
+        content: workflow_file::to_toml(&workflow)?,
+        risks: assess_workflow(&workflow),
+        workflow,
+        attempts,
+    })
+}
+
+/// Ask the model with `prompt` until `parse` accepts its answer. A rejected
+/// answer goes back to the model with the error and `reply_hint`, up to
+/// `MAX_ATTEMPTS` times. Returns the parsed answer and the attempts it
+/// took; `task` finishes "The model didn't …" when all of them fail.
+pub fn generate_until_valid<T>(
+    client: &OllamaClient,
+    model: &str,
+    prompt: &str,
+    task: &str,
+    reply_hint: &str,
+    parse: impl Fn(&str) -> Result<T, String>,
+) -> Result<(T, usize), Box<dyn std::error::Error>> {
+    let mut feedback = String::new();
+    let mut last_error = String::new();
+
+    for attempt in 1..=MAX_ATTEMPTS {
+        let response = client.generate(model, &format!("{}{}", prompt, feedback), false)?;
+        match parse(&response) {
+            Ok(answer) => return Ok((answer, attempt)),
+            Err(error) => {
+                feedback = format!(
+                    "\n\nYour previous answer was:\n\n{}\n\nIt was rejected: {}\n{}",
+                    response.trim(), error, reply_hint
+                );
+                last_error = error;
+            }
+        }
+    }
+
+    Err(format!("The model didn't {} in {} attempts; last error: {}", task, MAX_ATTEMPTS, last_error).into())
+}
+
+fn describe(error: &WorkflowFileError) -> String {
+    if error.line > 0 {
+        format!("line {}, column {}: {}", error.line, error.column, error.message)
+    } else {
+        error.message.clone()
+    }
+}
+
+fn generate_prompt(goal: &str, name: Option<&str>, project: &str) -> String {
+    let name = match name {
+        Some(name) => format!("Name the workflow \"{}\".\n", name),
+        None => String::new(),
+    };
+    format!(
+        "You write workflow files for the forge CLI.\n\n{}\n\n\
+        The project directory contains:\n{}\n\n\
+        Write a workflow that does this: {}\n{}\
+        Reply with only the TOML file, no explanation.",
+        FORMAT_GUIDE, project, goal, name
+    )
+}
+
+/// The project's top-level files, so the model can pick its build tools
+pub fn project_summary(project: &Path) -> String {
+    let mut entries: Vec<String> = fs::read_dir(project)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| {
+                    let name = entry.file_name().to_string_lossy().into_owned();
+                    if entry.path().is_dir() { format!("{}/", name) } else { name }
+                })
+                .filter(|name| !name.starts_with('.') || name == ".github/" || name == ".forge/")
+                .collect()
+        })
+        .unwrap_or_default();
+    entries.sort();
+    entries.truncate(MAX_PROJECT_ENTRIES);
+    if entries.is_empty() {
+        return "(nothing)".to_string();
+    }
+    entries.iter().map(|entry| format!("- {}", entry)).collect::<Vec<_>>().join("\n")
+}
+
+/// The TOML in a model's answer, without a Markdown code fence around it
+fn extract_toml(response: &str) -> String {
+    let trimmed = response.trim();
+    let Some(start) = trimmed.find("```") else {
+        return trimmed.to_string();
+    };
+    let after_fence = &trimmed[start + 3..];
+    let body = after_fence.split_once('\n').map_or("", |(_, body)| body);
+    let body = body.find("```").map_or(body, |end| &body[..end]);
+    body.trim().to_string()
+}
+
+/// Assess every command and rollback the workflow could run
+pub fn assess_workflow(workflow: &Workflow) -> Vec<StepRisk> {
+    let checker = SafetyChecker::configured(Path::new("."));
+    let mut risks = Vec::new();
+    for step in workflow.steps.iter().chain(&workflow.finally) {
+        let commands = [("command", Some(&step.command)), ("rollback", step.rollback.as_ref())];
+        for (kind, command) in commands {
+            if let Some(command) = command {
+                risks.push(StepRisk {
+                    step: step.name.clone(),
+                    kind,
+                    command: command.clone(),
+                    risk: checker.assess_command(command),
+                });
+            }
+        }
+    }
+    risks
+}
+
+/// A prompt asking the model to explain an existing workflow
+pub fn explain_prompt(workflow: &Workflow) -> Result<String, Box<dyn std::error::Error>> {
+    let risky: Vec<String> = assess_workflow(workflow)
+        .into_iter()
+        .filter(|risk| risk_rank(&risk.risk.level) >= risk_rank(&RiskLevel::Medium))
+        .map(|risk| format!("- {} ({}): {:?}, {}", risk.step, risk.kind, risk.risk.level, risk.risk.reason))
+        .collect();
+    let risky = if risky.is_empty() { "none".to_string() } else { risky.join("\n") };
+
+    Ok(format!(
+        "You explain workflow files for the forge CLI.\n\n{}\n\n\
+        Explain this workflow to a developer who is about to run it:\n\n{}\n\
+        Commands forge flagged as risky:\n{}\n\n\
+        Say what it is for, what each step does and in what order steps run (which can run in parallel), \
+        what variables it needs, and what happens when a step fails. Point out anything risky. Be concise.",
+        FORMAT_GUIDE, workflow_file::to_toml(workflow)?, risky
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::json::escape_string;
+    use crate::http::stub::StubServer;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    const VALID: &str = "Here you go:\n```toml\nname = \"release\"\n\n[[steps]]\nname = \"Test\"\ncommand = \"cargo test\"\n\n[[steps]]\nname = \"Clean\"\ncommand = \"rm -rf target\"\n```\nDone.";
+
+    #[test]
+    fn test_draft_retries_with_the_validation_error() {
+        let calls = AtomicUsize::new(0);
+        let server = StubServer::start(move |_| {
+            let answer = if calls.fetch_add(1, Ordering::SeqCst) == 0 {
+                "[[steps]]\nname = \"Test\"\ncommand = \"cargo test\"\nretry = 2\n"
+            } else {
+                VALID
+            };
+            format!("{{\"response\":{}}}", escape_string(answer))
+        });
+        let project = tempfile::tempdir().unwrap();
+        fs::write(project.path().join("Cargo.toml"), "").unwrap();
+        fs::create_dir(project.path().join("src")).unwrap();
+
+        let draft = draft_workflow(&server.client(), "m", "test and clean up", Some("ship"), project.path()).unwrap();
+        assert_eq!(draft.attempts, 2);
+        assert_eq!(draft.workflow.name, "ship");
+        assert!(draft.content.contains("name = \"ship\""));
+        assert_eq!(draft.risks.len(), 2);
+        assert_eq!(draft.highest_risk(), draft.risks[1].risk.level);
+        assert!(risk_rank(&draft.highest_risk()) > risk_rank(&RiskLevel::Safe));
+
+        let requests = server.requests();
+        assert!(requests[0].body.contains("- Cargo.toml\\n- src/"));
+        assert!(requests[0].body.contains("Name the workflow \\\"ship\\\""));
+        assert!(requests[1].body.contains("It was rejected: line 4, column 1: unknown field `retry`"));
+    }
+
+    #[test]
+    fn test_extract_toml() {
+        assert_eq!(extract_toml("  name = \"x\"\n"), "name = \"x\"");
+        assert!(extract_toml(VALID).starts_with("name = \"release\""));
+        assert!(extract_toml(VALID).ends_with("command = \"rm -rf target\""));
+        assert_eq!(extract_toml("```\na = 1\n```"), "a = 1");
+    }
+}
@@ -0,0 +1,183 @@
+use std::collections::HashSet;
+use std::fmt;
+
+use super::workflow::WorkflowStep;
+
+/// Why the steps of a workflow don't form a DAG
+#[derive(Debug, Clone, PartialEq)]
+pub enum GraphError {
+    UnknownDependency { step: usize, step_name: String, dependency: String },
+    /// Step indices around the cycle, starting and ending with the same step
+    Cycle { steps: Vec<usize>, names: Vec<String> },
+}
+
+impl fmt::Display for GraphError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GraphError::UnknownDependency { step_name, dependency, .. } => {
+                write!(f, "step '{}' depends on unknown step '{}'", step_name, dependency)
+            }
+            GraphError::Cycle { names, .. } => write!(f, "dependency cycle: {}", names.join(" -> ")),
+        }
+    }
+}
+
+impl std::error::Error for GraphError {}
+
+/// Dependency graph between the steps of a workflow, by step index.
+///
+/// A step without `depends_on` runs after the step before it, so workflows
+/// that don't mention dependencies keep running in order.
+#[derive(Debug, Clone)]
+pub struct StepGraph {
+    dependencies: Vec<Vec<usize>>,
+}
+
+impl StepGraph {
+    pub fn new(steps: &[WorkflowStep]) -> Result<Self, GraphError> {
+        let mut dependencies = Vec::with_capacity(steps.len());
+        for (i, step) in steps.iter().enumerate() {
+            let mut deps = Vec::new();
+            match &step.depends_on {
+                Some(names) => {
+                    for name in names {
+                        let dep = steps.iter().position(|s| &s.name == name).ok_or_else(|| GraphError::UnknownDependency {
+                            step: i,
+                            step_name: step.name.clone(),
+                            dependency: name.clone(),
+                        })?;
+                        if !deps.contains(&dep) {
+                            deps.push(dep);
+                        }
+                    }
+                }
+                None if i > 0 => deps.push(i - 1),
+                None => {}
+            }
+            dependencies.push(deps);
+        }
+
+        let graph = StepGraph { dependencies };
+        if let Some(cycle) = graph.find_cycle() {
+            let names = cycle.iter().map(|&i| steps[i].name.clone()).collect();
+            return Err(GraphError::Cycle { steps: cycle, names });
+        }
+        Ok(graph)
+    }
+
+    pub fn len(&self) -> usize {
+        self.dependencies.len()
+    }
+
+    pub fn dependencies(&self, step: usize) -> &[usize] {
+        &self.dependencies[step]
+    }
+
+    /// Every step that must finish before `step` can start
+    pub fn ancestors(&self, step: usize) -> HashSet<usize> {
+        let mut seen = HashSet::new();
+        let mut stack = self.dependencies[step].clone();
+        while let Some(next) = stack.pop() {
+            if seen.insert(next) {
+                stack.extend(&self.dependencies[next]);
+            }
+        }
+        seen
+    }
+
+    /// Depth-first search for a back edge, returning the cycle it closes
+    fn find_cycle(&self) -> Option<Vec<usize>> {
+        #[derive(Clone, Copy, PartialEq)]
+        enum Mark {
+            New,
+            OnPath,
+            Done,
+        }
+
+        let mut marks = vec![Mark::New; self.len()];
+        for start in 0..self.len() {
+            if marks[start] != Mark::New {
+                continue;
+            }
+            // (step, index of the next dependency to visit)
+            let mut path = vec![(start, 0)];
+            marks[start] = Mark::OnPath;
+
+            while let Some(&mut (step, ref mut next)) = path.last_mut() {
+                match self.dependencies[step].get(*next) {
+                    Some(&dep) => {
+                        *next += 1;
+                        match marks[dep] {
+                            Mark::New => {
+                                marks[dep] = Mark::OnPath;
+                                path.push((dep, 0));
+                            }
+                            Mark::OnPath => {
+                                let from = path.iter().position(|&(s, _)| s == dep).unwrap_or(0);
+                                let mut cycle: Vec<usize> = path[from..].iter().map(|&(s, _)| s).collect();
+                                cycle.push(dep);
+                                return Some(cycle);
+                            }
+                            Mark::Done => {}
+                        }
+                    }
+                    None => {
+                        marks[step] = Mark::Done;
+                        path.pop();
+                    }
+                }
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn step(name: &str, depends_on: Option<&[&str]>) -> WorkflowStep {
+        WorkflowStep {
+            name: name.to_string(),
+            command: format!("echo {}", name),
+            depends_on: depends_on.map(|deps| deps.iter().map(|d| d.to_string()).collect()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_graph_dependencies() {
+        let steps = vec![
+            step("fetch", None),
+            step("build", None),
+            step("lint", Some(&["fetch"])),
+            step("test", Some(&["build", "lint"])),
+            step("docs", Some(&[])),
+        ];
+        let graph = StepGraph::new(&steps).unwrap();
+
+        assert_eq!(graph.dependencies(1), &[0]);
+        assert_eq!(graph.dependencies(3), &[1, 2]);
+        assert!(graph.dependencies(4).is_empty());
+        assert_eq!(graph.ancestors(3), HashSet::from([0, 1, 2]));
+        assert!(graph.ancestors(4).is_empty());
+    }
+
+    #[test]
+    fn test_graph_errors() {
+        let steps = vec![step("a", Some(&["c"])), step("b", None), step("c", None)];
+        match StepGraph::new(&steps).unwrap_err() {
+            GraphError::Cycle { steps, names } => {
+                assert_eq!(steps.first(), steps.last());
+                assert_eq!(names.len(), 4);
+            }
+            other => panic!("expected a cycle, got {}", other),
+        }
+
+        let steps = vec![step("a", Some(&["a"]))];
+        assert_eq!(StepGraph::new(&steps).unwrap_err().to_string(), "dependency cycle: a -> a");
+
+        let steps = vec![step("a", Some(&["missing"]))];
+        assert!(matches!(StepGraph::new(&steps).unwrap_err(), GraphError::UnknownDependency { step: 0, .. }));
+    }
+}
@@ -0,0 +1,543 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::http::json::{parse_json, JsonValue};
+use super::records::{format_utc, parse_utc, Fields};
+use super::workflow::{SkipReason, StepResult, Workflow, WorkflowExecution};
+
+/// How much of a step's stdout and stderr a run record keeps
+const TAIL_LINES: usize = 40;
+const TAIL_BYTES: usize = 8 * 1024;
+
+/// Which part of the workflow a step ran in
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Phase {
+    Step,
+    Rollback,
+    Finally,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StepStatus {
+    Success,
+    Failure,
+    Skipped,
+    Cancelled,
+}
+
+impl Phase {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Phase::Step => "step",
+            Phase::Rollback => "rollback",
+            Phase::Finally => "finally",
+        }
+    }
+
+    fn parse(s: &str) -> Option<Self> {
+        [Phase::Step, Phase::Rollback, Phase::Finally].into_iter().find(|phase| phase.as_str() == s)
+    }
+}
+
+impl StepStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            StepStatus::Success => "success",
+            StepStatus::Failure => "failure",
+            StepStatus::Skipped => "skipped",
+            StepStatus::Cancelled => "cancelled",
+        }
+    }
+
+    pub fn icon(self) -> &'static str {
+        match self {
+            StepStatus::Success => "✅",
+            StepStatus::Failure => "❌",
+            StepStatus::Skipped => "⏭",
+            StepStatus::Cancelled => "🚫",
+        }
+    }
+
+    fn parse(s: &str) -> Option<Self> {
+        [StepStatus::Success, StepStatus::Failure, StepStatus::Skipped, StepStatus::Cancelled]
+            .into_iter()
+            .find(|status| status.as_str() == s)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StepRecord {
+    pub name: String,
+    pub phase: Phase,
+    pub status: StepStatus,
+    pub duration: Duration,
+    pub retries: u32,
+    pub exit_code: Option<i32>,
+    /// The error of a failed step, or why a step didn't run
+    pub message: Option<String>,
+    pub stdout_tail: String,
+    pub stderr_tail: String,
+    pub outputs: BTreeMap<String, String>,
+}
+
+/// A finished workflow run, as saved under `.forge/runs`
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunRecord {
+    pub id: String,
+    pub workflow: String,
+    /// Seconds since the Unix epoch
+    pub started_at: u64,
+    pub duration: Duration,
+    pub success: bool,
+    pub user: String,
+    pub host: String,
+    pub directory: String,
+    pub steps: Vec<StepRecord>,
+}
+
+impl RunRecord {
+    /// Record a run, listing steps in workflow order followed by rollbacks
+    /// and `finally` steps. Steps that ran as iterations are listed as those.
+    pub fn from_execution(workflow: &Workflow, execution: &WorkflowExecution) -> Self {
+        let started = execution.started_at.duration_since(UNIX_EPOCH).unwrap_or_default();
+        let duration = execution.end_time.map(|end| end - execution.start_time).unwrap_or_default();
+
+        let mut steps = Vec::new();
+        for (phase, workflow_steps) in [(Phase::Step, &workflow.steps), (Phase::Finally, &workflow.finally)] {
+            if phase == Phase::Finally {
+                steps.extend(execution.rollback_results.iter().map(|result| step_record(result, Phase::Rollback)));
+            }
+            let results = if phase == Phase::Step { &execution.step_results } else { &execution.finally_results };
+            for step in workflow_steps {
+                if let Some(result) = results.iter().find(|result| result.step_name == step.name) {
+                    // A `matrix` or `for_each` step is recorded as its iterations
+                    if result.iterations.is_empty() {
+                        steps.push(step_record(result, phase));
+                    }
+                    steps.extend(result.iterations.iter().map(|iteration| step_record(iteration, phase)));
+                } else if let Some(skipped) = execution.skipped_steps.iter().find(|skipped| skipped.step_name == step.name) {
+                    let status = match skipped.reason {
+                        SkipReason::ConditionsNotMet | SkipReason::NoItems => StepStatus::Skipped,
+                        SkipReason::Cancelled { .. } => StepStatus::Cancelled,
+                    };
+                    steps.push(StepRecord {
+                        name: step.name.clone(),
+                        phase,
+                        status,
+                        duration: Duration::ZERO,
+                        retries: 0,
+                        exit_code: None,
+                        message: Some(skipped.reason.to_string()),
+                        stdout_tail: String::new(),
+                        stderr_tail: String::new(),
+                        outputs: BTreeMap::new(),
+                    });
+                }
+            }
+        }
+
+        RunRecord {
+            id: run_id(execution.started_at),
+            workflow: workflow.name.clone(),
+            started_at: started.as_secs(),
+            duration,
+            success: execution.overall_success,
+            user: std::env::var("USER").or_else(|_| std::env::var("USERNAME")).unwrap_or_else(|_| "unknown".to_string()),
+            host: host_name(),
+            directory: std::env::current_dir().map(|dir| dir.display().to_string()).unwrap_or_default(),
+            steps,
+        }
+    }
+
+    /// When the run started, like `2026-10-18 15:30:12 UTC`
+    pub fn started(&self) -> String {
+        format_utc(self.started_at).replace('T', " ").replace('Z', " UTC")
+    }
+
+    pub fn count(&self, status: StepStatus) -> usize {
+        self.steps.iter().filter(|step| step.status == status).count()
+    }
+
+    pub fn to_json(&self) -> String {
+        let string = |s: &str| JsonValue::String(s.to_string());
+        let number = |n: f64| JsonValue::Number(n);
+        let millis = |d: Duration| number(d.as_millis() as f64);
+
+        let steps = self.steps.iter().map(|step| {
+            let mut fields = HashMap::new();
+            fields.insert("name".to_string(), string(&step.name));
+            fields.insert("phase".to_string(), string(step.phase.as_str()));
+            fields.insert("status".to_string(), string(step.status.as_str()));
+            fields.insert("duration_ms".to_string(), millis(step.duration));
+            fields.insert("retries".to_string(), number(step.retries as f64));
+            fields.insert("exit_code".to_string(), step.exit_code.map_or(JsonValue::Null, |code| number(code as f64)));
+            fields.insert("message".to_string(), step.message.as_deref().map_or(JsonValue::Null, string));
+            fields.insert("stdout_tail".to_string(), string(&step.stdout_tail));
+            fields.insert("stderr_tail".to_string(), string(&step.stderr_tail));
+            fields.insert("outputs".to_string(), JsonValue::Object(
+                step.outputs.iter().map(|(name, value)| (name.clone(), string(value))).collect(),
+            ));
+            JsonValue::Object(fields)
+        }).collect();
+
+        let mut fields = HashMap::new();
+        fields.insert("id".to_string(), string(&self.id));
+        fields.insert("workflow".to_string(), string(&self.workflow));
+        fields.insert("started_at".to_string(), string(&format_utc(self.started_at)));
+        fields.insert("duration_ms".to_string(), millis(self.duration));
+        fields.insert("success".to_string(), JsonValue::Boolean(self.success));
+        fields.insert("user".to_string(), string(&self.user));
+        fields.insert("host".to_string(), string(&self.host));
+        fields.insert("directory".to_string(), string(&self.directory));
+        fields.insert("steps".to_string(), JsonValue::Array(steps));
+        JsonValue::Object(fields).to_string()
+    }
+
+    pub fn from_json(text: &str) -> Result<Self, String> {
+        let document = parse_json(text).map_err(|e| format!("not valid JSON: {}", e.0))?;
+        let run = Fields::of(&document, "run")?;
+
+        let mut steps = Vec::new();
+        for (i, step) in run.array("steps")?.iter().enumerate() {
+            let step = Fields::of(step, &format!("steps[{}]", i))?;
+            let outputs = step.object("outputs")?.iter()
+                .map(|(name, value)| value.as_string().map(|value| (name.clone(), value.clone())))
+                .collect::<Option<BTreeMap<_, _>>>()
+                .ok_or_else(|| format!("steps[{}].outputs must hold strings", i))?;
+            steps.push(StepRecord {
+                name: step.string("name")?,
+                phase: Phase::parse(&step.string("phase")?).ok_or_else(|| format!("steps[{}] has an unknown phase", i))?,
+                status: StepStatus::parse(&step.string("status")?).ok_or_else(|| format!("steps[{}] has an unknown status", i))?,
+                duration: Duration::from_millis(step.number("duration_ms")? as u64),
+                retries: step.number("retries")? as u32,
+                exit_code: step.optional_number("exit_code")?.map(|code| code as i32),
+                message: step.optional_string("message")?,
+                stdout_tail: step.string("stdout_tail")?,
+                stderr_tail: step.string("stderr_tail")?,
+                outputs,
+            });
+        }
+
+        let started_at = run.string("started_at")?;
+        Ok(RunRecord {
+            id: run.string("id")?,
+            workflow: run.string("workflow")?,
+            started_at: parse_utc(&started_at).ok_or_else(|| format!("bad started_at '{}'", started_at))?,
+            duration: Duration::from_millis(run.number("duration_ms")? as u64),
+            success: run.boolean("success")?,
+            user: run.string("user")?,
+            host: run.string("host")?,
+            directory: run.string("directory")?,
+            steps,
+        })
+    }
+
+    /// Write the record to `<dir>/<id>.json`
+    pub fn save(&self, dir: &Path) -> Result<PathBuf, Box<dyn std::error::Error>> {
+        fs::create_dir_all(dir)?;
+        let path = dir.join(format!("{}.json", self.id));
+        fs::write(&path, self.to_json())?;
+        Ok(path)
+    }
+}
+
+fn step_record(result: &StepResult, phase: Phase) -> StepRecord {
+    StepRecord {
+        name: result.step_name.clone(),
+        phase,
+        status: if result.success { StepStatus::Success } else { StepStatus::Failure },
+        duration: result.duration,
+        retries: result.retry_attempts,
+        exit_code: result.exit_code,
+        message: result.error.clone(),
+        stdout_tail: tail(&result.output),
+        stderr_tail: tail(&result.stderr),
+        outputs: result.outputs.iter().map(|(name, value)| (name.clone(), value.clone())).collect(),
+    }
+}
+
+/// The last `TAIL_LINES` lines of `text`, at most `TAIL_BYTES` long
+fn tail(text: &str) -> String {
+    let text = text.trim_end();
+    let mut start = text.len().saturating_sub(TAIL_BYTES);
+    while !text.is_char_boundary(start) {
+        start += 1;
+    }
+    let lines: Vec<&str> = text[start..].lines().collect();
+    lines[lines.len().saturating_sub(TAIL_LINES)..].join("\n")
+}
+
+/// A sortable id like `20261018-153012-3fa2`
+fn run_id(started_at: SystemTime) -> String {
+    let since_epoch = started_at.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let stamp: String = format_utc(since_epoch.as_secs()).chars().filter(|c| c.is_ascii_digit() || *c == 'T').collect();
+    let salt = (since_epoch.subsec_nanos() ^ std::process::id().rotate_left(16)) & 0xffff;
+    format!("{}-{:04x}", stamp.replace('T', "-"), salt)
+}
+
+fn host_name() -> String {
+    std::env::var("HOSTNAME")
+        .or_else(|_| std::env::var("COMPUTERNAME"))
+        .ok()
+        .or_else(|| fs::read_to_string("/etc/hostname").ok())
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+/// `.forge/runs` of the enclosing project, or of `start` if there is none
+pub fn runs_dir(start: &Path) -> PathBuf {
+    start
+        .ancestors()
+        .map(|dir| dir.join(".forge"))
+        .find(|dir| dir.is_dir())
+        .unwrap_or_else(|| start.join(".forge"))
+        .join("runs")
+}
+
+/// Every saved run, newest first, and the files that couldn't be read
+pub fn list_runs(dir: &Path) -> (Vec<RunRecord>, Vec<(PathBuf, String)>) {
+    let mut runs = Vec::new();
+    let mut errors = Vec::new();
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return (runs, errors),
+    };
+
+    for path in entries.filter_map(|entry| entry.ok()).map(|entry| entry.path()) {
+        if path.extension().is_none_or(|ext| ext != "json") {
+            continue;
+        }
+        match fs::read_to_string(&path).map_err(|e| e.to_string()).and_then(|text| RunRecord::from_json(&text)) {
+            Ok(run) => runs.push(run),
+            Err(e) => errors.push((path, e)),
+        }
+    }
+    runs.sort_by(|a, b| b.started_at.cmp(&a.started_at).then_with(|| b.id.cmp(&a.id)));
+    (runs, errors)
+}
+
+/// A run by id, unique id prefix, or `latest`
+pub fn find_run(dir: &Path, id: &str) -> Result<RunRecord, Box<dyn std::error::Error>> {
+    let (runs, _) = list_runs(dir);
+    if id == "latest" {
+        return runs.into_iter().next().ok_or_else(|| format!("No workflow runs recorded in {}", dir.display()).into());
+    }
+    if let Some(run) = runs.iter().find(|run| run.id == id) {
+        return Ok(run.clone());
+    }
+
+    let mut matches: Vec<RunRecord> = runs.into_iter().filter(|run| run.id.starts_with(id)).collect();
+    match matches.len() {
+        0 => Err(format!("No run '{}' (see `forge workflow history`)", id).into()),
+        1 => Ok(matches.remove(0)),
+        n => Err(format!("'{}' matches {} runs; give more of the id", id, n).into()),
+    }
+}
+
+/// A JUnit XML report with a test case per step, for CI test reporting
+pub fn junit_report(run: &RunRecord) -> String {
+    let seconds = |d: Duration| format!("{:.3}", d.as_secs_f64());
+    let failures = run.count(StepStatus::Failure);
+    let skipped = run.count(StepStatus::Skipped) + run.count(StepStatus::Cancelled);
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    let _ = writeln!(xml, "<testsuites name=\"forge\" tests=\"{}\" failures=\"{}\" skipped=\"{}\" time=\"{}\">",
+        run.steps.len(), failures, skipped, seconds(run.duration));
+    let _ = writeln!(xml, "  <testsuite name=\"{}\" id=\"{}\" timestamp=\"{}\" hostname=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"0\" skipped=\"{}\" time=\"{}\">",
+        xml_escape(&run.workflow), xml_escape(&run.id), format_utc(run.started_at).trim_end_matches('Z'), xml_escape(&run.host),
+        run.steps.len(), failures, skipped, seconds(run.duration));
+
+    for step in &run.steps {
+        let _ = write!(xml, "    <testcase classname=\"{}.{}\" name=\"{}\" time=\"{}\"",
+            xml_escape(&run.workflow), step.phase.as_str(), xml_escape(&step.name), seconds(step.duration));
+        let message = xml_escape(step.message.as_deref().unwrap_or_default());
+        let has_body = step.status != StepStatus::Success || !step.stdout_tail.is_empty() || !step.stderr_tail.is_empty();
+        if !has_body {
+            xml.push_str("/>\n");
+            continue;
+        }
+        xml.push_str(">\n");
+        match step.status {
+            StepStatus::Success => {}
+            StepStatus::Failure => {
+                let kind = step.exit_code.map_or("error".to_string(), |code| format!("exit code {}", code));
+                let _ = writeln!(xml, "      <failure message=\"{}\" type=\"{}\"/>", message, kind);
+            }
+            StepStatus::Skipped | StepStatus::Cancelled => {
+                let _ = writeln!(xml, "      <skipped message=\"{}\"/>", message);
+            }
+        }
+        for (tag, text) in [("system-out", &step.stdout_tail), ("system-err", &step.stderr_tail)] {
+            if !text.is_empty() {
+                let _ = writeln!(xml, "      <{}>{}</{}>", tag, xml_escape(text), tag);
+            }
+        }
+        xml.push_str("    </testcase>\n");
+    }
+
+    xml.push_str("  </testsuite>\n</testsuites>\n");
+    xml
+}
+
+/// Escape text for XML, dropping control characters XML can't hold
+fn xml_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\n' | '\t' | '\r' => escaped.push(c),
+            c if (c as u32) < 0x20 => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// A Markdown summary of a run for pull requests and reviews
+pub fn markdown_report(run: &RunRecord) -> String {
+    let mut md = String::new();
+    let (icon, outcome) = if run.success { ("✅", "passed") } else { ("❌", "failed") };
+    let _ = writeln!(md, "## {} Workflow `{}` {}\n", icon, run.workflow, outcome);
+    let _ = writeln!(md, "Run `{}` by {} on {}, started {}, took {:.2}s.\n",
+        run.id, run.user, run.host, run.started(), run.duration.as_secs_f64());
+
+    md.push_str("| Step | Status | Duration | Retries | Exit code |\n");
+    md.push_str("|------|--------|---------:|--------:|----------:|\n");
+    for step in &run.steps {
+        let name = match step.phase {
+            Phase::Step => cell(&step.name),
+            phase => format!("{} ({})", cell(&step.name), phase.as_str()),
+        };
+        let exit_code = step.exit_code.map(|code| code.to_string()).unwrap_or_default();
+        let _ = writeln!(md, "| {} | {} {} | {:.2}s | {} | {} |",
+            name, step.status.icon(), step.status.as_str(), step.duration.as_secs_f64(), step.retries, exit_code);
+    }
+
+    let failed: Vec<&StepRecord> = run.steps.iter().filter(|step| step.status == StepStatus::Failure).collect();
+    if !failed.is_empty() {
+        md.push_str("\n### Failures\n");
+    }
+    for step in failed {
+        let _ = writeln!(md, "\n#### {}\n", step.name);
+        if let Some(message) = &step.message {
+            let _ = writeln!(md, "{}", message.lines().next().unwrap_or_default());
+        }
+        for (label, text) in [("stdout", &step.stdout_tail), ("stderr", &step.stderr_tail)] {
+            if !text.is_empty() {
+                let fence = if text.contains("```") { "````" } else { "```" };
+                let _ = writeln!(md, "\n<details><summary>{}</summary>\n\n{}\n{}\n{}\n\n</details>", label, fence, text, fence);
+            }
+        }
+    }
+    md
+}
+
+/// Text safe to put in a Markdown table cell
+fn cell(text: &str) -> String {
+    text.replace('|', "\\|").replace('\n', " ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_run() -> RunRecord {
+        let step = |name: &str, status| StepRecord {
+            name: name.to_string(),
+            phase: Phase::Step,
+            status,
+            duration: Duration::from_millis(1250),
+            retries: 0,
+            exit_code: Some(0),
+            message: None,
+            stdout_tail: String::new(),
+            stderr_tail: String::new(),
+            outputs: BTreeMap::new(),
+        };
+        let mut build = step("Build", StepStatus::Success);
+        build.outputs.insert("sha".to_string(), "abc123".to_string());
+        build.stdout_tail = "Compiling forge\nFinished".to_string();
+        let test = StepRecord {
+            retries: 2,
+            exit_code: Some(101),
+            message: Some("Command failed: 1 test <failed> & \"quoted\"".to_string()),
+            stderr_tail: "thread 'main' panicked | here\u{1b}[0m".to_string(),
+            ..step("Test | unit", StepStatus::Failure)
+        };
+        let deploy = StepRecord { exit_code: None, message: Some("cancelled: 'Test | unit' failed".to_string()), ..step("Deploy", StepStatus::Cancelled) };
+        let cleanup = StepRecord { phase: Phase::Finally, ..step("Clean", StepStatus::Success) };
+
+        RunRecord {
+            id: "20261018-153012-3fa2".to_string(),
+            workflow: "release".to_string(),
+            started_at: 1_792_337_412,
+            duration: Duration::from_millis(4321),
+            success: false,
+            user: "dev".to_string(),
+            host: "ci-1".to_string(),
+            directory: "/src/app".to_string(),
+            steps: vec![build, test, deploy, cleanup],
+        }
+    }
+
+    #[test]
+    fn test_json_round_trip_and_dates() {
+        let run = sample_run();
+        assert_eq!(RunRecord::from_json(&run.to_json()).unwrap(), run);
+        assert!(RunRecord::from_json(r#"{"id": "x"}"#).unwrap_err().contains("missing"));
+
+        assert_eq!(run.started(), "2026-10-18 15:30:12 UTC");
+
+        let id = run_id(UNIX_EPOCH + Duration::from_secs(1_792_337_412));
+        assert!(id.starts_with("20261018-153012-"), "{}", id);
+        assert_eq!(tail(&"line\n".repeat(100)).lines().count(), TAIL_LINES);
+    }
+
+    #[test]
+    fn test_reports() {
+        let run = sample_run();
+
+        let xml = junit_report(&run);
+        assert!(xml.contains("<testsuite name=\"release\" id=\"20261018-153012-3fa2\" timestamp=\"2026-10-18T15:30:12\""));
+        assert!(xml.contains("tests=\"4\" failures=\"1\" errors=\"0\" skipped=\"1\" time=\"4.321\""));
+        assert!(xml.contains("<testcase classname=\"release.step\" name=\"Test | unit\" time=\"1.250\">"));
+        assert!(xml.contains("<failure message=\"Command failed: 1 test &lt;failed&gt; &amp; &quot;quoted&quot;\" type=\"exit code 101\"/>"));
+        assert!(xml.contains("<system-err>thread &apos;main&apos; panicked | here[0m</system-err>"));
+        assert!(xml.contains("<skipped message=\"cancelled: &apos;Test | unit&apos; failed\"/>"));
+        assert!(xml.contains("<testcase classname=\"release.finally\" name=\"Clean\" time=\"1.250\"/>"));
+
+        let md = markdown_report(&run);
+        assert!(md.starts_with("## ❌ Workflow `release` failed\n"));
+        assert!(md.contains("| Test \\| unit | ❌ failure | 1.25s | 2 | 101 |"));
+        assert!(md.contains("| Clean (finally) | ✅ success |"));
+        assert!(md.contains("#### Test | unit\n\nCommand failed"));
+        assert!(md.contains("<details><summary>stderr</summary>"));
+    }
+
+    #[test]
+    fn test_save_list_and_find() {
+        let dir = tempfile::tempdir().unwrap();
+        let older = RunRecord { id: "20261017-090000-0001".to_string(), started_at: 1_792_227_600, ..sample_run() };
+        let newer = sample_run();
+        older.save(dir.path()).unwrap();
+        newer.save(dir.path()).unwrap();
+        fs::write(dir.path().join("broken.json"), "{").unwrap();
+
+        let (runs, errors) = list_runs(dir.path());
+        assert_eq!(runs.iter().map(|run| run.id.as_str()).collect::<Vec<_>>(), vec![newer.id.as_str(), older.id.as_str()]);
+        assert_eq!(errors.len(), 1);
+
+        assert_eq!(find_run(dir.path(), "latest").unwrap().id, newer.id);
+        assert_eq!(find_run(dir.path(), "20261017").unwrap().id, older.id);
+        assert!(find_run(dir.path(), "2026101").unwrap_err().to_string().contains("matches 2 runs"));
+        assert!(find_run(dir.path(), "1999").is_err());
+    }
+}
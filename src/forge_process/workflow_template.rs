@@ -0,0 +1,222 @@
+use std::fmt;
+use std::ops::Range;
+
+use crate::http::json::{parse_json, JsonValue};
+use super::regex::Regex;
+use super::workflow::OutputSource;
+
+/// A `${...}` placeholder in a workflow command
+#[derive(Debug, Clone, PartialEq)]
+pub enum Reference {
+    /// `${NAME}`: a workflow variable
+    Variable(String),
+    /// `${env.NAME}`
+    Env(String),
+    /// `${steps.<step>.outputs.<name>}`
+    StepOutput { step: String, output: String },
+    /// `${steps.<step>.exit_code}`
+    StepExitCode(String),
+    /// `${matrix.<key>}`: a value of the step's `matrix`
+    Matrix(String),
+    /// `${item}`: the current `for_each` item
+    Item,
+}
+
+impl Reference {
+    /// The step whose results this refers to
+    pub fn step(&self) -> Option<&str> {
+        match self {
+            Reference::StepOutput { step, .. } | Reference::StepExitCode(step) => Some(step),
+            Reference::Variable(_) | Reference::Env(_) | Reference::Matrix(_) | Reference::Item => None,
+        }
+    }
+}
+
+impl fmt::Display for Reference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Reference::Variable(name) => write!(f, "${{{}}}", name),
+            Reference::Env(name) => write!(f, "${{env.{}}}", name),
+            Reference::StepOutput { step, output } => write!(f, "${{steps.{}.outputs.{}}}", step, output),
+            Reference::StepExitCode(step) => write!(f, "${{steps.{}.exit_code}}", step),
+            Reference::Matrix(key) => write!(f, "${{matrix.{}}}", key),
+            Reference::Item => write!(f, "${{item}}"),
+        }
+    }
+}
+
+/// Every placeholder in `template`, with its byte range. A `${` without a
+/// closing `}` is left as text.
+pub fn references(template: &str) -> Result<Vec<(Range<usize>, Reference)>, String> {
+    let mut found = Vec::new();
+    let mut rest = 0;
+    while let Some(start) = template[rest..].find("${").map(|i| rest + i) {
+        let end = match template[start..].find('}') {
+            Some(i) => start + i + 1,
+            None => break,
+        };
+        let reference = parse_reference(&template[start + 2..end - 1])?;
+        found.push((start..end, reference));
+        rest = end;
+    }
+    Ok(found)
+}
+
+fn parse_reference(body: &str) -> Result<Reference, String> {
+    let expected = || format!(
+        "unknown reference '${{{}}}'; expected ${{NAME}}, ${{env.NAME}}, ${{matrix.KEY}}, ${{item}}, ${{steps.<step>.outputs.<name>}} or ${{steps.<step>.exit_code}}",
+        body
+    );
+
+    if body == "item" {
+        return Ok(Reference::Item);
+    }
+    if !body.contains('.') {
+        return Ok(Reference::Variable(body.to_string()));
+    }
+    if let Some(name) = body.strip_prefix("env.") {
+        if name.is_empty() {
+            return Err(expected());
+        }
+        return Ok(Reference::Env(name.to_string()));
+    }
+    if let Some(key) = body.strip_prefix("matrix.") {
+        if key.is_empty() {
+            return Err(expected());
+        }
+        return Ok(Reference::Matrix(key.to_string()));
+    }
+
+    // Step names may contain dots, so take the known suffixes off the end
+    let step_ref = body.strip_prefix("steps.").ok_or_else(expected)?;
+    if let Some(step) = step_ref.strip_suffix(".exit_code") {
+        if !step.is_empty() {
+            return Ok(Reference::StepExitCode(step.to_string()));
+        }
+    }
+    match step_ref.rfind(".outputs.") {
+        Some(i) if i > 0 && i + ".outputs.".len() < step_ref.len() => Ok(Reference::StepOutput {
+            step: step_ref[..i].to_string(),
+            output: step_ref[i + ".outputs.".len()..].to_string(),
+        }),
+        _ => Err(expected()),
+    }
+}
+
+/// Fill in the placeholders of `template`. `resolve` returns `None` to
+/// leave a placeholder as written, which lets `${HOME}` and the like reach
+/// the shell's own expansion.
+pub fn render(template: &str, mut resolve: impl FnMut(&Reference) -> Result<Option<String>, String>) -> Result<String, String> {
+    let mut rendered = String::with_capacity(template.len());
+    let mut last = 0;
+    for (range, reference) in references(template)? {
+        rendered.push_str(&template[last..range.start]);
+        match resolve(&reference)? {
+            Some(value) => rendered.push_str(&value),
+            None => rendered.push_str(&template[range.clone()]),
+        }
+        last = range.end;
+    }
+    rendered.push_str(&template[last..]);
+    Ok(rendered)
+}
+
+/// Pull a step output's value out of the step's stdout
+pub fn extract_output(source: &OutputSource, stdout: &str) -> Result<String, String> {
+    match source {
+        OutputSource::Stdout => Ok(stdout.trim().to_string()),
+        OutputSource::Regex(pattern) => {
+            let regex = Regex::new(pattern).map_err(|e| e.to_string())?;
+            let captures = regex.captures(stdout)
+                .map_err(|e| format!("regex '{}': {}", pattern, e))?
+                .ok_or_else(|| format!("regex '{}' did not match", pattern))?;
+            let group = if regex.group_count() > 0 { captures[1] } else { captures[0] };
+            Ok(group.unwrap_or_default().to_string())
+        }
+        OutputSource::JsonPath(path) => {
+            let document = parse_json(stdout.trim()).map_err(|e| format!("output is not JSON: {}", e.0))?;
+            match json_path(&document, path)? {
+                JsonValue::String(s) => Ok(s.clone()),
+                value => Ok(value.to_string()),
+            }
+        }
+    }
+}
+
+/// Follow a path like `release.assets[0].name`; a leading `$` is allowed
+fn json_path<'v>(document: &'v JsonValue, path: &str) -> Result<&'v JsonValue, String> {
+    let mut value = document;
+    let mut rest = path.trim().strip_prefix('$').unwrap_or(path.trim());
+
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix('.') {
+            rest = after;
+            continue;
+        }
+        if let Some(after) = rest.strip_prefix('[') {
+            let close = after.find(']').ok_or_else(|| format!("missing ']' in JSON path '{}'", path))?;
+            let index: usize = after[..close].trim().parse().map_err(|_| format!("bad index '{}' in JSON path '{}'", &after[..close], path))?;
+            let items = value.as_array().ok_or_else(|| format!("'{}' indexes into something that isn't an array", path))?;
+            value = items.get(index).ok_or_else(|| format!("index {} is out of range in '{}'", index, path))?;
+            rest = &after[close + 1..];
+            continue;
+        }
+
+        let end = rest.find(['.', '[']).unwrap_or(rest.len());
+        let key = &rest[..end];
+        let object = value.as_object().ok_or_else(|| format!("'{}' looks up key '{}' in something that isn't an object", path, key))?;
+        value = object.get(key).ok_or_else(|| format!("no key '{}' for JSON path '{}'", key, path))?;
+        rest = &rest[end..];
+    }
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_references_and_render() {
+        let template = "deploy ${VERSION} ${steps.Build v1.2.outputs.sha} ${steps.Test.exit_code} ${env.HOME} ${matrix.os} ${item} ${unclosed";
+        let found: Vec<Reference> = references(template).unwrap().into_iter().map(|(_, r)| r).collect();
+        assert_eq!(found, vec![
+            Reference::Variable("VERSION".to_string()),
+            Reference::StepOutput { step: "Build v1.2".to_string(), output: "sha".to_string() },
+            Reference::StepExitCode("Test".to_string()),
+            Reference::Env("HOME".to_string()),
+            Reference::Matrix("os".to_string()),
+            Reference::Item,
+        ]);
+        assert!(references("${steps.Build.stdout}").unwrap_err().contains("unknown reference"));
+        assert!(references("${matrix.}").is_err());
+        assert!(references("${item.name}").is_err());
+
+        let rendered = render("cp ${FILE} ${OTHER} ${steps.a.exit_code}", |reference| Ok(match reference {
+            Reference::Variable(name) if name == "FILE" => Some("a.txt".to_string()),
+            Reference::StepExitCode(_) => Some("0".to_string()),
+            _ => None,
+        }));
+        assert_eq!(rendered.unwrap(), "cp a.txt ${OTHER} 0");
+    }
+
+    #[test]
+    fn test_extract_output() {
+        assert_eq!(extract_output(&OutputSource::Stdout, "  abc123\n").unwrap(), "abc123");
+
+        let regex = OutputSource::Regex(r"version (\d+(\.\d+)*)".to_string());
+        assert_eq!(extract_output(&regex, "forge version 0.4.1\n").unwrap(), "0.4.1");
+        assert!(extract_output(&regex, "nothing here").is_err());
+        let repeated = OutputSource::Regex("(?:a?){500}".to_string());
+        let error = extract_output(&repeated, &"a".repeat(200_000)).unwrap_err();
+        assert!(error.contains("too many"), "{}", error);
+
+        let json = r#"{"release": {"tag": "v2", "assets": [{"size": 1024}, {"size": 2.5}], "draft": false}}"#;
+        let path = |p: &str| extract_output(&OutputSource::JsonPath(p.to_string()), json);
+        assert_eq!(path("release.tag").unwrap(), "v2");
+        assert_eq!(path("$.release.assets[1].size").unwrap(), "2.5");
+        assert_eq!(path("release.assets[0]").unwrap(), r#"{"size":1024}"#);
+        assert_eq!(path("release.draft").unwrap(), "false");
+        assert!(path("release.assets[5]").unwrap_err().contains("out of range"));
+        assert!(path("release.missing").is_err());
+    }
+}
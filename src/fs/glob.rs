@@ -1,7 +1,10 @@
 use std::path::{Path, PathBuf};
 use std::fs;
 
+use crate::fs::ignore::{IgnoreMatcher, IgnoreOptions};
+
 /// A simple glob pattern matcher that supports basic patterns
+#[derive(Debug, Clone)]
 pub struct GlobMatcher {
     pattern: String,
 }
@@ -14,11 +17,17 @@ impl GlobMatcher {
     }
 
     /// Match a file path against the glob pattern
+    #[allow(dead_code)]
     pub fn matches(&self, path: &Path) -> bool {
         let path_str = path.to_string_lossy();
         self.match_pattern(&self.pattern, &path_str)
     }
 
+    /// Match a `/`-separated path string against the glob pattern
+    pub fn matches_str(&self, text: &str) -> bool {
+        self.match_pattern(&self.pattern, text)
+    }
+
     /// Internal pattern matching implementation
     fn match_pattern(&self, pattern: &str, text: &str) -> bool {
         let pattern_chars: Vec<char> = pattern.chars().collect();
@@ -43,11 +52,9 @@ impl GlobMatcher {
 
             // Try matching from current position to end of text
             for i in t_idx..=text.len() {
-                // Skip to next directory boundary or end
-                if i == text.len() || (i > t_idx && text[i - 1] == '/') {
-                    if self.match_recursive(pattern, text, next_p, i) {
-                        return true;
-                    }
+                // Match zero directories, or skip to the next directory boundary or end
+                if (i == t_idx || i == text.len() || text[i - 1] == '/') && self.match_recursive(pattern, text, next_p, i) {
+                    return true;
                 }
             }
             return false;
@@ -96,7 +103,7 @@ impl GlobMatcher {
                 let mut i = 0;
 
                 // Check for negation
-                if !char_set.is_empty() && char_set[0] == '^' {
+                if !char_set.is_empty() && (char_set[0] == '^' || char_set[0] == '!') {
                     negated = true;
                     i = 1;
                 }
@@ -137,36 +144,77 @@ impl GlobMatcher {
     }
 }
 
-/// Find all files matching a glob pattern
+/// Find all files matching a glob pattern, skipping hidden and ignored files
+#[allow(dead_code)]
 pub fn glob(pattern: &str) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
-    let matcher = GlobMatcher::new(pattern);
+    glob_with_options(pattern, IgnoreOptions::default())
+}
+
+/// Find all files matching a glob pattern with explicit ignore handling
+pub fn glob_with_options(pattern: &str, options: IgnoreOptions) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
     let mut results = Vec::new();
-    
+    let (base, relative_pattern) = split_glob_base(pattern);
+
     // Handle absolute vs relative patterns
-    let (root, search_pattern) = if pattern.starts_with('/') {
-        (PathBuf::from("/"), pattern)
-    } else if pattern.starts_with("./") {
-        (std::env::current_dir()?, pattern)
+    let root = if base.as_os_str().is_empty() {
+        std::env::current_dir()?
     } else {
-        (std::env::current_dir()?, pattern)
+        base
     };
 
-    // For ** patterns, we need recursive search
-    if search_pattern.contains("**") {
-        collect_recursive(&root, &matcher, &mut results)?;
-    } else {
-        // For non-recursive patterns, be more efficient
-        collect_directory(&root, &matcher, &mut results)?;
+    if relative_pattern.is_empty() {
+        // No wildcards at all - just check the literal path
+        if root.exists() {
+            results.push(root);
+        }
+        return Ok(results);
     }
 
+    let matcher = GlobMatcher::new(&relative_pattern);
+
+    // Without ** we never need to go deeper than the pattern has components
+    let max_depth = if relative_pattern.contains("**") {
+        None
+    } else {
+        Some(relative_pattern.split('/').count())
+    };
+
+    let ignore = IgnoreMatcher::new(&root, options);
+    collect_recursive(&root, &root, &matcher, &ignore, max_depth, 1, &mut results)?;
+
     // Sort results for consistent output
     results.sort();
     Ok(results)
 }
 
-/// Recursively collect files matching the pattern
-fn collect_recursive(dir: &Path, matcher: &GlobMatcher, results: &mut Vec<PathBuf>) -> Result<(), Box<dyn std::error::Error>> {
-    if !dir.is_dir() {
+/// Split a pattern into its literal leading directory and the wildcard remainder
+fn split_glob_base(pattern: &str) -> (PathBuf, String) {
+    let pattern = pattern.strip_prefix("./").unwrap_or(pattern);
+    let components: Vec<&str> = pattern.split('/').collect();
+    let first_wild = components
+        .iter()
+        .position(|c| c.contains(['*', '?', '[']))
+        .unwrap_or(components.len());
+
+    let mut base = components[..first_wild].join("/");
+    if base.is_empty() && pattern.starts_with('/') {
+        base.push('/');
+    }
+
+    (PathBuf::from(base), components[first_wild..].join("/"))
+}
+
+/// Recursively collect files matching the pattern, pruning ignored directories
+fn collect_recursive(
+    root: &Path,
+    dir: &Path,
+    matcher: &GlobMatcher,
+    ignore: &IgnoreMatcher,
+    max_depth: Option<usize>,
+    depth: usize,
+    results: &mut Vec<PathBuf>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if !dir.is_dir() || max_depth.is_some_and(|max| depth > max) {
         return Ok(());
     }
 
@@ -174,32 +222,26 @@ fn collect_recursive(dir: &Path, matcher: &GlobMatcher, results: &mut Vec<PathBu
     for entry in entries {
         let entry = entry?;
         let path = entry.path();
-        
-        if matcher.matches(&path) {
-            results.push(path.clone());
-        }
+        let is_dir = path.is_dir();
 
-        if path.is_dir() {
-            collect_recursive(&path, matcher, results)?;
+        if ignore.is_ignored(&path, is_dir) {
+            continue;
         }
-    }
 
-    Ok(())
-}
+        let relative = path.strip_prefix(root).unwrap_or(&path);
+        let relative = relative
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
 
-/// Collect files from a single directory (non-recursive)
-fn collect_directory(dir: &Path, matcher: &GlobMatcher, results: &mut Vec<PathBuf>) -> Result<(), Box<dyn std::error::Error>> {
-    if !dir.is_dir() {
-        return Ok(());
-    }
+        if matcher.matches_str(&relative) {
+            results.push(path.clone());
+        }
 
-    let entries = fs::read_dir(dir)?;
-    for entry in entries {
-        let entry = entry?;
-        let path = entry.path();
-        
-        if matcher.matches(&path) {
-            results.push(path);
+        if is_dir {
+            let child_ignore = ignore.enter_directory(&path);
+            collect_recursive(root, &path, matcher, &child_ignore, max_depth, depth + 1, results)?;
         }
     }
 
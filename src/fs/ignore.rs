@@ -0,0 +1,436 @@
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+
+use crate::config;
+use crate::fs::glob::GlobMatcher;
+
+/// Per-directory ignore files, in increasing order of precedence
+const IGNORE_FILE_NAMES: [&str; 2] = [".gitignore", ".forgeignore"];
+
+/// Controls which files are skipped while walking a tree
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IgnoreOptions {
+    /// Honor .gitignore, .forgeignore, .git/info/exclude and global excludes
+    pub respect_ignore_files: bool,
+    /// Include dot-files and dot-directories
+    pub include_hidden: bool,
+}
+
+impl Default for IgnoreOptions {
+    fn default() -> Self {
+        Self {
+            respect_ignore_files: true,
+            include_hidden: false,
+        }
+    }
+}
+
+impl IgnoreOptions {
+    /// Options that walk everything, including hidden and ignored files
+    #[allow(dead_code)]
+    pub fn none() -> Self {
+        Self {
+            respect_ignore_files: false,
+            include_hidden: true,
+        }
+    }
+
+    /// Apply `--no-ignore` and `--hidden` flags from a command line.
+    ///
+    /// Returns the options together with the remaining arguments.
+    pub fn from_args(args: &[String]) -> (Self, Vec<String>) {
+        let mut options = Self::default();
+        let mut rest = Vec::new();
+
+        for arg in args {
+            match arg.as_str() {
+                "--no-ignore" => options.respect_ignore_files = false,
+                "--hidden" => options.include_hidden = true,
+                _ => rest.push(arg.clone()),
+            }
+        }
+
+        (options, rest)
+    }
+}
+
+/// A single line from an ignore file
+#[derive(Debug)]
+struct IgnorePattern {
+    matcher: GlobMatcher,
+    negated: bool,
+    dir_only: bool,
+    anchored: bool,
+}
+
+impl IgnorePattern {
+    fn parse(line: &str) -> Option<Self> {
+        let line = line.trim_end_matches('\r');
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+
+        let mut pattern = trim_unescaped_trailing_spaces(line);
+
+        // A leading `!` negates; `\!` and `\#` escape a literal first character
+        let negated = pattern.starts_with('!');
+        if negated || pattern.starts_with("\\!") || pattern.starts_with("\\#") {
+            pattern = &pattern[1..];
+        }
+
+        let dir_only = pattern.ends_with('/');
+        let pattern = pattern.trim_end_matches('/');
+        if pattern.is_empty() {
+            return None;
+        }
+
+        // A slash anywhere but the end ties the pattern to the ignore file's directory
+        let anchored = pattern.contains('/');
+        let pattern = pattern.strip_prefix('/').unwrap_or(pattern);
+
+        Some(Self {
+            matcher: GlobMatcher::new(pattern),
+            negated,
+            dir_only,
+            anchored,
+        })
+    }
+
+    /// Match a path relative to the ignore file's directory
+    fn matches(&self, relative: &str, is_dir: bool) -> bool {
+        if self.dir_only && !is_dir {
+            return false;
+        }
+
+        if self.anchored {
+            self.matcher.matches_str(relative)
+        } else {
+            let name = relative.rsplit('/').next().unwrap_or(relative);
+            self.matcher.matches_str(name)
+        }
+    }
+}
+
+fn trim_unescaped_trailing_spaces(line: &str) -> &str {
+    let mut end = line.len();
+    while end > 0 && line.as_bytes()[end - 1] == b' ' {
+        if end >= 2 && line.as_bytes()[end - 2] == b'\\' {
+            break;
+        }
+        end -= 1;
+    }
+    &line[..end]
+}
+
+/// Patterns loaded from one ignore file, relative to `base`
+#[derive(Debug)]
+struct IgnoreFile {
+    base: PathBuf,
+    patterns: Vec<IgnorePattern>,
+}
+
+impl IgnoreFile {
+    fn parse(base: &Path, content: &str) -> Self {
+        Self {
+            base: base.to_path_buf(),
+            patterns: content.lines().filter_map(IgnorePattern::parse).collect(),
+        }
+    }
+
+    fn load(base: &Path, file: &Path) -> Option<Self> {
+        let content = fs::read_to_string(file).ok()?;
+        let ignore_file = Self::parse(base, &content);
+        if ignore_file.patterns.is_empty() {
+            None
+        } else {
+            Some(ignore_file)
+        }
+    }
+
+    /// Returns Some(true) if ignored, Some(false) if re-included by a negation
+    fn decide(&self, absolute: &Path, is_dir: bool) -> Option<bool> {
+        let relative = absolute.strip_prefix(&self.base).ok()?;
+        let relative = to_slash_path(relative);
+        if relative.is_empty() {
+            return None;
+        }
+
+        // Later patterns override earlier ones
+        self.patterns
+            .iter()
+            .rev()
+            .find(|p| p.matches(&relative, is_dir))
+            .map(|p| !p.negated)
+    }
+}
+
+/// Gitignore-style matcher for a directory tree.
+///
+/// Built once for the walk root and extended with `enter_directory` as the
+/// walk descends, so nested ignore files only affect their own subtree.
+#[derive(Debug, Clone)]
+pub struct IgnoreMatcher {
+    options: IgnoreOptions,
+    root: PathBuf,
+    absolute_root: PathBuf,
+    files: Vec<Arc<IgnoreFile>>,
+}
+
+impl IgnoreMatcher {
+    /// Create a matcher for walking `root`.
+    ///
+    /// Loads global excludes, `.git/info/exclude` and the ignore files of every
+    /// directory from the enclosing repository root down to `root`.
+    pub fn new(root: &Path, options: IgnoreOptions) -> Self {
+        let absolute_root = absolutize(root);
+        let mut matcher = Self {
+            options,
+            root: root.to_path_buf(),
+            absolute_root: absolute_root.clone(),
+            files: Vec::new(),
+        };
+
+        if !options.respect_ignore_files {
+            return matcher;
+        }
+
+        let repo_root = find_repo_root(&absolute_root);
+        let top = repo_root.clone().unwrap_or_else(|| absolute_root.clone());
+
+        if let Some(global) = global_excludes_file() {
+            matcher.push_file(IgnoreFile::load(&top, &global));
+        }
+        if let Some(forge_global) = config::config_dir().map(|dir| dir.join("ignore")) {
+            matcher.push_file(IgnoreFile::load(&top, &forge_global));
+        }
+        if let Some(repo) = &repo_root {
+            matcher.push_file(IgnoreFile::load(repo, &repo.join(".git").join("info").join("exclude")));
+        }
+
+        // Ancestors between the repository root and the walk root, outermost first
+        let mut ancestors: Vec<&Path> = absolute_root
+            .ancestors()
+            .skip(1)
+            .take_while(|dir| repo_root.as_deref().is_some_and(|repo| dir.starts_with(repo)))
+            .collect();
+        ancestors.reverse();
+        for dir in ancestors {
+            matcher.load_directory(dir);
+        }
+
+        matcher.load_directory(&absolute_root.clone());
+        matcher
+    }
+
+    /// Matcher for the contents of `dir`, including its own ignore files
+    pub fn enter_directory(&self, dir: &Path) -> Self {
+        let mut child = self.clone();
+        if self.options.respect_ignore_files {
+            let absolute = self.to_absolute(dir);
+            child.load_directory(&absolute);
+        }
+        child
+    }
+
+    /// Check whether a path found during the walk should be skipped
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        let name = match path.file_name() {
+            Some(name) => name.to_string_lossy(),
+            None => return false,
+        };
+
+        if !self.options.include_hidden && name.starts_with('.') {
+            return true;
+        }
+
+        if !self.options.respect_ignore_files {
+            return false;
+        }
+
+        // Never descend into repository metadata
+        if is_dir && name == ".git" {
+            return true;
+        }
+
+        let absolute = self.to_absolute(path);
+        self.files
+            .iter()
+            .rev()
+            .find_map(|file| file.decide(&absolute, is_dir))
+            .unwrap_or(false)
+    }
+
+    fn load_directory(&mut self, dir: &Path) {
+        for name in IGNORE_FILE_NAMES {
+            self.push_file(IgnoreFile::load(dir, &dir.join(name)));
+        }
+    }
+
+    fn push_file(&mut self, file: Option<IgnoreFile>) {
+        if let Some(file) = file {
+            self.files.push(Arc::new(file));
+        }
+    }
+
+    fn to_absolute(&self, path: &Path) -> PathBuf {
+        match path.strip_prefix(&self.root) {
+            Ok(relative) => normalize(&self.absolute_root.join(relative)),
+            Err(_) => absolutize(path),
+        }
+    }
+}
+
+fn absolutize(path: &Path) -> PathBuf {
+    if let Ok(canonical) = fs::canonicalize(path) {
+        return canonical;
+    }
+    let joined = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        std::env::current_dir().unwrap_or_default().join(path)
+    };
+    normalize(&joined)
+}
+
+/// Remove `.` components so prefix comparisons work
+fn normalize(path: &Path) -> PathBuf {
+    path.components()
+        .filter(|c| !matches!(c, Component::CurDir))
+        .collect()
+}
+
+fn to_slash_path(path: &Path) -> String {
+    path.components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn find_repo_root(start: &Path) -> Option<PathBuf> {
+    start
+        .ancestors()
+        .find(|dir| dir.join(".git").exists())
+        .map(Path::to_path_buf)
+}
+
+/// Locate git's global excludes file (`core.excludesFile` or the XDG default)
+fn global_excludes_file() -> Option<PathBuf> {
+    let home = dirs::home_dir()?;
+
+    if let Ok(gitconfig) = fs::read_to_string(home.join(".gitconfig")) {
+        let mut in_core = false;
+        for line in gitconfig.lines() {
+            let line = line.trim();
+            if line.starts_with('[') {
+                in_core = line.eq_ignore_ascii_case("[core]");
+                continue;
+            }
+            if !in_core {
+                continue;
+            }
+            if let Some((key, value)) = line.split_once('=') {
+                if key.trim().eq_ignore_ascii_case("excludesfile") {
+                    let value = value.trim().trim_matches('"');
+                    return Some(match value.strip_prefix("~/") {
+                        Some(rest) => home.join(rest),
+                        None => PathBuf::from(value),
+                    });
+                }
+            }
+        }
+    }
+
+    let config_home = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| home.join(".config"));
+    Some(config_home.join("git").join("ignore"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn ignored(content: &str, path: &str, is_dir: bool) -> bool {
+        let file = IgnoreFile::parse(Path::new("/repo"), content);
+        file.decide(&Path::new("/repo").join(path), is_dir).unwrap_or(false)
+    }
+
+    #[test]
+    fn test_basename_patterns() {
+        assert!(ignored("*.log", "debug.log", false));
+        assert!(ignored("*.log", "logs/nested/debug.log", false));
+        assert!(!ignored("*.log", "debug.txt", false));
+    }
+
+    #[test]
+    fn test_anchored_patterns() {
+        assert!(ignored("/build", "build", true));
+        assert!(!ignored("/build", "src/build", true));
+        assert!(ignored("doc/*.txt", "doc/notes.txt", false));
+        assert!(!ignored("doc/*.txt", "doc/sub/notes.txt", false));
+        assert!(ignored("**/generated", "a/b/generated", true));
+        assert!(ignored("docs/**", "docs/a/b.md", false));
+    }
+
+    #[test]
+    fn test_directory_only_patterns() {
+        assert!(ignored("target/", "target", true));
+        assert!(!ignored("target/", "target", false));
+    }
+
+    #[test]
+    fn test_negation() {
+        let rules = "*.log\n!keep.log";
+        assert!(ignored(rules, "debug.log", false));
+        assert!(!ignored(rules, "keep.log", false));
+    }
+
+    #[test]
+    fn test_comments_and_escapes() {
+        assert!(!ignored("# comment", "# comment", false));
+        assert!(ignored("\\#notes", "#notes", false));
+        assert!(ignored("\\!bang", "!bang", false));
+        assert!(ignored("trailing   ", "trailing", false));
+    }
+
+    #[test]
+    fn test_nested_ignore_files() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = TempDir::new()?;
+        let root = temp_dir.path();
+        fs::create_dir(root.join(".git"))?;
+        fs::create_dir_all(root.join("sub"))?;
+        fs::write(root.join(".gitignore"), "*.tmp\ntarget/\n")?;
+        fs::write(root.join("sub").join(".forgeignore"), "!keep.tmp\n")?;
+
+        let matcher = IgnoreMatcher::new(root, IgnoreOptions::default());
+        assert!(matcher.is_ignored(&root.join("a.tmp"), false));
+        assert!(matcher.is_ignored(&root.join("target"), true));
+        assert!(matcher.is_ignored(&root.join(".git"), true));
+        assert!(matcher.is_ignored(&root.join(".env"), false));
+
+        let sub = matcher.enter_directory(&root.join("sub"));
+        assert!(!sub.is_ignored(&root.join("sub").join("keep.tmp"), false));
+        assert!(sub.is_ignored(&root.join("sub").join("other.tmp"), false));
+
+        // Starting the walk inside the repository still honors the root rules
+        let from_sub = IgnoreMatcher::new(&root.join("sub"), IgnoreOptions::default());
+        assert!(from_sub.is_ignored(&root.join("sub").join("other.tmp"), false));
+
+        let everything = IgnoreMatcher::new(root, IgnoreOptions::none());
+        assert!(!everything.is_ignored(&root.join("a.tmp"), false));
+        assert!(!everything.is_ignored(&root.join(".env"), false));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_options_from_args() {
+        let args: Vec<String> = ["foo", "--hidden", "src", "--no-ignore"].iter().map(|s| s.to_string()).collect();
+        let (options, rest) = IgnoreOptions::from_args(&args);
+        assert!(options.include_hidden);
+        assert!(!options.respect_ignore_files);
+        assert_eq!(rest, vec!["foo", "src"]);
+    }
+}
@@ -3,5 +3,6 @@ pub mod walker;
 pub mod search;
 pub mod glob;
 pub mod watcher;
+pub mod ignore;
 
 pub use operations::*;
\ No newline at end of file
@@ -1,5 +1,20 @@
-use crate::fs::operations::read_file;
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::Duration;
 
+/// Number of leading bytes inspected when deciding whether a file is binary
+const BINARY_SNIFF_LEN: usize = 8192;
+
+/// Number of paths a worker moves from the shared queue into its own deque at once
+const INJECTOR_BATCH: usize = 16;
+
+#[derive(Debug, Clone)]
 pub struct TextSearcher {
     case_sensitive: bool,
     whole_word: bool,
@@ -32,17 +47,23 @@ impl TextSearcher {
         let search_text = if self.case_sensitive { text.to_string() } else { text.to_lowercase() };
         let search_pattern = if self.case_sensitive { pattern.to_string() } else { pattern.to_lowercase() };
 
+        if search_pattern.is_empty() {
+            return matches;
+        }
+
         let mut start = 0;
         while let Some(pos) = search_text[start..].find(&search_pattern) {
             let actual_pos = start + pos;
+            // Advance by a whole character so we never slice inside a multi-byte sequence
+            let step = search_text[actual_pos..].chars().next().map_or(1, |c| c.len_utf8());
             
-            if self.whole_word && !self.is_word_boundary(text, actual_pos, search_pattern.len()) {
-                start = actual_pos + 1;
+            if self.whole_word && !self.is_word_boundary(&search_text, actual_pos, search_pattern.len()) {
+                start = actual_pos + step;
                 continue;
             }
 
             matches.push((actual_pos, search_pattern.len()));
-            start = actual_pos + 1;
+            start = actual_pos + step;
         }
 
         matches
@@ -50,39 +71,64 @@ impl TextSearcher {
 
     #[allow(dead_code)]
     pub fn search_in_file(&self, file_path: &str, pattern: &str) -> Result<Vec<(usize, usize, usize)>, Box<dyn std::error::Error>> {
-        let content = read_file(file_path)?;
-        let lines: Vec<&str> = content.lines().collect();
+        let matches = match self.search_file_lines(Path::new(file_path), pattern)? {
+            Some(matches) => matches,
+            None => return Ok(Vec::new()), // Binary files never match
+        };
+
+        Ok(matches
+            .into_iter()
+            .map(|m| (m.line_number, m.column, m.length))
+            .collect())
+    }
+
+    /// Search a file line by line without loading it into memory.
+    ///
+    /// Lines that are not valid UTF-8 are decoded lossily. Returns `Ok(None)`
+    /// when the file looks binary and was skipped.
+    pub fn search_file_lines(&self, path: &Path, pattern: &str) -> Result<Option<Vec<SearchMatch>>, Box<dyn std::error::Error>> {
+        let file = File::open(path)?;
+        let mut reader = BufReader::with_capacity(64 * 1024, file);
+
+        if is_binary(reader.fill_buf()?) {
+            return Ok(None);
+        }
+
         let mut matches = Vec::new();
+        let mut buffer = Vec::new();
+        let mut line_number = 0;
+
+        loop {
+            buffer.clear();
+            if reader.read_until(b'\n', &mut buffer)? == 0 {
+                break;
+            }
+            line_number += 1;
+
+            let line = String::from_utf8_lossy(&buffer);
+            let line = line.trim_end_matches(['\n', '\r']);
 
-        for (line_num, line) in lines.iter().enumerate() {
-            let line_matches = self.search_in_text(line, pattern);
-            for (col, len) in line_matches {
-                matches.push((line_num + 1, col + 1, len)); // 1-based indexing
+            for (col, len) in self.search_in_text(line, pattern) {
+                matches.push(SearchMatch {
+                    line_number,
+                    column: col + 1, // 1-based indexing
+                    length: len,
+                    line: line.to_string(),
+                });
             }
         }
 
-        Ok(matches)
+        Ok(Some(matches))
     }
 
     fn is_word_boundary(&self, text: &str, pos: usize, pattern_len: usize) -> bool {
-        let chars: Vec<char> = text.chars().collect();
-        
+        let is_word_char = |c: char| c.is_alphanumeric() || c == '_';
+
         // Check start boundary
-        let start_ok = if pos == 0 {
-            true
-        } else {
-            let prev_char = chars[pos - 1];
-            !prev_char.is_alphanumeric() && prev_char != '_'
-        };
+        let start_ok = text[..pos].chars().next_back().is_none_or(|c| !is_word_char(c));
 
         // Check end boundary
-        let end_pos = pos + pattern_len;
-        let end_ok = if end_pos >= chars.len() {
-            true
-        } else {
-            let next_char = chars[end_pos];
-            !next_char.is_alphanumeric() && next_char != '_'
-        };
+        let end_ok = text[pos + pattern_len..].chars().next().is_none_or(|c| !is_word_char(c));
 
         start_ok && end_ok
     }
@@ -94,6 +140,232 @@ impl Default for TextSearcher {
     }
 }
 
+/// Returns true if the buffer looks like binary content (contains a NUL byte)
+pub fn is_binary(head: &[u8]) -> bool {
+    head[..head.len().min(BINARY_SNIFF_LEN)].contains(&0)
+}
+
+/// A single match within a file
+#[derive(Debug, Clone)]
+pub struct SearchMatch {
+    pub line_number: usize,
+    pub column: usize,
+    pub length: usize,
+    pub line: String,
+}
+
+/// All matches found in one file
+#[derive(Debug, Clone)]
+pub struct FileMatches {
+    pub path: PathBuf,
+    pub matches: Vec<SearchMatch>,
+}
+
+/// Events streamed back from a parallel search as files are processed
+#[derive(Debug, Clone)]
+#[allow(dead_code)]
+pub enum SearchEvent {
+    Matched(FileMatches),
+    SkippedBinary(PathBuf),
+    Error(PathBuf, String),
+}
+
+/// Multi-threaded searcher that streams results back as they are found.
+///
+/// Paths are fed through a shared queue; each worker drains batches into its
+/// own deque and steals from the back of its peers' deques once idle, so a
+/// few huge files don't leave the other threads waiting.
+pub struct ParallelSearch {
+    searcher: TextSearcher,
+    pattern: String,
+    threads: usize,
+}
+
+impl ParallelSearch {
+    pub fn new(pattern: &str) -> Self {
+        let threads = thread::available_parallelism().map(|n| n.get()).unwrap_or(4);
+
+        Self {
+            searcher: TextSearcher::new(),
+            pattern: pattern.to_string(),
+            threads,
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn with_searcher(mut self, searcher: TextSearcher) -> Self {
+        self.searcher = searcher;
+        self
+    }
+
+    /// Set the number of worker threads (minimum 1)
+    #[allow(dead_code)]
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    /// Start searching the given paths and return a receiver for results.
+    ///
+    /// The paths are consumed lazily on a feeder thread, so a directory walk
+    /// can be passed in directly and results arrive while it is still running.
+    /// The receiver is closed once every path has been processed.
+    pub fn search<I>(self, paths: I) -> Receiver<SearchEvent>
+    where
+        I: IntoIterator<Item = PathBuf> + Send + 'static,
+    {
+        let (sender, receiver) = mpsc::channel();
+        let queue = Arc::new(WorkQueue::new(self.threads));
+
+        let feeder_queue = Arc::clone(&queue);
+        thread::spawn(move || {
+            for path in paths {
+                feeder_queue.push(path);
+            }
+            feeder_queue.close();
+        });
+
+        let searcher = Arc::new(self.searcher);
+        let pattern = Arc::new(self.pattern);
+
+        for worker_id in 0..self.threads {
+            let queue = Arc::clone(&queue);
+            let searcher = Arc::clone(&searcher);
+            let pattern = Arc::clone(&pattern);
+            let sender: Sender<SearchEvent> = sender.clone();
+
+            thread::spawn(move || {
+                while let Some(path) = queue.next(worker_id) {
+                    let event = match searcher.search_file_lines(&path, &pattern) {
+                        Ok(Some(matches)) if matches.is_empty() => continue,
+                        Ok(Some(matches)) => SearchEvent::Matched(FileMatches { path, matches }),
+                        Ok(None) => SearchEvent::SkippedBinary(path),
+                        Err(e) => SearchEvent::Error(path, e.to_string()),
+                    };
+
+                    if sender.send(event).is_err() {
+                        // Receiver dropped, nobody is listening anymore
+                        break;
+                    }
+                }
+            });
+        }
+
+        receiver
+    }
+}
+
+/// Work-stealing queue shared by the search workers
+struct WorkQueue {
+    injector: Mutex<VecDeque<PathBuf>>,
+    locals: Vec<Mutex<VecDeque<PathBuf>>>,
+    closed: AtomicBool,
+    available: Condvar,
+}
+
+impl WorkQueue {
+    fn new(workers: usize) -> Self {
+        Self {
+            injector: Mutex::new(VecDeque::new()),
+            locals: (0..workers).map(|_| Mutex::new(VecDeque::new())).collect(),
+            closed: AtomicBool::new(false),
+            available: Condvar::new(),
+        }
+    }
+
+    fn push(&self, path: PathBuf) {
+        if let Ok(mut injector) = self.injector.lock() {
+            injector.push_back(path);
+        }
+        self.available.notify_one();
+    }
+
+    fn close(&self) {
+        self.closed.store(true, Ordering::SeqCst);
+        self.available.notify_all();
+    }
+
+    /// Get the next path for a worker, blocking until work arrives or the queue is drained
+    fn next(&self, worker_id: usize) -> Option<PathBuf> {
+        loop {
+            if let Some(path) = self.pop_local(worker_id) {
+                return Some(path);
+            }
+
+            if let Some(path) = self.take_from_injector(worker_id) {
+                return Some(path);
+            }
+
+            if let Some(path) = self.steal(worker_id) {
+                return Some(path);
+            }
+
+            // Check `closed` before the final emptiness test so a push racing
+            // with `close` is never missed
+            let closed = self.closed.load(Ordering::SeqCst);
+            let injector = self.injector.lock().ok()?;
+            if !injector.is_empty() {
+                continue;
+            }
+            if closed {
+                return None;
+            }
+
+            // Time out periodically so a stealable local deque is never missed
+            let _ = self.available.wait_timeout(injector, Duration::from_millis(10));
+        }
+    }
+
+    fn pop_local(&self, worker_id: usize) -> Option<PathBuf> {
+        self.locals[worker_id].lock().ok()?.pop_front()
+    }
+
+    fn take_from_injector(&self, worker_id: usize) -> Option<PathBuf> {
+        let mut injector = self.injector.lock().ok()?;
+        let first = injector.pop_front()?;
+
+        let batch = injector.len().min(INJECTOR_BATCH - 1);
+        if batch > 0 {
+            let mut local = self.locals[worker_id].lock().ok()?;
+            local.extend(injector.drain(..batch));
+        }
+
+        Some(first)
+    }
+
+    fn steal(&self, worker_id: usize) -> Option<PathBuf> {
+        let workers = self.locals.len();
+
+        for offset in 1..workers {
+            let victim = (worker_id + offset) % workers;
+            let mut victim_queue = match self.locals[victim].lock() {
+                Ok(queue) => queue,
+                Err(_) => continue,
+            };
+
+            // Take half of the victim's remaining work from the back
+            let steal_count = victim_queue.len().div_ceil(2);
+            if steal_count == 0 {
+                continue;
+            }
+
+            let split_at = victim_queue.len() - steal_count;
+            let mut stolen = victim_queue.split_off(split_at);
+            drop(victim_queue);
+
+            let first = stolen.pop_front();
+            if !stolen.is_empty() {
+                if let Ok(mut local) = self.locals[worker_id].lock() {
+                    local.extend(stolen);
+                }
+            }
+            return first;
+        }
+
+        None
+    }
+}
+
 #[allow(dead_code)]
 pub fn search_text(text: &str, pattern: &str) -> Vec<(usize, usize)> {
     TextSearcher::new().search_in_text(text, pattern)
@@ -111,22 +383,86 @@ pub fn search_file(file_path: &str, pattern: &str) -> Result<Vec<(usize, usize,
 
 #[allow(dead_code)]
 pub fn search_multiple_files(file_paths: &[String], pattern: &str) -> Result<Vec<(String, Vec<(usize, usize, usize)>)>, Box<dyn std::error::Error>> {
-    let searcher = TextSearcher::new();
+    let paths: Vec<PathBuf> = file_paths.iter().map(PathBuf::from).collect();
     let mut results = Vec::new();
 
-    for file_path in file_paths {
-        match searcher.search_in_file(file_path, pattern) {
-            Ok(matches) => {
-                if !matches.is_empty() {
-                    results.push((file_path.clone(), matches));
-                }
-            }
-            Err(_) => {
-                // Skip files that can't be read
-                continue;
-            }
+    for event in ParallelSearch::new(pattern).search(paths) {
+        // Binary and unreadable files are skipped
+        if let SearchEvent::Matched(file) = event {
+            let matches = file.matches
+                .iter()
+                .map(|m| (m.line_number, m.column, m.length))
+                .collect();
+            results.push((file.path.to_string_lossy().to_string(), matches));
         }
     }
 
+    // Workers finish in arbitrary order; keep output stable
+    results.sort_by(|a, b| a.0.cmp(&b.0));
     Ok(results)
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_search_in_text() {
+        let searcher = TextSearcher::new();
+        assert_eq!(searcher.search_in_text("foo bar foo", "foo"), vec![(0, 3), (8, 3)]);
+
+        let searcher = TextSearcher::new().whole_word();
+        assert_eq!(searcher.search_in_text("foobar foo", "foo"), vec![(7, 3)]);
+    }
+
+    #[test]
+    fn test_multibyte_text_does_not_panic() {
+        let searcher = TextSearcher::new().whole_word();
+        assert_eq!(searcher.search_in_text("\u{FFFD}éab ab", "ab"), vec![(8, 2)]);
+    }
+
+    #[test]
+    fn test_binary_detection() {
+        assert!(is_binary(b"hello\0world"));
+        assert!(!is_binary(b"hello world"));
+    }
+
+    #[test]
+    fn test_non_utf8_file_is_searched() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = TempDir::new()?;
+        let path = temp_dir.path().join("latin1.txt");
+        fs::write(&path, b"caf\xe9\nneedle here\n")?;
+
+        let matches = TextSearcher::new().search_file_lines(&path, "needle")?.unwrap();
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].line_number, 2);
+        assert_eq!(matches[0].column, 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parallel_search_streams_matches() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = TempDir::new()?;
+        let mut paths = Vec::new();
+        for i in 0..50 {
+            let path = temp_dir.path().join(format!("file{}.txt", i));
+            let content = if i % 5 == 0 { "has needle\n" } else { "nothing\n" };
+            fs::write(&path, content)?;
+            paths.push(path);
+        }
+        let binary = temp_dir.path().join("data.bin");
+        fs::write(&binary, b"needle\0\0\0")?;
+        paths.push(binary.clone());
+
+        let events: Vec<SearchEvent> = ParallelSearch::new("needle").with_threads(3).search(paths).into_iter().collect();
+
+        let matched = events.iter().filter(|e| matches!(e, SearchEvent::Matched(_))).count();
+        assert_eq!(matched, 10);
+        assert!(events.iter().any(|e| matches!(e, SearchEvent::SkippedBinary(p) if *p == binary)));
+
+        Ok(())
+    }
+}
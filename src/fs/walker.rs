@@ -1,145 +1,575 @@
+use std::cmp::Ordering;
+use std::ffi::OsStr;
+use std::fmt;
 use std::fs;
-use std::path::Path;
+use std::io;
+use std::path::{Path, PathBuf};
 
+use crate::fs::ignore::{IgnoreMatcher, IgnoreOptions};
+
+/// A file or directory yielded by a `Walk`
+#[derive(Debug, Clone)]
+pub struct DirEntry {
+    path: PathBuf,
+    depth: usize,
+    file_type: fs::FileType,
+    followed_link: bool,
+}
+
+#[allow(dead_code)]
+impl DirEntry {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn into_path(self) -> PathBuf {
+        self.path
+    }
+
+    /// Distance from the walk root, which itself has depth 0
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// File type of the entry, resolved through the link when symlinks are followed
+    pub fn file_type(&self) -> fs::FileType {
+        self.file_type
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.file_type.is_dir()
+    }
+
+    pub fn is_file(&self) -> bool {
+        self.file_type.is_file()
+    }
+
+    /// Whether this entry was reached through a symlink that was followed
+    pub fn path_is_symlink(&self) -> bool {
+        self.followed_link || self.file_type.is_symlink()
+    }
+
+    pub fn file_name(&self) -> &OsStr {
+        self.path.file_name().unwrap_or(self.path.as_os_str())
+    }
+}
+
+/// Errors encountered while walking; the walk continues past them
+#[derive(Debug)]
+pub enum WalkError {
+    Io { path: PathBuf, depth: usize, error: io::Error },
+    SymlinkLoop { path: PathBuf, ancestor: PathBuf, depth: usize },
+}
+
+#[allow(dead_code)]
+impl WalkError {
+    pub fn path(&self) -> &Path {
+        match self {
+            WalkError::Io { path, .. } | WalkError::SymlinkLoop { path, .. } => path,
+        }
+    }
+
+    pub fn depth(&self) -> usize {
+        match self {
+            WalkError::Io { depth, .. } | WalkError::SymlinkLoop { depth, .. } => *depth,
+        }
+    }
+}
+
+impl fmt::Display for WalkError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WalkError::Io { path, error, .. } => write!(f, "{}: {}", path.display(), error),
+            WalkError::SymlinkLoop { path, ancestor, .. } => {
+                write!(f, "{}: symlink loop back to {}", path.display(), ancestor.display())
+            }
+        }
+    }
+}
+
+impl std::error::Error for WalkError {}
+
+/// Order in which the entries of each directory are visited
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SortOrder {
+    /// Whatever order the file system returns; entries are read lazily
+    #[default]
+    Unsorted,
+    /// By file name
+    Name,
+    /// Directories before files, each group by file name
+    DirectoriesFirst,
+}
+
+type EntryPredicate = Box<dyn FnMut(&DirEntry) -> bool + Send>;
+
+/// Builder for a depth-first directory walk.
+///
+/// Convert it into an iterator with `into_iter()`; directories are only read
+/// as the walk reaches them.
 pub struct DirectoryWalker {
-    stack: Vec<String>,
+    root: PathBuf,
     follow_symlinks: bool,
     max_depth: Option<usize>,
-    current_depth: usize,
+    ignore_options: IgnoreOptions,
+    sort: SortOrder,
+    filter: Option<EntryPredicate>,
+    prune: Option<EntryPredicate>,
 }
 
+#[allow(dead_code)]
 impl DirectoryWalker {
-    pub fn new(root: &str) -> Self {
+    pub fn new<P: AsRef<Path>>(root: P) -> Self {
         Self {
-            stack: vec![root.to_string()],
+            root: root.as_ref().to_path_buf(),
             follow_symlinks: false,
             max_depth: None,
-            current_depth: 0,
+            ignore_options: IgnoreOptions::default(),
+            sort: SortOrder::Unsorted,
+            filter: None,
+            prune: None,
         }
     }
 
-    #[allow(dead_code)]
+    /// Do not descend below `depth`; 0 yields only the root
     pub fn with_max_depth(mut self, depth: usize) -> Self {
         self.max_depth = Some(depth);
         self
     }
 
-    #[allow(dead_code)]
     pub fn follow_symlinks(mut self, follow: bool) -> Self {
         self.follow_symlinks = follow;
         self
     }
 
-    #[allow(dead_code)]
-    pub fn walk(&mut self) -> Result<Vec<String>, Box<dyn std::error::Error>> {
-        let mut all_files = Vec::new();
+    /// Control hidden-file and ignore-file handling (defaults honor both)
+    pub fn with_ignore_options(mut self, options: IgnoreOptions) -> Self {
+        self.ignore_options = options;
+        self
+    }
+
+    pub fn with_sort(mut self, sort: SortOrder) -> Self {
+        self.sort = sort;
+        self
+    }
 
-        while let Some(current_path) = self.stack.pop() {
-            if let Some(max_depth) = self.max_depth {
-                if self.current_depth >= max_depth {
-                    continue;
-                }
+    /// Only yield entries for which `filter` returns true; directories that
+    /// are filtered out are still descended into
+    pub fn with_filter<F>(mut self, filter: F) -> Self
+    where
+        F: FnMut(&DirEntry) -> bool + Send + 'static,
+    {
+        self.filter = Some(Box::new(filter));
+        self
+    }
+
+    /// Skip the contents of directories for which `prune` returns true; the
+    /// directory itself is still yielded
+    pub fn with_prune<F>(mut self, prune: F) -> Self
+    where
+        F: FnMut(&DirEntry) -> bool + Send + 'static,
+    {
+        self.prune = Some(Box::new(prune));
+        self
+    }
+}
+
+impl IntoIterator for DirectoryWalker {
+    type Item = Result<DirEntry, WalkError>;
+    type IntoIter = Walk;
+
+    fn into_iter(self) -> Walk {
+        Walk {
+            root: Some(self.root),
+            follow_symlinks: self.follow_symlinks,
+            max_depth: self.max_depth,
+            ignore_options: self.ignore_options,
+            sort: self.sort,
+            filter: self.filter,
+            prune: self.prune,
+            stack: Vec::new(),
+            pending_error: None,
+        }
+    }
+}
+
+enum DirContents {
+    Lazy(fs::ReadDir),
+    Sorted(std::vec::IntoIter<io::Result<fs::DirEntry>>),
+}
+
+impl Iterator for DirContents {
+    type Item = io::Result<fs::DirEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            DirContents::Lazy(read_dir) => read_dir.next(),
+            DirContents::Sorted(entries) => entries.next(),
+        }
+    }
+}
+
+/// An open directory on the walk stack
+struct DirFrame {
+    path: PathBuf,
+    depth: usize,
+    contents: DirContents,
+    ignore: IgnoreMatcher,
+    /// Canonical path, kept only when following symlinks to detect loops
+    canonical: Option<PathBuf>,
+}
+
+/// Lazy depth-first iterator over a directory tree
+pub struct Walk {
+    root: Option<PathBuf>,
+    follow_symlinks: bool,
+    max_depth: Option<usize>,
+    ignore_options: IgnoreOptions,
+    sort: SortOrder,
+    filter: Option<EntryPredicate>,
+    prune: Option<EntryPredicate>,
+    stack: Vec<DirFrame>,
+    pending_error: Option<WalkError>,
+}
+
+impl Walk {
+    /// Paths of the regular files in the walk, skipping errors
+    pub fn files(self) -> impl Iterator<Item = PathBuf> {
+        self.filter_map(Result::ok)
+            .filter(|entry| entry.is_file())
+            .map(DirEntry::into_path)
+    }
+
+    fn root_entry(&self, root: PathBuf) -> Result<DirEntry, WalkError> {
+        // The root is always resolved, even when it is a symlink
+        match fs::metadata(&root) {
+            Ok(metadata) => Ok(DirEntry {
+                followed_link: fs::symlink_metadata(&root).is_ok_and(|m| m.file_type().is_symlink()),
+                path: root,
+                depth: 0,
+                file_type: metadata.file_type(),
+            }),
+            Err(error) => Err(WalkError::Io { path: root, depth: 0, error }),
+        }
+    }
+
+    fn child_entry(&self, raw: fs::DirEntry, depth: usize) -> Result<DirEntry, WalkError> {
+        let path = raw.path();
+        let file_type = raw.file_type().map_err(|error| WalkError::Io {
+            path: path.clone(),
+            depth,
+            error,
+        })?;
+
+        if file_type.is_symlink() && self.follow_symlinks {
+            // Broken links are reported as the link itself
+            if let Ok(metadata) = fs::metadata(&path) {
+                return Ok(DirEntry { path, depth, file_type: metadata.file_type(), followed_link: true });
             }
+        }
 
-            let path = Path::new(&current_path);
-            
-            if path.is_file() {
-                all_files.push(current_path);
-                continue;
+        Ok(DirEntry { path, depth, file_type, followed_link: false })
+    }
+
+    /// Push `entry` onto the stack if the walk should descend into it
+    fn descend(&mut self, entry: &DirEntry) -> Result<(), WalkError> {
+        if !entry.is_dir() || self.max_depth.is_some_and(|max| entry.depth >= max) {
+            return Ok(());
+        }
+        if let Some(prune) = self.prune.as_mut() {
+            if prune(entry) {
+                return Ok(());
             }
+        }
 
-            if !path.is_dir() {
-                continue;
+        let canonical = if self.follow_symlinks {
+            let canonical = fs::canonicalize(&entry.path).map_err(|error| WalkError::Io {
+                path: entry.path.clone(),
+                depth: entry.depth,
+                error,
+            })?;
+            if let Some(ancestor) = self.stack.iter().find(|frame| frame.canonical.as_ref() == Some(&canonical)) {
+                return Err(WalkError::SymlinkLoop {
+                    path: entry.path.clone(),
+                    ancestor: ancestor.path.clone(),
+                    depth: entry.depth,
+                });
             }
+            Some(canonical)
+        } else {
+            None
+        };
 
-            match fs::read_dir(&current_path) {
-                Ok(entries) => {
-                    let mut dirs = Vec::new();
-                    
-                    for entry in entries {
-                        if let Ok(entry) = entry {
-                            let entry_path = entry.path();
-                            let path_str = entry_path.to_string_lossy().to_string();
-
-                            if entry_path.is_file() {
-                                all_files.push(path_str);
-                            } else if entry_path.is_dir() {
-                                dirs.push(path_str);
-                            } else if entry_path.is_symlink() && self.follow_symlinks {
-                                if let Ok(target) = fs::read_link(&entry_path) {
-                                    let target_str = target.to_string_lossy().to_string();
-                                    if Path::new(&target_str).is_file() {
-                                        all_files.push(path_str);
-                                    } else if Path::new(&target_str).is_dir() {
-                                        dirs.push(path_str);
-                                    }
-                                }
-                            }
-                        }
-                    }
+        let read_dir = fs::read_dir(&entry.path).map_err(|error| WalkError::Io {
+            path: entry.path.clone(),
+            depth: entry.depth,
+            error,
+        })?;
+        let contents = match self.sort {
+            SortOrder::Unsorted => DirContents::Lazy(read_dir),
+            order => DirContents::Sorted(sorted(read_dir, order)),
+        };
+
+        let ignore = match self.stack.last() {
+            Some(parent) => parent.ignore.enter_directory(&entry.path),
+            None => IgnoreMatcher::new(&entry.path, self.ignore_options),
+        };
+
+        self.stack.push(DirFrame {
+            path: entry.path.clone(),
+            depth: entry.depth,
+            contents,
+            ignore,
+            canonical,
+        });
+        Ok(())
+    }
+
+    /// Descend into `entry` as needed and decide whether to yield it
+    fn visit(&mut self, entry: DirEntry) -> Option<Result<DirEntry, WalkError>> {
+        if let Err(error) = self.descend(&entry) {
+            if matches!(error, WalkError::SymlinkLoop { .. }) {
+                return Some(Err(error));
+            }
+            self.pending_error = Some(error);
+        }
+
+        if let Some(filter) = self.filter.as_mut() {
+            if !filter(&entry) {
+                return self.pending_error.take().map(Err);
+            }
+        }
+        Some(Ok(entry))
+    }
+}
+
+impl Iterator for Walk {
+    type Item = Result<DirEntry, WalkError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(error) = self.pending_error.take() {
+            return Some(Err(error));
+        }
 
-                    // Add directories to stack in reverse order for depth-first traversal
-                    for dir in dirs.into_iter().rev() {
-                        self.stack.push(dir);
+        if let Some(root) = self.root.take() {
+            match self.root_entry(root) {
+                Ok(entry) => {
+                    if let Some(item) = self.visit(entry) {
+                        return Some(item);
                     }
                 }
-                Err(_) => {
-                    // Skip directories we can't read
+                Err(error) => return Some(Err(error)),
+            }
+        }
+
+        loop {
+            let frame = self.stack.last_mut()?;
+            let depth = frame.depth + 1;
+
+            let raw = match frame.contents.next() {
+                Some(Ok(raw)) => raw,
+                Some(Err(error)) => {
+                    return Some(Err(WalkError::Io { path: frame.path.clone(), depth, error }));
+                }
+                None => {
+                    self.stack.pop();
                     continue;
                 }
+            };
+
+            let entry = match self.child_entry(raw, depth) {
+                Ok(entry) => entry,
+                Err(error) => return Some(Err(error)),
+            };
+
+            let frame = self.stack.last()?;
+            if frame.ignore.is_ignored(&entry.path, entry.is_dir()) {
+                continue;
             }
+
+            if let Some(item) = self.visit(entry) {
+                return Some(item);
+            }
+            if let Some(error) = self.pending_error.take() {
+                return Some(Err(error));
+            }
+        }
+    }
+}
+
+fn sorted(read_dir: fs::ReadDir, order: SortOrder) -> std::vec::IntoIter<io::Result<fs::DirEntry>> {
+    let mut entries: Vec<io::Result<fs::DirEntry>> = read_dir.collect();
+    entries.sort_by(|a, b| match (a, b) {
+        (Ok(a), Ok(b)) => {
+            let dirs_first = if order == SortOrder::DirectoriesFirst {
+                let a_dir = a.file_type().is_ok_and(|t| t.is_dir());
+                let b_dir = b.file_type().is_ok_and(|t| t.is_dir());
+                b_dir.cmp(&a_dir)
+            } else {
+                Ordering::Equal
+            };
+            dirs_first.then_with(|| a.file_name().cmp(&b.file_name()))
         }
+        // Report read errors before any entries
+        (Err(_), Ok(_)) => Ordering::Less,
+        (Ok(_), Err(_)) => Ordering::Greater,
+        (Err(_), Err(_)) => Ordering::Equal,
+    });
+    entries.into_iter()
+}
 
-        Ok(all_files)
+/// Collect the files under `walker`, failing only if the root itself can't be read
+fn collect_files(walker: DirectoryWalker) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    let mut files = Vec::new();
+    for entry in walker {
+        match entry {
+            Ok(entry) if entry.is_file() => files.push(entry.into_path()),
+            Ok(_) => {}
+            Err(error) if error.depth() == 0 => return Err(Box::new(error)),
+            Err(_) => {
+                // Skip entries we can't read
+            }
+        }
     }
+    Ok(files)
 }
 
 #[allow(dead_code)]
-pub fn walk_directory(root: &str) -> Result<Vec<String>, Box<dyn std::error::Error>> {
-    let mut walker = DirectoryWalker::new(root);
-    walker.walk()
+pub fn walk_directory<P: AsRef<Path>>(root: P) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    collect_files(DirectoryWalker::new(root))
 }
 
+/// Walk a directory with explicit hidden-file and ignore-file handling
 #[allow(dead_code)]
-pub fn walk_directory_with_depth(root: &str, max_depth: usize) -> Result<Vec<String>, Box<dyn std::error::Error>> {
-    let mut walker = DirectoryWalker::new(root).with_max_depth(max_depth);
-    walker.walk()
+pub fn walk_directory_with_options<P: AsRef<Path>>(root: P, options: IgnoreOptions) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    collect_files(DirectoryWalker::new(root).with_ignore_options(options))
 }
 
 #[allow(dead_code)]
-pub fn find_files_by_extension(root: &str, extension: &str) -> Result<Vec<String>, Box<dyn std::error::Error>> {
-    let all_files = walk_directory(root)?;
-    let extension = extension.to_lowercase();
-    
-    let filtered_files: Vec<String> = all_files
-        .into_iter()
-        .filter(|file| {
-            if let Some(file_ext) = Path::new(file).extension() {
-                file_ext.to_string_lossy().to_lowercase() == extension
-            } else {
-                false
-            }
-        })
-        .collect();
+pub fn walk_directory_with_depth<P: AsRef<Path>>(root: P, max_depth: usize) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    collect_files(DirectoryWalker::new(root).with_max_depth(max_depth))
+}
 
-    Ok(filtered_files)
+#[allow(dead_code)]
+pub fn find_files_by_extension<P: AsRef<Path>>(root: P, extension: &str) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    let extension = extension.to_lowercase();
+    let walker = DirectoryWalker::new(root).with_filter(move |entry| {
+        entry.is_file()
+            && entry
+                .path()
+                .extension()
+                .is_some_and(|ext| ext.to_string_lossy().to_lowercase() == extension)
+    });
+    collect_files(walker)
 }
 
 #[allow(dead_code)]
-pub fn find_files_by_name_pattern(root: &str, pattern: &str) -> Result<Vec<String>, Box<dyn std::error::Error>> {
-    let all_files = walk_directory(root)?;
+pub fn find_files_by_name_pattern<P: AsRef<Path>>(root: P, pattern: &str) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
     let pattern = pattern.to_lowercase();
-    
-    let filtered_files: Vec<String> = all_files
-        .into_iter()
-        .filter(|file| {
-            if let Some(filename) = Path::new(file).file_name() {
-                filename.to_string_lossy().to_lowercase().contains(&pattern)
-            } else {
-                false
-            }
-        })
-        .collect();
+    let walker = DirectoryWalker::new(root).with_filter(move |entry| {
+        entry.is_file() && entry.file_name().to_string_lossy().to_lowercase().contains(&pattern)
+    });
+    collect_files(walker)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn build_tree(root: &Path) {
+        fs::create_dir_all(root.join("a/b/c")).unwrap();
+        fs::create_dir_all(root.join("z")).unwrap();
+        fs::write(root.join("top.txt"), "").unwrap();
+        fs::write(root.join("a/one.rs"), "").unwrap();
+        fs::write(root.join("a/b/two.rs"), "").unwrap();
+        fs::write(root.join("a/b/c/three.rs"), "").unwrap();
+        fs::write(root.join("z/last.txt"), "").unwrap();
+    }
+
+    fn relative(root: &Path, entries: Vec<DirEntry>) -> Vec<String> {
+        entries
+            .iter()
+            .map(|e| e.path().strip_prefix(root).unwrap().to_string_lossy().replace('\\', "/"))
+            .collect()
+    }
+
+    #[test]
+    fn test_depth_and_max_depth() {
+        let dir = tempdir().unwrap();
+        build_tree(dir.path());
+
+        let entries: Vec<DirEntry> = DirectoryWalker::new(dir.path())
+            .with_max_depth(2)
+            .with_sort(SortOrder::Name)
+            .into_iter()
+            .map(Result::unwrap)
+            .collect();
+
+        assert_eq!(entries[0].depth(), 0);
+        assert!(entries.iter().all(|e| e.depth() <= 2));
+        let names = relative(dir.path(), entries);
+        assert_eq!(names, vec!["", "a", "a/b", "a/one.rs", "top.txt", "z", "z/last.txt"]);
+
+        let files = walk_directory_with_depth(dir.path(), 1).unwrap();
+        assert_eq!(files, vec![dir.path().join("top.txt")]);
+    }
+
+    #[test]
+    fn test_sorting_filter_and_prune() {
+        let dir = tempdir().unwrap();
+        build_tree(dir.path());
+
+        let entries: Vec<DirEntry> = DirectoryWalker::new(dir.path())
+            .with_sort(SortOrder::DirectoriesFirst)
+            .with_prune(|e| e.file_name() == "b")
+            .with_filter(|e| e.depth() > 0)
+            .into_iter()
+            .map(Result::unwrap)
+            .collect();
+
+        let names = relative(dir.path(), entries);
+        assert_eq!(names, vec!["a", "a/b", "a/one.rs", "z", "z/last.txt", "top.txt"]);
+    }
 
-    Ok(filtered_files)
-}
\ No newline at end of file
+    #[test]
+    fn test_respects_ignore_files() {
+        let dir = tempdir().unwrap();
+        build_tree(dir.path());
+        fs::write(dir.path().join(".forgeignore"), "c/\n*.txt\n").unwrap();
+
+        let mut files = walk_directory(dir.path()).unwrap();
+        files.sort();
+        assert_eq!(files, vec![dir.path().join("a/b/two.rs"), dir.path().join("a/one.rs")]);
+    }
+
+    #[test]
+    fn test_missing_root_is_an_error() {
+        let dir = tempdir().unwrap();
+        assert!(walk_directory(dir.path().join("missing")).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlink_loop_detection() {
+        let dir = tempdir().unwrap();
+        build_tree(dir.path());
+        std::os::unix::fs::symlink(dir.path().join("a"), dir.path().join("a/b/back")).unwrap();
+
+        let results: Vec<_> = DirectoryWalker::new(dir.path()).follow_symlinks(true).into_iter().collect();
+        let loops: Vec<_> = results
+            .iter()
+            .filter_map(|r| r.as_ref().err())
+            .filter(|e| matches!(e, WalkError::SymlinkLoop { .. }))
+            .collect();
+        assert_eq!(loops.len(), 1);
+        assert_eq!(loops[0].path(), dir.path().join("a/b/back"));
+
+        // Without following, the link is yielded but never descended into
+        let entries: Vec<DirEntry> = DirectoryWalker::new(dir.path()).into_iter().map(Result::unwrap).collect();
+        let link = entries.iter().find(|e| e.file_name() == "back").unwrap();
+        assert!(link.path_is_symlink());
+        assert!(!entries.iter().any(|e| e.path().parent() == Some(link.path())));
+    }
+}
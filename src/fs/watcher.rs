@@ -178,6 +178,8 @@ pub fn watch_files<P: AsRef<Path>>(paths: &[P]) -> Result<Receiver<FileEvent>, B
 }
 
 /// Watch files matching a glob pattern
+///
+/// Hidden files and paths excluded by ignore files are not watched.
 pub fn watch_glob(pattern: &str) -> Result<Receiver<FileEvent>, Box<dyn std::error::Error>> {
     use crate::fs::glob::glob;
     
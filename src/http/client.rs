@@ -157,6 +157,61 @@ impl OllamaClient {
         Ok(models)
     }
 
+    /// Embed each input with `/api/embed`, returning one vector per input
+    pub fn embed(&self, model: &str, inputs: &[String]) -> Result<Vec<Vec<f32>>, Box<dyn std::error::Error>> {
+        // Parse URL
+        let url = format!("{}/api/embed", self.base_url);
+        let url = url.strip_prefix("http://").ok_or("Invalid URL format")?;
+
+        let (host, port) = self.parse_url(url)?;
+        let path = self.extract_path(url, "/api/embed");
+
+        let json_body = json::serialize_embed_request(model, inputs);
+
+        // Create HTTP request
+        let request = HttpRequest::new("POST", path)
+            .with_header("Content-Type", "application/json")
+            .with_header("Accept", "application/json")
+            .with_header("Connection", "close")
+            .with_body(json_body);
+
+        // Connect and send request
+        let mut stream = TcpStream::connect((host, port))?;
+        let request_str = request.to_http_string(host);
+        stream.write_all(request_str.as_bytes())?;
+        stream.flush()?;
+
+        // Parse response
+        let response = HttpResponse::parse(stream)?;
+
+        if response.status_code != 200 {
+            return Err(format!("HTTP error {}: {}", response.status_code, response.status_text).into());
+        }
+
+        let json_response = json::parse_json(&response.body)?;
+        let embeddings = json_response
+            .as_object()
+            .and_then(|obj| obj.get("embeddings"))
+            .and_then(|e| e.as_array())
+            .ok_or("Failed to extract embeddings from JSON")?;
+
+        let vectors: Vec<Vec<f32>> = embeddings
+            .iter()
+            .map(|vector| {
+                vector
+                    .as_array()
+                    .map(|values| values.iter().filter_map(|v| v.as_f64()).map(|v| v as f32).collect())
+                    .unwrap_or_default()
+            })
+            .collect();
+
+        if vectors.len() != inputs.len() {
+            return Err(format!("Expected {} embeddings, got {}", inputs.len(), vectors.len()).into());
+        }
+
+        Ok(vectors)
+    }
+
     // Helper methods to reduce code duplication
     fn parse_url<'a>(&self, url: &'a str) -> Result<(&'a str, u16), Box<dyn std::error::Error>> {
         let (host, port) = if let Some(colon_pos) = url.find(':') {
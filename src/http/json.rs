@@ -77,6 +77,7 @@ impl JsonParser {
                     'n' => string.push('\n'),
                     'r' => string.push('\r'),
                     't' => string.push('\t'),
+                    'u' => string.push(self.parse_unicode_escape()?),
                     _ => return Err(JsonError("Invalid escape sequence".to_string())),
                 }
             } else {
@@ -93,6 +94,30 @@ impl JsonParser {
         Ok(JsonValue::String(string))
     }
 
+    /// Decode the `XXXX` of a `\uXXXX` escape, joining surrogate pairs
+    fn parse_unicode_escape(&mut self) -> Result<char, JsonError> {
+        let high = self.read_hex4()?;
+        if (0xD800..0xDC00).contains(&high)
+            && self.input.get(self.pos + 1) == Some(&'\\')
+            && self.input.get(self.pos + 2) == Some(&'u')
+        {
+            self.pos += 2;
+            let low = self.read_hex4()?;
+            let code = 0x10000 + ((high - 0xD800) << 10) + (low.wrapping_sub(0xDC00) & 0x3FF);
+            return Ok(char::from_u32(code).unwrap_or('\u{FFFD}'));
+        }
+        Ok(char::from_u32(high).unwrap_or('\u{FFFD}'))
+    }
+
+    fn read_hex4(&mut self) -> Result<u32, JsonError> {
+        if self.pos + 4 >= self.input.len() {
+            return Err(JsonError("Invalid unicode escape".to_string()));
+        }
+        let hex: String = self.input[self.pos + 1..self.pos + 5].iter().collect();
+        self.pos += 4;
+        u32::from_str_radix(&hex, 16).map_err(|_| JsonError("Invalid unicode escape".to_string()))
+    }
+
     fn parse_object(&mut self) -> Result<JsonValue, JsonError> {
         if self.input[self.pos] != '{' {
             return Err(JsonError("Expected '{'".to_string()));
@@ -230,6 +255,17 @@ impl JsonParser {
             self.pos += 1;
         }
 
+        // Exponent, as produced for small embedding components like 1.5e-05
+        if self.pos < self.input.len() && (self.input[self.pos] == 'e' || self.input[self.pos] == 'E') {
+            self.pos += 1;
+            if self.pos < self.input.len() && (self.input[self.pos] == '+' || self.input[self.pos] == '-') {
+                self.pos += 1;
+            }
+            while self.pos < self.input.len() && self.input[self.pos].is_ascii_digit() {
+                self.pos += 1;
+            }
+        }
+
         let number_str: String = self.input[start..self.pos].iter().collect();
         match number_str.parse::<f64>() {
             Ok(n) => Ok(JsonValue::Number(n)),
@@ -258,18 +294,114 @@ impl JsonValue {
             _ => None,
         }
     }
+
+    pub fn as_array(&self) -> Option<&Vec<JsonValue>> {
+        match self {
+            JsonValue::Array(arr) => Some(arr),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            JsonValue::Number(n) => Some(*n),
+            _ => None,
+        }
+    }
+}
+
+/// Compact JSON, with object keys sorted so output is stable
+impl fmt::Display for JsonValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            JsonValue::String(s) => write!(f, "{}", escape_string(s)),
+            JsonValue::Number(n) if n.fract() == 0.0 && n.abs() < 1e15 => write!(f, "{}", *n as i64),
+            JsonValue::Number(n) => write!(f, "{}", n),
+            JsonValue::Boolean(b) => write!(f, "{}", b),
+            JsonValue::Null => write!(f, "null"),
+            JsonValue::Array(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, "]")
+            }
+            JsonValue::Object(obj) => {
+                let mut keys: Vec<&String> = obj.keys().collect();
+                keys.sort();
+                write!(f, "{{")?;
+                for (i, key) in keys.into_iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}:{}", escape_string(key), obj[key])?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+/// Quote and escape a string for embedding in a JSON document
+pub fn escape_string(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len() + 2);
+    escaped.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
 }
 
 pub fn serialize_ollama_request(model: &str, prompt: &str, stream: bool) -> String {
     format!(
-        r#"{{"model":"{}","prompt":"{}","stream":{}}}"#,
-        model.replace('"', r#"\""#),
-        prompt.replace('"', r#"\""#).replace('\n', r#"\n"#),
+        r#"{{"model":{},"prompt":{},"stream":{}}}"#,
+        escape_string(model),
+        escape_string(prompt),
         stream
     )
 }
 
+/// Body for Ollama's `/api/embed`, which embeds several inputs in one call
+pub fn serialize_embed_request(model: &str, inputs: &[String]) -> String {
+    let inputs: Vec<String> = inputs.iter().map(|input| escape_string(input)).collect();
+    format!(r#"{{"model":{},"input":[{}]}}"#, escape_string(model), inputs.join(","))
+}
+
 pub fn parse_json(input: &str) -> Result<JsonValue, JsonError> {
     let mut parser = JsonParser::new(input);
     parser.parse()
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_exponents_and_unicode_escapes() {
+        let value = parse_json(r#"{"v":[1.5e-05,-2E+2,3],"s":"caf\u00e9 \ud83d\ude00"}"#).unwrap();
+        let obj = value.as_object().unwrap();
+
+        let numbers: Vec<f64> = obj["v"].as_array().unwrap().iter().filter_map(|v| v.as_f64()).collect();
+        assert_eq!(numbers, vec![1.5e-05, -200.0, 3.0]);
+        assert_eq!(obj["s"].as_string().unwrap(), "café 😀");
+    }
+
+    #[test]
+    fn test_request_escaping_round_trips() {
+        let prompt = "path C:\\tmp\t\"quoted\"\nnext\u{1}";
+        let body = serialize_ollama_request("llama3.2", prompt, false);
+        let parsed = parse_json(&body).unwrap();
+        assert_eq!(parsed.as_object().unwrap()["prompt"].as_string().unwrap(), prompt);
+    }
+}
@@ -2,5 +2,7 @@ pub mod client;
 pub mod json;
 pub mod request;
 pub mod response;
+#[cfg(test)]
+pub mod stub;
 
 pub use client::OllamaClient;
\ No newline at end of file
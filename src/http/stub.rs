@@ -0,0 +1,91 @@
+//! Minimal HTTP server standing in for Ollama in tests.
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::http::client::OllamaClient;
+
+/// A request received by the stub
+#[derive(Debug, Clone)]
+pub struct StubRequest {
+    pub path: String,
+    pub body: String,
+}
+
+type Handler = dyn Fn(&StubRequest) -> String + Send + Sync;
+
+/// Serves every request with a 200 response whose body comes from the handler
+pub struct StubServer {
+    url: String,
+    requests: Arc<Mutex<Vec<StubRequest>>>,
+}
+
+impl StubServer {
+    pub fn start<F>(handler: F) -> Self
+    where
+        F: Fn(&StubRequest) -> String + Send + Sync + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind stub server");
+        let url = format!("http://127.0.0.1:{}", listener.local_addr().unwrap().port());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+
+        let handler: Arc<Handler> = Arc::new(handler);
+        let recorded = Arc::clone(&requests);
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let _ = serve(stream, handler.as_ref(), &recorded);
+            }
+        });
+
+        Self { url, requests }
+    }
+
+    pub fn client(&self) -> OllamaClient {
+        OllamaClient::new(&self.url).unwrap()
+    }
+
+    pub fn requests(&self) -> Vec<StubRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+fn serve(stream: TcpStream, handler: &Handler, recorded: &Mutex<Vec<StubRequest>>) -> std::io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let path = request_line.split_whitespace().nth(1).unwrap_or("/").to_string();
+
+    let mut content_length = 0;
+    loop {
+        let mut header = String::new();
+        reader.read_line(&mut header)?;
+        let header = header.trim();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((key, value)) = header.split_once(':') {
+            if key.trim().eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().unwrap_or(0);
+            }
+        }
+    }
+
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body)?;
+
+    let request = StubRequest { path, body: String::from_utf8_lossy(&body).to_string() };
+    let response = handler(&request);
+    recorded.lock().unwrap().push(request);
+
+    let mut stream = stream;
+    write!(
+        stream,
+        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        response.len(),
+        response
+    )?;
+    stream.flush()
+}
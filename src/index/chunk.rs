@@ -0,0 +1,118 @@
+/// A window of source lines to be embedded
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chunk {
+    /// First line of the chunk, 1-based
+    pub start_line: usize,
+    /// Last line of the chunk, inclusive
+    pub end_line: usize,
+    pub text: String,
+}
+
+/// Splits text into overlapping line windows small enough for an embedding model
+#[derive(Debug, Clone)]
+pub struct Chunker {
+    max_lines: usize,
+    overlap: usize,
+    max_chars: usize,
+}
+
+impl Chunker {
+    pub fn new() -> Self {
+        Self {
+            max_lines: 40,
+            overlap: 8,
+            max_chars: 2000,
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn with_max_lines(mut self, max_lines: usize) -> Self {
+        self.max_lines = max_lines.max(1);
+        self.overlap = self.overlap.min(self.max_lines - 1);
+        self
+    }
+
+    #[allow(dead_code)]
+    pub fn with_overlap(mut self, overlap: usize) -> Self {
+        self.overlap = overlap.min(self.max_lines - 1);
+        self
+    }
+
+    pub fn chunk(&self, text: &str) -> Vec<Chunk> {
+        let lines: Vec<&str> = text.lines().collect();
+        let mut chunks = Vec::new();
+        let mut start = 0;
+
+        while start < lines.len() {
+            // Grow the window until it hits the line or character budget
+            let mut end = start;
+            let mut chars = 0;
+            while end < lines.len() && end - start < self.max_lines {
+                let len = lines[end].len() + 1;
+                if end > start && chars + len > self.max_chars {
+                    break;
+                }
+                chars += len;
+                end += 1;
+            }
+
+            let text = lines[start..end].join("\n");
+            if !text.trim().is_empty() {
+                chunks.push(Chunk {
+                    start_line: start + 1,
+                    end_line: end,
+                    text: truncate(&text, self.max_chars),
+                });
+            }
+
+            if end == lines.len() {
+                break;
+            }
+            start = if end - start > self.overlap { end - self.overlap } else { end };
+        }
+
+        chunks
+    }
+}
+
+impl Default for Chunker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Cut a single overlong line at a character boundary
+fn truncate(text: &str, max_chars: usize) -> String {
+    match text.char_indices().nth(max_chars) {
+        Some((cut, _)) => text[..cut].to_string(),
+        None => text.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_overlapping_windows() {
+        let text: String = (1..=10).map(|i| format!("line {}\n", i)).collect();
+        let chunks = Chunker::new().with_max_lines(4).with_overlap(1).chunk(&text);
+
+        let ranges: Vec<(usize, usize)> = chunks.iter().map(|c| (c.start_line, c.end_line)).collect();
+        assert_eq!(ranges, vec![(1, 4), (4, 7), (7, 10)]);
+        assert_eq!(chunks[0].text, "line 1\nline 2\nline 3\nline 4");
+    }
+
+    #[test]
+    fn test_skips_blank_windows_and_limits_size() {
+        let chunks = Chunker::new().with_max_lines(2).with_overlap(0).chunk("\n\n\nfn main() {}\n");
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].start_line, 3);
+
+        let long_line = "x".repeat(5000);
+        let chunks = Chunker::new().chunk(&format!("{}\nshort\n", long_line));
+        assert_eq!(chunks[0].end_line, 1);
+        assert_eq!(chunks[0].text.len(), 2000);
+        assert_eq!(chunks[1].text, "short");
+    }
+}
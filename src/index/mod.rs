@@ -0,0 +1,301 @@
+pub mod chunk;
+pub mod outline;
+pub mod retrieve;
+pub mod store;
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use crate::fs::ignore::IgnoreOptions;
+use crate::fs::search::is_binary;
+use crate::fs::walker::DirectoryWalker;
+use crate::http::client::OllamaClient;
+
+use chunk::Chunker;
+use store::{content_hash, FileRecord, IndexedChunk, SearchHit, VectorStore};
+
+/// Files larger than this are left out of the index
+const MAX_FILE_SIZE: u64 = 1024 * 1024;
+/// Chunks sent to the embedding endpoint per request
+const EMBED_BATCH_SIZE: usize = 32;
+
+/// What happened to a file during `SemanticIndex::update`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileStatus {
+    Embedded,
+    Unchanged,
+    Skipped,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct IndexStats {
+    pub embedded: usize,
+    pub unchanged: usize,
+    pub skipped: usize,
+    pub removed: usize,
+    pub chunks_embedded: usize,
+}
+
+/// Embedding index of a directory tree, stored under `.forge/index` in its root
+pub struct SemanticIndex {
+    root: PathBuf,
+    store: VectorStore,
+    chunker: Chunker,
+}
+
+impl SemanticIndex {
+    pub fn index_file(root: &Path) -> PathBuf {
+        root.join(".forge").join("index").join("embeddings.bin")
+    }
+
+    /// Open the index for `root`, starting afresh if none exists or it was
+    /// built with a different model
+    pub fn open(root: &Path, model: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let index_file = Self::index_file(root);
+        let store = if index_file.exists() {
+            let store = VectorStore::load(&index_file)?;
+            if store.model() == model { store } else { VectorStore::new(model) }
+        } else {
+            VectorStore::new(model)
+        };
+
+        Ok(Self {
+            root: root.to_path_buf(),
+            store,
+            chunker: Chunker::new(),
+        })
+    }
+
+    /// Open an existing index with whatever model it was built with
+    pub fn open_existing(root: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let index_file = Self::index_file(root);
+        if !index_file.exists() {
+            return Err(format!("No index found in {}; run `forge index` first", root.display()).into());
+        }
+
+        Ok(Self {
+            root: root.to_path_buf(),
+            store: VectorStore::load(&index_file)?,
+            chunker: Chunker::new(),
+        })
+    }
+
+    pub fn model(&self) -> &str {
+        self.store.model()
+    }
+
+    pub fn store(&self) -> &VectorStore {
+        &self.store
+    }
+
+    pub fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+        self.store.save(&Self::index_file(&self.root))
+    }
+
+    /// Bring the index up to date with the files under the root.
+    ///
+    /// Files whose mtime and size are unchanged are not read; files whose
+    /// contents hash the same are not re-embedded. `on_file` is called after
+    /// each file with its position in the walk. If embedding fails part way,
+    /// the files processed so far stay in the index so a later run resumes.
+    pub fn update<F>(&mut self, client: &OllamaClient, ignore_options: IgnoreOptions, mut on_file: F) -> Result<IndexStats, Box<dyn std::error::Error>>
+    where
+        F: FnMut(usize, usize, &Path, FileStatus),
+    {
+        let files: Vec<PathBuf> = DirectoryWalker::new(&self.root)
+            .with_ignore_options(ignore_options)
+            .into_iter()
+            .files()
+            .collect();
+
+        let mut stats = IndexStats::default();
+        let mut seen = HashSet::new();
+
+        for (i, path) in files.iter().enumerate() {
+            let relative = path.strip_prefix(&self.root).unwrap_or(path).to_path_buf();
+            let status = self.update_file(client, path, &relative, &mut stats)?;
+            if status != FileStatus::Skipped {
+                seen.insert(relative.clone());
+            }
+            on_file(i + 1, files.len(), &relative, status);
+        }
+
+        stats.removed = self.store.retain(|path| seen.contains(path));
+        Ok(stats)
+    }
+
+    fn update_file(&mut self, client: &OllamaClient, path: &Path, relative: &Path, stats: &mut IndexStats) -> Result<FileStatus, Box<dyn std::error::Error>> {
+        let metadata = match fs::metadata(path) {
+            Ok(metadata) => metadata,
+            Err(_) => {
+                stats.skipped += 1;
+                return Ok(FileStatus::Skipped);
+            }
+        };
+        let size = metadata.len();
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(0);
+
+        if size > MAX_FILE_SIZE {
+            stats.skipped += 1;
+            return Ok(FileStatus::Skipped);
+        }
+
+        if let Some(record) = self.store.file(relative) {
+            if record.modified == modified && record.size == size {
+                stats.unchanged += 1;
+                return Ok(FileStatus::Unchanged);
+            }
+        }
+
+        let bytes = match fs::read(path) {
+            Ok(bytes) => bytes,
+            Err(_) => {
+                stats.skipped += 1;
+                return Ok(FileStatus::Skipped);
+            }
+        };
+        if is_binary(&bytes) {
+            stats.skipped += 1;
+            return Ok(FileStatus::Skipped);
+        }
+
+        let hash = content_hash(&bytes);
+        if let Some(record) = self.store.file_mut(relative) {
+            if record.hash == hash {
+                // Touched but not edited
+                record.modified = modified;
+                record.size = size;
+                stats.unchanged += 1;
+                return Ok(FileStatus::Unchanged);
+            }
+        }
+
+        let text = String::from_utf8_lossy(&bytes);
+        let chunks = self.chunker.chunk(&text);
+        let mut indexed = Vec::with_capacity(chunks.len());
+
+        for batch in chunks.chunks(EMBED_BATCH_SIZE) {
+            // Lead with the path so the model sees where the code lives
+            let inputs: Vec<String> = batch
+                .iter()
+                .map(|chunk| format!("{}\n{}", relative.display(), chunk.text))
+                .collect();
+            let vectors = client.embed(self.store.model(), &inputs)?;
+
+            for (chunk, vector) in batch.iter().zip(vectors) {
+                indexed.push(IndexedChunk {
+                    start_line: chunk.start_line,
+                    end_line: chunk.end_line,
+                    text: chunk.text.clone(),
+                    vector,
+                });
+            }
+        }
+
+        stats.embedded += 1;
+        stats.chunks_embedded += indexed.len();
+        self.store.insert(relative.to_path_buf(), FileRecord { modified, size, hash, chunks: indexed });
+        Ok(FileStatus::Embedded)
+    }
+
+    /// Embed `question` and return the closest chunks
+    pub fn query(&self, client: &OllamaClient, question: &str, top_k: usize) -> Result<Vec<SearchHit<'_>>, Box<dyn std::error::Error>> {
+        let vector = client
+            .embed(self.store.model(), &[question.to_string()])?
+            .into_iter()
+            .next()
+            .ok_or("Embedding endpoint returned no vector")?;
+        Ok(self.store.search(&vector, top_k))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::json;
+    use crate::http::stub::StubServer;
+    use tempfile::tempdir;
+
+    /// Embeds text as normalized letter counts, so similar words score alike
+    fn letter_embeddings(body: &str) -> String {
+        let request = json::parse_json(body).unwrap();
+        let inputs = request.as_object().unwrap().get("input").unwrap().as_array().unwrap();
+
+        let vectors: Vec<String> = inputs
+            .iter()
+            .map(|input| {
+                let mut counts = [0u32; 26];
+                for c in input.as_string().unwrap().to_lowercase().chars() {
+                    if c.is_ascii_lowercase() {
+                        counts[(c as u8 - b'a') as usize] += 1;
+                    }
+                }
+                let values: Vec<String> = counts.iter().map(|n| n.to_string()).collect();
+                format!("[{}]", values.join(","))
+            })
+            .collect();
+        format!(r#"{{"model":"stub","embeddings":[{}]}}"#, vectors.join(","))
+    }
+
+    #[test]
+    fn test_index_and_query_with_stub_server() {
+        let server = StubServer::start(|request| letter_embeddings(&request.body));
+        let client = server.client();
+
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("http.rs"), "parse status line zzz zzz zzz\n").unwrap();
+        fs::write(dir.path().join("math.rs"), "qqq xxx qqq xxx\n").unwrap();
+        fs::write(dir.path().join("blob.bin"), b"\0\x01\x02").unwrap();
+
+        let mut index = SemanticIndex::open(dir.path(), "stub").unwrap();
+        let stats = index.update(&client, IgnoreOptions::default(), |_, _, _, _| {}).unwrap();
+        assert_eq!((stats.embedded, stats.skipped, stats.chunks_embedded), (2, 1, 2));
+        index.save().unwrap();
+
+        let index = SemanticIndex::open_existing(dir.path()).unwrap();
+        let hits = index.query(&client, "zzz", 1).unwrap();
+        assert_eq!(hits[0].path, Path::new("http.rs"));
+        assert_eq!((hits[0].chunk.start_line, hits[0].chunk.end_line), (1, 1));
+    }
+
+    #[test]
+    fn test_incremental_update() {
+        let server = StubServer::start(|request| letter_embeddings(&request.body));
+        let client = server.client();
+
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("a.rs"), "fn a() {}\n").unwrap();
+        fs::write(dir.path().join("b.rs"), "fn b() {}\n").unwrap();
+
+        let mut index = SemanticIndex::open(dir.path(), "stub").unwrap();
+        index.update(&client, IgnoreOptions::default(), |_, _, _, _| {}).unwrap();
+        index.save().unwrap();
+        assert_eq!(server.requests().len(), 2);
+        assert!(server.requests().iter().all(|r| r.path == "/api/embed"));
+
+        // Nothing changed: no embedding requests
+        let mut index = SemanticIndex::open(dir.path(), "stub").unwrap();
+        let stats = index.update(&client, IgnoreOptions::default(), |_, _, _, _| {}).unwrap();
+        assert_eq!((stats.embedded, stats.unchanged), (0, 2));
+        assert_eq!(server.requests().len(), 2);
+
+        // One edit and one deletion
+        fs::write(dir.path().join("a.rs"), "fn a() { changed() }\n").unwrap();
+        fs::remove_file(dir.path().join("b.rs")).unwrap();
+        let stats = index.update(&client, IgnoreOptions::default(), |_, _, _, _| {}).unwrap();
+        assert_eq!((stats.embedded, stats.removed), (1, 1));
+        assert_eq!(server.requests().len(), 3);
+        assert_eq!(index.store().file_count(), 1);
+
+        // A different model starts over
+        let index = SemanticIndex::open(dir.path(), "other").unwrap();
+        assert_eq!(index.store().file_count(), 0);
+    }
+}
@@ -0,0 +1,861 @@
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::fs::search::is_binary;
+
+/// Languages the outline extractor understands
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Language {
+    Rust,
+    Python,
+    JavaScript,
+    TypeScript,
+    Go,
+}
+
+impl Language {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_string_lossy().to_lowercase();
+        match extension.as_str() {
+            "rs" => Some(Language::Rust),
+            "py" | "pyi" => Some(Language::Python),
+            "js" | "jsx" | "mjs" | "cjs" => Some(Language::JavaScript),
+            "ts" | "tsx" | "mts" | "cts" => Some(Language::TypeScript),
+            "go" => Some(Language::Go),
+            _ => None,
+        }
+    }
+
+    fn is_javascript_like(self) -> bool {
+        matches!(self, Language::JavaScript | Language::TypeScript)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolKind {
+    Function,
+    Method,
+    Struct,
+    Enum,
+    Class,
+    Interface,
+    Trait,
+    Impl,
+    Module,
+    Type,
+    Macro,
+}
+
+impl SymbolKind {
+    /// Kinds whose functions are methods
+    fn holds_methods(self) -> bool {
+        matches!(self, SymbolKind::Impl | SymbolKind::Trait | SymbolKind::Class | SymbolKind::Interface)
+    }
+}
+
+impl fmt::Display for SymbolKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let label = match self {
+            SymbolKind::Function => "fn",
+            SymbolKind::Method => "method",
+            SymbolKind::Struct => "struct",
+            SymbolKind::Enum => "enum",
+            SymbolKind::Class => "class",
+            SymbolKind::Interface => "interface",
+            SymbolKind::Trait => "trait",
+            SymbolKind::Impl => "impl",
+            SymbolKind::Module => "mod",
+            SymbolKind::Type => "type",
+            SymbolKind::Macro => "macro",
+        };
+        write!(f, "{}", label)
+    }
+}
+
+/// A declaration and the lines it spans (1-based, inclusive)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    pub start_line: usize,
+    pub end_line: usize,
+    /// Enclosing symbol, or the receiver type of a Go method
+    pub parent: Option<String>,
+    /// Nesting level in the outline, 0 for top-level declarations
+    pub depth: usize,
+}
+
+/// Extract the declarations in `source`
+pub fn extract_outline(source: &str, language: Language) -> Vec<Symbol> {
+    match language {
+        Language::Python => extract_python(source),
+        _ => extract_braced(source, language),
+    }
+}
+
+/// Outline of a source file, or `None` if its language isn't supported or it's binary
+pub fn outline_file(path: &Path) -> Result<Option<Vec<Symbol>>, Box<dyn std::error::Error>> {
+    let language = match Language::from_path(path) {
+        Some(language) => language,
+        None => return Ok(None),
+    };
+    let bytes = fs::read(path)?;
+    if is_binary(&bytes) {
+        return Ok(None);
+    }
+    Ok(Some(extract_outline(&String::from_utf8_lossy(&bytes), language)))
+}
+
+/// Symbols named `query` across `files`, exact (case-insensitive) matches
+/// first, then names containing it
+pub fn find_symbols(files: &[PathBuf], query: &str) -> Vec<(PathBuf, Symbol)> {
+    let query = query.to_lowercase();
+    let mut exact = Vec::new();
+    let mut partial = Vec::new();
+
+    for path in files {
+        let symbols = match outline_file(path) {
+            Ok(Some(symbols)) => symbols,
+            _ => continue,
+        };
+        for symbol in symbols {
+            let name = symbol.name.to_lowercase();
+            if name == query {
+                exact.push((path.clone(), symbol));
+            } else if name.contains(&query) {
+                partial.push((path.clone(), symbol));
+            }
+        }
+    }
+
+    exact.extend(partial);
+    exact
+}
+
+/// Compact listing of the declarations in each file, small enough to hand
+/// to a model so it can choose which files to read. Stops before `budget`
+/// characters.
+pub fn repo_map(root: &Path, files: &[PathBuf], budget: usize) -> String {
+    let mut map = String::new();
+
+    for path in files {
+        let symbols = match outline_file(path) {
+            Ok(Some(symbols)) if !symbols.is_empty() => symbols,
+            _ => continue,
+        };
+
+        let mut entry = format!("{}\n", path.strip_prefix(root).unwrap_or(path).display());
+        for symbol in symbols.iter().filter(|s| s.depth <= 1) {
+            entry.push_str(&format!("{}{} {}\n", "  ".repeat(symbol.depth + 1), symbol.kind, symbol.name));
+        }
+
+        if map.len() + entry.len() > budget {
+            break;
+        }
+        map.push_str(&entry);
+    }
+
+    map
+}
+
+// ---------------------------------------------------------------------------
+// Brace-delimited languages: Rust, JavaScript/TypeScript and Go
+
+fn extract_braced(source: &str, language: Language) -> Vec<Symbol> {
+    let code = strip_comments_and_strings(source, language);
+    let mut symbols: Vec<Symbol> = Vec::new();
+    // (end line, index into symbols) of the declarations enclosing the current line
+    let mut open: Vec<(usize, usize)> = Vec::new();
+
+    for (index, line) in code.iter().enumerate() {
+        let line_number = index + 1;
+        while open.last().is_some_and(|(end, _)| *end < line_number) {
+            open.pop();
+        }
+
+        let parent = open.last().map(|(_, i)| &symbols[*i]);
+        let declaration = match language {
+            Language::Rust => parse_rust(line),
+            Language::Go => parse_go(line),
+            _ => parse_javascript(line, parent.map(|p| p.kind)),
+        };
+        let (mut kind, name, receiver) = match declaration {
+            Some(declaration) => declaration,
+            None => continue,
+        };
+
+        if kind == SymbolKind::Function && parent.is_some_and(|p| p.kind.holds_methods()) {
+            kind = SymbolKind::Method;
+        }
+
+        let end_line = block_end(&code, index).unwrap_or(line_number);
+        let symbol = Symbol {
+            name,
+            kind,
+            start_line: line_number,
+            end_line,
+            parent: receiver.or_else(|| parent.map(|p| p.name.clone())),
+            depth: open.len(),
+        };
+
+        symbols.push(symbol);
+        if end_line > line_number {
+            open.push((end_line, symbols.len() - 1));
+        }
+    }
+
+    symbols
+}
+
+/// Line where the block opened by the declaration on `start` closes. A `;`
+/// before any `{` means the declaration has no body.
+fn block_end(code: &[String], start: usize) -> Option<usize> {
+    let mut nesting = 0i32;
+    let mut braces = 0i32;
+    let mut opened = false;
+
+    for (index, line) in code.iter().enumerate().skip(start) {
+        for c in line.chars() {
+            match c {
+                '(' | '[' => nesting += 1,
+                ')' | ']' => nesting -= 1,
+                '{' => {
+                    braces += 1;
+                    opened = true;
+                }
+                '}' if opened => {
+                    braces -= 1;
+                    if braces == 0 {
+                        return Some(index + 1);
+                    }
+                }
+                ';' if !opened && nesting <= 0 => return Some(index + 1),
+                _ => {}
+            }
+        }
+        // Give up on declarations that never open a body
+        if !opened && index > start + 20 {
+            return None;
+        }
+    }
+
+    None
+}
+
+fn strip_prefixes<'a>(mut line: &'a str, prefixes: &[&str]) -> &'a str {
+    loop {
+        let before = line;
+        for prefix in prefixes {
+            if let Some(rest) = line.strip_prefix(prefix) {
+                line = rest.trim_start();
+            }
+        }
+        if line == before {
+            return line;
+        }
+    }
+}
+
+fn identifier(text: &str) -> Option<String> {
+    let text = text.trim_start();
+    let end = text
+        .char_indices()
+        .find(|(_, c)| !(c.is_alphanumeric() || *c == '_' || *c == '$' || *c == '#'))
+        .map(|(i, _)| i)
+        .unwrap_or(text.len());
+    if end == 0 {
+        None
+    } else {
+        Some(text[..end].to_string())
+    }
+}
+
+type Declaration = (SymbolKind, String, Option<String>);
+
+fn parse_rust(line: &str) -> Option<Declaration> {
+    let mut rest = line.trim_start();
+
+    // Visibility, including pub(crate) and pub(in path)
+    if let Some(after) = rest.strip_prefix("pub(") {
+        rest = after.split_once(')').map(|(_, r)| r.trim_start())?;
+    }
+    rest = strip_prefixes(rest, &["pub ", "default ", "async ", "unsafe ", "extern "]);
+    // extern "C" leaves an (already blanked) ABI string behind
+    if rest.starts_with('"') {
+        rest = rest[1..].split_once('"').map(|(_, r)| r.trim_start())?;
+    }
+    if let Some(after) = rest.strip_prefix("const ") {
+        // const fn, but not const items
+        if after.trim_start().starts_with("fn ") || after.trim_start().starts_with("unsafe ") {
+            rest = strip_prefixes(after, &["unsafe ", "async "]);
+        }
+    }
+
+    let keywords = [
+        ("fn ", SymbolKind::Function),
+        ("struct ", SymbolKind::Struct),
+        ("enum ", SymbolKind::Enum),
+        ("union ", SymbolKind::Struct),
+        ("trait ", SymbolKind::Trait),
+        ("mod ", SymbolKind::Module),
+        ("type ", SymbolKind::Type),
+    ];
+    for (keyword, kind) in keywords {
+        if let Some(after) = rest.strip_prefix(keyword) {
+            return identifier(after).map(|name| (kind, name, None));
+        }
+    }
+
+    if let Some(after) = rest.strip_prefix("macro_rules!") {
+        return identifier(after).map(|name| (SymbolKind::Macro, name, None));
+    }
+
+    if rest.starts_with("impl<") || rest.starts_with("impl ") {
+        let after = skip_generics(&rest[4..]);
+        let header = after.split('{').next().unwrap_or("");
+        let header = header.split(" where").next().unwrap_or(header);
+        let name = header.split_whitespace().collect::<Vec<_>>().join(" ");
+        if !name.is_empty() {
+            return Some((SymbolKind::Impl, name, None));
+        }
+    }
+
+    None
+}
+
+/// Skip a leading `<...>` generic parameter list
+fn skip_generics(text: &str) -> &str {
+    let text = text.trim_start();
+    if !text.starts_with('<') {
+        return text;
+    }
+    let mut depth = 0;
+    for (i, c) in text.char_indices() {
+        match c {
+            '<' => depth += 1,
+            '>' => {
+                depth -= 1;
+                if depth == 0 {
+                    return text[i + 1..].trim_start();
+                }
+            }
+            _ => {}
+        }
+    }
+    ""
+}
+
+fn parse_go(line: &str) -> Option<Declaration> {
+    // Only top-level declarations start in column zero in gofmt'd code
+    if let Some(after) = line.strip_prefix("func ") {
+        let after = after.trim_start();
+        if let Some(receiver) = after.strip_prefix('(') {
+            let (receiver, rest) = receiver.split_once(')')?;
+            let receiver_type = receiver
+                .split_whitespace()
+                .last()?
+                .trim_start_matches('*')
+                .split('[')
+                .next()?
+                .to_string();
+            return identifier(rest).map(|name| (SymbolKind::Method, name, Some(receiver_type)));
+        }
+        return identifier(after).map(|name| (SymbolKind::Function, name, None));
+    }
+
+    if let Some(after) = line.strip_prefix("type ") {
+        let name = identifier(after)?;
+        let rest = after.trim_start()[name.len()..].trim_start();
+        let rest = skip_brackets(rest);
+        let kind = if rest.starts_with("struct") {
+            SymbolKind::Struct
+        } else if rest.starts_with("interface") {
+            SymbolKind::Interface
+        } else {
+            SymbolKind::Type
+        };
+        return Some((kind, name, None));
+    }
+
+    None
+}
+
+/// Skip Go type parameters like `[T any]`
+fn skip_brackets(text: &str) -> &str {
+    if text.starts_with('[') {
+        text.split_once(']').map(|(_, r)| r.trim_start()).unwrap_or(text)
+    } else {
+        text
+    }
+}
+
+const JS_NON_METHODS: &[&str] = &[
+    "if", "for", "while", "switch", "catch", "return", "function", "new", "typeof", "await", "else", "do", "super",
+];
+
+fn parse_javascript(line: &str, parent: Option<SymbolKind>) -> Option<Declaration> {
+    let rest = strip_prefixes(line.trim_start(), &["export ", "default ", "declare ", "abstract ", "async "]);
+
+    if let Some(after) = rest.strip_prefix("function") {
+        let after = after.trim_start_matches('*');
+        if after.starts_with(' ') || after.starts_with('(') {
+            let name = identifier(after).unwrap_or_else(|| "default".to_string());
+            return Some((SymbolKind::Function, name, None));
+        }
+    }
+
+    let keywords = [
+        ("class ", SymbolKind::Class),
+        ("interface ", SymbolKind::Interface),
+        ("enum ", SymbolKind::Enum),
+        ("const enum ", SymbolKind::Enum),
+        ("namespace ", SymbolKind::Module),
+        ("module ", SymbolKind::Module),
+    ];
+    for (keyword, kind) in keywords {
+        if let Some(after) = rest.strip_prefix(keyword) {
+            return identifier(after).map(|name| (kind, name, None));
+        }
+    }
+
+    if let Some(after) = rest.strip_prefix("type ") {
+        let name = identifier(after)?;
+        let remainder = after.trim_start()[name.len()..].trim_start();
+        if remainder.starts_with('=') || remainder.starts_with('<') {
+            return Some((SymbolKind::Type, name, None));
+        }
+    }
+
+    // const handler = (...) => ..., const handler = function ...
+    for keyword in ["const ", "let ", "var "] {
+        if let Some(after) = rest.strip_prefix(keyword) {
+            let name = identifier(after)?;
+            let value = after.split_once('=')?.1.trim_start();
+            let value = value.strip_prefix("async").map(str::trim_start).unwrap_or(value);
+            if value.starts_with("function") || value.contains("=>") {
+                return Some((SymbolKind::Function, name, None));
+            }
+            return None;
+        }
+    }
+
+    // Methods only make sense directly inside a class body
+    if matches!(parent, Some(SymbolKind::Class)) {
+        let member = strip_prefixes(rest, &[
+            "static ", "public ", "private ", "protected ", "readonly ", "override ", "get ", "set ", "async ", "*",
+        ]);
+        let name = identifier(member)?;
+        let after = member[name.len()..].trim_start();
+        if (after.starts_with('(') || after.starts_with('<')) && !JS_NON_METHODS.contains(&name.as_str()) {
+            return Some((SymbolKind::Method, name, None));
+        }
+    }
+
+    None
+}
+
+/// Blank out comments and the contents of string literals, keeping line
+/// structure and quote characters, so braces and keywords inside them are
+/// not mistaken for code
+fn strip_comments_and_strings(source: &str, language: Language) -> Vec<String> {
+    #[derive(PartialEq)]
+    enum State {
+        Code,
+        LineComment,
+        BlockComment(usize),
+        Str(char),
+        RawStr(usize),
+    }
+
+    let chars: Vec<char> = source.chars().collect();
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    let mut state = State::Code;
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+
+        if c == '\n' {
+            lines.push(std::mem::take(&mut line));
+            if state == State::LineComment {
+                state = State::Code;
+            }
+            i += 1;
+            continue;
+        }
+
+        match state {
+            State::Code => {
+                if c == '/' && next == Some('/') {
+                    state = State::LineComment;
+                    line.push(' ');
+                } else if c == '/' && next == Some('*') {
+                    state = State::BlockComment(1);
+                    line.push_str("  ");
+                    i += 1;
+                } else if language == Language::Rust && c == 'r' && is_raw_string_start(&chars, i) {
+                    let hashes = chars[i + 1..].iter().take_while(|&&h| h == '#').count();
+                    state = State::RawStr(hashes);
+                    line.push('"');
+                    i += hashes + 1;
+                } else if c == '"' || (c == '`' && language != Language::Rust) {
+                    state = State::Str(c);
+                    line.push(c);
+                } else if c == '\'' {
+                    if language == Language::Rust && !is_rust_char_literal(&chars, i) {
+                        // A lifetime
+                        line.push(c);
+                    } else {
+                        state = State::Str(c);
+                        line.push(c);
+                    }
+                } else {
+                    line.push(c);
+                }
+            }
+            State::LineComment => line.push(' '),
+            State::BlockComment(depth) => {
+                if c == '*' && next == Some('/') {
+                    state = if depth == 1 { State::Code } else { State::BlockComment(depth - 1) };
+                    line.push_str("  ");
+                    i += 1;
+                } else if language == Language::Rust && c == '/' && next == Some('*') {
+                    state = State::BlockComment(depth + 1);
+                    line.push_str("  ");
+                    i += 1;
+                } else {
+                    line.push(' ');
+                }
+            }
+            State::Str(quote) => {
+                if c == '\\' && (quote != '`' || language.is_javascript_like()) {
+                    line.push_str("  ");
+                    if next.is_some_and(|n| n != '\n') {
+                        i += 1;
+                    }
+                } else if c == quote {
+                    state = State::Code;
+                    line.push(c);
+                } else {
+                    line.push(' ');
+                }
+            }
+            State::RawStr(hashes) => {
+                let closes = c == '"' && chars[i + 1..].iter().take(hashes).filter(|&&h| h == '#').count() == hashes;
+                if closes {
+                    state = State::Code;
+                    line.push('"');
+                    i += hashes;
+                } else {
+                    line.push(' ');
+                }
+            }
+        }
+        i += 1;
+    }
+
+    lines.push(line);
+    lines
+}
+
+fn is_raw_string_start(chars: &[char], i: usize) -> bool {
+    let preceded_by_identifier = i > 0 && (chars[i - 1].is_alphanumeric() || chars[i - 1] == '_');
+    if preceded_by_identifier {
+        return false;
+    }
+    let hashes = chars[i + 1..].iter().take_while(|&&h| h == '#').count();
+    chars.get(i + 1 + hashes) == Some(&'"')
+}
+
+/// Distinguish `'a'` and `'\n'` from the lifetime `'a`
+fn is_rust_char_literal(chars: &[char], i: usize) -> bool {
+    match chars.get(i + 1) {
+        Some('\\') => true,
+        Some(_) => chars.get(i + 2) == Some(&'\''),
+        None => false,
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Python: blocks end where indentation returns to the declaration's level
+
+fn extract_python(source: &str) -> Vec<Symbol> {
+    let code = strip_python(source);
+    let indents: Vec<Option<usize>> = code
+        .iter()
+        .map(|line| {
+            if line.trim().is_empty() {
+                None
+            } else {
+                Some(line.len() - line.trim_start().len())
+            }
+        })
+        .collect();
+
+    let mut symbols: Vec<Symbol> = Vec::new();
+    // (indent, index into symbols) of enclosing class and def blocks
+    let mut open: Vec<(usize, usize)> = Vec::new();
+
+    for (index, line) in code.iter().enumerate() {
+        let indent = match indents[index] {
+            Some(indent) => indent,
+            None => continue,
+        };
+        while open.last().is_some_and(|(open_indent, _)| *open_indent >= indent) {
+            open.pop();
+        }
+
+        let rest = line.trim_start();
+        let rest = rest.strip_prefix("async ").map(str::trim_start).unwrap_or(rest);
+        let (kind, after) = if let Some(after) = rest.strip_prefix("def ") {
+            (SymbolKind::Function, after)
+        } else if let Some(after) = rest.strip_prefix("class ") {
+            (SymbolKind::Class, after)
+        } else {
+            continue;
+        };
+        let name = match identifier(after) {
+            Some(name) => name,
+            None => continue,
+        };
+
+        // The block runs to the last line indented deeper than the header
+        let mut end = index;
+        for (offset, line_indent) in indents.iter().enumerate().skip(index + 1) {
+            match line_indent {
+                Some(line_indent) if *line_indent <= indent => break,
+                Some(_) => end = offset,
+                None => {}
+            }
+        }
+
+        let parent = open.last().map(|(_, i)| &symbols[*i]);
+        let kind = if kind == SymbolKind::Function && parent.is_some_and(|p| p.kind == SymbolKind::Class) {
+            SymbolKind::Method
+        } else {
+            kind
+        };
+
+        symbols.push(Symbol {
+            name,
+            kind,
+            start_line: index + 1,
+            end_line: end + 1,
+            parent: parent.map(|p| p.name.clone()),
+            depth: open.len(),
+        });
+        open.push((indent, symbols.len() - 1));
+    }
+
+    symbols
+}
+
+/// Blank out comments and string contents (including triple-quoted strings)
+fn strip_python(source: &str) -> Vec<String> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    // Quote character and whether it is tripled
+    let mut string: Option<(char, bool)> = None;
+    let mut comment = false;
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        if c == '\n' {
+            lines.push(std::mem::take(&mut line));
+            comment = false;
+            // Unterminated single-quoted strings end at the line break
+            if string.is_some_and(|(_, triple)| !triple) {
+                string = None;
+            }
+            i += 1;
+            continue;
+        }
+
+        if comment {
+            line.push(' ');
+        } else if let Some((quote, triple)) = string {
+            if c == '\\' {
+                line.push(' ');
+                if chars.get(i + 1).is_some_and(|&n| n != '\n') {
+                    line.push(' ');
+                    i += 1;
+                }
+            } else if c == quote && (!triple || (chars.get(i + 1) == Some(&quote) && chars.get(i + 2) == Some(&quote))) {
+                string = None;
+                line.push(c);
+                if triple {
+                    line.push_str("  ");
+                    i += 2;
+                }
+            } else {
+                line.push(' ');
+            }
+        } else if c == '#' {
+            comment = true;
+            line.push(' ');
+        } else if c == '"' || c == '\'' {
+            let triple = chars.get(i + 1) == Some(&c) && chars.get(i + 2) == Some(&c);
+            string = Some((c, triple));
+            line.push(c);
+            if triple {
+                line.push_str("  ");
+                i += 2;
+            }
+        } else {
+            line.push(c);
+        }
+        i += 1;
+    }
+
+    lines.push(line);
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summary(symbols: &[Symbol]) -> Vec<String> {
+        symbols
+            .iter()
+            .map(|s| format!("{}{} {} {}-{}", "  ".repeat(s.depth), s.kind, s.name, s.start_line, s.end_line))
+            .collect()
+    }
+
+    #[test]
+    fn test_rust_outline() {
+        let source = r##"
+pub(crate) mod http {
+    pub struct Client<'a> { base: &'a str }
+
+    impl<'a> fmt::Display for Client<'a> {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            let brace = '{';
+            write!(f, "}} {}", r#"fn fake() {"#)
+        }
+    }
+}
+
+/* fn commented() { */
+pub trait Handler {
+    fn handle(&self);
+    async fn ready(&self) -> bool { true }
+}
+
+macro_rules! retry { () => {}; }
+
+pub const fn answer() -> u32 {
+    42
+}
+"##;
+        let outline = extract_outline(source, Language::Rust);
+        assert_eq!(summary(&outline), vec![
+            "mod http 2-11",
+            "  struct Client 3-3",
+            "  impl fmt::Display for Client<'a> 5-10",
+            "    method fmt 6-9",
+            "trait Handler 14-17",
+            "  method handle 15-15",
+            "  method ready 16-16",
+            "macro retry 19-19",
+            "fn answer 21-23",
+        ]);
+        assert_eq!(outline[3].parent.as_deref(), Some("fmt::Display for Client<'a>"));
+    }
+
+    #[test]
+    fn test_python_outline() {
+        let source = r#"
+import os
+
+class Store:
+    """A store.
+
+def not_a_function():
+    """
+
+    def get(self, key):
+        # def also_not(self):
+        return self.items[key]
+
+    async def refresh(self):
+        pass
+
+def main():
+    Store().get("x")
+"#;
+        let outline = extract_outline(source, Language::Python);
+        assert_eq!(summary(&outline), vec![
+            "class Store 4-15",
+            "  method get 10-12",
+            "  method refresh 14-15",
+            "fn main 17-18",
+        ]);
+    }
+
+    #[test]
+    fn test_javascript_and_typescript_outline() {
+        let source = r#"
+export default class Api extends Base {
+  static async fetch(url) {
+    if (ok) { return `}`; }
+  }
+  get name() { return "{"; }
+}
+
+export interface Options { retries: number }
+export type Handler = (req: Request) => void;
+const handle = async (req) => {
+  return null;
+};
+function* ids() {}
+"#;
+        let outline = extract_outline(source, Language::TypeScript);
+        assert_eq!(summary(&outline), vec![
+            "class Api 2-7",
+            "  method fetch 3-5",
+            "  method name 6-6",
+            "interface Options 9-9",
+            "type Handler 10-10",
+            "fn handle 11-13",
+            "fn ids 14-14",
+        ]);
+    }
+
+    #[test]
+    fn test_go_outline() {
+        let source = r#"
+package server
+
+type Server struct {
+	addr string
+}
+
+type Handler interface {
+	Serve() error
+}
+
+func (s *Server) Start() error {
+	s.log(`{`)
+	return nil
+}
+
+func New[T any](addr string) *Server {
+	return &Server{addr: addr}
+}
+"#;
+        let outline = extract_outline(source, Language::Go);
+        assert_eq!(summary(&outline), vec![
+            "struct Server 4-6",
+            "interface Handler 8-10",
+            "method Start 12-15",
+            "fn New 17-19",
+        ]);
+        assert_eq!(outline[2].parent.as_deref(), Some("Server"));
+    }
+}
@@ -0,0 +1,447 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::fs::search::{ParallelSearch, SearchEvent, TextSearcher};
+use crate::http::client::OllamaClient;
+use crate::index::SemanticIndex;
+use crate::index::outline::{extract_outline, Language};
+
+/// Lines of context kept around each keyword hit
+const CONTEXT_LINES: usize = 8;
+/// Nearby hits are merged into one window up to this many lines
+const MAX_WINDOW_LINES: usize = 40;
+/// Keyword windows read from disk before packing
+const MAX_KEYWORD_SNIPPETS: usize = 24;
+/// Rough size of a token, used to turn the context window into a character budget
+const CHARS_PER_TOKEN: usize = 4;
+
+const STOP_WORDS: &[&str] = &[
+    "the", "and", "for", "are", "but", "not", "you", "all", "any", "can", "how", "what", "where",
+    "when", "why", "who", "which", "does", "did", "this", "that", "with", "from", "into", "there",
+    "here", "have", "has", "was", "were", "will", "would", "should", "could", "our", "out", "use",
+    "used", "uses", "using", "code", "file", "files", "about", "then", "than", "them", "they",
+    "its", "also", "get", "gets", "set", "way", "make", "work", "works", "doing", "happen",
+    "happens", "find", "show", "explain", "tell",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SnippetSource {
+    Keyword,
+    Semantic,
+    Symbol,
+}
+
+/// A span of a file offered to the model as context
+#[derive(Debug, Clone)]
+pub struct Snippet {
+    /// Path relative to the search root
+    pub path: PathBuf,
+    pub start_line: usize,
+    pub end_line: usize,
+    pub text: String,
+    pub score: f32,
+    pub source: SnippetSource,
+}
+
+impl Snippet {
+    /// The snippet with line numbers, as shown to the model
+    pub fn render(&self) -> String {
+        let mut rendered = format!("--- {}:{}-{} ---\n", self.path.display(), self.start_line, self.end_line);
+        for (offset, line) in self.text.lines().enumerate() {
+            rendered.push_str(&format!("{:>5} | {}\n", self.start_line + offset, line));
+        }
+        rendered
+    }
+
+    fn overlaps(&self, other: &Snippet) -> bool {
+        self.path == other.path && self.start_line <= other.end_line && other.start_line <= self.end_line
+    }
+}
+
+/// Identifiers and significant words from a natural-language question
+pub fn extract_keywords(question: &str) -> Vec<String> {
+    let mut seen = HashSet::new();
+    let mut keywords: Vec<String> = question
+        .split(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .filter(|word| word.chars().count() >= 3)
+        .filter(|word| !STOP_WORDS.contains(&word.to_lowercase().as_str()))
+        .filter(|word| seen.insert(word.to_lowercase()))
+        .map(|word| word.to_string())
+        .collect();
+
+    // Longer words are usually more specific
+    keywords.sort_by_key(|word| std::cmp::Reverse(word.len()));
+    keywords.truncate(8);
+    keywords
+}
+
+/// Windows around keyword hits, scored by how many distinct (and how rare)
+/// keywords they contain
+pub fn keyword_snippets(root: &Path, files: &[PathBuf], keywords: &[String]) -> Vec<Snippet> {
+    if keywords.is_empty() || files.is_empty() {
+        return Vec::new();
+    }
+
+    // line hits per file, tagged with the keyword that produced them
+    let mut hits: HashMap<PathBuf, Vec<(usize, usize)>> = HashMap::new();
+    let mut weights = Vec::with_capacity(keywords.len());
+
+    for (keyword_index, keyword) in keywords.iter().enumerate() {
+        let searcher = TextSearcher::new().case_insensitive();
+        let mut files_matched = 0;
+        for event in ParallelSearch::new(keyword).with_searcher(searcher).search(files.to_vec()) {
+            if let SearchEvent::Matched(file) = event {
+                files_matched += 1;
+                let lines = hits.entry(file.path).or_default();
+                lines.extend(file.matches.iter().map(|m| (m.line_number, keyword_index)));
+            }
+        }
+        // Inverse document frequency: keywords found everywhere say little
+        weights.push(((files.len() as f32 + 1.0) / (files_matched as f32 + 1.0)).ln() + 0.1);
+    }
+
+    let mut windows = Vec::new();
+    for (path, mut lines) in hits {
+        lines.sort();
+        let mut current: Option<(usize, usize, HashSet<usize>, usize)> = None;
+
+        for (line, keyword_index) in lines {
+            let start = line.saturating_sub(CONTEXT_LINES).max(1);
+            let end = line + CONTEXT_LINES;
+            match current.as_mut() {
+                Some((window_start, window_end, keywords, count))
+                    if start <= *window_end && end - *window_start < MAX_WINDOW_LINES =>
+                {
+                    *window_end = end;
+                    keywords.insert(keyword_index);
+                    *count += 1;
+                }
+                _ => {
+                    if let Some(window) = current.take() {
+                        windows.push((path.clone(), window));
+                    }
+                    current = Some((start, end, HashSet::from([keyword_index]), 1));
+                }
+            }
+        }
+        if let Some(window) = current {
+            windows.push((path.clone(), window));
+        }
+    }
+
+    let mut scored: Vec<(PathBuf, usize, usize, f32)> = windows
+        .into_iter()
+        .map(|(path, (start, end, matched, count))| {
+            let score = matched.iter().map(|&k| weights[k]).sum::<f32>() + 0.05 * count.min(5) as f32;
+            (path, start, end, score)
+        })
+        .collect();
+    scored.sort_by(|a, b| b.3.partial_cmp(&a.3).unwrap_or(std::cmp::Ordering::Equal));
+    scored.truncate(MAX_KEYWORD_SNIPPETS);
+
+    let mut contents: HashMap<PathBuf, Vec<String>> = HashMap::new();
+    let mut snippets = Vec::new();
+    for (path, start, end, score) in scored {
+        let lines = contents.entry(path.clone()).or_insert_with(|| {
+            fs::read(&path)
+                .map(|bytes| String::from_utf8_lossy(&bytes).lines().map(str::to_string).collect())
+                .unwrap_or_default()
+        });
+        if lines.is_empty() {
+            continue;
+        }
+        let end = end.min(lines.len());
+        snippets.push(Snippet {
+            path: path.strip_prefix(root).unwrap_or(&path).to_path_buf(),
+            start_line: start,
+            end_line: end,
+            text: lines[start - 1..end].join("\n"),
+            score,
+            source: SnippetSource::Keyword,
+        });
+    }
+
+    snippets
+}
+
+/// Declarations whose names match a keyword, taken whole up to
+/// `MAX_WINDOW_LINES` so a function is seen from its signature on
+pub fn symbol_snippets(root: &Path, files: &[PathBuf], keywords: &[String]) -> Vec<Snippet> {
+    if keywords.is_empty() {
+        return Vec::new();
+    }
+    let keywords: Vec<String> = keywords.iter().map(|k| k.to_lowercase()).collect();
+
+    let mut snippets = Vec::new();
+    for path in files {
+        let language = match Language::from_path(path) {
+            Some(language) => language,
+            None => continue,
+        };
+        let source = match fs::read(path) {
+            Ok(bytes) => String::from_utf8_lossy(&bytes).into_owned(),
+            Err(_) => continue,
+        };
+        let lines: Vec<&str> = source.lines().collect();
+
+        for symbol in extract_outline(&source, language) {
+            let name = symbol.name.to_lowercase();
+            let score = if keywords.contains(&name) {
+                1.0
+            } else if keywords.iter().any(|k| k.len() >= 4 && name.contains(k.as_str())) {
+                0.5
+            } else {
+                continue;
+            };
+
+            let end = symbol.end_line.min(symbol.start_line + MAX_WINDOW_LINES - 1).min(lines.len());
+            if symbol.start_line == 0 || symbol.start_line > end {
+                continue;
+            }
+            snippets.push(Snippet {
+                path: path.strip_prefix(root).unwrap_or(path).to_path_buf(),
+                start_line: symbol.start_line,
+                end_line: end,
+                text: lines[symbol.start_line - 1..end].join("\n"),
+                score,
+                source: SnippetSource::Symbol,
+            });
+        }
+    }
+
+    snippets.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal));
+    snippets.truncate(MAX_KEYWORD_SNIPPETS);
+    snippets
+}
+
+/// The closest chunks from the semantic index
+pub fn semantic_snippets(index: &SemanticIndex, client: &OllamaClient, question: &str, top_k: usize) -> Result<Vec<Snippet>, Box<dyn std::error::Error>> {
+    Ok(index
+        .query(client, question, top_k)?
+        .into_iter()
+        .map(|hit| Snippet {
+            path: hit.path.to_path_buf(),
+            start_line: hit.chunk.start_line,
+            end_line: hit.chunk.end_line,
+            text: hit.chunk.text.clone(),
+            score: hit.score,
+            source: SnippetSource::Semantic,
+        })
+        .collect())
+}
+
+/// Combine ranked lists into one, best first, dropping spans that overlap a
+/// better one. Scores are normalized per source so neither dominates.
+pub fn merge_snippets(sources: Vec<Vec<Snippet>>) -> Vec<Snippet> {
+    let mut all = Vec::new();
+    for mut snippets in sources {
+        let max = snippets.iter().map(|s| s.score).fold(0.0f32, f32::max);
+        if max > 0.0 {
+            for snippet in &mut snippets {
+                snippet.score /= max;
+            }
+        }
+        all.extend(snippets);
+    }
+    all.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal));
+
+    let mut merged: Vec<Snippet> = Vec::new();
+    for snippet in all {
+        if !merged.iter().any(|kept| kept.overlaps(&snippet)) {
+            merged.push(snippet);
+        }
+    }
+    merged
+}
+
+/// Characters of context that fit in the prompt, leaving room for the
+/// instructions, the question and the answer
+pub fn context_budget(context_window: u32, max_answer_tokens: u32, question: &str) -> usize {
+    let answer_tokens = max_answer_tokens.min(context_window / 2);
+    let prompt_chars = context_window.saturating_sub(answer_tokens) as usize * CHARS_PER_TOKEN;
+    prompt_chars.saturating_sub(INSTRUCTIONS.len() + question.len())
+}
+
+/// Take snippets in order while they fit in `budget` characters
+pub fn pack_snippets(snippets: Vec<Snippet>, budget: usize) -> Vec<Snippet> {
+    let mut remaining = budget;
+    let mut packed = Vec::new();
+
+    for mut snippet in snippets {
+        let size = snippet.render().len();
+        if size <= remaining {
+            remaining -= size;
+            packed.push(snippet);
+        } else if packed.is_empty() {
+            // Always offer something: trim the best snippet to fit
+            while snippet.end_line > snippet.start_line && snippet.render().len() > remaining {
+                snippet.end_line -= 1;
+                let keep = snippet.end_line - snippet.start_line + 1;
+                snippet.text = snippet.text.lines().take(keep).collect::<Vec<_>>().join("\n");
+            }
+            if snippet.render().len() <= remaining {
+                remaining -= snippet.render().len();
+                packed.push(snippet);
+            }
+        }
+    }
+
+    packed
+}
+
+const INSTRUCTIONS: &str = "You are Forge, a coding assistant answering questions about a codebase. \
+Answer using the numbered excerpts below. Cite every line you rely on as path:line \
+(for example src/main.rs:42) exactly as the paths appear in the excerpt headers. \
+If the excerpts do not contain the answer, say so instead of guessing.\n\n";
+
+pub fn build_prompt(question: &str, snippets: &[Snippet]) -> String {
+    let mut prompt = String::from(INSTRUCTIONS);
+    for snippet in snippets {
+        prompt.push_str(&snippet.render());
+        prompt.push('\n');
+    }
+    prompt.push_str(&format!("Question: {}\nAnswer:", question));
+    prompt
+}
+
+/// A `path:line` reference to one of the known files
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Citation {
+    /// Byte range of the reference in the text
+    pub start: usize,
+    pub end: usize,
+    pub path: String,
+    pub line: usize,
+}
+
+/// Find `path:line` and `path:start-end` references to the given paths
+pub fn find_citations(text: &str, known_paths: &HashSet<String>) -> Vec<Citation> {
+    let bytes = text.as_bytes();
+    let is_path_byte = |b: u8| b.is_ascii_alphanumeric() || matches!(b, b'_' | b'.' | b'/' | b'-' | b'\\');
+    let mut citations = Vec::new();
+
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] != b':' || !bytes.get(i + 1).is_some_and(u8::is_ascii_digit) {
+            i += 1;
+            continue;
+        }
+
+        let mut start = i;
+        while start > 0 && is_path_byte(bytes[start - 1]) {
+            start -= 1;
+        }
+        let mut end = i + 1;
+        while end < bytes.len() && bytes[end].is_ascii_digit() {
+            end += 1;
+        }
+        let line: usize = text[i + 1..end].parse().unwrap_or(0);
+        if end + 1 < bytes.len() && bytes[end] == b'-' && bytes[end + 1].is_ascii_digit() {
+            end += 1;
+            while end < bytes.len() && bytes[end].is_ascii_digit() {
+                end += 1;
+            }
+        }
+
+        let path = text[start..i].trim_start_matches("./");
+        if known_paths.contains(path) {
+            citations.push(Citation { start, end, path: path.to_string(), line });
+        }
+        i = end;
+    }
+
+    citations
+}
+
+/// `file://` link to a line of a file, as editors and terminals take it
+pub fn file_url(path: &Path, line: usize) -> String {
+    format!("file://{}#L{}", path.display(), line)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn snippet(path: &str, start: usize, end: usize, score: f32) -> Snippet {
+        let text = (start..=end).map(|n| format!("line {}", n)).collect::<Vec<_>>().join("\n");
+        Snippet { path: PathBuf::from(path), start_line: start, end_line: end, text, score, source: SnippetSource::Keyword }
+    }
+
+    #[test]
+    fn test_extract_keywords() {
+        let keywords = extract_keywords("Where do we parse the HTTP status_line? How does parse work?");
+        assert_eq!(keywords, vec!["status_line", "parse", "HTTP"]);
+    }
+
+    #[test]
+    fn test_keyword_snippets_prefer_rare_terms_together() {
+        let dir = tempdir().unwrap();
+        let mut common = String::new();
+        for i in 0..40 {
+            common.push_str(&format!("response {}\n", i));
+        }
+        fs::write(dir.path().join("common.rs"), &common).unwrap();
+        fs::write(dir.path().join("status.rs"), "fn helper() {}\n// parse the status line of a response\nfn parse_status() {}\n").unwrap();
+
+        let files = vec![dir.path().join("common.rs"), dir.path().join("status.rs")];
+        let keywords = vec!["response".to_string(), "status".to_string()];
+        let snippets = keyword_snippets(dir.path(), &files, &keywords);
+
+        assert_eq!(snippets[0].path, PathBuf::from("status.rs"));
+        assert_eq!((snippets[0].start_line, snippets[0].end_line), (1, 3));
+        assert!(snippets[0].text.contains("parse_status"));
+    }
+
+    #[test]
+    fn test_symbol_snippets_cover_declarations() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("lib.rs"), "use std::io;\n\nfn parse_header(line: &str) {\n    todo!()\n}\n\nfn other() {}\n").unwrap();
+        fs::write(dir.path().join("notes.txt"), "parse_header\n").unwrap();
+
+        let files = vec![dir.path().join("lib.rs"), dir.path().join("notes.txt")];
+        let snippets = symbol_snippets(dir.path(), &files, &["parse_header".to_string()]);
+
+        assert_eq!(snippets.len(), 1);
+        assert_eq!(snippets[0].path, PathBuf::from("lib.rs"));
+        assert_eq!((snippets[0].start_line, snippets[0].end_line), (3, 5));
+        assert_eq!(snippets[0].source, SnippetSource::Symbol);
+    }
+
+    #[test]
+    fn test_merge_and_pack() {
+        let keyword = vec![snippet("a.rs", 1, 10, 4.0), snippet("b.rs", 1, 5, 2.0)];
+        let semantic = vec![snippet("a.rs", 5, 15, 0.5), snippet("c.rs", 1, 3, 0.9)];
+        let merged = merge_snippets(vec![keyword, semantic]);
+        let names: Vec<String> = merged.iter().map(|s| s.path.display().to_string()).collect();
+        assert_eq!(names, vec!["a.rs", "c.rs", "b.rs"]);
+
+        let budget = merged[0].render().len() + merged[2].render().len();
+        let packed = pack_snippets(merged.clone(), budget);
+        assert_eq!(packed.len(), 2);
+        assert_eq!(packed[1].path, PathBuf::from("c.rs"));
+
+        // The best snippet is trimmed rather than dropped
+        let packed = pack_snippets(merged, 60);
+        assert_eq!(packed.len(), 1);
+        assert!(packed[0].render().len() <= 60);
+        assert!(packed[0].end_line < 10);
+    }
+
+    #[test]
+    fn test_find_citations() {
+        let known: HashSet<String> = ["src/http/response.rs".to_string()].into_iter().collect();
+        let text = "See `src/http/response.rs:14` and ./src/http/response.rs:20-31, not other.rs:3 or 10:30.";
+        let citations = find_citations(text, &known);
+
+        assert_eq!(citations.len(), 2);
+        assert_eq!(&text[citations[0].start..citations[0].end], "src/http/response.rs:14");
+        assert_eq!(citations[1].line, 20);
+        assert_eq!(&text[citations[1].start..citations[1].end], "./src/http/response.rs:20-31");
+
+        let root = Path::new("/work/forge");
+        assert_eq!(file_url(&root.join(&citations[0].path), citations[0].line), "file:///work/forge/src/http/response.rs#L14");
+        assert_eq!(file_url(&root.join(&citations[1].path), citations[1].line), "file:///work/forge/src/http/response.rs#L20");
+    }
+}
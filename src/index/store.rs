@@ -0,0 +1,319 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+const MAGIC: &[u8; 8] = b"FORGEIDX";
+const VERSION: u32 = 1;
+
+/// An embedded chunk of a file
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexedChunk {
+    pub start_line: usize,
+    pub end_line: usize,
+    pub text: String,
+    pub vector: Vec<f32>,
+}
+
+/// Everything indexed for one file, keyed for incremental updates
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileRecord {
+    /// Modification time in nanoseconds since the Unix epoch
+    pub modified: u64,
+    pub size: u64,
+    /// FNV-1a hash of the contents, used when only the mtime changed
+    pub hash: u64,
+    pub chunks: Vec<IndexedChunk>,
+}
+
+/// A chunk matching a query, best first
+#[derive(Debug)]
+pub struct SearchHit<'a> {
+    pub path: &'a Path,
+    pub chunk: &'a IndexedChunk,
+    pub score: f32,
+}
+
+/// On-disk store of chunk embeddings for a directory tree.
+///
+/// Paths are relative to the indexed root. The file is a small versioned
+/// little-endian binary format, rewritten in full on save.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VectorStore {
+    model: String,
+    files: BTreeMap<PathBuf, FileRecord>,
+}
+
+impl VectorStore {
+    pub fn new(model: &str) -> Self {
+        Self {
+            model: model.to_string(),
+            files: BTreeMap::new(),
+        }
+    }
+
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+
+    pub fn file(&self, path: &Path) -> Option<&FileRecord> {
+        self.files.get(path)
+    }
+
+    pub fn file_mut(&mut self, path: &Path) -> Option<&mut FileRecord> {
+        self.files.get_mut(path)
+    }
+
+    pub fn insert(&mut self, path: PathBuf, record: FileRecord) {
+        self.files.insert(path, record);
+    }
+
+    /// Drop every file for which `keep` returns false, returning how many were removed
+    pub fn retain<F: FnMut(&Path) -> bool>(&mut self, mut keep: F) -> usize {
+        let before = self.files.len();
+        self.files.retain(|path, _| keep(path));
+        before - self.files.len()
+    }
+
+    pub fn file_count(&self) -> usize {
+        self.files.len()
+    }
+
+    pub fn chunk_count(&self) -> usize {
+        self.files.values().map(|record| record.chunks.len()).sum()
+    }
+
+    /// Rank every chunk by cosine similarity to `query`
+    pub fn search(&self, query: &[f32], top_k: usize) -> Vec<SearchHit<'_>> {
+        let mut hits: Vec<SearchHit> = self
+            .files
+            .iter()
+            .flat_map(|(path, record)| {
+                record.chunks.iter().map(move |chunk| SearchHit {
+                    path: path.as_path(),
+                    chunk,
+                    score: cosine_similarity(query, &chunk.vector),
+                })
+            })
+            .collect();
+
+        hits.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal));
+        hits.truncate(top_k);
+        hits
+    }
+
+    pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let file = fs::File::open(path)?;
+        // Lengths read from the file are checked against what's left of it,
+        // so a corrupt one can't make us allocate more than the file holds
+        let size = file.metadata()?.len();
+        let mut reader = BufReader::new(file).take(size);
+
+        let mut magic = [0u8; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(format!("{} is not a forge index", path.display()).into());
+        }
+        let version = read_u32(&mut reader)?;
+        if version != VERSION {
+            return Err(format!("Unsupported index version {} in {}", version, path.display()).into());
+        }
+
+        let mut store = VectorStore::new(&read_string(&mut reader)?);
+        let file_count = read_u64(&mut reader)?;
+        for _ in 0..file_count {
+            let file_path = PathBuf::from(read_string(&mut reader)?);
+            let modified = read_u64(&mut reader)?;
+            let size = read_u64(&mut reader)?;
+            let hash = read_u64(&mut reader)?;
+
+            let chunk_count = read_u64(&mut reader)?;
+            let mut chunks = Vec::new();
+            for _ in 0..chunk_count {
+                let start_line = read_u64(&mut reader)? as usize;
+                let end_line = read_u64(&mut reader)? as usize;
+                let text = read_string(&mut reader)?;
+                let dimensions = read_u32(&mut reader)?;
+                let dimensions = check_length(&reader, dimensions as u64 * 4)? / 4;
+                let mut vector = Vec::with_capacity(dimensions);
+                for _ in 0..dimensions {
+                    let mut bytes = [0u8; 4];
+                    reader.read_exact(&mut bytes)?;
+                    vector.push(f32::from_le_bytes(bytes));
+                }
+                chunks.push(IndexedChunk { start_line, end_line, text, vector });
+            }
+
+            store.insert(file_path, FileRecord { modified, size, hash, chunks });
+        }
+
+        Ok(store)
+    }
+
+    /// Write the store atomically, creating parent directories as needed
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let temp_path = path.with_extension("tmp");
+        {
+            let mut writer = BufWriter::new(fs::File::create(&temp_path)?);
+            writer.write_all(MAGIC)?;
+            writer.write_all(&VERSION.to_le_bytes())?;
+            write_string(&mut writer, &self.model)?;
+            writer.write_all(&(self.files.len() as u64).to_le_bytes())?;
+
+            for (file_path, record) in &self.files {
+                write_string(&mut writer, &file_path.to_string_lossy())?;
+                writer.write_all(&record.modified.to_le_bytes())?;
+                writer.write_all(&record.size.to_le_bytes())?;
+                writer.write_all(&record.hash.to_le_bytes())?;
+                writer.write_all(&(record.chunks.len() as u64).to_le_bytes())?;
+
+                for chunk in &record.chunks {
+                    writer.write_all(&(chunk.start_line as u64).to_le_bytes())?;
+                    writer.write_all(&(chunk.end_line as u64).to_le_bytes())?;
+                    write_string(&mut writer, &chunk.text)?;
+                    writer.write_all(&(chunk.vector.len() as u32).to_le_bytes())?;
+                    for value in &chunk.vector {
+                        writer.write_all(&value.to_le_bytes())?;
+                    }
+                }
+            }
+            writer.flush()?;
+        }
+        fs::rename(&temp_path, path)?;
+        Ok(())
+    }
+}
+
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() || a.is_empty() {
+        return 0.0;
+    }
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        0.0
+    } else {
+        dot / (norm_a * norm_b)
+    }
+}
+
+/// 64-bit FNV-1a, stable across builds unlike `DefaultHasher`
+pub fn content_hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> io::Result<u32> {
+    let mut bytes = [0u8; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> io::Result<u64> {
+    let mut bytes = [0u8; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+/// `len` as a size to allocate, or an error if fewer bytes are left to read
+fn check_length<R: Read>(reader: &io::Take<R>, len: u64) -> io::Result<usize> {
+    if len > reader.limit() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("length {} runs past the end of the index", len),
+        ));
+    }
+    Ok(len as usize)
+}
+
+fn read_string<R: Read>(reader: &mut io::Take<R>) -> io::Result<String> {
+    let len = read_u64(reader)?;
+    let mut bytes = vec![0u8; check_length(reader, len)?];
+    reader.read_exact(&mut bytes)?;
+    String::from_utf8(bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+fn write_string<W: Write>(writer: &mut W, value: &str) -> io::Result<()> {
+    writer.write_all(&(value.len() as u64).to_le_bytes())?;
+    writer.write_all(value.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn chunk(text: &str, vector: Vec<f32>) -> IndexedChunk {
+        IndexedChunk { start_line: 1, end_line: 3, text: text.to_string(), vector }
+    }
+
+    #[test]
+    fn test_save_and_load_round_trip() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("index").join("embeddings.bin");
+
+        let mut store = VectorStore::new("nomic-embed-text");
+        store.insert(PathBuf::from("src/main.rs"), FileRecord {
+            modified: 42,
+            size: 7,
+            hash: content_hash(b"fn main"),
+            chunks: vec![chunk("fn main() {}", vec![0.5, -1.25, 3.0e-5])],
+        });
+        store.save(&path).unwrap();
+
+        assert_eq!(VectorStore::load(&path).unwrap(), store);
+    }
+
+    #[test]
+    fn test_corrupt_lengths_are_refused() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("embeddings.bin");
+        let mut store = VectorStore::new("test");
+        store.insert(PathBuf::from("a.rs"), FileRecord { modified: 1, size: 2, hash: 3, chunks: vec![chunk("a", vec![1.0, 2.0, 3.0])] });
+        store.save(&path).unwrap();
+        let saved = fs::read(&path).unwrap();
+
+        // The model name's length, then the vector's dimensions just before its three values
+        let mut bytes = saved.clone();
+        bytes[12..20].copy_from_slice(&u64::MAX.to_le_bytes());
+        fs::write(&path, &bytes).unwrap();
+        assert!(VectorStore::load(&path).unwrap_err().to_string().contains("runs past the end"));
+
+        let mut bytes = saved.clone();
+        let dimensions = bytes.len() - 16;
+        bytes[dimensions..dimensions + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        fs::write(&path, &bytes).unwrap();
+        assert!(VectorStore::load(&path).unwrap_err().to_string().contains("runs past the end"));
+
+        fs::write(&path, &saved[..saved.len() - 2]).unwrap();
+        assert!(VectorStore::load(&path).is_err());
+    }
+
+    #[test]
+    fn test_search_ranks_by_similarity() {
+        let mut store = VectorStore::new("test");
+        store.insert(PathBuf::from("a.rs"), FileRecord {
+            modified: 0,
+            size: 0,
+            hash: 0,
+            chunks: vec![chunk("far", vec![0.0, 1.0]), chunk("near", vec![1.0, 0.1])],
+        });
+        store.insert(PathBuf::from("b.rs"), FileRecord {
+            modified: 0,
+            size: 0,
+            hash: 0,
+            chunks: vec![chunk("middle", vec![1.0, 1.0])],
+        });
+
+        let hits = store.search(&[1.0, 0.0], 2);
+        let texts: Vec<&str> = hits.iter().map(|h| h.chunk.text.as_str()).collect();
+        assert_eq!(texts, vec!["near", "middle"]);
+        assert_eq!(hits[1].path, Path::new("b.rs"));
+    }
+}
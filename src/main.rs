@@ -7,6 +7,7 @@ mod fs;
 mod terminal;
 mod forge_process;
 mod config;
+mod index;
 
 use terminal::output::{StyledText, Color, error_text, success_text, info_text, warning_text, dim_text};
 
@@ -43,13 +44,65 @@ fn main() {
             }
         }
         "search" => {
-            if args.len() < 3 {
+            let (ignore_options, search_args) = fs::ignore::IgnoreOptions::from_args(&args[2..]);
+            if search_args.is_empty() {
+                eprintln!("{}", error_text("❌ Error: search command requires a query"));
+                eprintln!("{}", dim_text("   Usage: forge search <query> [path] [--no-ignore] [--hidden] [--semantic [--top <n>] | --symbol]"));
+                process::exit(1);
+            }
+            let (mode, search_args) = match cli::commands::search::SearchMode::from_args(&search_args) {
+                Ok(parsed) => parsed,
+                Err(e) => {
+                    eprintln!("{}", error_text(&format!("❌ Error: {}", e)));
+                    process::exit(1);
+                }
+            };
+            if search_args.is_empty() {
                 eprintln!("{}", error_text("❌ Error: search command requires a query"));
-                eprintln!("{}", dim_text("   Usage: forge search <query> [path]"));
                 process::exit(1);
             }
-            let path = if args.len() > 3 { Some(args[3].as_str()) } else { None };
-            if let Err(e) = cli::commands::search::run(&args[2], path) {
+            let path = search_args.get(1).map(|s| s.as_str());
+            let result = match mode {
+                cli::commands::search::SearchMode::Text => cli::commands::search::run(&search_args[0], path, ignore_options),
+                cli::commands::search::SearchMode::Semantic { top_k } => cli::commands::search::run_semantic(&search_args[0], path, top_k),
+                cli::commands::search::SearchMode::Symbol => cli::commands::search::run_symbol(&search_args[0], path, ignore_options),
+            };
+            if let Err(e) = result {
+                eprintln!("{}", error_text(&format!("❌ Error: {}", e)));
+                process::exit(1);
+            }
+        }
+        "ask" => {
+            if args.len() < 3 {
+                eprintln!("{}", error_text("❌ Error: ask command requires a question"));
+                eprintln!("{}", dim_text("   Usage: forge ask \"<question>\" [path]"));
+                process::exit(1);
+            }
+            let path = args.get(3).map(|s| s.as_str());
+            if let Err(e) = cli::commands::ask::run(&args[2], path) {
+                eprintln!("{}", error_text(&format!("❌ Error: {}", e)));
+                process::exit(1);
+            }
+        }
+        "index" => {
+            let (ignore_options, index_args) = fs::ignore::IgnoreOptions::from_args(&args[2..]);
+            let path = index_args.first().map(|s| s.as_str());
+            if let Err(e) = cli::commands::index::run(path, ignore_options) {
+                eprintln!("{}", error_text(&format!("❌ Error: {}", e)));
+                process::exit(1);
+            }
+        }
+        "outline" => {
+            let result = match args.get(2).map(|s| s.as_str()) {
+                Some("--map") => cli::commands::outline::run_map(args.get(3).map(|s| s.as_str())),
+                Some(file) => cli::commands::outline::run(file),
+                None => {
+                    eprintln!("{}", error_text("❌ Error: outline command requires a file"));
+                    eprintln!("{}", dim_text("   Usage: forge outline <file> | forge outline --map [path]"));
+                    process::exit(1);
+                }
+            };
+            if let Err(e) = result {
                 eprintln!("{}", error_text(&format!("❌ Error: {}", e)));
                 process::exit(1);
             }
@@ -57,7 +110,7 @@ fn main() {
         "exec" => {
             if args.len() < 3 {
                 eprintln!("{}", error_text("❌ Error: exec command requires a command"));
-                eprintln!("{}", dim_text("   Usage: forge exec <command>"));
+                eprintln!("{}", dim_text("   Usage: forge exec [--explain-failure] [--yes[=<level>]|--deny-risky|--force-dangerous] <command>"));
                 process::exit(1);
             }
             let command_args = args[2..].to_vec();
@@ -68,7 +121,25 @@ fn main() {
         }
         "shell" => {
             println!("{}", info_text("🐚 Starting interactive shell..."));
-            if let Err(e) = cli::commands::shell::run() {
+            if let Err(e) = cli::commands::shell::run(&args[2..]) {
+                eprintln!("{}", error_text(&format!("❌ Error: {}", e)));
+                process::exit(1);
+            }
+        }
+        "suggest" => {
+            if let Err(e) = cli::commands::suggest::run(&args[2..]) {
+                eprintln!("{}", error_text(&format!("❌ Error: {}", e)));
+                process::exit(1);
+            }
+        }
+        "policy" => {
+            if let Err(e) = cli::commands::policy::run(&args[2..]) {
+                eprintln!("{}", error_text(&format!("❌ Error: {}", e)));
+                process::exit(1);
+            }
+        }
+        "audit" => {
+            if let Err(e) = cli::commands::audit::run(&args[2..]) {
                 eprintln!("{}", error_text(&format!("❌ Error: {}", e)));
                 process::exit(1);
             }
@@ -144,8 +215,14 @@ fn show_help() {
     print_command_help("💬", "chat", "", "Start interactive chat with AI");
     print_command_help("✏️ ", "edit", "<file> [instruction]", "Edit a file with AI assistance");
     print_command_help("🔍", "search", "<query> [path]", "Search files for text or patterns");
-    print_command_help("⚡", "exec", "<command>", "Execute commands with enhanced safety");
-    print_command_help("🐚", "shell", "", "Start interactive shell with safety features");
+    print_command_help("❓", "ask", "<question> [path]", "Answer questions about the codebase with cited sources");
+    print_command_help("🧠", "index", "[path]", "Build the embedding index for semantic search");
+    print_command_help("🗂️ ", "outline", "<file> | --map [path]", "List functions, types and modules in source files");
+    print_command_help("⚡", "exec", "[options] <command>", "Execute commands with enhanced safety");
+    print_command_help("🐚", "shell", "[--explain-failure]", "Start interactive shell with safety features");
+    print_command_help("💡", "suggest", "<request>", "Turn a request into a shell command to review and run");
+    print_command_help("🛡️ ", "policy", "check <command>", "Rate a command with the safety policies in effect");
+    print_command_help("📜", "audit", "[filters]", "Show the commands forge has run, newest first");
     print_command_help("📋", "workflow", "[subcommand]", "Manage and execute command workflows");
     print_command_help("📊", "status", "[--clear|--demo]", "Show background tasks and notifications");
     print_command_help("⚙️ ", "config", "[subcommand]", "Manage configuration settings");
@@ -158,6 +235,19 @@ fn show_help() {
         .bold());
     print_option_help("-h, --help", "Show this help message");
     print_option_help("-v, --version", "Show version information");
+    print_option_help("--no-ignore", "search: Don't respect .gitignore/.forgeignore files");
+    print_option_help("--hidden", "search: Include hidden files and directories");
+    print_option_help("--semantic", "search: Rank indexed code by meaning (see forge index)");
+    print_option_help("--top <n>", "search: Number of semantic results (default 5)");
+    print_option_help("--symbol", "search: Find functions, types and modules by name");
+    print_option_help("--explain-failure", "exec, shell: Explain failed commands and offer a fix");
+    print_option_help("--yes[=<level>]", "exec, workflow run: Run risky commands up to a level (default medium) without asking");
+    print_option_help("--deny-risky", "exec, workflow run: Refuse anything that isn't rated safe");
+    print_option_help("--force-dangerous", "exec, workflow run: Run even critical commands");
+    print_option_help("--since <age|date>", "audit: Entries since 2h, 7d or 2026-10-18");
+    print_option_help("--risk <level>", "audit: Entries rated at least this risky");
+    print_option_help("--status <status>", "audit: ok, failed or refused");
+    print_option_help("--by <initiator>", "audit: user, workflow or agent");
     
     println!();
     println!("{}", dim_text("Examples:"));
@@ -170,6 +260,24 @@ fn show_help() {
     println!("  {} {}", 
         StyledText::new("forge search \"fn main\" src/").fg(Color::BrightGreen),
         dim_text("# Search for functions"));
+    println!("  {} {}", 
+        StyledText::new("forge search --semantic \"where is the HTTP status line parsed?\"").fg(Color::BrightGreen),
+        dim_text("# Search indexed code by meaning"));
+    println!("  {} {}", 
+        StyledText::new("forge ask \"how are streaming responses parsed?\"").fg(Color::BrightGreen),
+        dim_text("# Ask about the codebase"));
+    println!("  {} {}", 
+        StyledText::new("forge search --symbol parse_response").fg(Color::BrightGreen),
+        dim_text("# Find where a symbol is declared"));
+    println!("  {} {}", 
+        StyledText::new("forge suggest \"rust files changed this week over 10kb\"").fg(Color::BrightGreen),
+        dim_text("# Get a command for a task"));
+    println!("  {} {}", 
+        StyledText::new("forge policy check \"git push --force\"").fg(Color::BrightGreen),
+        dim_text("# See how a command is rated"));
+    println!("  {} {}", 
+        StyledText::new("forge audit --since 1d --risk high").fg(Color::BrightGreen),
+        dim_text("# Review risky commands from the last day"));
     println!();
     println!("{}", info_text("💡 Tip: Make sure Ollama is running with 'ollama serve'"));
 }
@@ -2,7 +2,12 @@
 
 use super::input::{InputReader, Key};
 use super::history::CommandHistory;
+use super::raw::RawTerminal;
 use std::io::{self, Write};
+use std::path::PathBuf;
+
+type CompletionCallback = Box<dyn Fn(&str) -> Vec<String>>;
+type ContinuationCheck = Box<dyn Fn(&str) -> bool>;
 
 pub struct LineEditor {
     input_reader: InputReader,
@@ -10,8 +15,13 @@ pub struct LineEditor {
     current_line: String,
     cursor_pos: usize,
     prompt: String,
+    continuation_prompt: String,
+    /// Earlier lines of a multi-line entry
+    pending: String,
     multiline_mode: bool,
-    completion_callback: Option<Box<dyn Fn(&str) -> Vec<String>>>,
+    history_file: Option<PathBuf>,
+    completion_callback: Option<CompletionCallback>,
+    continuation_check: Option<ContinuationCheck>,
     suggestions: Vec<String>,
     suggestion_index: Option<usize>,
 }
@@ -24,8 +34,12 @@ impl LineEditor {
             current_line: String::new(),
             cursor_pos: 0,
             prompt: "> ".to_string(),
+            continuation_prompt: "... ".to_string(),
+            pending: String::new(),
             multiline_mode: false,
+            history_file: None,
             completion_callback: None,
+            continuation_check: None,
             suggestions: Vec::new(),
             suggestion_index: None,
         }
@@ -36,11 +50,16 @@ impl LineEditor {
         self
     }
 
-    pub fn with_history_file(mut self, path: &str) -> Self {
-        let _ = self.history.load_from_file(path);
+    /// Load history from `path`, and save it there after every line
+    pub fn with_history_file(mut self, path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let _ = self.history.load_from_file(&path);
+        self.history_file = Some(path);
         self
     }
 
+    /// The callback gets the line up to the cursor and returns what the
+    /// word before the cursor could become
     pub fn with_completion_callback<F>(mut self, callback: F) -> Self 
     where 
         F: Fn(&str) -> Vec<String> + 'static,
@@ -49,6 +68,27 @@ impl LineEditor {
         self
     }
 
+    /// In multiline mode, Enter starts another line while `check` says the
+    /// text so far is incomplete
+    pub fn with_continuation_check<F>(mut self, check: F) -> Self
+    where
+        F: Fn(&str) -> bool + 'static,
+    {
+        self.continuation_check = Some(Box::new(check));
+        self
+    }
+
+    pub fn set_prompt(&mut self, prompt: &str) {
+        self.prompt = prompt.to_string();
+    }
+
+    pub fn set_completion_callback<F>(&mut self, callback: F)
+    where
+        F: Fn(&str) -> Vec<String> + 'static,
+    {
+        self.completion_callback = Some(Box::new(callback));
+    }
+
     pub fn enable_multiline(&mut self) {
         self.multiline_mode = true;
     }
@@ -57,14 +97,25 @@ impl LineEditor {
         self.multiline_mode = false;
     }
 
+    /// Read a line with the terminal in raw mode. Ctrl+C returns an
+    /// "Interrupted" error and Ctrl+D on an empty line "EOF".
     pub fn read_line(&mut self) -> Result<String, Box<dyn std::error::Error>> {
-        self.current_line.clear();
-        self.cursor_pos = 0;
+        self.read_line_with("")
+    }
+
+    /// Read a line that starts out as `initial`, for editing
+    pub fn read_line_with(&mut self, initial: &str) -> Result<String, Box<dyn std::error::Error>> {
+        let mut terminal = RawTerminal::new()?;
+        terminal.enable_raw_mode()?;
+
+        self.current_line = initial.to_string();
+        self.pending.clear();
+        self.cursor_pos = self.current_line.len();
         self.suggestions.clear();
         self.suggestion_index = None;
         self.history.reset_navigation();
 
-        self.display_prompt()?;
+        self.redraw_line()?;
 
         loop {
             let key = self.input_reader.read_key()?;
@@ -82,11 +133,12 @@ impl LineEditor {
                 Key::Ctrl('c') => {
                     println!("^C");
                     self.current_line.clear();
+                    self.pending.clear();
                     return Err("Interrupted".into());
                 }
                 
                 Key::Ctrl('d') => {
-                    if self.current_line.is_empty() {
+                    if self.current_line.is_empty() && self.pending.is_empty() {
                         return Err("EOF".into());
                     } else {
                         self.delete_char_at_cursor()?;
@@ -145,8 +197,9 @@ impl LineEditor {
                     self.handle_tab_completion()?;
                 }
                 
-                Key::Ctrl('r') => {
-                    self.handle_reverse_search()?;
+                Key::Ctrl('r') if self.handle_reverse_search()? => {
+                    self.finish_input()?;
+                    break;
                 }
                 
                 Key::Char(c) => {
@@ -163,16 +216,30 @@ impl LineEditor {
             }
         }
 
-        let result = self.current_line.clone();
-        if !result.trim().is_empty() {
-            self.history.add_command(result.clone());
-        }
+        let result = format!("{}{}", std::mem::take(&mut self.pending), self.current_line);
+        self.add_history(&result);
 
         Ok(result)
     }
 
+    /// Add a command to history, as if it had been typed
+    pub fn add_history(&mut self, command: &str) {
+        if command.trim().is_empty() {
+            return;
+        }
+        // The history file has one entry per line
+        self.history.add_command(command.replace("\\\n", "").replace('\n', " "));
+        if let Some(path) = &self.history_file {
+            let _ = self.history.save_to_file(path);
+        }
+    }
+
+    fn active_prompt(&self) -> &str {
+        if self.pending.is_empty() { &self.prompt } else { &self.continuation_prompt }
+    }
+
     fn display_prompt(&self) -> Result<(), Box<dyn std::error::Error>> {
-        print!("{}", self.prompt);
+        print!("{}", self.active_prompt());
         io::stdout().flush()?;
         Ok(())
     }
@@ -182,12 +249,11 @@ impl LineEditor {
         print!("\r\x1B[K");
         
         // Display prompt and current line
-        print!("{}{}", self.prompt, self.current_line);
+        print!("{}{}", self.active_prompt(), self.current_line);
         
         // Position cursor correctly
-        let cursor_offset = self.cursor_pos;
-        if cursor_offset < self.current_line.len() {
-            let remaining = self.current_line.len() - cursor_offset;
+        let remaining = self.current_line[self.cursor_pos..].chars().count();
+        if remaining > 0 {
             print!("\x1B[{}D", remaining);
         }
         
@@ -200,15 +266,15 @@ impl LineEditor {
         self.suggestion_index = None;
         
         self.current_line.insert(self.cursor_pos, c);
-        self.cursor_pos += 1;
+        self.cursor_pos += c.len_utf8();
         
         self.redraw_line()?;
         Ok(())
     }
 
     fn handle_backspace(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-        if self.cursor_pos > 0 {
-            self.cursor_pos -= 1;
+        if let Some(c) = self.char_before_cursor() {
+            self.cursor_pos -= c.len_utf8();
             self.current_line.remove(self.cursor_pos);
             self.redraw_line()?;
         }
@@ -224,8 +290,8 @@ impl LineEditor {
     }
 
     fn move_cursor_left(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-        if self.cursor_pos > 0 {
-            self.cursor_pos -= 1;
+        if let Some(c) = self.char_before_cursor() {
+            self.cursor_pos -= c.len_utf8();
             print!("\x1B[D"); // Move cursor left
             io::stdout().flush()?;
         }
@@ -233,8 +299,8 @@ impl LineEditor {
     }
 
     fn move_cursor_right(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-        if self.cursor_pos < self.current_line.len() {
-            self.cursor_pos += 1;
+        if let Some(c) = self.current_line[self.cursor_pos..].chars().next() {
+            self.cursor_pos += c.len_utf8();
             print!("\x1B[C"); // Move cursor right
             io::stdout().flush()?;
         }
@@ -243,7 +309,7 @@ impl LineEditor {
 
     fn move_cursor_to_start(&mut self) -> Result<(), Box<dyn std::error::Error>> {
         if self.cursor_pos > 0 {
-            print!("\x1B[{}D", self.cursor_pos);
+            print!("\x1B[{}D", self.current_line[..self.cursor_pos].chars().count());
             self.cursor_pos = 0;
             io::stdout().flush()?;
         }
@@ -252,7 +318,7 @@ impl LineEditor {
 
     fn move_cursor_to_end(&mut self) -> Result<(), Box<dyn std::error::Error>> {
         if self.cursor_pos < self.current_line.len() {
-            let distance = self.current_line.len() - self.cursor_pos;
+            let distance = self.current_line[self.cursor_pos..].chars().count();
             print!("\x1B[{}C", distance);
             self.cursor_pos = self.current_line.len();
             io::stdout().flush()?;
@@ -304,19 +370,12 @@ impl LineEditor {
             return Ok(());
         }
 
-        let mut new_pos = self.cursor_pos;
-        let chars: Vec<char> = self.current_line.chars().collect();
-        
-        // Skip whitespace backwards
-        while new_pos > 0 && chars[new_pos - 1].is_whitespace() {
-            new_pos -= 1;
-        }
-        
-        // Skip word characters backwards
-        while new_pos > 0 && !chars[new_pos - 1].is_whitespace() {
-            new_pos -= 1;
-        }
-        
+        // Skip whitespace, then the word before it
+        let new_pos = self.current_line[..self.cursor_pos]
+            .trim_end()
+            .trim_end_matches(|c: char| !c.is_whitespace())
+            .len();
+
         // Remove the range
         self.current_line.drain(new_pos..self.cursor_pos);
         self.cursor_pos = new_pos;
@@ -332,15 +391,19 @@ impl LineEditor {
 
     fn handle_tab_completion(&mut self) -> Result<(), Box<dyn std::error::Error>> {
         if let Some(callback) = &self.completion_callback {
-            let prefix = self.get_current_word();
-            if !prefix.is_empty() {
-                let completions = callback(&prefix);
-                
-                if completions.len() == 1 {
-                    // Single completion - insert it
-                    self.complete_current_word(&completions[0])?;
-                } else if completions.len() > 1 {
-                    // Multiple completions - show them
+            let completions = callback(&self.current_line[..self.cursor_pos]);
+            let word = self.get_current_word();
+
+            if let [completion] = completions.as_slice() {
+                // Single completion - insert it, ready for the next word
+                let separator = if completion.ends_with('/') { "" } else { " " };
+                self.complete_current_word(&format!("{}{}", completion, separator))?;
+            } else if completions.len() > 1 {
+                // Multiple completions - insert what they share, or show them
+                let common = common_prefix(&completions);
+                if common.len() > word.len() {
+                    self.complete_current_word(&common)?;
+                } else {
                     self.show_completions(&completions)?;
                 }
             }
@@ -348,78 +411,91 @@ impl LineEditor {
         Ok(())
     }
 
-    fn handle_reverse_search(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-        print!("\r\x1B[K");
-        print!("(reverse-i-search)`': ");
-        io::stdout().flush()?;
-        
+    /// Search history for the most recent command containing what's typed;
+    /// Ctrl+R again goes to older matches. Enter runs the match, Escape
+    /// keeps it for editing and Ctrl+C or Ctrl+G go back to the line as it
+    /// was. Returns whether to run it.
+    fn handle_reverse_search(&mut self) -> Result<bool, Box<dyn std::error::Error>> {
+        let original = self.current_line.clone();
         let mut search_term = String::new();
-        let mut search_active = true;
-        
-        while search_active {
-            let key = self.input_reader.read_key()?;
-            
-            match key {
+        let mut skipped = 0;
+        self.update_search_display(&search_term, skipped)?;
+
+        let run = loop {
+            match self.input_reader.read_key()? {
                 Key::Char(c) => {
                     search_term.push(c);
-                    self.update_search_display(&search_term)?;
+                    skipped = 0;
+                }
+                Key::Space => {
+                    search_term.push(' ');
+                    skipped = 0;
                 }
                 Key::Backspace => {
-                    if !search_term.is_empty() {
-                        search_term.pop();
-                        self.update_search_display(&search_term)?;
-                    }
+                    search_term.pop();
+                    skipped = 0;
                 }
                 Key::Ctrl('r') => {
-                    // Continue search
-                    if let Some(result) = self.find_in_history(&search_term) {
-                        self.current_line = result.clone();
-                        self.cursor_pos = self.current_line.len();
+                    if self.find_in_history(&search_term, skipped + 1).is_some() {
+                        skipped += 1;
                     }
-                    self.update_search_display(&search_term)?;
                 }
-                Key::Enter | Key::Ctrl('c') | Key::Escape => {
-                    search_active = false;
+                Key::Enter => break true,
+                Key::Ctrl('c') | Key::Ctrl('g') => {
+                    self.current_line = original;
+                    break false;
                 }
-                _ => {}
+                Key::Escape | Key::ArrowLeft | Key::ArrowRight | Key::Tab => break false,
+                _ => continue,
             }
-        }
-        
-        print!("\r\x1B[K");
+            if let Some(found) = self.find_in_history(&search_term, skipped) {
+                self.current_line = found;
+            }
+            self.update_search_display(&search_term, skipped)?;
+        };
+
+        self.cursor_pos = self.current_line.len();
         self.redraw_line()?;
-        Ok(())
+        Ok(run)
     }
 
-    fn update_search_display(&self, search_term: &str) -> Result<(), Box<dyn std::error::Error>> {
+    fn update_search_display(&self, search_term: &str, skipped: usize) -> Result<(), Box<dyn std::error::Error>> {
         print!("\r\x1B[K");
-        if let Some(result) = self.find_in_history(search_term) {
-            print!("(reverse-i-search)`{}': {}", search_term, result);
+        if search_term.is_empty() || self.find_in_history(search_term, skipped).is_some() {
+            print!("(reverse-i-search)`{}': {}", search_term, self.current_line);
         } else {
-            print!("(failed reverse-i-search)`{}': ", search_term);
+            print!("(failed reverse-i-search)`{}': {}", search_term, self.current_line);
         }
         io::stdout().flush()?;
         Ok(())
     }
 
-    fn find_in_history(&self, search_term: &str) -> Option<&String> {
+    /// The `skip`th most recent command containing `search_term`
+    fn find_in_history(&self, search_term: &str, skip: usize) -> Option<String> {
         if search_term.is_empty() {
             return None;
         }
         
         let matches = self.history.find_matching(search_term);
-        matches.first().copied()
+        matches.get(skip).map(|command| command.to_string())
     }
 
+    fn char_before_cursor(&self) -> Option<char> {
+        self.current_line[..self.cursor_pos].chars().next_back()
+    }
+
+    /// The word before the cursor. Whitespace escaped with a backslash, as
+    /// in `my\ dir/`, is part of it.
     fn get_current_word(&self) -> String {
-        let chars: Vec<char> = self.current_line.chars().collect();
-        let mut start = self.cursor_pos;
-        
-        // Find the start of the current word
-        while start > 0 && !chars[start - 1].is_whitespace() {
-            start -= 1;
+        let before = &self.current_line[..self.cursor_pos];
+        let mut start = before.len();
+        for (i, c) in before.char_indices().rev() {
+            if c.is_whitespace() && !before[..i].ends_with('\\') {
+                break;
+            }
+            start = i;
         }
-        
-        chars[start..self.cursor_pos].iter().collect()
+        before[start..].to_string()
     }
 
     fn complete_current_word(&mut self, completion: &str) -> Result<(), Box<dyn std::error::Error>> {
@@ -450,7 +526,7 @@ impl LineEditor {
             }
         }
         
-        if completions.len() % columns != 0 {
+        if !completions.len().is_multiple_of(columns) {
             println!();
         }
         
@@ -459,8 +535,13 @@ impl LineEditor {
     }
 
     fn should_continue_multiline(&self) -> bool {
+        let text = format!("{}{}", self.pending, self.current_line);
+        if let Some(check) = &self.continuation_check {
+            return check(&text);
+        }
+
         // Simple heuristic: continue if line ends with backslash or has unclosed brackets
-        let trimmed = self.current_line.trim_end();
+        let trimmed = text.trim_end();
         if trimmed.ends_with('\\') {
             return true;
         }
@@ -470,7 +551,7 @@ impl LineEditor {
         let mut brace_count = 0;
         let mut bracket_count = 0;
         
-        for c in self.current_line.chars() {
+        for c in text.chars() {
             match c {
                 '(' => paren_count += 1,
                 ')' => paren_count -= 1,
@@ -486,10 +567,12 @@ impl LineEditor {
     }
 
     fn handle_multiline_continue(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-        self.current_line.push('\n');
+        self.pending.push_str(&self.current_line);
+        self.pending.push('\n');
+        self.current_line.clear();
+        self.cursor_pos = 0;
         println!(); // Move to next line
-        print!("... "); // Continuation prompt
-        io::stdout().flush()?;
+        self.display_prompt()?;
         Ok(())
     }
 
@@ -503,6 +586,23 @@ impl LineEditor {
     }
 }
 
+/// The longest prefix all of `words` start with
+fn common_prefix(words: &[String]) -> String {
+    let Some(first) = words.first() else {
+        return String::new();
+    };
+    let mut length = first.len();
+    for word in &words[1..] {
+        // Compared a character at a time so the cut is on a boundary
+        length = first[..length]
+            .char_indices()
+            .zip(word.chars())
+            .find(|((_, a), b)| a != b)
+            .map_or(length.min(word.len()), |((i, _), _)| i);
+    }
+    first[..length].to_string()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -533,4 +633,49 @@ mod tests {
         editor.disable_multiline();
         assert!(!editor.multiline_mode);
     }
+
+    #[test]
+    fn test_continuation_and_completion_helpers() {
+        let mut editor = LineEditor::new().with_continuation_check(|text| text.ends_with('|'));
+        editor.current_line = "ls |".to_string();
+        assert!(editor.should_continue_multiline());
+        editor.handle_multiline_continue().unwrap();
+        assert_eq!((editor.pending.as_str(), editor.active_prompt()), ("ls |\n", "... "));
+
+        let words = ["cargo".to_string(), "cat".to_string(), "cal".to_string()];
+        assert_eq!(common_prefix(&words), "ca");
+        assert_eq!(common_prefix(&words[..1]), "cargo");
+        assert_eq!(common_prefix(&[]), "");
+        assert_eq!(common_prefix(&["café.txt".to_string(), "cafè.txt".to_string()]), "caf");
+        assert_eq!(common_prefix(&["naïve".to_string(), "naï".to_string()]), "naï");
+    }
+
+    #[test]
+    fn test_editing_non_ascii() {
+        let mut editor = LineEditor::new();
+        for c in "echo café!".chars() {
+            editor.insert_char(c).unwrap();
+        }
+        assert_eq!((editor.current_line.as_str(), editor.cursor_pos), ("echo café!", "echo café!".len()));
+
+        editor.move_cursor_left().unwrap();
+        editor.move_cursor_left().unwrap();
+        assert_eq!(editor.cursor_pos, "echo caf".len());
+        editor.insert_char('ü').unwrap();
+        editor.delete_char_at_cursor().unwrap();
+        assert_eq!(editor.current_line, "echo cafü!");
+        editor.handle_backspace().unwrap();
+        editor.handle_backspace().unwrap();
+        assert_eq!(editor.current_line, "echo ca!");
+        editor.move_cursor_right().unwrap();
+        assert_eq!(editor.cursor_pos, editor.current_line.len());
+
+        editor.current_line = "ls my\\ dïr".to_string();
+        editor.cursor_pos = editor.current_line.len();
+        assert_eq!(editor.get_current_word(), "my\\ dïr");
+        editor.complete_current_word("my\\ dïrectory/").unwrap();
+        assert_eq!(editor.current_line, "ls my\\ dïrectory/");
+        editor.kill_word_backwards().unwrap();
+        assert_eq!(editor.current_line, "ls my\\ ");
+    }
 }
\ No newline at end of file
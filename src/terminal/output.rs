@@ -586,6 +586,7 @@ impl EnhancedProgressBar {
         self.status = Some(status.to_string());
     }
 
+    #[allow(dead_code)]
     pub fn set_eta(&mut self, seconds: u64) {
         self.eta = Some(seconds);
     }
@@ -757,6 +758,11 @@ impl TerminalControl {
     pub fn restore_cursor() -> &'static str {
         "\x1B[u"
     }
+
+    /// OSC 8 hyperlink; terminals without support show just `text`
+    pub fn hyperlink(url: &str, text: &str) -> String {
+        format!("\x1B]8;;{}\x1B\\{}\x1B]8;;\x1B\\", url, text)
+    }
 }
 
 // Enhanced table rendering
@@ -917,6 +923,7 @@ pub enum StageStatus {
     InProgress,
     Completed,
     Failed(String),
+    Skipped(String),
 }
 
 impl MultiStageProgress {
@@ -961,6 +968,29 @@ impl MultiStageProgress {
         Ok(())
     }
 
+    /// Mark a stage in progress without completing the ones before it, for
+    /// stages that run concurrently or out of order
+    pub fn begin_stage(&mut self, stage_index: usize) -> Result<(), String> {
+        if stage_index >= self.stages.len() {
+            return Err("Stage index out of bounds".to_string());
+        }
+
+        self.stages[stage_index].status = StageStatus::InProgress;
+        self.update_total_progress();
+        Ok(())
+    }
+
+    pub fn skip_stage(&mut self, stage_index: usize, reason: &str) -> Result<(), String> {
+        if stage_index >= self.stages.len() {
+            return Err("Stage index out of bounds".to_string());
+        }
+
+        self.stages[stage_index].status = StageStatus::Skipped(reason.to_string());
+        self.stages[stage_index].sub_message = None;
+        self.update_total_progress();
+        Ok(())
+    }
+
     pub fn update_stage_progress(&mut self, stage_index: usize, progress: f64, sub_message: Option<&str>) -> Result<(), String> {
         if stage_index >= self.stages.len() {
             return Err("Stage index out of bounds".to_string());
@@ -1006,7 +1036,7 @@ impl MultiStageProgress {
         self.total_progress = self.stages.iter()
             .map(|stage| {
                 let stage_progress = match stage.status {
-                    StageStatus::Completed => 1.0,
+                    StageStatus::Completed | StageStatus::Skipped(_) => 1.0,
                     StageStatus::Failed(_) => 0.0,
                     _ => stage.progress,
                 };
@@ -1041,6 +1071,7 @@ impl MultiStageProgress {
                 StageStatus::InProgress => StyledText::new("⚙️").fg(Color::BrightYellow),
                 StageStatus::Completed => StyledText::new("✅").fg(Color::Green),
                 StageStatus::Failed(_) => StyledText::new("❌").fg(Color::Red),
+                StageStatus::Skipped(_) => StyledText::new("⏭").fg(Color::BrightBlack),
             };
 
             let stage_filled = ((stage.progress * 20.0) as usize).min(20);
@@ -1064,6 +1095,11 @@ impl MultiStageProgress {
                     StyledText::new(error).fg(Color::Red)));
             }
 
+            if let StageStatus::Skipped(ref reason) = stage.status {
+                output.push_str(&format!(" ({})", 
+                    StyledText::new(reason).fg(Color::BrightBlack)));
+            }
+
             output.push('\n');
         }
 
@@ -1072,7 +1108,7 @@ impl MultiStageProgress {
 
     #[allow(dead_code)]
     pub fn is_complete(&self) -> bool {
-        self.stages.iter().all(|stage| matches!(stage.status, StageStatus::Completed))
+        self.stages.iter().all(|stage| matches!(stage.status, StageStatus::Completed | StageStatus::Skipped(_)))
     }
 
     #[allow(dead_code)]
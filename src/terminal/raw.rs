@@ -4,7 +4,7 @@ use std::io::{self, Write};
 use std::os::unix::io::AsRawFd;
 
 #[cfg(unix)]
-use libc::{tcgetattr, tcsetattr, termios, ECHO, ICANON, TCSANOW, VMIN, VTIME};
+use libc::{tcgetattr, tcsetattr, termios, ECHO, ICANON, ISIG, TCSANOW, VMIN, VTIME};
 
 #[cfg(windows)]
 use winapi::um::consoleapi::{GetConsoleMode, SetConsoleMode};
@@ -58,8 +58,9 @@ impl RawTerminal {
             
             self.original_termios = Some(termios);
             
-            // Disable canonical mode and echo
-            termios.c_lflag &= !(ICANON | ECHO);
+            // Disable canonical mode, echo and signal keys, so Ctrl+C and
+            // Ctrl+Z reach the reader as keys
+            termios.c_lflag &= !(ICANON | ECHO | ISIG);
             // Set minimum read to 1 byte, no timeout
             termios.c_cc[VMIN] = 1;
             termios.c_cc[VTIME] = 0;